@@ -68,6 +68,7 @@ use xtra::Actor;
 use xtra_bitmex_price_feed::LatestQuotes;
 use xtra_bitmex_price_feed::Quote;
 use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::multiaddress_ext::MultiaddrExt;
 
 pub mod flow;
@@ -707,8 +708,16 @@ impl Maker {
             config.n_payouts,
             projection_actor,
             identities.clone(),
-            endpoint_listen.clone(),
+            vec![endpoint_listen.clone()],
+            HashSet::from([endpoint_listen.clone()]),
             config.blocked_peers.clone(),
+            false,
+            ConnectionLimits::default(),
+            None,
+            None,
+            None,
+            None,
+            None,
         )
         .unwrap();
 
@@ -905,8 +914,11 @@ impl Taker {
             Duration::from_secs(10),
             projection_actor,
             maker_identity,
-            maker_multiaddr.clone(),
+            vec![maker_multiaddr.clone()],
+            None,
             Environment::Test,
+            None,
+            None,
         )
         .unwrap();
 
@@ -951,6 +963,7 @@ impl Taker {
                 maker_peer_id: Some(self.maker_peer_id),
                 from_commit_txid: latest_dlc.commit.0.txid(),
                 from_settlement_event_id: latest_dlc.settlement_event_id,
+                policy: None,
             })
             .await
             .unwrap();
@@ -969,6 +982,7 @@ impl Taker {
                 maker_peer_id: Some(self.maker_peer_id),
                 from_commit_txid,
                 from_settlement_event_id,
+                policy: None,
             })
             .await
             .unwrap();
@@ -995,6 +1009,7 @@ impl Taker {
                     // Funding fee irrelevant because only CompleteFee is used
                     funding_fee: dummy_funding_fee(),
                     complete_fee: Some(complete_fee),
+                    hours_charged: None,
                 },
             ))
             .await
@@ -0,0 +1,176 @@
+//! Compares the JSON and CBOR encoding of the messages exchanged during contract setup and
+//! rollover.
+//!
+//! The bulk of those messages are the adaptor signatures of the CETs, one per payout interval of
+//! every announcement.
+//!
+//! Run with `cargo bench -p daemon --bench encoding`. The message sizes are printed before the
+//! timings.
+
+use asynchronous_codec::BytesMut;
+use asynchronous_codec::Decoder;
+use asynchronous_codec::Encoder;
+use bdk::bitcoin::secp256k1::ecdsa::Signature;
+use bdk::bitcoin::secp256k1::SecretKey;
+use bdk::bitcoin::PublicKey;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use daemon::order::protocol::Msg1;
+use daemon::order::protocol::SetupMsg;
+use maia_core::secp256k1_zkp::EcdsaAdaptorSignature;
+use model::olivia::BitMexPriceEventId;
+use model::ContractSymbol;
+use rollover::protocol::RolloverMsg;
+use rollover::protocol::RolloverMsg0;
+use rollover::protocol::RolloverMsg1;
+use rollover::protocol::RolloverMsg2;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use time::macros::datetime;
+use time::Duration;
+use xtra_libp2p_request_response::framed::Encoding;
+use xtra_libp2p_request_response::framed::FramedCodec;
+
+/// Number of payout intervals per announcement, roughly what we generate for a single CFD.
+const N_PAYOUTS: u64 = 200;
+
+/// Number of announcements covered by a single CFD.
+const N_ANNOUNCEMENTS: i64 = 24;
+
+const ADAPTOR_SIGNATURE: &str = "03424d14a5471c048ab87b3b83f6085d125d5864249ae4297a57c84e74710bb6730223f325042fce535d040fee52ec13231bf709ccd84233c6944b90317e62528b2527dff9d659a96db4c99f9750168308633c1867b70f3a18fb0f4539a1aecedcd1fc0148fc22f36b6303083ece3f872b18e35d368b3958efe5fb081f7716736ccb598d269aa3084d57e1855e1ea9a45efc10463bbf32ae378029f5763ceb40173f";
+
+const SIGNATURE: &str = "3046022100839c1fbc5304de944f697c9f4b1d01d1faeba32d751c0f7acb21ac8a0f436a72022100e89bd46bb3a5a62adc679f659b7ce876d83ee297c7a5587b2011c4fcc72eab45";
+
+const REVOCATION_PK: &str = "03b8eff4a54fa7f5aa70540b50e83cbbe22b7f7643534fa7c31b7da1ad7af8e4b6";
+
+const PUBLISH_PK: &str = "0326aed07dc7e02ded71d49add1bc33d2a019ec58e198925aeffdae8bbaa42a9f3";
+
+fn adaptor_signature() -> EcdsaAdaptorSignature {
+    ADAPTOR_SIGNATURE.parse().unwrap()
+}
+
+fn signature() -> Signature {
+    Signature::from_str(SIGNATURE).unwrap()
+}
+
+fn cets() -> HashMap<String, Vec<(RangeInclusive<u64>, EcdsaAdaptorSignature)>> {
+    let adaptor_signature = adaptor_signature();
+
+    (0..N_ANNOUNCEMENTS)
+        .map(|announcement| {
+            let event_id = BitMexPriceEventId::with_20_digits(
+                datetime!(2022-10-01 0:00 UTC) + Duration::hours(announcement),
+                ContractSymbol::BtcUsd,
+            );
+            let cets = (0..N_PAYOUTS)
+                .map(|i| (i * 5_000..=(i + 1) * 5_000 - 1, adaptor_signature))
+                .collect();
+
+            (event_id.to_string(), cets)
+        })
+        .collect()
+}
+
+fn setup_msg1() -> SetupMsg {
+    SetupMsg::Msg1(Msg1 {
+        commit: adaptor_signature(),
+        cets: cets(),
+        refund: signature(),
+    })
+}
+
+fn rollover_msg0() -> RolloverMsg {
+    RolloverMsg::Msg0(RolloverMsg0 {
+        revocation_pk: PublicKey::from_str(REVOCATION_PK).unwrap(),
+        publish_pk: PublicKey::from_str(PUBLISH_PK).unwrap(),
+    })
+}
+
+fn rollover_msg1() -> RolloverMsg {
+    RolloverMsg::Msg1(RolloverMsg1 {
+        commit: adaptor_signature(),
+        cets: cets(),
+        refund: signature(),
+    })
+}
+
+fn rollover_msg2() -> RolloverMsg {
+    RolloverMsg::Msg2(RolloverMsg2 {
+        revocation_sk: SecretKey::from_slice(&[1; 32]).unwrap(),
+    })
+}
+
+fn encode<M>(encoding: Encoding, message: M) -> BytesMut
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    let mut buffer = BytesMut::new();
+    FramedCodec::<M, M>::new(encoding)
+        .encode(message, &mut buffer)
+        .unwrap();
+
+    buffer
+}
+
+fn decode<M>(encoding: Encoding, mut buffer: BytesMut) -> M
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    FramedCodec::<M, M>::new(encoding)
+        .decode(&mut buffer)
+        .unwrap()
+        .unwrap()
+}
+
+fn bench_message<M>(c: &mut Criterion, name: &str, message: fn() -> M)
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    let json = encode(Encoding::Json, message()).len();
+    let cbor = encode(Encoding::Cbor, message()).len();
+    println!(
+        "{name}: {json} bytes as JSON, {cbor} bytes as CBOR ({:.1}% smaller)",
+        100.0 * (json as f64 - cbor as f64) / json as f64
+    );
+
+    let mut group = c.benchmark_group(name);
+    for encoding in [Encoding::Json, Encoding::Cbor] {
+        group.bench_function(BenchmarkId::new("encode", format!("{encoding:?}")), |b| {
+            b.iter_batched(
+                message,
+                |message| encode(encoding, message),
+                BatchSize::LargeInput,
+            )
+        });
+
+        let buffer = encode(encoding, message());
+        group.bench_with_input(
+            BenchmarkId::new("decode", format!("{encoding:?}")),
+            &buffer,
+            |b, buffer| {
+                b.iter_batched(
+                    || buffer.clone(),
+                    |buffer| decode::<M>(encoding, buffer),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn encoding_benchmark(c: &mut Criterion) {
+    bench_message(c, "setup_msg1", setup_msg1);
+    bench_message(c, "rollover_msg0", rollover_msg0);
+    bench_message(c, "rollover_msg1", rollover_msg1);
+    bench_message(c, "rollover_msg2", rollover_msg2);
+}
+
+criterion_group!(benches, encoding_benchmark);
+criterion_main!(benches);
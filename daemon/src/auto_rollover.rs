@@ -8,6 +8,8 @@ use model::libp2p::PeerId;
 use model::olivia::BitMexPriceEventId;
 use model::CannotRollover;
 use model::OrderId;
+use model::RolloverPolicyCheck;
+use model::RolloverPolicyViolation;
 use rollover::taker::ProposeRollover;
 use sqlite_db;
 use std::time::Duration;
@@ -56,6 +58,7 @@ impl Actor {
             maker_peer_id,
             from_commit_txid,
             from_settlement_event_id,
+            policy,
         }: Rollover,
     ) {
         if let Some(maker_peer_id) = maker_peer_id {
@@ -66,6 +69,7 @@ impl Actor {
                     maker_peer_id,
                     from_commit_txid,
                     from_settlement_event_id,
+                    policy,
                 })
                 .await
             {
@@ -99,13 +103,27 @@ impl Actor {
             let order_id = cfd.id();
             let maker_peer_id = cfd.counterparty_peer_id();
 
-            match cfd.can_auto_rollover_taker(OffsetDateTime::now_utc()) {
+            let now = OffsetDateTime::now_utc();
+            match cfd.can_auto_rollover_taker(now) {
                 Ok((from_commit_txid, from_settlement_event_id)) => {
+                    let policy = match self.check_rollover_policy(&cfd, now).await {
+                        Ok(Ok(policy)) => policy,
+                        Ok(Err(violation)) => {
+                            tracing::debug!(%order_id, %violation, "Rollover policy prevents auto-rollover");
+                            continue;
+                        }
+                        Err(e) => {
+                            tracing::warn!(%order_id, "Failed to check rollover policy: {e:#}");
+                            continue;
+                        }
+                    };
+
                     this.send_async_next(Rollover {
                         order_id,
                         maker_peer_id,
                         from_commit_txid,
                         from_settlement_event_id,
+                        policy: Some(policy),
                     })
                     .await;
                 }
@@ -122,6 +140,39 @@ impl Actor {
     }
 }
 
+impl Actor {
+    /// Check the rollover policy of the CFD with the funding rate we expect to be charged.
+    ///
+    /// The returned policy is checked again with the funding rate the maker charges.
+    async fn check_rollover_policy(
+        &self,
+        cfd: &model::Cfd,
+        now: OffsetDateTime,
+    ) -> Result<Result<RolloverPolicyCheck, RolloverPolicyViolation>> {
+        let order_id = cfd.id();
+        let policy = self.db.load_rollover_policy(order_id).await?;
+        let completed_rollovers = self.db.count_completed_rollovers(order_id).await?;
+
+        // The maker only tells us the funding rate once it accepted the rollover, we expect it to
+        // charge the rate of the previous rollover
+        let funding_rate = self
+            .db
+            .load_funding_fee_ledger(order_id)
+            .await?
+            .last()
+            .map(|entry| entry.rate)
+            .unwrap_or_else(|| cfd.initial_funding_rate());
+
+        let result = cfd.check_rollover_policy(&policy, completed_rollovers, funding_rate, now)?;
+
+        Ok(result.map(|()| RolloverPolicyCheck {
+            policy,
+            completed_rollovers,
+            now,
+        }))
+    }
+}
+
 #[async_trait]
 impl xtra::Actor for Actor {
     type Stop = ();
@@ -155,4 +206,6 @@ pub struct Rollover {
     pub maker_peer_id: Option<PeerId>,
     pub from_commit_txid: Txid,
     pub from_settlement_event_id: BitMexPriceEventId,
+    /// Checked again once the maker accepted the rollover, `None` to roll over unconditionally.
+    pub policy: Option<RolloverPolicyCheck>,
 }
@@ -1,4 +1,32 @@
-mod current;
-pub mod deprecated;
+pub mod maker;
+pub mod protocol;
+pub mod taker;
 
-pub use current::*;
+use model::InverseMaxPrice;
+use xtra_libp2p::versions::Versions;
+
+/// The supported versions of the collaborative settlement protocol, newest first.
+pub const VERSIONS: Versions<Version> = Versions::new(&[
+    ("/itchysats/collab-settlement/2.0.0", Version::V2),
+    ("/itchysats/collab-settlement/1.0.0", Version::V1),
+]);
+
+/// The versions of the collaborative settlement protocol.
+///
+/// Both versions exchange the same messages, they only differ in how the listener verifies the
+/// proposed settlement transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// Inverse payout curves are capped at double the initial price.
+    V1,
+    V2,
+}
+
+impl Version {
+    fn inverse_max_price(&self) -> InverseMaxPrice {
+        match self {
+            Version::V1 => InverseMaxPrice::DoubleOfInitial,
+            Version::V2 => InverseMaxPrice::OliviaMax,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use crate::collab_settlement::protocol::*;
+use crate::collab_settlement::Version;
+use crate::collab_settlement::VERSIONS;
 use crate::command;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -27,8 +29,8 @@ type ListenerConnection = (
     PeerId,
 );
 
-/// Permanent actor to handle incoming substreams for the `/itchysats/collab-settlement/1.0.0`
-/// protocol.
+/// Permanent actor to handle incoming substreams for all [`VERSIONS`] of the collaborative
+/// settlement protocol.
 ///
 /// There is only one instance of this actor for all connections, meaning we must always spawn a
 /// task whenever we interact with a substream to not block the execution of other connections.
@@ -64,6 +66,9 @@ impl Actor {
         tokio_extras::spawn_fallible(
             &address.clone(),
             async move {
+                let version = VERSIONS
+                    .get(stream.protocol())
+                    .context("Unsupported collab settlement protocol")?;
                 let mut framed =
                     Framed::new(stream, JsonCodec::<ListenerMessage, DialerMessage>::new());
 
@@ -79,6 +84,7 @@ impl Actor {
                         propose,
                         framed,
                         peer_id,
+                        version,
                     })
                     .await?;
 
@@ -98,6 +104,7 @@ impl Actor {
             propose,
             framed,
             peer_id,
+            version,
         } = msg;
         let order_id = propose.id;
 
@@ -105,10 +112,11 @@ impl Actor {
             .executor
             .execute(order_id, |cfd| {
                 cfd.verify_counterparty_peer_id(&peer_id.into())?;
-                cfd.start_collab_settlement_maker_olivia_max(
+                cfd.start_collab_settlement_maker(
                     propose.price,
                     self.n_payouts,
                     &propose.unsigned_tx,
+                    version.inverse_max_price(),
                 )
             })
             .await
@@ -244,6 +252,7 @@ struct ProposeReceived {
     propose: Propose,
     framed: Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
     peer_id: PeerId,
+    version: Version,
 }
 
 #[derive(Clone, Copy)]
@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use crate::bitcoin::secp256k1::ecdsa::Signature;
 use crate::bitcoin::Transaction;
-use crate::collab_settlement::PROTOCOL;
+use crate::collab_settlement::VERSIONS;
 use crate::command;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -37,8 +37,11 @@ pub async fn dialer(
     counterparty: PeerId,
     collab_settlement_tx: SettlementTransaction,
 ) -> Result<CollaborativeSettlement, DialerFailed> {
+    // The taker always proposes a settlement transaction according to the latest version, hence
+    // older versions are not offered to the maker
+    let (protocol, _) = VERSIONS.latest();
     let substream = endpoint
-        .send(OpenSubstream::single_protocol(counterparty, PROTOCOL))
+        .send(OpenSubstream::single_protocol(counterparty, protocol))
         .await
         .context("Endpoint is disconnected")?
         .context("No connection to peer")?
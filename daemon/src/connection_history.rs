@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use model::Timestamp;
+use xtra_libp2p::endpoint;
+use xtra_productivity::xtra_productivity;
+
+/// Actor that persists when peers connect and disconnect, based on the notifications of the
+/// `Endpoint`.
+pub struct Actor {
+    db: sqlite_db::Connection,
+}
+
+impl Actor {
+    pub fn new(db: sqlite_db::Connection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, _: &mut xtra::Context<Self>) {
+        // Connections which were open when we last stopped were dropped at the latest now
+        if let Err(e) = self.db.close_open_connections(Timestamp::now()).await {
+            tracing::warn!("Failed to close open connections in connection history: {e:#}");
+        }
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle_connection_established(&mut self, msg: endpoint::ConnectionEstablished) {
+        let peer_id = msg.peer_id;
+
+        if let Err(e) = self
+            .db
+            .insert_connection_established(peer_id.into(), &msg.address, Timestamp::now())
+            .await
+        {
+            tracing::warn!(%peer_id, "Failed to record established connection: {e:#}");
+        }
+    }
+
+    async fn handle_connection_dropped(&mut self, msg: endpoint::ConnectionDropped) {
+        let peer_id = msg.peer_id;
+
+        if let Err(e) = self
+            .db
+            .update_connection_dropped(peer_id.into(), Timestamp::now())
+            .await
+        {
+            tracing::warn!(%peer_id, "Failed to record dropped connection: {e:#}");
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Delivers CFD events to external systems through webhooks.
+//!
+//! Events are written to an outbox in the database before they are delivered, hence they survive
+//! restarts and are retried with exponential backoff until the subscriber accepts them or
+//! [`MAX_ATTEMPTS`] is reached. Each
+//! request carries an HMAC-SHA256 signature of the body, keyed with the secret of the subscriber,
+//! in the `X-ItchySats-Signature` header.
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use hmac::Hmac;
+use hmac::Mac;
+use model::CfdEvent;
+use model::EventName;
+use model::OrderId;
+use model::Role;
+use model::Timestamp;
+use reqwest::Url;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_with::serde_as;
+use serde_with::DisplayFromStr;
+use sha2::Sha256;
+use sqlite_db::webhook_outbox::OutboxEntry;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::Duration;
+use uuid::Uuid;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often the outbox is checked for webhooks which are due.
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Delay before the first retry, doubled with every failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// After this many failed attempts the webhook is dropped.
+const MAX_ATTEMPTS: u32 = 15;
+
+const SIGNATURE_HEADER: &str = "X-ItchySats-Signature";
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Subscriber {
+    #[serde_as(as = "DisplayFromStr")]
+    pub url: Url,
+    /// Secret the signature of the payload is derived from.
+    pub secret: String,
+    /// Names of the events delivered to the subscriber, e.g. `RolloverCompleted`.
+    ///
+    /// If not specified, all events are delivered.
+    #[serde(default)]
+    pub events: Option<HashSet<EventName>>,
+}
+
+impl Subscriber {
+    fn accepts(&self, event: EventName) -> bool {
+        match &self.events {
+            None => true,
+            Some(events) => events.contains(&event),
+        }
+    }
+}
+
+/// The body of a webhook request.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    /// Unique per event, allows subscribers to detect duplicate deliveries.
+    id: Uuid,
+    order_id: OrderId,
+    event: &'a str,
+    timestamp: Timestamp,
+    role: Role,
+}
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+    role: Role,
+    subscribers: Vec<Subscriber>,
+    client: reqwest::Client,
+}
+
+impl Actor {
+    pub fn new(db: sqlite_db::Connection, role: Role, subscribers: Vec<Subscriber>) -> Self {
+        Self {
+            db,
+            role,
+            subscribers,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn enqueue(&self, event: CfdEvent) -> Result<()> {
+        let (name, _) = event.event.to_json();
+
+        let urls = self
+            .subscribers
+            .iter()
+            .filter(|subscriber| subscriber.accepts(EventName::from(&event.event)))
+            .map(|subscriber| subscriber.url.to_string())
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            return Ok(());
+        }
+
+        let payload = serde_json::to_string(&Payload {
+            id: Uuid::new_v4(),
+            order_id: event.id,
+            event: &name,
+            timestamp: event.timestamp,
+            role: self.role,
+        })?;
+
+        self.db
+            .enqueue_webhooks(&urls, &payload, Timestamp::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn deliver(&self, entry: OutboxEntry) -> Result<()> {
+        let subscriber = match self
+            .subscribers
+            .iter()
+            .find(|subscriber| subscriber.url.as_str() == entry.url)
+        {
+            Some(subscriber) => subscriber,
+            None => {
+                tracing::warn!(url = %entry.url, "Dropping webhook of removed subscriber");
+                self.db.delete_webhook(entry.id).await?;
+                return Ok(());
+            }
+        };
+
+        match self.post(subscriber, &entry.payload).await {
+            Ok(()) => self.db.delete_webhook(entry.id).await,
+            Err(e) => record_failed_delivery(&self.db, &entry, e).await,
+        }
+    }
+
+    async fn post(&self, subscriber: &Subscriber, payload: &str) -> Result<()> {
+        let url = &subscriber.url;
+
+        let response = self
+            .client
+            .post(url.clone())
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", signature(&subscriber.secret, payload)),
+            )
+            .body(payload.to_owned())
+            .send()
+            .await
+            .with_context(|| format!("Failed to POST {url}"))?;
+
+        let code = response.status();
+        if !code.is_success() {
+            bail!("POST {url} responded with {code}");
+        }
+
+        Ok(())
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: Publish) {
+        let order_id = msg.0.id;
+
+        if let Err(e) = self.enqueue(msg.0).await {
+            tracing::error!(%order_id, "Failed to queue webhooks: {e:#}");
+        }
+    }
+
+    async fn handle(&mut self, _: DeliverDue) {
+        let due = match self.db.load_due_webhooks(Timestamp::now()).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::error!("Failed to load webhooks: {e:#}");
+                return;
+            }
+        };
+
+        // Subscribers are delivered to concurrently, each in the order its webhooks were queued
+        let mut per_subscriber = HashMap::<String, Vec<OutboxEntry>>::new();
+        for entry in due {
+            per_subscriber
+                .entry(entry.url.clone())
+                .or_default()
+                .push(entry);
+        }
+
+        let this = &*self;
+        let deliveries = per_subscriber.into_values().map(|entries| async move {
+            for entry in entries {
+                let id = entry.id;
+                if let Err(e) = this.deliver(entry).await {
+                    tracing::error!(%id, "Failed to update webhook outbox: {e:#}");
+                }
+            }
+        });
+        futures::future::join_all(deliveries).await;
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+        tokio_extras::spawn(
+            &this.clone(),
+            this.send_interval(DELIVERY_INTERVAL, || DeliverDue, xtras::IncludeSpan::Always),
+        );
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Publish a CFD event to the subscribers interested in it.
+pub struct Publish(pub CfdEvent);
+
+/// Message sent to ourselves at an interval to deliver the webhooks which are due.
+#[derive(Clone, Copy)]
+pub struct DeliverDue;
+
+/// Retry the webhook with exponential backoff, or drop it after [`MAX_ATTEMPTS`].
+pub(crate) async fn record_failed_delivery(
+    db: &sqlite_db::Connection,
+    entry: &OutboxEntry,
+    e: anyhow::Error,
+) -> Result<()> {
+    let attempts = entry.attempts + 1;
+    if attempts >= MAX_ATTEMPTS {
+        tracing::error!(
+            url = %entry.url,
+            "Giving up on webhook after {attempts} attempts: {e:#}"
+        );
+        db.delete_webhook(entry.id).await?;
+        return Ok(());
+    }
+
+    let backoff = backoff(attempts);
+    tracing::warn!(
+        url = %entry.url,
+        "Failed to deliver webhook, retrying in {}s: {e:#}",
+        backoff.as_secs()
+    );
+
+    let next_attempt_at = Timestamp::new(Timestamp::now().seconds() + backoff.as_secs() as i64);
+    db.reschedule_webhook(entry.id, attempts, next_attempt_at)
+        .await?;
+
+    Ok(())
+}
+
+/// Hex encoded HMAC-SHA256 of the payload.
+fn signature(secret: &str, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC to accept keys of any length");
+    mac.update(payload.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn backoff(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+
+    INITIAL_BACKOFF.saturating_mul(factor).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_is_hmac_sha256_of_payload() {
+        assert_eq!(
+            signature("secret", r#"{"event":"RolloverCompleted"}"#),
+            "173c6e0db4aa5ac8c1d1478941ea42147010ec9a46d8c1caf56f8fc4c2f4812f"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_until_max() {
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(2), Duration::from_secs(20));
+        assert_eq!(backoff(5), Duration::from_secs(160));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(100), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn subscriber_without_events_accepts_all() {
+        let subscriber: Subscriber =
+            serde_json::from_str(r#"{ "url": "https://example.com/hook", "secret": "secret" }"#)
+                .unwrap();
+
+        assert!(subscriber.accepts(EventName::RolloverCompleted));
+        assert!(subscriber.accepts(EventName::CetConfirmed));
+    }
+
+    #[test]
+    fn subscriber_only_accepts_configured_events() {
+        let subscriber: Subscriber = serde_json::from_str(
+            r#"{
+                "url": "https://example.com/hook",
+                "secret": "secret",
+                "events": ["RolloverCompleted"]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(subscriber.accepts(EventName::RolloverCompleted));
+        assert!(!subscriber.accepts(EventName::CetConfirmed));
+    }
+
+    #[test]
+    fn subscriber_with_unknown_event_is_rejected() {
+        let subscriber = serde_json::from_str::<Subscriber>(
+            r#"{
+                "url": "https://example.com/hook",
+                "secret": "secret",
+                "events": ["RolloverComplete"]
+            }"#,
+        );
+
+        assert!(subscriber.is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::Environment;
+use libp2p_core::Multiaddr;
 use std::collections::HashSet;
 
 pub mod dialer;
@@ -13,6 +14,8 @@ pub struct PeerInfo {
     pub daemon_version: String,
     pub environment: Environment,
     pub protocols: HashSet<String>,
+    /// The addresses the peer listens on, as advertised by the peer.
+    pub listen_addrs: HashSet<Multiaddr>,
 }
 
 impl TryFrom<protocol::IdentifyMsg> for PeerInfo {
@@ -24,6 +27,7 @@ impl TryFrom<protocol::IdentifyMsg> for PeerInfo {
             daemon_version: identity_msg.daemon_version()?,
             environment: identity_msg.environment().into(),
             protocols: identity_msg.protocols(),
+            listen_addrs: identity_msg.listen_addrs(),
         };
 
         Ok(identity_info)
@@ -44,7 +48,6 @@ mod tests {
     use crate::Environment;
     use libp2p_core::PublicKey;
     use std::collections::HashSet;
-    use std::sync::Arc;
     use std::time::Duration;
     use tokio::sync::watch;
     use xtra::spawn::TokioGlobalSpawnExt;
@@ -57,6 +60,7 @@ mod tests {
     use xtra_libp2p::libp2p::transport::MemoryTransport;
     use xtra_libp2p::libp2p::Multiaddr;
     use xtra_libp2p::libp2p::PeerId;
+    use xtra_libp2p::limits::ConnectionLimits;
     use xtra_libp2p::Connect;
     use xtra_libp2p::Endpoint;
     use xtra_libp2p::ListenOn;
@@ -67,7 +71,7 @@ mod tests {
             "0.4.22".to_string(),
             Environment::Unknown,
             Keypair::generate_ed25519().public(),
-            HashSet::new(),
+            HashSet::from([maker_listen_addr()]),
             HashSet::from(["some_maker_protocol".to_string()]),
         );
         let (_, taker_endpoint, taker_receiver) = create_endpoint_with_identify(
@@ -103,6 +107,7 @@ mod tests {
             daemon_version: "0.4.22".to_string(),
             environment: Environment::Unknown,
             protocols: HashSet::from(["some_maker_protocol".to_string()]),
+            listen_addrs: HashSet::from([maker_listen_addr()]),
         };
 
         let expected_taker_peer_info = PeerInfo {
@@ -110,6 +115,7 @@ mod tests {
             daemon_version: "0.4.22".to_string(),
             environment: Environment::Umbrel,
             protocols: HashSet::from(["some_taker_protocol".to_string()]),
+            listen_addrs: HashSet::new(),
         };
 
         assert_eq!(maker_peer_info, expected_maker_peer_info);
@@ -152,7 +158,8 @@ mod tests {
                 vec![],
                 vec![],
             ),
-            Arc::new(HashSet::default()),
+            HashSet::default(),
+            ConnectionLimits::default(),
         );
 
         #[allow(clippy::disallowed_methods)]
@@ -161,6 +168,10 @@ mod tests {
         (id.public().to_peer_id(), endpoint_address, receiver)
     }
 
+    fn maker_listen_addr() -> Multiaddr {
+        "/dns4/maker.example.com/tcp/10000".parse().unwrap()
+    }
+
     async fn retry_until_some<F, T>(mut f: F) -> T
     where
         F: FnMut() -> Option<T>,
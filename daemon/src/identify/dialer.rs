@@ -56,6 +56,9 @@ impl xtra::Actor for Actor {
     async fn stopped(self) -> Self::Stop {}
 }
 
+/// Get the peer info of all connected peers which responded to the identify protocol.
+pub struct GetPeerInfos;
+
 pub(crate) struct IdentifyMsgReceived {
     peer_id: PeerId,
     identify_msg: protocol::IdentifyMsg,
@@ -97,6 +100,10 @@ impl Actor {
         }
     }
 
+    async fn handle(&mut self, _: GetPeerInfos) -> HashMap<PeerId, PeerInfo> {
+        self.peer_infos.clone()
+    }
+
     async fn handle_connections_established(
         &mut self,
         msg: endpoint::ConnectionEstablished,
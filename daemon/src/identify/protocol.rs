@@ -74,6 +74,10 @@ impl IdentifyMsg {
     pub fn protocols(&self) -> HashSet<String> {
         self.protocols.clone()
     }
+
+    pub fn listen_addrs(&self) -> HashSet<Multiaddr> {
+        self.listen_addrs.clone()
+    }
 }
 
 pub(crate) async fn recv<S>(stream: S) -> Result<IdentifyMsg>
@@ -12,7 +12,6 @@ use bdk::bitcoin::Amount;
 use bdk::FeeRate;
 use identify::PeerInfo;
 use libp2p_core::Multiaddr;
-use libp2p_tcp::TokioTcpConfig;
 pub use maia;
 pub use maia_core;
 use maia_core::secp256k1_zkp::XOnlyPublicKey;
@@ -24,14 +23,16 @@ use model::Leverage;
 use model::OfferId;
 use model::OrderId;
 use model::Price;
+use model::ReferralCode;
 use model::Role;
+use model::RolloverPolicy;
 use online_status::ConnectionStatus;
 use parse_display::Display;
 use ping_pong::ping;
 use ping_pong::pong;
 use seed::Identities;
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::net::SocketAddr;
 use std::time::Duration;
 use time::ext::NumericalDuration;
 use tokio::sync::watch;
@@ -41,7 +42,9 @@ use xtra::prelude::*;
 use xtra_bitmex_price_feed::QUOTE_INTERVAL_MINUTES;
 use xtra_libp2p::dialer;
 use xtra_libp2p::endpoint;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::multiaddress_ext::MultiaddrExt;
+use xtra_libp2p::relay::RelayTransport;
 use xtra_libp2p::Endpoint;
 use xtras::supervisor::always_restart_after;
 use xtras::supervisor::Supervisor;
@@ -51,10 +54,13 @@ pub mod archive_failed_cfds;
 pub mod auto_rollover;
 pub mod collab_settlement;
 pub mod command;
+pub mod connection_history;
+pub mod event_webhooks;
 pub mod identify;
 pub mod libp2p_utils;
 pub mod listen_protocols;
 pub mod monitor;
+pub mod notifications;
 pub mod online_status;
 pub mod oracle;
 pub mod order;
@@ -83,6 +89,8 @@ pub struct TakerActorSystem<O, W, P> {
     pub auto_rollover_actor: Address<auto_rollover::Actor>,
     pub price_feed_actor: Address<P>,
     executor: command::Executor,
+    db: sqlite_db::Connection,
+    projection_actor: Address<projection::Actor>,
     _close_cfds_actor: Address<archive_closed_cfds::Actor>,
     _archive_failed_cfds_actor: Address<archive_failed_cfds::Actor>,
     _pong_actor: Address<pong::Actor>,
@@ -135,8 +143,11 @@ where
         connect_timeout: Duration,
         projection_actor: Address<projection::Actor>,
         maker_identity: Identity,
-        maker_multiaddr: Multiaddr,
+        maker_multiaddrs: Vec<Multiaddr>,
+        tor_socks_proxy: Option<SocketAddr>,
         environment: Environment,
+        rollover_failed_notifications: Option<MessageChannel<notifications::RolloverFailed, ()>>,
+        referral_code: Option<ReferralCode>,
     ) -> Result<Self>
     where
         M: Handler<monitor::MonitorAfterContractSetup, Return = ()>
@@ -147,6 +158,14 @@ where
             + Handler<monitor::TryBroadcastTransaction, Return = Result<()>>
             + Actor<Stop = ()>,
     {
+        let maker_peer_id = maker_multiaddrs
+            .first()
+            .context("No maker address given")?
+            .clone()
+            .extract_peer_id()
+            .context("Unable to extract peer id from maker address")?;
+        let maker_address_book = dialer::AddressBook::new(maker_multiaddrs);
+
         let (maker_online_status_feed_sender, maker_online_status_feed_receiver) =
             watch::channel(ConnectionStatus::Offline);
 
@@ -173,6 +192,8 @@ where
             monitor_addr.clone().into(),
             monitor_addr.into(),
             oracle_addr.clone().into(),
+            rollover_failed_notifications,
+            None,
         )));
 
         let (endpoint_addr, endpoint_context) = Context::new(None);
@@ -193,6 +214,7 @@ where
                     (wallet.clone().into(), wallet.clone().into()),
                     projection.clone(),
                     endpoint.clone(),
+                    referral_code.clone(),
                 )
             }
         });
@@ -212,16 +234,11 @@ where
 
         let cfd_actor_addr = taker_cfd::Actor::new(
             db.clone(),
-            projection_actor,
+            projection_actor.clone(),
             collab_settlement_addr,
             order,
             maker_identity,
-            PeerId::from(
-                maker_multiaddr
-                    .clone()
-                    .extract_peer_id()
-                    .context("Unable to extract peer id from maker address")?,
-            ),
+            PeerId::from(maker_peer_id),
         )
         .create(None)
         .spawn(&mut tasks);
@@ -248,10 +265,7 @@ where
 
         let online_status_actor = online_status::Actor::new(
             endpoint_addr.clone(),
-            maker_multiaddr
-                .clone()
-                .extract_peer_id()
-                .expect("to be able to extract peer id"),
+            maker_peer_id,
             maker_online_status_feed_sender,
         )
         .create(None)
@@ -262,7 +276,10 @@ where
 
         let dialer_constructor = {
             let endpoint_addr = endpoint_addr.clone();
-            move || dialer::Actor::new(endpoint_addr.clone(), maker_multiaddr.clone())
+            let maker_address_book = maker_address_book.clone();
+            move || {
+                dialer::Actor::with_address_book(endpoint_addr.clone(), maker_address_book.clone())
+            }
         };
         let (dialer_supervisor, dialer_actor) = Supervisor::<_, dialer::Error>::with_policy(
             dialer_constructor,
@@ -271,7 +288,9 @@ where
 
         let (offer_supervisor, offer_addr) = Supervisor::new({
             let cfd_actor_addr = cfd_actor_addr.clone();
-            move || offer::taker::Actor::new(cfd_actor_addr.clone().into())
+            move || {
+                offer::taker::Actor::new(offer::taker::Handler::new(cfd_actor_addr.clone().into()))
+            }
         });
 
         let (identify_listener_supervisor, identify_listener_actor) = Supervisor::new({
@@ -291,14 +310,23 @@ where
             identify::dialer::Actor::new_with_subscriber(endpoint_addr.clone());
         let identify_dialer_actor = identify_dialer_actor.create(None).spawn(&mut tasks);
 
+        tasks.add(learn_maker_addresses(
+            identify_info_feed_receiver.clone(),
+            maker_address_book,
+        ));
+
         let pong_address = pong::Actor.create(None).spawn(&mut tasks);
 
+        let relay_transport = RelayTransport::new(&endpoint_addr);
+
         let (supervisor, ping_actor) =
             Supervisor::new(move || ping::Actor::new(endpoint_addr.clone(), PING_INTERVAL));
         tasks.add(supervisor.run_log_summary());
 
         let endpoint = Endpoint::new(
-            Box::new(TokioTcpConfig::new),
+            Box::new(move || {
+                libp2p_utils::create_taker_transport(relay_transport.clone(), tor_socks_proxy)
+            }),
             identity.libp2p,
             ENDPOINT_CONNECTION_TIMEOUT,
             TAKER_LISTEN_PROTOCOLS.inbound_substream_handlers(
@@ -321,7 +349,8 @@ where
                 vec![],
                 vec![],
             ),
-            Arc::new(HashSet::default()), // Taker does not block peers
+            HashSet::default(), // Taker does not block peers
+            ConnectionLimits::default(),
         );
 
         tasks.add(endpoint_context.run(endpoint));
@@ -333,7 +362,7 @@ where
         let close_cfds_actor = archive_closed_cfds::Actor::new(db.clone())
             .create(None)
             .spawn(&mut tasks);
-        let archive_failed_cfds_actor = archive_failed_cfds::Actor::new(db)
+        let archive_failed_cfds_actor = archive_failed_cfds::Actor::new(db.clone())
             .create(None)
             .spawn(&mut tasks);
 
@@ -346,6 +375,8 @@ where
             auto_rollover_actor: auto_rollover_addr,
             price_feed_actor,
             executor,
+            db,
+            projection_actor,
             _close_cfds_actor: close_cfds_actor,
             _archive_failed_cfds_actor: archive_failed_cfds_actor,
             _tasks: tasks,
@@ -424,6 +455,31 @@ where
             .await?
     }
 
+    /// Update the policy according to which the CFD is rolled over automatically.
+    ///
+    /// Returns `false` if there is no open CFD with the given id.
+    #[instrument(skip(self), err)]
+    pub async fn set_rollover_policy(
+        &self,
+        order_id: OrderId,
+        policy: RolloverPolicy,
+    ) -> Result<bool> {
+        // There is no point in a policy for a closed CFD
+        match self.db.load_open_cfd::<model::Cfd>(order_id, ()).await {
+            Ok(_) => {}
+            Err(sqlite_db::Error::OpenCfdNotFound) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        }
+
+        self.db.upsert_rollover_policy(order_id, &policy).await?;
+
+        self.projection_actor
+            .send(projection::CfdChanged(order_id))
+            .await?;
+
+        Ok(true)
+    }
+
     #[instrument(skip(self), err)]
     pub async fn withdraw(
         &self,
@@ -482,6 +538,25 @@ pub fn version() -> String {
     VERSION.to_string()
 }
 
+/// Add the addresses the maker advertises via identify to the addresses we dial.
+async fn learn_maker_addresses(
+    mut identify_info_feed_receiver: watch::Receiver<Option<PeerInfo>>,
+    maker_address_book: dialer::AddressBook,
+) {
+    while identify_info_feed_receiver.changed().await.is_ok() {
+        let listen_addrs = match identify_info_feed_receiver.borrow().as_ref() {
+            Some(peer_info) => peer_info.listen_addrs.clone(),
+            None => continue,
+        };
+
+        for address in listen_addrs.into_iter().filter(libp2p_utils::is_dialable) {
+            if maker_address_book.add(address.clone()) {
+                tracing::info!(%address, "Learned new maker address");
+            }
+        }
+    }
+}
+
 fn into_price_feed_symbol(symbol: model::ContractSymbol) -> xtra_bitmex_price_feed::ContractSymbol {
     match symbol {
         model::ContractSymbol::BtcUsd => xtra_bitmex_price_feed::ContractSymbol::BtcUsd,
@@ -1,11 +1,65 @@
+use anyhow::bail;
 use anyhow::ensure;
 use anyhow::Context;
 use anyhow::Result;
+use libp2p_dns::ResolverConfig;
+use libp2p_dns::ResolverOpts;
+use libp2p_dns::TokioDnsConfig;
+use libp2p_tcp::TokioTcpConfig;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use xtra_libp2p::relay::RelayTransport;
+use xtra_libp2p::tor::TorTransport;
 
+use libp2p_core::either::EitherTransport;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::OrTransport;
 use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
+use libp2p_core::Transport;
+
+/// Creates a TCP transport that resolves `/dns`, `/dns4` and `/dns6` addresses upon dialing.
+///
+/// Addresses are resolved on every dial, which allows the other party to change their IP address,
+/// e.g. when running behind dynamic DNS.
+pub fn create_tcp_transport() -> TokioDnsConfig<TokioTcpConfig> {
+    TokioDnsConfig::system(TokioTcpConfig::new()).unwrap_or_else(|e| {
+        tracing::warn!("Failed to load system DNS configuration, using default resolver: {e:#}");
+
+        TokioDnsConfig::custom(
+            TokioTcpConfig::new(),
+            ResolverConfig::default(),
+            ResolverOpts::default(),
+        )
+        .expect("default DNS configuration to be valid")
+    })
+}
+
+/// Creates the transport of the maker.
+///
+/// In addition to TCP, the maker can listen on `/p2p-circuit` addresses of relays, which allows
+/// takers to reach a maker which cannot be dialed directly, e.g. because it is behind a NAT.
+pub fn create_maker_transport(
+    relay: RelayTransport,
+) -> OrTransport<RelayTransport, TokioDnsConfig<TokioTcpConfig>> {
+    relay.or_transport(create_tcp_transport())
+}
+
+/// Creates the transport for connecting to the maker.
+///
+/// If the SOCKS5 proxy of a Tor daemon is given, all connections are made through Tor, which hides
+/// our IP address from the maker. Makers listening on a relay are dialed through the relay.
+pub fn create_taker_transport(
+    relay: RelayTransport,
+    tor_socks_proxy: Option<SocketAddr>,
+) -> OrTransport<RelayTransport, EitherTransport<TokioDnsConfig<TokioTcpConfig>, TorTransport>> {
+    let transport = match tor_socks_proxy {
+        Some(socks_proxy) => EitherTransport::Right(TorTransport::new(socks_proxy)),
+        None => EitherTransport::Left(create_tcp_transport()),
+    };
+
+    relay.or_transport(transport)
+}
 
 /// Creates MultiAddr from SocketAddr and PeerId
 pub fn create_connect_tcp_multiaddr(
@@ -14,9 +68,57 @@ pub fn create_connect_tcp_multiaddr(
 ) -> Result<Multiaddr> {
     let ip = socket_addr.ip();
     let port = socket_addr.port();
-    ensure!(socket_addr.is_ipv4(), "only ipv4 is supported");
+    let ip_protocol = ip_protocol(&ip);
+
+    format!("/{ip_protocol}/{ip}/tcp/{port}/p2p/{peer_id}")
+        .parse::<Multiaddr>()
+        .with_context(|| "failed to construct multiaddr")
+}
+
+/// Creates MultiAddr from the address of the other party and their PeerId.
+///
+/// The address is either a multiaddr, e.g. `/dns4/example.com/tcp/10000`, or of the form
+/// `host:port`, where host is an IPv4 address, an IPv6 address in brackets or a hostname.
+/// Hostnames are only resolved upon dialing, see [`create_tcp_transport`].
+pub fn parse_connect_tcp_multiaddr(address: &str, peer_id: PeerId) -> Result<Multiaddr> {
+    if address.starts_with('/') {
+        let mut multiaddr = address
+            .parse::<Multiaddr>()
+            .with_context(|| format!("failed to parse multiaddr {address}"))?;
+
+        return match multiaddr.iter().last() {
+            Some(Protocol::P2p(hash)) => {
+                let address_peer_id =
+                    PeerId::from_multihash(hash).context("invalid peer id in multiaddr")?;
+                ensure!(
+                    address_peer_id == peer_id,
+                    "peer id {address_peer_id} in multiaddr does not match {peer_id}"
+                );
 
-    format!("/ip4/{ip}/tcp/{port}/p2p/{peer_id}")
+                Ok(multiaddr)
+            }
+            _ => {
+                multiaddr.push(Protocol::P2p(peer_id.into()));
+                Ok(multiaddr)
+            }
+        };
+    }
+
+    if let Ok(socket_addr) = address.parse::<SocketAddr>() {
+        return create_connect_tcp_multiaddr(&socket_addr, peer_id);
+    }
+
+    let (host, port) = address
+        .rsplit_once(':')
+        .with_context(|| format!("address {address} does not contain a port"))?;
+    let port = port
+        .parse::<u16>()
+        .with_context(|| format!("invalid port in address {address}"))?;
+    if host.is_empty() || host.contains(':') {
+        bail!("invalid host in address {address}");
+    }
+
+    format!("/dns/{host}/tcp/{port}/p2p/{peer_id}")
         .parse::<Multiaddr>()
         .with_context(|| "failed to construct multiaddr")
 }
@@ -34,13 +136,35 @@ pub fn create_connect_multiaddr(
 
 /// Creates MultiAddr from SocketAddr
 pub fn create_listen_tcp_multiaddr(ip: &IpAddr, port: u16) -> Result<Multiaddr> {
-    ensure!(ip.is_ipv4(), "only ipv4 is supported");
+    let ip_protocol = ip_protocol(ip);
 
-    format!("/ip4/{ip}/tcp/{port}")
+    format!("/{ip_protocol}/{ip}/tcp/{port}")
         .parse::<Multiaddr>()
         .with_context(|| "failed to construct multiaddr")
 }
 
+fn ip_protocol(ip: &IpAddr) -> &'static str {
+    match ip {
+        IpAddr::V4(_) => "ip4",
+        IpAddr::V6(_) => "ip6",
+    }
+}
+
+/// Whether the address can be used to dial the other party.
+///
+/// Listen addresses with an unspecified IP, e.g. `/ip4/0.0.0.0/tcp/10000`, are only meaningful to
+/// the party listening on them.
+pub fn is_dialable(address: &Multiaddr) -> bool {
+    match address.iter().next() {
+        Some(Protocol::Ip4(ip)) => !ip.is_unspecified(),
+        Some(Protocol::Ip6(ip)) => !ip.is_unspecified(),
+        Some(Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) | Protocol::Onion3(_)) => {
+            true
+        }
+        _ => false,
+    }
+}
+
 /// Determine whether to use libp2p or fallback to a legacy protocol
 pub fn can_use_libp2p(cfd: &model::Cfd) -> bool {
     // Our abitily to kick-off a libp2p version of protocol is constrained by
@@ -48,3 +172,83 @@ pub fn can_use_libp2p(cfd: &model::Cfd) -> bool {
     // can't dial in to them using libp2p.
     cfd.counterparty_peer_id().is_some()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER_ID: &str = "12D3KooWP3BN6bq9jPy8cP7Grj1QyUBfr7U6BeQFgMwfTTu12wuY";
+
+    fn peer_id() -> PeerId {
+        PEER_ID.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_ipv4_ipv6_and_hostname_addresses() {
+        let cases = [
+            (
+                "127.0.0.1:10000",
+                format!("/ip4/127.0.0.1/tcp/10000/p2p/{PEER_ID}"),
+            ),
+            ("[::1]:10000", format!("/ip6/::1/tcp/10000/p2p/{PEER_ID}")),
+            (
+                "mainnet.itchysats.network:10001",
+                format!("/dns/mainnet.itchysats.network/tcp/10001/p2p/{PEER_ID}"),
+            ),
+            (
+                "/dns6/maker.example.com/tcp/10000",
+                format!("/dns6/maker.example.com/tcp/10000/p2p/{PEER_ID}"),
+            ),
+        ];
+
+        for (address, expected) in cases {
+            let multiaddr = parse_connect_tcp_multiaddr(address, peer_id()).unwrap();
+
+            assert_eq!(
+                multiaddr,
+                expected.parse::<Multiaddr>().unwrap(),
+                "{address}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_address_with_different_peer_id() {
+        let other_peer_id = PeerId::random();
+        let address = format!("/ip4/127.0.0.1/tcp/10000/p2p/{other_peer_id}");
+
+        assert!(parse_connect_tcp_multiaddr(&address, peer_id()).is_err());
+    }
+
+    #[test]
+    fn rejects_address_without_port() {
+        assert!(parse_connect_tcp_multiaddr("maker.example.com", peer_id()).is_err());
+        assert!(parse_connect_tcp_multiaddr("::1", peer_id()).is_err());
+    }
+
+    #[test]
+    fn unspecified_addresses_are_not_dialable() {
+        for address in [
+            "/ip4/0.0.0.0/tcp/10000",
+            "/ip6/::/tcp/10000",
+            "/memory/10000",
+        ] {
+            assert!(!is_dialable(&address.parse().unwrap()), "{address}");
+        }
+
+        for address in [
+            "/ip4/127.0.0.1/tcp/10000",
+            "/ip6/::1/tcp/10000",
+            "/dns/maker.example.com/tcp/10000",
+        ] {
+            assert!(is_dialable(&address.parse().unwrap()), "{address}");
+        }
+    }
+
+    #[test]
+    fn creates_ipv6_listen_address() {
+        let multiaddr = create_listen_tcp_multiaddr(&"::".parse().unwrap(), 10000).unwrap();
+
+        assert_eq!(multiaddr, "/ip6/::/tcp/10000".parse::<Multiaddr>().unwrap());
+    }
+}
@@ -7,45 +7,49 @@ use ping_pong::pong;
 use std::collections::HashSet;
 use xtra::message_channel::MessageChannel;
 use xtra::Address;
+use xtra_libp2p::relay;
+use xtra_libp2p::versions::Versions;
 use xtra_libp2p::NewInboundSubstream;
 
 pub const MAKER_LISTEN_PROTOCOLS: MakerListenProtocols = MakerListenProtocols::new(
     ping_pong::PROTOCOL,
     identify::PROTOCOL,
-    (order::PROTOCOL, order::deprecated::PROTOCOL),
-    (rollover::PROTOCOL, rollover::deprecated::PROTOCOL),
-    (
-        collab_settlement::PROTOCOL,
-        collab_settlement::deprecated::PROTOCOL,
-    ),
+    &order::VERSIONS,
+    &rollover::VERSIONS,
+    &collab_settlement::VERSIONS,
+    relay::STOP_PROTOCOL,
 );
 
-pub const TAKER_LISTEN_PROTOCOLS: TakerListenProtocols =
-    TakerListenProtocols::new(ping_pong::PROTOCOL, identify::PROTOCOL, offer::PROTOCOL);
+// Takers only understand the latest version of the offer protocol
+pub const TAKER_LISTEN_PROTOCOLS: TakerListenProtocols = TakerListenProtocols::new(
+    ping_pong::PROTOCOL,
+    identify::PROTOCOL,
+    offer::VERSIONS.latest().0,
+);
 
+// Takers always propose collaborative settlements according to the latest version
 pub const REQUIRED_MAKER_LISTEN_PROTOCOLS: RequiredMakerListenProtocols =
     RequiredMakerListenProtocols::new(
         ping_pong::PROTOCOL,
         identify::PROTOCOL,
-        order::PROTOCOL,
-        rollover::PROTOCOL,
-        collab_settlement::PROTOCOL,
+        &order::VERSIONS,
+        &rollover::VERSIONS,
+        collab_settlement::VERSIONS.latest().0,
     );
 
 /// Verify if the listen protocols that the `maker` supports are
 /// sufficient to fulfil the `requirements` of the taker.
+///
+/// For protocols with several versions, the maker has to support at least one of them.
 pub fn does_maker_satisfy_taker_needs(
     maker: &HashSet<String>,
     requirements: RequiredMakerListenProtocols,
 ) -> Result<(), HashSet<String>> {
     // missing protocols are those that are in requirements but not in maker protocols
-    let missing_protocols = &HashSet::<String>::from(requirements)
-        .difference(maker)
-        .cloned()
-        .collect::<HashSet<_>>();
+    let missing_protocols = requirements.missing(maker);
 
     if !missing_protocols.is_empty() {
-        return Err(missing_protocols.clone());
+        return Err(missing_protocols);
     }
 
     Ok(())
@@ -56,99 +60,99 @@ pub fn does_maker_satisfy_taker_needs(
 pub struct MakerListenProtocols {
     ping: &'static str,
     identify: &'static str,
-    order: &'static str,
-    order_deprecated: &'static str,
-    rollover: &'static str,
-    rollover_deprecated: &'static str,
-    collaborative_settlement: &'static str,
-    collaborative_settlement_deprecated: &'static str,
+    order: &'static Versions<order::Version>,
+    rollover: &'static Versions<rollover::Version>,
+    collaborative_settlement: &'static Versions<collab_settlement::Version>,
+    /// Accepting circuits from the relays the maker listens on.
+    relay: &'static str,
 }
 
-type RolloverAddress<R> =
-    Address<rollover::maker::Actor<command::Executor, oracle::AnnouncementsChannel, R>>;
-
-type RolloverDeprecatedAddress<RD> = Address<
-    rollover::deprecated::maker::Actor<command::Executor, oracle::AnnouncementsChannel, RD>,
->;
+type RolloverAddress<R, P> =
+    Address<rollover::maker::Actor<command::Executor, oracle::AnnouncementsChannel, R, P>>;
 
 impl MakerListenProtocols {
-    pub const NR_OF_SUPPORTED_PROTOCOLS: usize = 8;
+    pub const NR_OF_SUPPORTED_PROTOCOLS: usize = 3
+        + order::VERSIONS.count()
+        + rollover::VERSIONS.count()
+        + collab_settlement::VERSIONS.count();
 
     pub const fn new(
         ping: &'static str,
         identify: &'static str,
-        (order, order_deprecated): (&'static str, &'static str),
-        (rollover, rollover_deprecated): (&'static str, &'static str),
-        (collaborative_settlement, collaborative_settlement_deprecated): (
-            &'static str,
-            &'static str,
-        ),
+        order: &'static Versions<order::Version>,
+        rollover: &'static Versions<rollover::Version>,
+        collaborative_settlement: &'static Versions<collab_settlement::Version>,
+        relay: &'static str,
     ) -> Self {
         Self {
             ping,
             identify,
             order,
-            order_deprecated,
             rollover,
-            rollover_deprecated,
             collaborative_settlement,
-            collaborative_settlement_deprecated,
+            relay,
         }
     }
 
     /// Construct a map of protocol identifiers to actor addresses.
     ///
     /// This is used so that the `Endpoint` knows who to delegate to
-    /// when receiving new inbound substreams.
-    pub fn inbound_substream_handlers<R, RD>(
+    /// when receiving new inbound substreams. All versions of a protocol are handled by the same
+    /// actor.
+    pub fn inbound_substream_handlers<R, P>(
         &self,
         ping_handler: Address<pong::Actor>,
         identify_handler: Address<identify::listener::Actor>,
-        (order_handler, order_deprecated_handler): (
-            Address<order::maker::Actor>,
-            Address<order::deprecated::maker::Actor>,
-        ),
-        (rollover_handler, rollover_deprecated_handler): (
-            RolloverAddress<R>,
-            RolloverDeprecatedAddress<RD>,
-        ),
-        (collaborative_settlement_handler, collaborative_settlement_deprecated_handler): (
-            Address<collab_settlement::maker::Actor>,
-            Address<collab_settlement::deprecated::maker::Actor>,
-        ),
+        order_handler: Address<order::maker::Actor>,
+        rollover_handler: RolloverAddress<R, P>,
+        collaborative_settlement_handler: Address<collab_settlement::maker::Actor>,
+        relay_handler: Address<relay::client::Actor>,
     ) -> [(&'static str, MessageChannel<NewInboundSubstream, ()>); Self::NR_OF_SUPPORTED_PROTOCOLS]
     where
         R: rollover::protocol::GetRates + Send + Sync + Clone + 'static,
-        RD: rollover::deprecated::protocol::GetRates + Send + Sync + Clone + 'static,
+        P: rollover::protocol::DecideRollover + Send + Sync + Clone + 'static,
     {
         // We deconstruct to ensure that all protocols are being used
         let MakerListenProtocols {
             ping,
             identify,
             order,
-            order_deprecated,
             rollover,
-            rollover_deprecated,
             collaborative_settlement,
-            collaborative_settlement_deprecated,
-        } = self;
+            relay,
+        } = *self;
 
-        [
+        let mut handlers: Vec<(&'static str, MessageChannel<NewInboundSubstream, ()>)> = vec![
             (ping, ping_handler.into()),
             (identify, identify_handler.into()),
-            (order, order_handler.into()),
-            (order_deprecated, order_deprecated_handler.into()),
-            (rollover, rollover_handler.into()),
-            (rollover_deprecated, rollover_deprecated_handler.into()),
-            (
-                collaborative_settlement,
-                collaborative_settlement_handler.into(),
-            ),
-            (
-                collaborative_settlement_deprecated,
-                collaborative_settlement_deprecated_handler.into(),
-            ),
-        ]
+            (relay, relay_handler.into()),
+        ];
+        handlers.extend(
+            order
+                .protocols()
+                .into_iter()
+                .map(|protocol| (protocol, order_handler.clone().into())),
+        );
+        handlers.extend(
+            rollover
+                .protocols()
+                .into_iter()
+                .map(|protocol| (protocol, rollover_handler.clone().into())),
+        );
+        handlers.extend(
+            collaborative_settlement
+                .protocols()
+                .into_iter()
+                .map(|protocol| (protocol, collaborative_settlement_handler.clone().into())),
+        );
+
+        handlers.try_into().unwrap_or_else(|handlers: Vec<_>| {
+            unreachable!(
+                "Expected {} handlers but got {}",
+                Self::NR_OF_SUPPORTED_PROTOCOLS,
+                handlers.len()
+            )
+        })
     }
 }
 
@@ -159,23 +163,18 @@ impl From<MakerListenProtocols> for HashSet<String> {
             ping,
             identify,
             order,
-            order_deprecated,
             rollover,
-            rollover_deprecated,
             collaborative_settlement,
-            collaborative_settlement_deprecated,
+            relay,
         } = maker;
 
-        HashSet::from([
-            ping.to_string(),
-            identify.to_string(),
-            order.to_string(),
-            order_deprecated.to_string(),
-            rollover.to_string(),
-            rollover_deprecated.to_string(),
-            collaborative_settlement.to_string(),
-            collaborative_settlement_deprecated.to_string(),
-        ])
+        [ping, identify, relay]
+            .into_iter()
+            .chain(order.protocols())
+            .chain(rollover.protocols())
+            .chain(collaborative_settlement.protocols())
+            .map(|protocol| protocol.to_string())
+            .collect()
     }
 }
 
@@ -185,8 +184,8 @@ impl From<MakerListenProtocols> for HashSet<String> {
 pub struct RequiredMakerListenProtocols {
     ping: &'static str,
     identify: &'static str,
-    order: &'static str,
-    rollover: &'static str,
+    order: &'static Versions<order::Version>,
+    rollover: &'static Versions<rollover::Version>,
     collaborative_settlement: &'static str,
 }
 
@@ -194,8 +193,8 @@ impl RequiredMakerListenProtocols {
     pub const fn new(
         ping: &'static str,
         identify: &'static str,
-        order: &'static str,
-        rollover: &'static str,
+        order: &'static Versions<order::Version>,
+        rollover: &'static Versions<rollover::Version>,
         collaborative_settlement: &'static str,
     ) -> Self {
         Self {
@@ -206,26 +205,35 @@ impl RequiredMakerListenProtocols {
             collaborative_settlement,
         }
     }
-}
 
-impl From<RequiredMakerListenProtocols> for HashSet<String> {
-    fn from(required: RequiredMakerListenProtocols) -> Self {
-        // We deconstruct to ensure that all protocols are being used
+    /// The required protocols which are not supported by the `maker`.
+    ///
+    /// If none of the versions of a protocol is supported, the latest version is reported as
+    /// missing.
+    fn missing(&self, maker: &HashSet<String>) -> HashSet<String> {
+        // We deconstruct to ensure that all protocols are being checked
         let RequiredMakerListenProtocols {
             ping,
             identify,
             order,
             rollover,
             collaborative_settlement,
-        } = required;
-
-        HashSet::from([
-            ping.to_string(),
-            identify.to_string(),
-            order.to_string(),
-            rollover.to_string(),
-            collaborative_settlement.to_string(),
-        ])
+        } = *self;
+
+        let unversioned = [ping, identify, collaborative_settlement]
+            .into_iter()
+            .filter(|protocol| !maker.contains(*protocol));
+        let order = order.negotiate(maker).is_none().then(|| order.latest().0);
+        let rollover = rollover
+            .negotiate(maker)
+            .is_none()
+            .then(|| rollover.latest().0);
+
+        unversioned
+            .chain(order)
+            .chain(rollover)
+            .map(|protocol| protocol.to_string())
+            .collect()
     }
 }
 
@@ -330,6 +338,41 @@ mod tests {
         assert!(result.is_ok(), "Missing protocols detected: {result:?}");
     }
 
+    #[test]
+    fn given_maker_supports_only_older_version_then_ok() {
+        let mut maker_protocols_as_hashset: HashSet<String> = MAKER_LISTEN_PROTOCOLS.into();
+        let newer_versions = order::VERSIONS.count() - 1;
+        for protocol in order::VERSIONS.protocols().into_iter().take(newer_versions) {
+            maker_protocols_as_hashset.remove(protocol);
+        }
+
+        let result = does_maker_satisfy_taker_needs(
+            &maker_protocols_as_hashset,
+            REQUIRED_MAKER_LISTEN_PROTOCOLS,
+        );
+
+        assert!(result.is_ok(), "Missing protocols detected: {result:?}");
+    }
+
+    #[test]
+    fn given_maker_supports_no_version_then_error_with_latest_version() {
+        let mut maker_protocols_as_hashset: HashSet<String> = MAKER_LISTEN_PROTOCOLS.into();
+        for protocol in rollover::VERSIONS.protocols() {
+            maker_protocols_as_hashset.remove(protocol);
+        }
+
+        let err = does_maker_satisfy_taker_needs(
+            &maker_protocols_as_hashset,
+            REQUIRED_MAKER_LISTEN_PROTOCOLS,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err,
+            HashSet::from([rollover::VERSIONS.latest().0.to_string()])
+        )
+    }
+
     #[test]
     fn ensure_nr_of_maker_protocols_matches_hashset_len() {
         let maker_protocols_as_hashset: HashSet<String> = MAKER_LISTEN_PROTOCOLS.into();
@@ -0,0 +1,692 @@
+//! Notifies the user about CFDs which require their attention, e.g. because they are about to
+//! expire without having been rolled over.
+//!
+//! Notifications are delivered through all configured channels: a webhook, email through an SMTP
+//! server and the desktop, if the daemon runs inside the desktop application.
+//!
+//! Which reminders were sent is persisted, hence they are not repeated after a restart. Webhook
+//! notifications are queued in the webhook outbox, which the taker does not use otherwise, and
+//! retried like the webhooks of CFD events.
+
+use crate::event_webhooks;
+use crate::online_status::ConnectionStatus;
+use crate::projection;
+use crate::projection::CfdState;
+use anyhow::bail;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use model::OrderId;
+use model::Position;
+use model::Timestamp;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::sync::watch;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+pub use reqwest::Url;
+
+/// How often the CFDs are checked for reminders which are due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the webhook outbox is checked for notifications which are due.
+const WEBHOOK_DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Timeout for delivering a notification through a webhook or email.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to connect to the maker after startup before it is considered offline.
+const MAKER_CONNECT_GRACE_PERIOD: time::Duration = time::Duration::minutes(5);
+
+pub struct Config {
+    /// Remind the user if a CFD expires within this duration.
+    pub expiry_reminder: time::Duration,
+    /// Warn the user if the price is within this percentage of the liquidation price.
+    pub liquidation_warning_percent: u8,
+    /// URL notifications are `POST`ed to as JSON.
+    pub webhook: Option<Url>,
+    pub email: Option<EmailConfig>,
+}
+
+/// Deliver notifications as email through an SMTP server which accepts mail without
+/// authentication, such as a relay running on the same machine.
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    /// Address of the SMTP server, see [`parse_smtp_server`].
+    pub smtp_server: SocketAddr,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub order_id: Option<OrderId>,
+    pub title: String,
+    pub message: String,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    ExpiryApproaching,
+    RolloverFailed,
+    LiquidationApproaching,
+    MakerOffline,
+}
+
+impl Config {
+    fn liquidation_warning_threshold(&self) -> Decimal {
+        Decimal::new(i64::from(self.liquidation_warning_percent), 2)
+    }
+}
+
+impl Notification {
+    fn new(kind: NotificationKind, order_id: Option<OrderId>, message: String) -> Self {
+        let title = match kind {
+            NotificationKind::ExpiryApproaching => "CFD about to expire",
+            NotificationKind::RolloverFailed => "Rollover failed",
+            NotificationKind::LiquidationApproaching => "Liquidation price approaching",
+            NotificationKind::MakerOffline => "Maker offline",
+        };
+
+        Self {
+            kind,
+            order_id,
+            title: title.to_owned(),
+            message,
+            timestamp: Timestamp::now(),
+        }
+    }
+}
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+    config: Config,
+    client: reqwest::Client,
+    desktop: Option<mpsc::UnboundedSender<Notification>>,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    quotes: watch::Receiver<projection::LatestQuotes>,
+    maker_online_status: watch::Receiver<ConnectionStatus>,
+    reminders: Reminders,
+    started_at: OffsetDateTime,
+}
+
+impl Actor {
+    pub fn new(
+        db: sqlite_db::Connection,
+        config: Config,
+        desktop: Option<mpsc::UnboundedSender<Notification>>,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+        quotes: watch::Receiver<projection::LatestQuotes>,
+        maker_online_status: watch::Receiver<ConnectionStatus>,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            client: reqwest::Client::new(),
+            desktop,
+            cfds,
+            quotes,
+            maker_online_status,
+            reminders: Reminders::default(),
+            started_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    async fn load_reminders(&mut self) -> Result<()> {
+        self.reminders.expiry = self.db.load_expiry_reminders().await?;
+        self.reminders.maker_offline = self.db.is_maker_offline_notified().await?;
+
+        Ok(())
+    }
+
+    /// Persist that the reminder was sent, to not repeat it after a restart.
+    async fn record_reminder(&self, notification: &Notification) -> Result<()> {
+        match (notification.kind, notification.order_id) {
+            (NotificationKind::ExpiryApproaching, Some(order_id)) => {
+                let expiry = *self
+                    .reminders
+                    .expiry
+                    .get(&order_id)
+                    .context("Expiry reminder not tracked")?;
+                self.db.upsert_expiry_reminder(order_id, expiry).await?;
+            }
+            (NotificationKind::MakerOffline, _) => {
+                self.db
+                    .insert_maker_offline_reminder(notification.timestamp)
+                    .await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Collect the notifications which are due given the current CFDs, quotes and status of the
+    /// maker.
+    fn due_notifications(&mut self) -> Vec<Notification> {
+        let cfds = self.cfds.borrow();
+        let cfds = match cfds.as_ref() {
+            Some(cfds) => cfds,
+            // CFDs are still being loaded
+            None => return Vec::new(),
+        };
+        let quotes = self.quotes.borrow();
+        let now = OffsetDateTime::now_utc();
+
+        let mut notifications = Vec::new();
+
+        let open_cfds = cfds.iter().filter(|cfd| is_open(cfd.state));
+        for cfd in open_cfds.clone() {
+            if let Some(expiry) = cfd.expiry_timestamp {
+                if expiry - now <= self.config.expiry_reminder
+                    && self
+                        .reminders
+                        .expiry(cfd.order_id, Timestamp::new(expiry.unix_timestamp()))
+                {
+                    let hours = (expiry - now).whole_hours().max(0);
+                    notifications.push(Notification::new(
+                        NotificationKind::ExpiryApproaching,
+                        Some(cfd.order_id),
+                        format!(
+                            "CFD {} expires in {hours} hours and has not been rolled over yet. \
+                             Keep the daemon online to roll it over, otherwise it will be \
+                             settled at the oracle's attested price.",
+                            cfd.order_id
+                        ),
+                    ));
+                }
+            }
+
+            let price = quotes
+                .get(&cfd.contract_symbol)
+                .map(|quote| quote.closing_price(cfd.role, cfd.position));
+            let price = match price {
+                Some(Ok(price)) => price.into_decimal(),
+                Some(Err(e)) => {
+                    tracing::debug!(order_id = %cfd.order_id, "Invalid quote: {e:#}");
+                    continue;
+                }
+                None => continue,
+            };
+
+            let distance = liquidation_distance(cfd.position, cfd.liquidation_price, price);
+            let in_danger = distance <= self.config.liquidation_warning_threshold();
+            if self.reminders.liquidation(cfd.order_id, in_danger) {
+                notifications.push(Notification::new(
+                    NotificationKind::LiquidationApproaching,
+                    Some(cfd.order_id),
+                    format!(
+                        "The price of {} is within {}% of the liquidation price {} of CFD {}.",
+                        cfd.contract_symbol,
+                        (distance * Decimal::ONE_HUNDRED)
+                            .max(Decimal::ZERO)
+                            .round_dp(1),
+                        cfd.liquidation_price.round_dp(2),
+                        cfd.order_id
+                    ),
+                ));
+            }
+        }
+
+        let open_cfds = open_cfds.count();
+        let maker_online = self.maker_online_status.borrow().is_online();
+        let connecting = now - self.started_at < MAKER_CONNECT_GRACE_PERIOD;
+        if maker_online {
+            self.reminders.maker_online();
+        } else if open_cfds > 0 && !connecting && self.reminders.maker_offline() {
+            notifications.push(Notification::new(
+                NotificationKind::MakerOffline,
+                None,
+                format!(
+                    "The maker is offline. Your {open_cfds} open CFD(s) cannot be rolled over or \
+                     settled collaboratively until it is back online."
+                ),
+            ));
+        }
+
+        notifications
+    }
+
+    async fn notify(&self, notification: Notification, this: &xtra::Address<Self>) {
+        tracing::info!(
+            kind = ?notification.kind,
+            order_id = ?notification.order_id,
+            "{}",
+            notification.message
+        );
+
+        if let Some(desktop) = &self.desktop {
+            if desktop.send(notification.clone()).is_err() {
+                tracing::warn!("Desktop notifications are no longer received");
+            }
+        }
+
+        if let Some(url) = &self.config.webhook {
+            if let Err(e) = self.enqueue_webhook(url, &notification).await {
+                tracing::warn!("Failed to queue webhook notification: {e:#}");
+            }
+        }
+
+        if let Some(email) = self.config.email.clone() {
+            tokio_extras::spawn_fallible(
+                this,
+                async move {
+                    tokio_extras::time::timeout(
+                        DELIVERY_TIMEOUT,
+                        send_email(&email, &notification),
+                        || tracing::debug_span!("Send email"),
+                    )
+                    .await
+                    .context("SMTP server did not respond in time")?
+                },
+                |e| async move { tracing::warn!("Failed to deliver notification by email: {e:#}") },
+            );
+        }
+    }
+
+    async fn enqueue_webhook(&self, url: &Url, notification: &Notification) -> Result<()> {
+        let payload = serde_json::to_string(notification)?;
+        self.db
+            .enqueue_webhooks(&[url.to_string()], &payload, Timestamp::now())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn post(&self, url: &Url, payload: &str) -> Result<()> {
+        let response = self
+            .client
+            .post(url.clone())
+            .timeout(DELIVERY_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.to_owned())
+            .send()
+            .await
+            .with_context(|| format!("Failed to POST {url}"))?;
+
+        let code = response.status();
+        if !code.is_success() {
+            bail!("POST {url} responded with {code}");
+        }
+
+        Ok(())
+    }
+
+    async fn deliver_webhooks(&self) -> Result<()> {
+        for entry in self.db.load_due_webhooks(Timestamp::now()).await? {
+            match &self.config.webhook {
+                Some(url) if url.as_str() == entry.url => {
+                    match self.post(url, &entry.payload).await {
+                        Ok(()) => self.db.delete_webhook(entry.id).await?,
+                        Err(e) => {
+                            event_webhooks::record_failed_delivery(&self.db, &entry, e).await?
+                        }
+                    }
+                }
+                _ => {
+                    tracing::warn!(url = %entry.url, "Dropping notification of removed webhook");
+                    self.db.delete_webhook(entry.id).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: CheckReminders, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        let maker_offline_notified = self.reminders.maker_offline;
+        let notifications = self.due_notifications();
+        if maker_offline_notified && !self.reminders.maker_offline {
+            if let Err(e) = self.db.update_maker_online(Timestamp::now()).await {
+                tracing::warn!("Failed to record maker being online: {e:#}");
+            }
+        }
+
+        for notification in notifications {
+            if let Err(e) = self.record_reminder(&notification).await {
+                tracing::warn!("Failed to record reminder: {e:#}");
+            }
+            self.notify(notification, &this).await;
+        }
+    }
+
+    async fn handle(&mut self, _: DeliverWebhooks) {
+        if let Err(e) = self.deliver_webhooks().await {
+            tracing::error!("Failed to deliver webhook notifications: {e:#}");
+        }
+    }
+
+    async fn handle(&mut self, msg: RolloverFailed, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+
+        self.notify(
+            Notification::new(
+                NotificationKind::RolloverFailed,
+                Some(msg.order_id),
+                format!(
+                    "Rolling over CFD {} failed. It will be retried automatically while the \
+                     daemon is online.",
+                    msg.order_id
+                ),
+            ),
+            &this,
+        )
+        .await;
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        if let Err(e) = self.load_reminders().await {
+            tracing::warn!("Failed to load reminders: {e:#}");
+        }
+
+        let this = ctx.address().expect("we are alive");
+        tokio_extras::spawn(
+            &this.clone(),
+            this.clone().send_interval(
+                CHECK_INTERVAL,
+                || CheckReminders,
+                xtras::IncludeSpan::Always,
+            ),
+        );
+        tokio_extras::spawn(
+            &this.clone(),
+            this.send_interval(
+                WEBHOOK_DELIVERY_INTERVAL,
+                || DeliverWebhooks,
+                xtras::IncludeSpan::Always,
+            ),
+        );
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Message sent to ourselves at an interval to check for reminders which are due.
+#[derive(Clone, Copy)]
+pub struct CheckReminders;
+
+/// Message sent to ourselves at an interval to deliver the webhook notifications which are due.
+#[derive(Clone, Copy)]
+pub struct DeliverWebhooks;
+
+/// A rollover of the CFD failed.
+#[derive(Clone, Copy)]
+pub struct RolloverFailed {
+    pub order_id: OrderId,
+}
+
+/// Keeps track of the reminders which were already sent, to only send each once.
+#[derive(Default)]
+struct Reminders {
+    /// The expiry the user was last reminded of per CFD.
+    ///
+    /// A rollover extends the expiry, which re-arms the reminder.
+    expiry: HashMap<OrderId, Timestamp>,
+    /// CFDs for which the user was warned of the approaching liquidation.
+    ///
+    /// The warning is re-armed once the price moves away from the liquidation price.
+    liquidation: HashSet<OrderId>,
+    /// Whether the user was notified about the current outage of the maker.
+    maker_offline: bool,
+}
+
+impl Reminders {
+    /// Whether the user has to be reminded of the expiry of the CFD.
+    fn expiry(&mut self, order_id: OrderId, expiry: Timestamp) -> bool {
+        self.expiry.insert(order_id, expiry) != Some(expiry)
+    }
+
+    /// Whether the user has to be warned of the approaching liquidation of the CFD.
+    fn liquidation(&mut self, order_id: OrderId, in_danger: bool) -> bool {
+        if in_danger {
+            self.liquidation.insert(order_id)
+        } else {
+            self.liquidation.remove(&order_id);
+            false
+        }
+    }
+
+    /// Whether the user has to be notified about the maker being offline, once per outage.
+    fn maker_offline(&mut self) -> bool {
+        !std::mem::replace(&mut self.maker_offline, true)
+    }
+
+    /// The maker is online, which ends the outage.
+    fn maker_online(&mut self) {
+        self.maker_offline = false;
+    }
+}
+
+fn is_open(state: CfdState) -> bool {
+    matches!(
+        state,
+        CfdState::Open
+            | CfdState::IncomingSettlementProposal
+            | CfdState::OutgoingSettlementProposal
+            | CfdState::RolloverSetup
+    )
+}
+
+/// The distance of the price from the liquidation price, as fraction of the price.
+///
+/// Negative if the price already crossed the liquidation price.
+fn liquidation_distance(position: Position, liquidation_price: Decimal, price: Decimal) -> Decimal {
+    if price.is_zero() {
+        return Decimal::ZERO;
+    }
+
+    match position {
+        Position::Long => (price - liquidation_price) / price,
+        Position::Short => (liquidation_price - price) / price,
+    }
+}
+
+/// Parse the address of the SMTP server as `ip:port`.
+///
+/// Mail is sent unencrypted and without authentication, hence only servers on the loopback
+/// interface are accepted.
+pub fn parse_smtp_server(s: &str) -> Result<SocketAddr> {
+    let address = s
+        .parse::<SocketAddr>()
+        .with_context(|| format!("Invalid SMTP server address: {s}"))?;
+    ensure!(
+        address.ip().is_loopback(),
+        "SMTP server has to be on the loopback interface, got {address}"
+    );
+
+    Ok(address)
+}
+
+/// Send the notification as email through a minimal SMTP exchange.
+async fn send_email(config: &EmailConfig, notification: &Notification) -> Result<()> {
+    let stream = TcpStream::connect(&config.smtp_server)
+        .await
+        .with_context(|| format!("Failed to connect to SMTP server {}", config.smtp_server))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    expect_reply(&mut reader, 220).await?;
+
+    for (command, code) in [
+        ("HELO itchysats".to_owned(), 250),
+        (format!("MAIL FROM:<{}>", config.from), 250),
+        (format!("RCPT TO:<{}>", config.to), 250),
+        ("DATA".to_owned(), 354),
+    ] {
+        writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await?;
+        expect_reply(&mut reader, code)
+            .await
+            .with_context(|| format!("SMTP command {command} failed"))?;
+    }
+
+    writer
+        .write_all(email_body(config, notification).as_bytes())
+        .await?;
+    expect_reply(&mut reader, 250).await?;
+
+    writer.write_all(b"QUIT\r\n").await?;
+
+    Ok(())
+}
+
+/// Read a (possibly multiline) reply of the SMTP server and check its code.
+async fn expect_reply<R>(reader: &mut R, expected: u16) -> Result<()>
+where
+    R: AsyncBufReadExt + Unpin,
+{
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("SMTP server closed the connection");
+        }
+
+        let code = line
+            .get(..3)
+            .and_then(|code| code.parse::<u16>().ok())
+            .with_context(|| format!("Invalid SMTP reply: {}", line.trim_end()))?;
+
+        // A hyphen after the code indicates that the reply continues on the next line
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+
+        if code != expected {
+            bail!("Expected SMTP reply {expected}, got: {}", line.trim_end());
+        }
+
+        return Ok(());
+    }
+}
+
+/// The email including headers, terminated by the end of data marker.
+fn email_body(config: &EmailConfig, notification: &Notification) -> String {
+    // Lines starting with a dot have to be escaped, as a single dot ends the data
+    let message = notification
+        .message
+        .lines()
+        .map(|line| match line.strip_prefix('.') {
+            Some(_) => format!(".{line}"),
+            None => line.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: ItchySats: {}\r\n\r\n{message}\r\n.\r\n",
+        config.from, config.to, notification.title
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn expiry_reminder_is_sent_once_per_expiry() {
+        let mut reminders = Reminders::default();
+        let order_id = OrderId::default();
+        let expiry = Timestamp::new(1_665_000_000);
+        let rolled_over_expiry = Timestamp::new(1_665_086_400);
+
+        assert!(reminders.expiry(order_id, expiry));
+        assert!(!reminders.expiry(order_id, expiry));
+        assert!(reminders.expiry(order_id, rolled_over_expiry));
+        assert!(reminders.expiry(OrderId::default(), expiry));
+    }
+
+    #[test]
+    fn liquidation_warning_is_rearmed_once_price_recovers() {
+        let mut reminders = Reminders::default();
+        let order_id = OrderId::default();
+
+        assert!(!reminders.liquidation(order_id, false));
+        assert!(reminders.liquidation(order_id, true));
+        assert!(!reminders.liquidation(order_id, true));
+        assert!(!reminders.liquidation(order_id, false));
+        assert!(reminders.liquidation(order_id, true));
+    }
+
+    #[test]
+    fn maker_offline_is_notified_once_per_outage() {
+        let mut reminders = Reminders::default();
+
+        assert!(reminders.maker_offline());
+        assert!(!reminders.maker_offline());
+        reminders.maker_online();
+        assert!(reminders.maker_offline());
+    }
+
+    #[test]
+    fn smtp_server_has_to_be_on_loopback_interface() {
+        assert_eq!(
+            parse_smtp_server("127.0.0.1:25").unwrap(),
+            "127.0.0.1:25".parse().unwrap()
+        );
+        assert!(parse_smtp_server("[::1]:25").is_ok());
+        assert!(parse_smtp_server("192.0.2.1:25").is_err());
+        assert!(parse_smtp_server("localhost:25").is_err());
+    }
+
+    #[test]
+    fn liquidation_distance_depends_on_position() {
+        assert_eq!(
+            liquidation_distance(Position::Long, dec!(10000), dec!(20000)),
+            dec!(0.5)
+        );
+        assert_eq!(
+            liquidation_distance(Position::Short, dec!(22000), dec!(20000)),
+            dec!(0.1)
+        );
+        assert_eq!(
+            liquidation_distance(Position::Long, dec!(21000), dec!(20000)),
+            dec!(-0.05)
+        );
+    }
+
+    #[test]
+    fn email_body_escapes_lines_starting_with_dot() {
+        let config = EmailConfig {
+            smtp_server: "127.0.0.1:25".parse().unwrap(),
+            from: "taker@localhost".to_owned(),
+            to: "user@localhost".to_owned(),
+        };
+        let notification = Notification::new(
+            NotificationKind::MakerOffline,
+            None,
+            "first\n.second".to_owned(),
+        );
+
+        assert_eq!(
+            email_body(&config, &notification),
+            "From: taker@localhost\r\nTo: user@localhost\r\nSubject: ItchySats: Maker offline\r\n\r\nfirst\r\n..second\r\n.\r\n"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
 use std::time::Duration;
 use tokio::sync::watch;
@@ -8,12 +9,21 @@ use xtra_libp2p::Endpoint;
 use xtra_libp2p::GetConnectionStats;
 use xtra_productivity::xtra_productivity;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ConnectionStatus {
-    Online,
+    /// Connected to the peer through the given address.
+    Online {
+        address: Multiaddr,
+    },
     Offline,
 }
 
+impl ConnectionStatus {
+    pub fn is_online(&self) -> bool {
+        matches!(self, ConnectionStatus::Online { .. })
+    }
+}
+
 /// Actor that transmits updates of ConnectionStatus of a specified PeerId based on
 /// information transmitted by the Endpoint via a watch channel.
 pub struct Actor {
@@ -49,13 +59,14 @@ impl xtra::Actor for Actor {
 
         match self.endpoint.send(GetConnectionStats).await {
             Ok(connection_stats) => {
-                let status = if connection_stats
-                    .connected_peers
-                    .contains(&self.watched_peer)
+                let status = match connection_stats
+                    .connection_addresses
+                    .get(&self.watched_peer)
                 {
-                    ConnectionStatus::Online
-                } else {
-                    ConnectionStatus::Offline
+                    Some(address) => ConnectionStatus::Online {
+                        address: address.clone(),
+                    },
+                    None => ConnectionStatus::Offline,
                 };
                 self.sender
                     .send(status)
@@ -89,7 +100,9 @@ impl Actor {
         );
         if msg.peer_id == self.watched_peer {
             self.sender
-                .send(ConnectionStatus::Online)
+                .send(ConnectionStatus::Online {
+                    address: msg.address,
+                })
                 .expect("Receiver to outlive this actor");
         }
     }
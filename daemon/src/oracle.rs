@@ -435,23 +435,6 @@ impl AnnouncementsChannel {
     }
 }
 
-#[async_trait]
-impl rollover::deprecated::protocol::GetAnnouncements for AnnouncementsChannel {
-    async fn get_announcements(
-        &self,
-        events: Vec<BitMexPriceEventId>,
-    ) -> Result<Vec<olivia::Announcement>> {
-        let announcements = self
-            .0
-            .send(GetAnnouncements(events))
-            .await
-            .context("Oracle actor disconnected")?
-            .context("Failed to get announcements")?;
-
-        Ok(announcements)
-    }
-}
-
 #[async_trait]
 impl rollover::protocol::GetAnnouncements for AnnouncementsChannel {
     async fn get_announcements(
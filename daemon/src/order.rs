@@ -1,4 +1,44 @@
-mod current;
-pub mod deprecated;
+mod contract_setup;
+pub mod maker;
+pub mod protocol;
+pub mod taker;
 
-pub use current::*;
+use model::InverseMaxPrice;
+use xtra_libp2p::versions::Versions;
+use xtra_libp2p_request_response::framed::Encoding;
+
+/// The supported versions of the order protocol, newest first.
+pub const VERSIONS: Versions<Version> = Versions::new(&[
+    ("/itchysats/order/3.0.0", Version::V3),
+    ("/itchysats/order/2.0.0", Version::V2),
+    ("/itchysats/order/1.0.0", Version::V1),
+]);
+
+/// The versions of the order protocol.
+///
+/// All versions exchange the same messages, they only differ in how the messages are encoded and
+/// how the payouts are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// Inverse payout curves are capped at double the initial price.
+    V1,
+    V2,
+    /// Messages are encoded in CBOR instead of JSON.
+    V3,
+}
+
+impl Version {
+    fn encoding(&self) -> Encoding {
+        match self {
+            Version::V1 | Version::V2 => Encoding::Json,
+            Version::V3 => Encoding::Cbor,
+        }
+    }
+
+    fn inverse_max_price(&self) -> InverseMaxPrice {
+        match self {
+            Version::V1 => InverseMaxPrice::DoubleOfInitial,
+            Version::V2 | Version::V3 => InverseMaxPrice::OliviaMax,
+        }
+    }
+}
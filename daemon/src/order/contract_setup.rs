@@ -1,9 +1,10 @@
 use crate::bitcoin::secp256k1::SecretKey;
 use crate::bitcoin::PublicKey;
-use crate::order::current::protocol::Msg0;
-use crate::order::current::protocol::Msg1;
-use crate::order::current::protocol::Msg2;
-use crate::order::current::protocol::Msg3;
+use crate::order::protocol::Msg0;
+use crate::order::protocol::Msg1;
+use crate::order::protocol::Msg2;
+use crate::order::protocol::Msg3;
+use crate::order::Version;
 use crate::wallet;
 use anyhow::bail;
 use anyhow::Context;
@@ -78,8 +79,9 @@ pub async fn new(
     own_role: Role,
     position: Position,
     n_payouts: usize,
+    version: Version,
 ) -> Result<Dlc> {
-    tracing::debug!(?setup_params, ?own_role, ?position, ?n_payouts);
+    tracing::debug!(?setup_params, ?own_role, ?position, ?n_payouts, ?version);
     tracing::trace!(?oracle_pk, ?announcements);
 
     let (own, own_punish, key_pairs) =
@@ -120,6 +122,7 @@ pub async fn new(
         position,
         own_role,
         n_payouts,
+        version,
     )
     .await?;
 
@@ -299,6 +302,7 @@ async fn create_cfd_transactions(
     position: Position,
     role: Role,
     n_payouts: usize,
+    version: Version,
 ) -> Result<(CfdTransactions, BitMexPriceEventId)> {
     let expected_margin = setup_params.counterparty_margin;
     let actual_margin = params.counterparty.lock_amount;
@@ -312,13 +316,14 @@ async fn create_cfd_transactions(
     let settlement_event_id = announcements.last().context("Empty announcements")?.id;
 
     let payouts = match setup_params.contract_symbol {
-        ContractSymbol::BtcUsd => Payouts::new_inverse_olivia_max(
+        ContractSymbol::BtcUsd => Payouts::new_inverse(
             (position, role),
             setup_params.price,
             setup_params.quantity,
             (setup_params.long_leverage, setup_params.short_leverage),
             n_payouts,
             setup_params.fee_account.settle(),
+            version.inverse_max_price(),
         )?,
         ContractSymbol::EthUsd => Payouts::new_quanto(
             (position, role),
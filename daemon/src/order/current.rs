@@ -1,6 +0,0 @@
-mod contract_setup;
-pub mod maker;
-mod protocol;
-pub mod taker;
-
-pub const PROTOCOL: &str = "/itchysats/order/2.0.0";
@@ -1,5 +0,0 @@
-mod contract_setup;
-pub mod maker;
-mod protocol;
-
-pub const PROTOCOL: &str = "/itchysats/order/1.0.0";
@@ -1,32 +1,36 @@
 use crate::command;
 use crate::oracle;
 use crate::oracle::NoAnnouncement;
-use crate::order::current::contract_setup;
-use crate::order::current::protocol;
-use crate::order::current::protocol::MakerMessage;
-use crate::order::current::protocol::SetupMsg;
-use crate::order::current::protocol::TakerMessage;
+use crate::order::contract_setup;
+use crate::order::protocol;
+use crate::order::protocol::MakerMessage;
+use crate::order::protocol::SetupMsg;
+use crate::order::protocol::TakerMessage;
+use crate::order::VERSIONS;
 use crate::process_manager;
 use crate::projection;
 use crate::wallet;
 use anyhow::anyhow;
+use anyhow::ensure;
 use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
 use asynchronous_codec::Framed;
-use asynchronous_codec::JsonCodec;
 use bdk::bitcoin::psbt::PartiallySignedTransaction;
 use bdk::bitcoin::XOnlyPublicKey;
 use futures::channel::oneshot;
 use futures::future;
 use futures::SinkExt;
 use futures::StreamExt;
+use libp2p_core::PeerId;
 use maia_core::PartyParams;
 use model::olivia;
 use model::Cfd;
 use model::Identity;
 use model::OfferId;
+use model::OpeningFee;
 use model::OrderId;
+use model::ReferralCode;
 use model::Role;
 use std::collections::HashMap;
 use std::fmt;
@@ -36,6 +40,7 @@ use tracing::instrument;
 use xtra::prelude::MessageChannel;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_libp2p::Substream;
+use xtra_libp2p_request_response::framed::FramedCodec;
 use xtra_productivity::xtra_productivity;
 use xtras::SendAsyncSafe;
 
@@ -52,7 +57,7 @@ pub struct Actor {
     n_payouts: usize,
     decision_senders: HashMap<OrderId, oneshot::Sender<protocol::Decision>>,
     db: sqlite_db::Connection,
-    latest_offers: MessageChannel<offer::maker::GetLatestOffers, Vec<model::Offer>>,
+    latest_offers: MessageChannel<offer::maker::GetOffersFor, Vec<model::Offer>>,
 }
 
 impl Actor {
@@ -69,7 +74,7 @@ impl Actor {
             MessageChannel<wallet::Sign, Result<PartiallySignedTransaction>>,
         ),
         projection: xtra::Address<projection::Actor>,
-        latest_offers: MessageChannel<offer::maker::GetLatestOffers, Vec<model::Offer>>,
+        latest_offers: MessageChannel<offer::maker::GetOffersFor, Vec<model::Offer>>,
     ) -> Self {
         Self {
             executor: command::Executor::new(db.clone(), process_manager),
@@ -88,7 +93,7 @@ impl Actor {
     #[instrument(skip(self), err)]
     async fn receive_order(
         &mut self,
-        framed: &mut Framed<Substream, JsonCodec<MakerMessage, TakerMessage>>,
+        framed: &mut Framed<Substream, FramedCodec<MakerMessage, TakerMessage>>,
     ) -> Result<TakerMessage> {
         let order = framed
             .next()
@@ -101,18 +106,51 @@ impl Actor {
         Ok(order)
     }
 
+    /// Attribute the CFD to the partner which referred the taker.
+    ///
+    /// Only codes registered by the maker are attributed, so that takers cannot make up codes. An
+    /// invalid or unknown referral code does not prevent the taker from trading, the CFD is just
+    /// not attributed to any partner.
+    async fn record_referral(
+        &self,
+        order_id: OrderId,
+        referral_code: &str,
+        opening_fee: OpeningFee,
+    ) {
+        let result = async {
+            let referral_code = referral_code.parse::<ReferralCode>()?;
+
+            if !self.db.is_referral_code_registered(&referral_code).await? {
+                tracing::info!(%order_id, %referral_code, "Ignoring unknown referral code");
+                return Ok(());
+            }
+
+            self.db
+                .insert_referral(order_id, &referral_code, opening_fee)
+                .await
+        }
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(%order_id, "Failed to record referral: {e:#}");
+        }
+    }
+
+    /// Pick the offer as it was sent to the taker.
     #[instrument(skip(self))]
-    async fn pick_offer(&self, offer_id: OfferId) -> Result<model::Offer> {
+    async fn pick_offer(&self, peer_id: PeerId, offer_id: OfferId) -> Result<model::Offer> {
         let latest_offers = self
             .latest_offers
-            .send(offer::maker::GetLatestOffers)
+            .send(offer::maker::GetOffersFor(peer_id))
             .await
             .context("Failed to retrieve latest offer from offers actor")?;
 
         let offer = latest_offers
             .iter()
             .find(|offer| offer.id == offer_id)
-            .with_context(|| format!("Offer with id {offer_id} not found in current offers"))?
+            .with_context(|| {
+                format!("Offer with id {offer_id} not found in the offers sent to the taker")
+            })?
             .clone();
 
         Ok(offer)
@@ -124,7 +162,17 @@ impl Actor {
     async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut xtra::Context<Self>) {
         let NewInboundSubstream { peer_id, stream } = msg;
 
-        let mut framed = Framed::new(stream, JsonCodec::<MakerMessage, TakerMessage>::new());
+        let version = match VERSIONS.get(stream.protocol()) {
+            Some(version) => version,
+            None => {
+                tracing::error!(protocol = %stream.protocol(), "Unsupported order protocol");
+                return;
+            }
+        };
+        let mut framed = Framed::new(
+            stream,
+            FramedCodec::<MakerMessage, TakerMessage>::new(version.encoding()),
+        );
 
         let order = match self.receive_order(&mut framed).await {
             Ok(order) => order,
@@ -134,23 +182,38 @@ impl Actor {
             }
         };
 
-        let (order_id, offer_id, quantity, leverage) = match order {
+        let (order_id, offer_id, quantity, leverage, taker_opening_fee, referral_code) = match order
+        {
             TakerMessage::PlaceOrder {
                 id,
                 offer,
                 quantity,
                 leverage,
-            } => (id, offer.id, quantity, leverage),
+                opening_fee,
+                referral_code,
+            } => (id, offer.id, quantity, leverage, opening_fee, referral_code),
             TakerMessage::ContractSetupMsg(_) => {
                 tracing::error!("Unexpected message");
                 return;
             }
         };
 
-        tracing::info!(%peer_id, %quantity, %order_id, %offer_id, "Taker wants to place an order");
-
-        // Reject the order if the offer cannot be found in the latest offers
-        let offer = match self.pick_offer(offer_id).await {
+        tracing::info!(%peer_id, %quantity, %order_id, %offer_id, ?version, "Taker wants to place an order");
+
+        // Reject the order if the offer cannot be found in the latest offers sent to the taker or
+        // the taker does not agree on the opening fee of that offer
+        let offer = match self.pick_offer(peer_id, offer_id).await.and_then(|offer| {
+            let opening_fee = offer.opening_fee_for(quantity);
+            let taker_opening_fee = taker_opening_fee.unwrap_or(offer.opening_fee);
+            ensure!(
+                opening_fee == taker_opening_fee,
+                "Taker expects opening fee of {} instead of {}",
+                taker_opening_fee.to_inner(),
+                opening_fee.to_inner()
+            );
+
+            Ok(offer)
+        }) {
             Ok(offer) => offer,
             Err(e) => {
                 tracing::warn!(
@@ -199,6 +262,11 @@ impl Actor {
             return;
         }
 
+        if let Some(referral_code) = referral_code {
+            self.record_referral(order_id, &referral_code, cfd.opening_fee())
+                .await;
+        }
+
         if let Err(e) = self
             .projection
             .send_async_safe(projection::CfdChanged(cfd.id()))
@@ -281,6 +349,7 @@ impl Actor {
                     Role::Maker,
                     position,
                     n_payouts,
+                    version,
                 )
                 .await?;
 
@@ -12,6 +12,7 @@ use maia_core::PunishParams;
 use model::Contracts;
 use model::Leverage;
 use model::OfferId;
+use model::OpeningFee;
 use model::OrderId;
 use serde::Deserialize;
 use serde::Serialize;
@@ -25,6 +26,15 @@ pub(crate) enum TakerMessage {
         offer: Offer,
         quantity: Contracts,
         leverage: Leverage,
+        /// The opening fee the taker expects to pay.
+        ///
+        /// Not sent by takers which predate fee schedules, they expect to pay the flat fee of the
+        /// offer.
+        #[serde(default)]
+        opening_fee: Option<OpeningFee>,
+        /// Identifies the partner which referred the taker, if any.
+        #[serde(default)]
+        referral_code: Option<String>,
     },
     ContractSetupMsg(Box<SetupMsg>),
 }
@@ -1,13 +1,13 @@
 use crate::command;
 use crate::oracle;
 use crate::oracle::NoAnnouncement;
-use crate::order::current::contract_setup;
-use crate::order::current::protocol;
-use crate::order::current::protocol::Decision;
-use crate::order::current::protocol::MakerMessage;
-use crate::order::current::protocol::SetupMsg;
-use crate::order::current::protocol::TakerMessage;
-use crate::order::current::PROTOCOL;
+use crate::order::contract_setup;
+use crate::order::protocol;
+use crate::order::protocol::Decision;
+use crate::order::protocol::MakerMessage;
+use crate::order::protocol::SetupMsg;
+use crate::order::protocol::TakerMessage;
+use crate::order::VERSIONS;
 use crate::process_manager;
 use crate::projection;
 use crate::wallet;
@@ -16,7 +16,6 @@ use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
 use asynchronous_codec::Framed;
-use asynchronous_codec::JsonCodec;
 use bdk::bitcoin::psbt::PartiallySignedTransaction;
 use bdk::bitcoin::XOnlyPublicKey;
 use futures::future;
@@ -31,10 +30,12 @@ use model::Identity;
 use model::Leverage;
 use model::Offer;
 use model::OrderId;
+use model::ReferralCode;
 use model::Role;
 use xtra::prelude::MessageChannel;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::OpenSubstream;
+use xtra_libp2p_request_response::framed::FramedCodec;
 use xtra_productivity::xtra_productivity;
 
 pub struct Actor {
@@ -48,6 +49,7 @@ pub struct Actor {
     projection: xtra::Address<projection::Actor>,
     n_payouts: usize,
     db: sqlite_db::Connection,
+    referral_code: Option<ReferralCode>,
 }
 
 impl Actor {
@@ -65,6 +67,7 @@ impl Actor {
         ),
         projection: xtra::Address<projection::Actor>,
         endpoint: xtra::Address<Endpoint>,
+        referral_code: Option<ReferralCode>,
     ) -> Self {
         Self {
             endpoint,
@@ -76,6 +79,7 @@ impl Actor {
             projection,
             n_payouts,
             db,
+            referral_code,
         }
     }
 }
@@ -95,6 +99,7 @@ impl Actor {
             let oracle_pk = self.oracle_pk;
             let n_payouts = self.n_payouts;
             let projection = self.projection.clone();
+            let referral_code = self.referral_code.as_ref().map(ToString::to_string);
             async move {
                 tracing::info!(order = ?msg, "Placing order");
 
@@ -127,16 +132,24 @@ impl Actor {
 
                 projection.send(projection::CfdChanged(cfd.id())).await?;
 
-                let stream = endpoint
-                    .send(OpenSubstream::single_protocol(maker_peer_id, PROTOCOL))
+                let (negotiated_protocol, stream) = endpoint
+                    .send(OpenSubstream::multiple_protocols(
+                        maker_peer_id,
+                        VERSIONS.protocols(),
+                    ))
                     .await
                     .context("Endpoint is disconnected")?
                     .context("No connection to peer")?
                     .await
                     .context("Failed to open substream")?;
+                let version = VERSIONS
+                    .get(negotiated_protocol)
+                    .context("Negotiated unsupported order protocol")?;
 
-                let mut framed =
-                    Framed::new(stream, JsonCodec::<TakerMessage, MakerMessage>::new());
+                let mut framed = Framed::new(
+                    stream,
+                    FramedCodec::<TakerMessage, MakerMessage>::new(version.encoding()),
+                );
 
                 framed
                     .send(TakerMessage::PlaceOrder {
@@ -144,6 +157,8 @@ impl Actor {
                         offer: protocol::Offer { id: offer.id },
                         quantity,
                         leverage,
+                        opening_fee: Some(offer.opening_fee_for(quantity)),
+                        referral_code,
                     })
                     .await?;
 
@@ -202,6 +217,7 @@ impl Actor {
                     Role::Taker,
                     position,
                     n_payouts,
+                    version,
                 )
                 .await?;
 
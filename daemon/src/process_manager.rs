@@ -1,16 +1,20 @@
+use crate::event_webhooks;
 use crate::monitor::MonitorAfterContractSetup;
 use crate::monitor::MonitorAfterRollover;
 use crate::monitor::MonitorCetFinality;
 use crate::monitor::MonitorCollaborativeSettlement;
 use crate::monitor::TransactionKind;
 use crate::monitor::TryBroadcastTransaction;
+use crate::notifications;
 use crate::oracle;
 use crate::position_metrics;
 use crate::projection;
+use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
 use model::CfdEvent;
 use model::EventKind;
+use model::FundingFeeLedgerEntry;
 use model::Role;
 use sqlite_db;
 use tracing::Instrument;
@@ -29,6 +33,9 @@ pub struct Actor {
     monitor_cet_finality: MessageChannel<MonitorCetFinality, Result<()>>,
     monitor_collaborative_settlement: MessageChannel<MonitorCollaborativeSettlement, ()>,
     monitor_attestation: MessageChannel<oracle::MonitorAttestations, ()>,
+    /// Only present for the taker, which notifies the user about failed rollovers.
+    rollover_failed: Option<MessageChannel<notifications::RolloverFailed, ()>>,
+    event_webhooks: Option<MessageChannel<event_webhooks::Publish, ()>>,
 }
 
 pub struct Event(CfdEvent);
@@ -52,6 +59,8 @@ impl Actor {
         monitor_cet_finality: MessageChannel<MonitorCetFinality, Result<()>>,
         monitor_collaborative_settlement: MessageChannel<MonitorCollaborativeSettlement, ()>,
         monitor_attestation: MessageChannel<oracle::MonitorAttestations, ()>,
+        rollover_failed: Option<MessageChannel<notifications::RolloverFailed, ()>>,
+        event_webhooks: Option<MessageChannel<event_webhooks::Publish, ()>>,
     ) -> Self {
         Self {
             db,
@@ -64,6 +73,39 @@ impl Actor {
             monitor_cet_finality,
             monitor_collaborative_settlement,
             monitor_attestation,
+            rollover_failed,
+            event_webhooks,
+        }
+    }
+}
+
+impl Actor {
+    /// Derive the funding fee charged by a `RolloverCompleted` event.
+    ///
+    /// Has to be called before the event is saved. The ledger is purely informational, hence
+    /// failing to derive an entry does not prevent the event from being processed.
+    async fn funding_fee_ledger_entry(&self, event: &CfdEvent) -> Option<FundingFeeLedgerEntry> {
+        if !matches!(event.event, EventKind::RolloverCompleted { .. }) {
+            return None;
+        }
+
+        let entry = async {
+            let cfd = self
+                .db
+                .load_open_cfd::<model::Cfd>(event.id, ())
+                .await
+                .context("Failed to load CFD")?;
+
+            cfd.funding_fee_ledger_entry(event)
+        }
+        .await;
+
+        match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::error!(order_id = %event.id, "Failed to derive funding fee: {e:#}");
+                None
+            }
         }
     }
 }
@@ -74,7 +116,26 @@ impl Actor {
         let event = msg.0;
 
         // 1. Safe in DB
+        let funding_fee_ledger_entry = self.funding_fee_ledger_entry(&event).await;
         self.db.append_event(event.clone()).await?;
+        if let Some(entry) = funding_fee_ledger_entry {
+            if let Err(e) = self
+                .db
+                .insert_funding_fee_ledger_entry(event.id, &entry)
+                .await
+            {
+                tracing::error!(order_id = %event.id, "Failed to record funding fee: {e:#}");
+            }
+        }
+        // Webhooks are queued in the DB as well, hence only after the event was saved
+        if let Some(webhooks) = &self.event_webhooks {
+            if let Err(e) = webhooks
+                .send_async_safe(event_webhooks::Publish(event.clone()))
+                .await
+            {
+                tracing::error!(order_id = %event.id, "Failed to publish event to webhooks: {e:#}");
+            }
+        }
 
         // 2. Post process event
         use EventKind::*;
@@ -189,6 +250,13 @@ impl Actor {
                     })
                     .await?;
             }
+            RolloverFailed => {
+                if let Some(rollover_failed) = &self.rollover_failed {
+                    rollover_failed
+                        .send_async_safe(notifications::RolloverFailed { order_id: event.id })
+                        .await?;
+                }
+            }
             RefundTimelockExpired { refund_tx: tx } => {
                 let span = tracing::debug_span!("Broadcast refund TX", order_id = %event.id);
                 self.try_broadcast_transaction
@@ -209,7 +277,6 @@ impl Actor {
             | RolloverStarted
             | RolloverAccepted
             | RolloverRejected
-            | RolloverFailed
             | CollaborativeSettlementProposalAccepted
             | LockConfirmed
             | LockConfirmedAfterFinality
@@ -38,6 +38,7 @@ use model::OrderId;
 use model::Position;
 use model::Price;
 use model::Role;
+use model::RolloverPolicy;
 use model::Settlement;
 use model::Timestamp;
 use model::SETTLEMENT_INTERVAL;
@@ -190,9 +191,15 @@ pub struct Cfd {
 
     pub counterparty: model::Identity,
 
+    #[serde(skip)]
+    pub counterparty_peer_id: Option<model::libp2p::PeerId>,
+
     #[serde(with = "round_to_two_dp::opt")]
     pub pending_settlement_proposal_price: Option<Price>,
 
+    /// The limits on automatically rolling over the CFD, only present for the taker.
+    pub rollover_policy: Option<RolloverPolicy>,
+
     #[serde(skip)]
     #[derivative(PartialEq = "ignore")]
     aggregated: Aggregated,
@@ -203,13 +210,14 @@ pub struct Cfd {
 
 /// Bundle all state extracted from the events in one struct.
 ///
-/// This struct is not serialized but simply carries all state we are interested in from the events.
-/// The [`Cfd`] struct above fulfills two roles currently:
+/// This struct is not part of the API model but simply carries all state we are interested in from
+/// the events. The [`Cfd`] struct above fulfills two roles currently:
 /// - It represents the API model that is serialized.
 /// - It serves as an aggregate that is hydrated from events.
 ///
-/// This dual-role motivates the existence of this struct.
-#[derive(Clone, Debug)]
+/// This dual-role motivates the existence of this struct. It is only serialized as part of a
+/// [`CfdSnapshot`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Aggregated {
     fee_account: FeeAccount,
 
@@ -311,7 +319,7 @@ fn extract_payout_amount(tx: Transaction, script: Script) -> Amount {
 }
 
 /// Capture state of protocol negotiation for the UI purposes.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 enum ProtocolNegotiationState {
     /// Protocol has been kicked off, likely by user action
     Started,
@@ -329,6 +337,7 @@ impl Cfd {
             taker_leverage,
             quantity,
             counterparty_network_identity,
+            counterparty_peer_id,
             role,
             opening_fee,
             initial_funding_rate,
@@ -411,7 +420,9 @@ impl Cfd {
             },
             expiry_timestamp: None,
             counterparty: counterparty_network_identity,
+            counterparty_peer_id,
             pending_settlement_proposal_price: None,
+            rollover_policy: None,
             aggregated: Aggregated::new(fee_account),
             network,
         }
@@ -450,6 +461,7 @@ impl Cfd {
                 dlc,
                 funding_fee,
                 complete_fee,
+                ..
             } => {
                 self.expiry_timestamp = dlc.as_ref().map(|dlc| dlc.settlement_event_id.timestamp());
 
@@ -467,8 +479,6 @@ impl Cfd {
                     }
                 };
 
-                self.accumulated_fees = self.aggregated.fee_account.balance();
-
                 self.aggregated.state = CfdState::Open;
             }
             RolloverAccepted | RolloverStarted { .. } => {
@@ -574,8 +584,16 @@ impl Cfd {
             }
         };
 
+        self.aggregated.version += 1;
+
+        self.derive_from_aggregated()
+    }
+
+    /// Update all fields that are derived from the aggregated state.
+    fn derive_from_aggregated(mut self) -> Self {
         self.state = self.aggregated.derive_cfd_state(self.role);
         self.actions = self.derive_actions();
+        self.accumulated_fees = self.aggregated.fee_account.balance();
 
         if let Some(lock_tx_url) = self.lock_tx_url(self.network) {
             self.details.tx_url_list.insert(lock_tx_url);
@@ -593,8 +611,6 @@ impl Cfd {
             self.details.tx_url_list.insert(cet_url);
         }
 
-        self.aggregated.version += 1;
-
         self
     }
 
@@ -821,6 +837,19 @@ struct State {
     cfds: Option<HashMap<OrderId, Cfd>>,
 }
 
+/// The state of a [`Cfd`] that is neither static nor derived from the aggregated state.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CfdSnapshot {
+    liquidation_price: Decimal,
+    closing_price: Option<Price>,
+    details: CfdDetails,
+    #[serde(with = "::time::serde::timestamp::option")]
+    expiry_timestamp: Option<OffsetDateTime>,
+    pending_settlement_proposal_price: Option<Price>,
+    aggregated: Aggregated,
+}
+
 impl sqlite_db::CfdAggregate for Cfd {
     type CtorArgs = Network;
 
@@ -835,6 +864,42 @@ impl sqlite_db::CfdAggregate for Cfd {
     fn version(&self) -> u32 {
         self.aggregated.version
     }
+
+    fn snapshot(&self) -> Result<Option<sqlite_db::Snapshot>> {
+        let snapshot = CfdSnapshot {
+            liquidation_price: self.liquidation_price,
+            closing_price: self.closing_price,
+            details: self.details.clone(),
+            expiry_timestamp: self.expiry_timestamp,
+            pending_settlement_proposal_price: self.pending_settlement_proposal_price,
+            aggregated: self.aggregated.clone(),
+        };
+
+        Ok(Some(sqlite_db::Snapshot::new(&snapshot)?))
+    }
+
+    fn restore(&self, snapshot: &sqlite_db::Snapshot) -> Option<Self> {
+        let CfdSnapshot {
+            liquidation_price,
+            closing_price,
+            details,
+            expiry_timestamp,
+            pending_settlement_proposal_price,
+            aggregated,
+        } = snapshot.restore()?;
+
+        let cfd = Self {
+            liquidation_price,
+            closing_price,
+            details,
+            expiry_timestamp,
+            pending_settlement_proposal_price,
+            aggregated,
+            ..self.clone()
+        };
+
+        Some(cfd.derive_from_aggregated())
+    }
 }
 
 impl sqlite_db::ClosedCfdAggregate for Cfd {
@@ -969,7 +1034,9 @@ impl sqlite_db::ClosedCfdAggregate for Cfd {
             details,
             expiry_timestamp: Some(expiry_timestamp),
             counterparty: counterparty_network_identity,
+            counterparty_peer_id,
             pending_settlement_proposal_price: None,
+            rollover_policy: None,
             aggregated,
             network,
         }
@@ -1054,7 +1121,9 @@ impl sqlite_db::FailedCfdAggregate for Cfd {
             },
             expiry_timestamp: None,
             counterparty: counterparty_network_identity,
+            counterparty_peer_id,
             pending_settlement_proposal_price: None,
+            rollover_policy: None,
             aggregated,
             network,
         }
@@ -1071,8 +1140,16 @@ impl State {
         }
     }
 
-    async fn update_cfd(&mut self, db: sqlite_db::Connection, id: OrderId) -> Result<()> {
-        let cfd = db.load_open_cfd(id, self.network).await?;
+    async fn update_cfd(
+        &mut self,
+        db: sqlite_db::Connection,
+        id: OrderId,
+        role: Role,
+    ) -> Result<()> {
+        let mut cfd: Cfd = db.load_open_cfd(id, self.network).await?;
+        if role == Role::Taker {
+            cfd.rollover_policy = Some(db.load_rollover_policy(id).await?);
+        }
 
         let cfds = self
             .cfds
@@ -1135,6 +1212,18 @@ impl Actor {
             cfds.insert(cfd.order_id, cfd);
         }
 
+        if self.role == Role::Taker {
+            match self.db.load_rollover_policies().await {
+                Ok(mut policies) => {
+                    for cfd in cfds.values_mut() {
+                        cfd.rollover_policy =
+                            Some(policies.remove(&cfd.order_id).unwrap_or_default());
+                    }
+                }
+                Err(e) => tracing::error!("Failed to load rollover policies: {e:#}"),
+            }
+        }
+
         self.state.cfds = Some(cfds);
 
         self.tx.send_cfds_update(
@@ -1147,7 +1236,11 @@ impl Actor {
     }
 
     async fn handle(&mut self, msg: CfdChanged) {
-        if let Err(e) = self.state.update_cfd(self.db.clone(), msg.0).await {
+        if let Err(e) = self
+            .state
+            .update_cfd(self.db.clone(), msg.0, self.role)
+            .await
+        {
             tracing::error!("Failed to rehydrate CFD: {e:#}");
             return;
         };
@@ -1249,6 +1342,18 @@ pub struct Quote {
     last_updated_at: Timestamp,
 }
 
+impl Quote {
+    /// The price at which the position would be closed at market.
+    pub fn closing_price(&self, role: Role, position: Position) -> Result<Price> {
+        Ok(market_closing_price(
+            Price::new(self.bid)?,
+            Price::new(self.ask)?,
+            role,
+            position,
+        ))
+    }
+}
+
 impl From<xtra_bitmex_price_feed::Quote> for Quote {
     fn from(quote: xtra_bitmex_price_feed::Quote) -> Self {
         Quote {
@@ -1285,6 +1390,22 @@ pub struct MakerOffers {
     pub ethusd_short: Option<CfdOffer>,
 }
 
+impl MakerOffers {
+    /// All offers that are currently available, regardless of symbol and position.
+    pub fn to_vec(&self) -> Vec<CfdOffer> {
+        [
+            &self.btcusd_long,
+            &self.btcusd_short,
+            &self.ethusd_long,
+            &self.ethusd_short,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct CfdOffer {
     pub id: OfferId,
@@ -1304,6 +1425,9 @@ pub struct CfdOffer {
     #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
     pub opening_fee: Option<Amount>,
 
+    /// Percentage of the notional value charged in addition to the `opening_fee`
+    pub opening_fee_percent: String,
+
     /// The interest as annualized percentage
     ///
     /// This is an estimate as the funding rate can fluctuate
@@ -1432,6 +1556,9 @@ impl CfdOffer {
                 .try_into()
                 .context("unable to convert settlement interval")?,
             opening_fee: Some(offer.opening_fee.to_inner()),
+            opening_fee_percent: (offer.opening_fee_rate * Decimal::ONE_HUNDRED)
+                .normalize()
+                .to_string(),
             funding_rate_annualized_percent: AnnualisedFundingPercent::from(offer.funding_rate)
                 .to_string(),
             funding_rate_hourly_percent: HourlyFundingPercent::from(offer.funding_rate).to_string(),
@@ -1439,7 +1566,7 @@ impl CfdOffer {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CfdState {
     PendingSetup,
     ContractSetup,
@@ -1459,7 +1586,7 @@ pub enum CfdState {
     SetupFailed,
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CfdDetails {
     tx_url_list: HashSet<TxUrl>,
 }
@@ -1571,7 +1698,7 @@ pub fn to_mempool_url(txid: Txid, network: Network) -> String {
 }
 
 /// Link to transaction on mempool.space for UI representation
-#[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 struct TxUrl {
     pub label: TxLabel,
     pub url: String,
@@ -1609,7 +1736,7 @@ impl TxUrl {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq, Hash)]
 pub enum TxLabel {
     Lock,
     Commit,
@@ -1669,6 +1796,7 @@ mod tests {
     use model::OpeningFee;
     use model::TxFeeRate;
     use sqlite_db::memory;
+    use sqlite_db::CfdAggregate;
 
     #[test]
     fn state_snapshot_test() {
@@ -1793,6 +1921,48 @@ mod tests {
         }
     }
 
+    fn cfd_row(cfd: &model::Cfd) -> sqlite_db::Cfd {
+        sqlite_db::Cfd {
+            id: cfd.id(),
+            offer_id: cfd.offer_id(),
+            position: cfd.position(),
+            initial_price: cfd.initial_price(),
+            taker_leverage: cfd.taker_leverage(),
+            settlement_interval: cfd.settlement_time_interval_hours(),
+            quantity: cfd.quantity(),
+            counterparty_network_identity: cfd.counterparty_network_identity(),
+            counterparty_peer_id: cfd.counterparty_peer_id(),
+            role: cfd.role(),
+            opening_fee: cfd.opening_fee(),
+            initial_funding_rate: cfd.initial_funding_rate(),
+            initial_tx_fee_rate: cfd.initial_tx_fee_rate(),
+            contract_symbol: cfd.contract_symbol(),
+        }
+    }
+
+    #[test]
+    fn given_settled_cfd_when_restoring_snapshot_then_projection_aggregate_stays_the_same() {
+        let (cfd, contract_setup_completed, collaborative_settlement_completed) =
+            cfd_collaboratively_settled();
+
+        let projection = <Cfd as CfdAggregate>::new(Network::Testnet, cfd_row(&cfd))
+            .apply(contract_setup_completed)
+            .apply(collaborative_settlement_completed);
+
+        let snapshot = projection.snapshot().unwrap().unwrap();
+
+        let restored = <Cfd as CfdAggregate>::new(Network::Testnet, cfd_row(&cfd))
+            .restore(&snapshot)
+            .unwrap();
+
+        assert_eq!(restored.version(), projection.version());
+        assert_eq!(
+            restored.aggregated.latest_dlc,
+            projection.aggregated.latest_dlc
+        );
+        assert_eq!(restored, projection);
+    }
+
     #[tokio::test]
     async fn given_contract_setup_failed_when_move_cfds_to_failed_table_then_projection_aggregate_stays_the_same(
     ) {
@@ -9,15 +9,19 @@ use async_trait::async_trait;
 use model::libp2p::PeerId;
 use model::market_closing_price;
 use model::Cfd;
+use model::ContractSymbol;
 use model::Contracts;
 use model::Identity;
 use model::Leverage;
 use model::OfferId;
 use model::OrderId;
+use model::Position;
 use model::Price;
 use model::Role;
+use model::Timestamp;
 use sqlite_db;
 use std::collections::HashMap;
+use strum::IntoEnumIterator;
 use time::OffsetDateTime;
 use xtra_productivity::xtra_productivity;
 use xtras::SendAsyncSafe;
@@ -68,9 +72,45 @@ impl Actor {
     }
 }
 
+impl Actor {
+    /// Record the funding rates published by the maker, for both positions of a contract symbol.
+    async fn record_funding_rates(&self, offers: &[model::Offer]) {
+        for contract_symbol in ContractSymbol::iter() {
+            let funding_rate = |position| {
+                offers
+                    .iter()
+                    .find(|offer| {
+                        offer.contract_symbol == contract_symbol && offer.position_maker == position
+                    })
+                    .map(|offer| offer.funding_rate)
+            };
+
+            let (funding_rate_long, funding_rate_short) =
+                match (funding_rate(Position::Long), funding_rate(Position::Short)) {
+                    (Some(long), Some(short)) => (long, short),
+                    _ => continue,
+                };
+
+            if let Err(e) = self
+                .db
+                .record_funding_rates(
+                    contract_symbol,
+                    funding_rate_long,
+                    funding_rate_short,
+                    Timestamp::now(),
+                )
+                .await
+            {
+                tracing::warn!(%contract_symbol, "Failed to record funding rates: {e:#}");
+            }
+        }
+    }
+}
+
 #[xtra_productivity]
 impl Actor {
     async fn handle_latest_offers(&mut self, msg: offer::taker::LatestOffers) {
+        self.record_funding_rates(&msg.0).await;
         self.offers.insert(msg.0.clone());
 
         if let Err(e) = self.projection_actor.send(projection::Update(msg.0)).await {
@@ -20,11 +20,13 @@ export default function Login() {
     const { login, loading, error } = useAuth();
     const [show, setShow] = useState(false);
     const handleClick = () => setShow(!show);
+    const [username, setUsername] = useState("admin");
     const [password, setPassword] = useState("");
+    const [totp, setTotp] = useState("");
 
     function handleSubmit(event: FormEvent<HTMLFormElement>) {
         event.preventDefault();
-        login(password);
+        login(username, password, totp);
     }
 
     return (
@@ -33,6 +35,12 @@ export default function Login() {
                 <VStack spacing="6">
                     <Heading>Welcome Satoshi</Heading>
                     <img src={logo} className="Logo" alt="logo" />
+                    <Input
+                        size="md"
+                        placeholder="Enter username"
+                        value={username}
+                        onChange={(e) => setUsername(e.target.value)}
+                    />
                     <InputGroup size="md">
                         <Input
                             pr="4.5rem"
@@ -47,6 +55,13 @@ export default function Login() {
                             </Button>
                         </InputRightElement>
                     </InputGroup>
+                    <Input
+                        size="md"
+                        placeholder="2FA code (if enabled)"
+                        autoComplete="one-time-code"
+                        value={totp}
+                        onChange={(e) => setTotp(e.target.value)}
+                    />
 
                     <Button disabled={loading} variant={"solid"} colorScheme={"blue"} isLoading={loading} type="submit">
                         Submit
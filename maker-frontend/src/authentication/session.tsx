@@ -3,6 +3,8 @@ import axios, { AxiosResponse } from "axios";
 export interface User {
     first_login: boolean;
     id: number;
+    username: string;
+    role: "viewer" | "trader" | "admin";
 }
 
 export interface HttpError {
@@ -11,11 +13,17 @@ export interface HttpError {
 }
 
 export async function login(params: {
+    username: string;
     password: string;
+    totp?: string;
 }): Promise<User> {
     try {
         const loginParams = new URLSearchParams();
+        loginParams.append("username", params.username);
         loginParams.append("password", params.password);
+        if (params.totp) {
+            loginParams.append("totp", params.totp);
+        }
 
         const response: AxiosResponse<User> = await axios.post("/api/login", loginParams);
         if (response.status === 200) {
@@ -9,7 +9,7 @@ interface AuthContextType {
     firstLogin: boolean;
     loading: boolean;
     error?: any;
-    login: (password: string) => void;
+    login: (username: string, password: string, totp?: string) => void;
     changePassword: (password: string) => void;
     logout: () => void;
 }
@@ -62,10 +62,10 @@ export function AuthProvider({
     //
     // Finally, signal the component that loading the
     // loading state is over.
-    function login(password: string) {
+    function login(username: string, password: string, totp?: string) {
         setLoading(true);
 
-        sessionsApi.login({ password })
+        sessionsApi.login({ username, password, totp })
             .then((user) => {
                 if (user.first_login) {
                     setFirstLogin(true);
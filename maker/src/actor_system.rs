@@ -1,5 +1,11 @@
 use crate::cfd;
+use crate::funding_rate;
+use crate::metrics::referrals;
 use crate::metrics::time_to_first_position;
+use crate::opening_fee;
+use crate::pnl;
+use crate::rollover_policy;
+use anyhow::Context as _;
 use anyhow::Result;
 use bdk::bitcoin;
 use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
@@ -9,7 +15,10 @@ use daemon::archive_closed_cfds;
 use daemon::archive_failed_cfds;
 use daemon::collab_settlement;
 use daemon::command;
+use daemon::connection_history;
+use daemon::event_webhooks;
 use daemon::identify;
+use daemon::identify::dialer::GetPeerInfos;
 use daemon::listen_protocols::MAKER_LISTEN_PROTOCOLS;
 use daemon::monitor;
 use daemon::oracle;
@@ -21,7 +30,6 @@ use daemon::projection;
 use daemon::seed::Identities;
 use daemon::wallet;
 use daemon::Environment;
-use libp2p_tcp::TokioTcpConfig;
 use maia_core::secp256k1_zkp::XOnlyPublicKey;
 use maia_core::PartyParams;
 use model::olivia::Announcement;
@@ -34,11 +42,14 @@ use model::OpeningFee;
 use model::OrderId;
 use model::Price;
 use model::Role;
+use model::Timestamp;
 use model::TxFeeRate;
 use ping_pong::ping;
 use ping_pong::pong;
+use sqlite_db::connection_history::ConnectionHistoryEntry;
+use sqlite_db::peer_list::PeerList;
+use sqlite_db::peer_list::PeerListEntry;
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 use tokio_extras::Tasks;
 use xtra::Actor;
@@ -48,8 +59,19 @@ use xtra::Handler;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::libp2p::Multiaddr;
 use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::BannedPeer;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::listener;
+use xtra_libp2p::relay;
+use xtra_libp2p::relay::RelayTransport;
+use xtra_libp2p::BanPeer;
+use xtra_libp2p::Disconnect;
 use xtra_libp2p::Endpoint;
+use xtra_libp2p::GetBannedPeers;
+use xtra_libp2p::GetConnectionStats;
+use xtra_libp2p::SetAllowedPeers;
+use xtra_libp2p::SetBlockedPeers;
+use xtra_libp2p::UnbanPeer;
 use xtras::supervisor::always_restart_after;
 use xtras::supervisor::Supervisor;
 
@@ -60,26 +82,47 @@ const PING_INTERVAL: Duration = Duration::from_secs(30);
 /// a failure.
 pub const RESTART_INTERVAL: Duration = Duration::from_secs(5);
 
+/// A taker connected to the maker.
+#[derive(Debug, Clone)]
+pub struct ConnectedTaker {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+    /// `None` until the taker responded to the identify protocol.
+    pub daemon_version: Option<String>,
+    pub environment: Option<Environment>,
+    /// `None` until the taker responded to a ping.
+    pub latency: Option<Duration>,
+}
+
 pub struct ActorSystem<O: 'static, W: 'static> {
     pub cfd_actor: Address<cfd::Actor>,
     wallet_actor: Address<W>,
 
     pub rollover_actor: Address<
-        rollover::maker::Actor<command::Executor, oracle::AnnouncementsChannel, cfd::RatesChannel>,
-    >,
-    pub rollover_actor_deprecated: Address<
-        rollover::deprecated::maker::Actor<
+        rollover::maker::Actor<
             command::Executor,
             oracle::AnnouncementsChannel,
             cfd::RatesChannel,
+            rollover_policy::PolicyChannel,
         >,
     >,
+    rollover_policy: Option<Address<rollover_policy::Actor>>,
+    opening_fee: Option<Address<opening_fee::Actor>>,
+    pnl: Option<Address<pnl::Actor>>,
     _oracle_actor: Address<O>,
     _archive_closed_cfds_actor: Address<archive_closed_cfds::Actor>,
     _archive_failed_cfds_actor: Address<archive_failed_cfds::Actor>,
+    _referral_metrics_actor: Address<referrals::Actor>,
     executor: command::Executor,
     _tasks: Tasks,
     _pong_actor: Address<pong::Actor>,
+    endpoint: Address<Endpoint>,
+    ping_actor: Address<ping::Actor>,
+    identify_dialer_actor: Address<identify::dialer::Actor>,
+    db: sqlite_db::Connection,
+    /// Peers blocked through the configuration file, in addition to the ones in the database.
+    static_blocked_peers: HashSet<PeerId>,
+    allowlist_only: bool,
 }
 
 impl<O, W> ActorSystem<O, W>
@@ -104,8 +147,16 @@ where
         n_payouts: usize,
         projection_actor: Address<projection::Actor>,
         identity: Identities,
-        listen_multiaddr: Multiaddr,
+        listen_multiaddrs: Vec<Multiaddr>,
+        advertised_multiaddrs: HashSet<Multiaddr>,
         blocked_peers: HashSet<PeerId>,
+        allowlist_only: bool,
+        connection_limits: ConnectionLimits,
+        funding_rate_engine: Option<Address<funding_rate::Actor>>,
+        event_webhooks: Option<Address<event_webhooks::Actor>>,
+        rollover_policy: Option<Address<rollover_policy::Actor>>,
+        opening_fee: Option<Address<opening_fee::Actor>>,
+        pnl: Option<Address<pnl::Actor>>,
     ) -> Result<Self>
     where
         M: Handler<monitor::MonitorAfterContractSetup, Return = ()>
@@ -140,26 +191,25 @@ where
             monitor_addr.clone().into(),
             monitor_addr.into(),
             oracle_addr.clone().into(),
+            None,
+            event_webhooks.map(Into::into),
         )));
 
         let (endpoint_addr, endpoint_context) = Context::new(None);
 
-        let (supervisor, maker_offer_address_deprecated) = Supervisor::new({
-            let endpoint_addr = endpoint_addr.clone();
-            move || offer::deprecated::maker::Actor::new(endpoint_addr.clone())
-        });
-        tasks.add(supervisor.run_log_summary());
-
         let (supervisor, maker_offer_address) = Supervisor::new({
             let endpoint_addr = endpoint_addr.clone();
-            move || offer::maker::Actor::new(endpoint_addr.clone())
+            let opening_fee = opening_fee.clone();
+            move || {
+                offer::maker::Actor::new(endpoint_addr.clone(), opening_fee.clone().map(Into::into))
+            }
         });
         tasks.add(supervisor.run_log_summary());
 
         let (order_supervisor, order) = Supervisor::new({
             let oracle = oracle_addr.clone();
             let db = db.clone();
-            let process_manager = process_manager_addr.clone();
+            let process_manager = process_manager_addr;
             let wallet = wallet_addr.clone();
             let projection = projection_actor.clone();
             let maker_offer_address = maker_offer_address.clone();
@@ -177,85 +227,36 @@ where
         });
         tasks.add(order_supervisor.run_log_summary());
 
-        let (order_deprecated_supervisor, order_deprecated) = Supervisor::new({
-            let oracle = oracle_addr.clone();
-            let db = db.clone();
-            let process_manager = process_manager_addr;
-            let wallet = wallet_addr.clone();
-            let projection = projection_actor.clone();
-            let maker_offer_address = maker_offer_address.clone();
-            move || {
-                order::deprecated::maker::Actor::new(
-                    n_payouts,
-                    oracle_pk,
-                    oracle.clone().into(),
-                    (db.clone(), process_manager.clone()),
-                    (wallet.clone().into(), wallet.clone().into()),
-                    projection.clone(),
-                    maker_offer_address.clone().into(),
-                )
-            }
-        });
-        tasks.add(order_deprecated_supervisor.run_log_summary());
-
         let (collab_settlement_supervisor, collab_settlement_addr) = Supervisor::new({
             let executor = executor.clone();
             move || collab_settlement::maker::Actor::new(executor.clone(), n_payouts)
         });
         tasks.add(collab_settlement_supervisor.run_log_summary());
 
-        let (collab_settlement_deprecated_supervisor, collab_settlement_deprecated_addr) =
-            Supervisor::new({
-                let executor = executor.clone();
-                move || {
-                    collab_settlement::deprecated::maker::Actor::new(executor.clone(), n_payouts)
-                }
-            });
-        tasks.add(collab_settlement_deprecated_supervisor.run_log_summary());
-
         let cfd_actor_addr = cfd::Actor::new(
             settlement_interval,
             projection_actor,
             time_to_first_position_addr,
-            (
-                collab_settlement_addr.clone(),
-                collab_settlement_deprecated_addr.clone(),
-            ),
-            (
-                maker_offer_address.clone(),
-                maker_offer_address_deprecated.clone(),
-            ),
-            (order.clone(), order_deprecated.clone()),
+            collab_settlement_addr.clone(),
+            maker_offer_address.clone(),
+            order.clone(),
+            funding_rate_engine,
         )
         .create(None)
         .spawn(&mut tasks);
 
-        let (rollover_deprecated_supervisor, rollover_deprecated_addr) = Supervisor::new({
-            let executor = executor.clone();
-            let oracle_addr = oracle_addr.clone();
-            let cfd_actor_addr = cfd_actor_addr.clone();
-            move || {
-                rollover::deprecated::maker::Actor::new(
-                    executor.clone(),
-                    oracle_pk,
-                    oracle::AnnouncementsChannel::new(oracle_addr.clone().into()),
-                    cfd::RatesChannel::new(cfd_actor_addr.clone().into()),
-                    n_payouts,
-                )
-            }
-        });
-        tasks.add(rollover_deprecated_supervisor.run_log_summary());
-
         let (rollover_supervisor, rollover_addr) = Supervisor::new({
             let executor = executor.clone();
             let oracle_addr = oracle_addr.clone();
             let cfd_actor_addr = cfd_actor_addr.clone();
+            let rollover_policy = rollover_policy.clone();
             move || {
                 rollover::maker::Actor::new(
                     executor.clone(),
                     oracle_pk,
                     oracle::AnnouncementsChannel::new(oracle_addr.clone().into()),
                     cfd::RatesChannel::new(cfd_actor_addr.clone().into()),
+                    rollover_policy::PolicyChannel::new(rollover_policy.clone().map(Into::into)),
                     n_payouts,
                 )
             }
@@ -267,18 +268,30 @@ where
             move || ping::Actor::new(endpoint_addr.clone(), PING_INTERVAL)
         });
 
-        let (listener_supervisor, listener_actor) = Supervisor::<_, listener::Error>::with_policy(
-            {
-                let listen_multiaddr = listen_multiaddr.clone();
-                let endpoint_addr = endpoint_addr.clone();
-                move || listener::Actor::new(endpoint_addr.clone(), listen_multiaddr.clone())
-            },
-            always_restart_after(RESTART_INTERVAL),
-        );
+        let (listener_supervisors, listener_actors): (Vec<_>, Vec<_>) = listen_multiaddrs
+            .into_iter()
+            .map(|listen_multiaddr| {
+                Supervisor::<_, listener::Error>::with_policy(
+                    {
+                        let endpoint_addr = endpoint_addr.clone();
+                        move || {
+                            listener::Actor::new(endpoint_addr.clone(), listen_multiaddr.clone())
+                        }
+                    },
+                    always_restart_after(RESTART_INTERVAL),
+                )
+            })
+            .unzip();
 
         // TODO: Shouldn't this actor also be supervised?
         let pong_address = pong::Actor.create(None).spawn(&mut tasks);
 
+        let relay_client = relay::client::Actor::default()
+            .create(None)
+            .spawn(&mut tasks);
+        let relay_transport =
+            RelayTransport::new(&endpoint_addr).with_listener(relay_client.clone());
+
         let (identify_listener_supervisor, identify_listener_actor) = Supervisor::new({
             let identity = identity.libp2p.clone();
             move || {
@@ -286,48 +299,59 @@ where
                     daemon::version(),
                     Environment::Unknown,
                     identity.public(),
-                    HashSet::from([listen_multiaddr.clone()]),
+                    advertised_multiaddrs.clone(),
                     MAKER_LISTEN_PROTOCOLS.into(),
                 )
             }
         });
 
-        let (identify_dialer_supervisor, identify_dialer_actor) =
-            Supervisor::new(move || identify::dialer::Actor::new(endpoint_addr.clone()));
+        let (identify_dialer_supervisor, identify_dialer_actor) = Supervisor::new({
+            let endpoint_addr = endpoint_addr.clone();
+            move || identify::dialer::Actor::new(endpoint_addr.clone())
+        });
+
+        let connection_history_actor = connection_history::Actor::new(db.clone())
+            .create(None)
+            .spawn(&mut tasks);
 
         let endpoint = Endpoint::new(
-            Box::new(TokioTcpConfig::new),
+            Box::new(move || daemon::libp2p_utils::create_maker_transport(relay_transport.clone())),
             identity.libp2p,
             ENDPOINT_CONNECTION_TIMEOUT,
             MAKER_LISTEN_PROTOCOLS.inbound_substream_handlers(
                 pong_address.clone(),
                 identify_listener_actor,
-                (order, order_deprecated),
-                (rollover_addr.clone(), rollover_deprecated_addr.clone()),
-                (collab_settlement_addr, collab_settlement_deprecated_addr),
+                order,
+                rollover_addr.clone(),
+                collab_settlement_addr,
+                relay_client.clone(),
             ),
             endpoint::Subscribers::new(
                 vec![
                     ping_address.clone().into(),
                     maker_offer_address.clone().into(),
-                    maker_offer_address_deprecated.clone().into(),
                     identify_dialer_actor.clone().into(),
+                    connection_history_actor.clone().into(),
                 ],
                 vec![
-                    ping_address.into(),
+                    ping_address.clone().into(),
                     maker_offer_address.into(),
-                    maker_offer_address_deprecated.into(),
-                    identify_dialer_actor.into(),
+                    identify_dialer_actor.clone().into(),
+                    connection_history_actor.into(),
+                    relay_client.into(),
                 ],
                 vec![],
-                vec![listener_actor.into()],
+                listener_actors.into_iter().map(Into::into).collect(),
             ),
-            Arc::new(blocked_peers),
+            blocked_peers.clone(),
+            connection_limits,
         );
 
         tasks.add(endpoint_context.run(endpoint));
 
-        tasks.add(listener_supervisor.run_log_summary());
+        for listener_supervisor in listener_supervisors {
+            tasks.add(listener_supervisor.run_log_summary());
+        }
         tasks.add(ping_supervisor.run_log_summary());
         tasks.add(identify_listener_supervisor.run_log_summary());
         tasks.add(identify_dialer_supervisor.run_log_summary());
@@ -344,7 +368,11 @@ where
             .create(None)
             .spawn(&mut tasks);
 
-        tasks.add(time_to_first_position_ctx.run(time_to_first_position::Actor::new(db)));
+        tasks.add(time_to_first_position_ctx.run(time_to_first_position::Actor::new(db.clone())));
+
+        let referral_metrics_actor = referrals::Actor::new(db.clone())
+            .create(None)
+            .spawn(&mut tasks);
 
         tracing::debug!("Maker actor system ready");
 
@@ -352,13 +380,22 @@ where
             cfd_actor: cfd_actor_addr,
             wallet_actor: wallet_addr,
             rollover_actor: rollover_addr,
-            rollover_actor_deprecated: rollover_deprecated_addr,
+            rollover_policy,
+            opening_fee,
+            pnl,
             _archive_closed_cfds_actor: archive_closed_cfds_actor,
             _archive_failed_cfds_actor: archive_failed_cfds_actor,
+            _referral_metrics_actor: referral_metrics_actor,
             executor,
             _oracle_actor: oracle_addr,
             _tasks: tasks,
             _pong_actor: pong_address,
+            endpoint: endpoint_addr,
+            ping_actor: ping_address,
+            identify_dialer_actor,
+            db,
+            static_blocked_peers: blocked_peers,
+            allowlist_only,
         })
     }
 
@@ -380,7 +417,8 @@ where
         contract_symbol: ContractSymbol,
         lot_size: LotSize,
     ) -> Result<()> {
-        self.cfd_actor
+        let published_rates = self
+            .cfd_actor
             .send(cfd::OfferParams {
                 price_long,
                 price_short,
@@ -396,9 +434,193 @@ where
             })
             .await??;
 
+        if let Err(e) = self
+            .db
+            .record_funding_rates(
+                contract_symbol,
+                published_rates.long,
+                published_rates.short,
+                Timestamp::now(),
+            )
+            .await
+        {
+            tracing::warn!(%contract_symbol, "Failed to record funding rates: {e:#}");
+        }
+
         Ok(())
     }
 
+    /// Disconnect the peer and prevent it from connecting again for the given duration.
+    pub async fn ban_peer(
+        &self,
+        peer_id: PeerId,
+        duration: Duration,
+        reason: String,
+    ) -> Result<()> {
+        self.endpoint
+            .send(BanPeer {
+                peer_id,
+                duration,
+                reason,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lift the ban of a peer, returns whether the peer was banned.
+    pub async fn unban_peer(&self, peer_id: PeerId) -> Result<bool> {
+        let was_banned = self.endpoint.send(UnbanPeer(peer_id)).await?;
+
+        Ok(was_banned)
+    }
+
+    pub async fn banned_peers(&self) -> Result<Vec<BannedPeer>> {
+        let banned_peers = self.endpoint.send(GetBannedPeers).await?;
+
+        Ok(banned_peers)
+    }
+
+    pub async fn blocked_peers(&self) -> Result<Vec<PeerListEntry>> {
+        self.db.load_peer_list(PeerList::Blocked).await
+    }
+
+    /// Block the peer from connecting, disconnecting it if currently connected.
+    pub async fn block_peer(&self, peer_id: PeerId, note: &str) -> Result<PeerListEntry> {
+        let entry = self
+            .db
+            .insert_peer_list_entry(PeerList::Blocked, peer_id.into(), note)
+            .await?;
+        self.sync_peer_access().await?;
+
+        Ok(entry)
+    }
+
+    pub async fn unblock_peer(&self, peer_id: PeerId) -> Result<()> {
+        self.db
+            .delete_peer_list_entry(PeerList::Blocked, peer_id.into())
+            .await?;
+        self.sync_peer_access().await?;
+
+        Ok(())
+    }
+
+    pub async fn allowed_peers(&self) -> Result<Vec<PeerListEntry>> {
+        self.db.load_peer_list(PeerList::Allowed).await
+    }
+
+    /// Add the peer to the list of peers which can connect in allowlist-only mode.
+    pub async fn allow_peer(&self, peer_id: PeerId, note: &str) -> Result<PeerListEntry> {
+        let entry = self
+            .db
+            .insert_peer_list_entry(PeerList::Allowed, peer_id.into(), note)
+            .await?;
+        self.sync_peer_access().await?;
+
+        Ok(entry)
+    }
+
+    /// Remove the peer from the allowlist, disconnecting it if only allowed peers can connect.
+    pub async fn disallow_peer(&self, peer_id: PeerId) -> Result<()> {
+        self.db
+            .delete_peer_list_entry(PeerList::Allowed, peer_id.into())
+            .await?;
+        self.sync_peer_access().await?;
+
+        Ok(())
+    }
+
+    /// Apply the blocked and allowed peers from the database to the endpoint.
+    ///
+    /// Connected peers which are no longer permitted to connect are disconnected.
+    pub async fn sync_peer_access(&self) -> Result<()> {
+        let blocked_peers = self
+            .db
+            .load_peer_list(PeerList::Blocked)
+            .await?
+            .into_iter()
+            .map(|entry| entry.peer_id.inner())
+            .chain(self.static_blocked_peers.iter().copied())
+            .collect::<HashSet<_>>();
+        let allowed_peers = match self.allowlist_only {
+            true => Some(
+                self.db
+                    .load_peer_list(PeerList::Allowed)
+                    .await?
+                    .into_iter()
+                    .map(|entry| entry.peer_id.inner())
+                    .collect::<HashSet<_>>(),
+            ),
+            false => None,
+        };
+
+        self.endpoint
+            .send(SetBlockedPeers(blocked_peers.clone()))
+            .await?;
+        self.endpoint
+            .send(SetAllowedPeers(allowed_peers.clone()))
+            .await?;
+
+        let connected_peers = self
+            .endpoint
+            .send(GetConnectionStats)
+            .await?
+            .connected_peers;
+        for peer_id in connected_peers {
+            let is_allowed = match &allowed_peers {
+                Some(allowed_peers) => allowed_peers.contains(&peer_id),
+                None => true,
+            };
+
+            if blocked_peers.contains(&peer_id) || !is_allowed {
+                tracing::info!(%peer_id, "Disconnecting peer which is no longer permitted");
+                self.endpoint.send(Disconnect(peer_id)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// List the connected takers, with the information they provided about themselves.
+    pub async fn connected_takers(&self) -> Result<Vec<ConnectedTaker>> {
+        let connection_addresses = self
+            .endpoint
+            .send(GetConnectionStats)
+            .await?
+            .connection_addresses;
+        let mut peer_infos = self.identify_dialer_actor.send(GetPeerInfos).await?;
+        let latencies = self.ping_actor.send(ping::GetLatencies).await?;
+
+        let takers = connection_addresses
+            .into_iter()
+            .map(|(peer_id, address)| {
+                let peer_info = peer_infos.remove(&peer_id);
+
+                ConnectedTaker {
+                    peer_id,
+                    address,
+                    daemon_version: peer_info
+                        .as_ref()
+                        .map(|peer_info| peer_info.daemon_version.clone()),
+                    environment: peer_info.map(|peer_info| peer_info.environment),
+                    latency: latencies.get(&peer_id).copied(),
+                }
+            })
+            .collect();
+
+        Ok(takers)
+    }
+
+    pub async fn connection_history(
+        &self,
+        peer_id: Option<PeerId>,
+        limit: u32,
+    ) -> Result<Vec<ConnectionHistoryEntry>> {
+        self.db
+            .load_connection_history(peer_id.map(Into::into), limit)
+            .await
+    }
+
     pub async fn accept_order(&self, order_id: OrderId) -> Result<()> {
         self.cfd_actor.send(cfd::AcceptOrder { order_id }).await??;
         Ok(())
@@ -452,11 +674,6 @@ where
     }
 
     pub async fn update_rollover_configuration(&self, is_accepting_rollovers: bool) -> Result<()> {
-        self.rollover_actor_deprecated
-            .send(rollover::deprecated::maker::UpdateConfiguration::new(
-                is_accepting_rollovers,
-            ))
-            .await?;
         self.rollover_actor
             .send(rollover::maker::UpdateConfiguration::new(
                 is_accepting_rollovers,
@@ -464,4 +681,61 @@ where
             .await?;
         Ok(())
     }
+
+    pub async fn rollover_rules(&self) -> Result<rollover_policy::Rules> {
+        let rules = self
+            .rollover_policy()?
+            .send(rollover_policy::GetRules)
+            .await?;
+
+        Ok(rules)
+    }
+
+    pub async fn update_rollover_rules(&self, rules: rollover_policy::Rules) -> Result<()> {
+        self.rollover_policy()?
+            .send(rollover_policy::UpdateRules(rules))
+            .await??;
+
+        Ok(())
+    }
+
+    fn rollover_policy(&self) -> Result<&Address<rollover_policy::Actor>> {
+        self.rollover_policy
+            .as_ref()
+            .context("Rollover policy is not enabled")
+    }
+
+    pub async fn fee_schedule(&self) -> Result<opening_fee::FeeSchedule> {
+        let schedule = self
+            .opening_fee()?
+            .send(opening_fee::GetFeeSchedule)
+            .await?;
+
+        Ok(schedule)
+    }
+
+    pub async fn update_fee_schedule(&self, schedule: opening_fee::FeeSchedule) -> Result<()> {
+        self.opening_fee()?
+            .send(opening_fee::UpdateFeeSchedule(schedule))
+            .await??;
+
+        Ok(())
+    }
+
+    fn opening_fee(&self) -> Result<&Address<opening_fee::Actor>> {
+        self.opening_fee
+            .as_ref()
+            .context("Opening fee schedule is not enabled")
+    }
+
+    pub async fn pnl_report(&self) -> Result<pnl::Report> {
+        let report = self
+            .pnl
+            .as_ref()
+            .context("PnL reporting is not enabled")?
+            .send(pnl::GetReport)
+            .await??;
+
+        Ok(report)
+    }
 }
@@ -1,3 +1,4 @@
+use crate::funding_rate;
 use crate::metrics::time_to_first_position;
 use anyhow::bail;
 use anyhow::Context;
@@ -17,7 +18,6 @@ use model::Position;
 use model::Price;
 use model::Timestamp;
 use model::TxFeeRate;
-use nonempty::NonEmpty;
 use std::collections::HashMap;
 use time::Duration;
 use time::OffsetDateTime;
@@ -153,8 +153,8 @@ struct RolloverParams {
 
 #[derive(Clone, Copy)]
 pub struct FundingRates {
-    long: FundingRate,
-    short: FundingRate,
+    pub long: FundingRate,
+    pub short: FundingRate,
 }
 
 pub struct Actor {
@@ -163,12 +163,9 @@ pub struct Actor {
     rollover_params: RolloverParams,
     time_to_first_position: xtra::Address<time_to_first_position::Actor>,
     collab_settlement: xtra::Address<daemon::collab_settlement::maker::Actor>,
-    collab_settlement_deprecated:
-        xtra::Address<daemon::collab_settlement::deprecated::maker::Actor>,
     offer: xtra::Address<offer::maker::Actor>,
-    offer_deprecated: xtra::Address<offer::deprecated::maker::Actor>,
     order: xtra::Address<order::maker::Actor>,
-    order_deprecated: xtra::Address<order::deprecated::maker::Actor>,
+    funding_rate_engine: Option<xtra::Address<funding_rate::Actor>>,
 }
 
 impl Actor {
@@ -176,18 +173,10 @@ impl Actor {
         settlement_interval: Duration,
         projection: xtra::Address<projection::Actor>,
         time_to_first_position: xtra::Address<time_to_first_position::Actor>,
-        (collab_settlement, collab_settlement_deprecated): (
-            xtra::Address<daemon::collab_settlement::maker::Actor>,
-            xtra::Address<daemon::collab_settlement::deprecated::maker::Actor>,
-        ),
-        (offer, offer_deprecated): (
-            xtra::Address<offer::maker::Actor>,
-            xtra::Address<offer::deprecated::maker::Actor>,
-        ),
-        (order, order_deprecated): (
-            xtra::Address<order::maker::Actor>,
-            xtra::Address<order::deprecated::maker::Actor>,
-        ),
+        collab_settlement: xtra::Address<daemon::collab_settlement::maker::Actor>,
+        offer: xtra::Address<offer::maker::Actor>,
+        order: xtra::Address<order::maker::Actor>,
+        funding_rate_engine: Option<xtra::Address<funding_rate::Actor>>,
     ) -> Self {
         Self {
             settlement_interval,
@@ -195,11 +184,41 @@ impl Actor {
             rollover_params: RolloverParams::default(),
             time_to_first_position,
             collab_settlement,
-            collab_settlement_deprecated,
             offer,
-            offer_deprecated,
             order,
-            order_deprecated,
+            funding_rate_engine,
+        }
+    }
+
+    /// The funding rates computed by the funding rate engine, if enabled.
+    ///
+    /// Returns `None` if the engine is disabled or could not compute a rate yet, in which case the
+    /// rates of the offer parameters are used.
+    ///
+    /// Only applied when publishing offers, rollovers use the rates of the latest offers so that
+    /// takers are charged the rates the maker published and recorded.
+    async fn computed_funding_rates(
+        &self,
+        contract_symbol: ContractSymbol,
+    ) -> Option<FundingRates> {
+        let engine = self.funding_rate_engine.as_ref()?;
+
+        match engine
+            .send(funding_rate::GetFundingRate(contract_symbol))
+            .await
+        {
+            Ok(Some(rate)) => Some(FundingRates {
+                long: rate,
+                short: rate,
+            }),
+            Ok(None) => {
+                tracing::debug!(%contract_symbol, "No computed funding rate available yet");
+                None
+            }
+            Err(e) => {
+                tracing::warn!("Funding rate engine disconnected: {e:#}");
+                None
+            }
         }
     }
 
@@ -238,27 +257,9 @@ impl Actor {
     async fn handle_accept_order(&mut self, msg: AcceptOrder) -> Result<()> {
         let AcceptOrder { order_id } = msg;
 
-        let res = self
-            .order
+        self.order
             .send(order::maker::Decision::Accept(order_id))
-            .await
-            .map_err(anyhow::Error::new);
-
-        // We try with the deprecated order protocol if the latest version fails
-        if let Err(e0) | Ok(Err(e0)) = res {
-            if let Err(e1) | Ok(Err(e1)) = self
-                .order_deprecated
-                .send(order::deprecated::maker::Decision::Accept(order_id))
-                .await
-                .map_err(anyhow::Error::new)
-            {
-                bail!(
-                    "Failed to accept order.
-                     Current version error: {e0:#}.
-                     Deprecated version error: {e1:#}"
-                );
-            }
-        }
+            .await??;
 
         Ok(())
     }
@@ -266,27 +267,9 @@ impl Actor {
     async fn handle_reject_order(&mut self, msg: RejectOrder) -> Result<()> {
         let RejectOrder { order_id } = msg;
 
-        let res = self
-            .order
+        self.order
             .send(order::maker::Decision::Reject(order_id))
-            .await
-            .map_err(anyhow::Error::new);
-
-        // We try with the deprecated order protocol if the latest version fails
-        if let Err(e0) | Ok(Err(e0)) = res {
-            if let Err(e1) | Ok(Err(e1)) = self
-                .order_deprecated
-                .send(order::deprecated::maker::Decision::Reject(order_id))
-                .await
-                .map_err(anyhow::Error::new)
-            {
-                bail!(
-                    "Failed to reject order.
-                     Current version error: {e0:#}.
-                     Deprecated version error: {e1:#}"
-                );
-            }
-        }
+            .await??;
 
         Ok(())
     }
@@ -294,27 +277,9 @@ impl Actor {
     async fn handle_accept_settlement(&mut self, msg: AcceptSettlement) -> Result<()> {
         let AcceptSettlement { order_id } = msg;
 
-        let res = self
-            .collab_settlement
+        self.collab_settlement
             .send(daemon::collab_settlement::maker::Accept { order_id })
-            .await
-            .map_err(anyhow::Error::new);
-
-        // We try with the deprecated collaborative settlement protocol if the latest version fails
-        if let Err(e0) | Ok(Err(e0)) = res {
-            if let Err(e1) | Ok(Err(e1)) = self
-                .collab_settlement_deprecated
-                .send(daemon::collab_settlement::deprecated::maker::Accept { order_id })
-                .await
-                .map_err(anyhow::Error::new)
-            {
-                bail!(
-                    "Failed to accept collaborative settlement.
-                     Current version error: {e0:#}.
-                     Deprecated version error: {e1:#}"
-                );
-            }
-        }
+            .await??;
 
         Ok(())
     }
@@ -322,27 +287,9 @@ impl Actor {
     async fn handle_reject_settlement(&mut self, msg: RejectSettlement) -> Result<()> {
         let RejectSettlement { order_id } = msg;
 
-        let res = self
-            .collab_settlement
+        self.collab_settlement
             .send(daemon::collab_settlement::maker::Reject { order_id })
-            .await
-            .map_err(anyhow::Error::new);
-
-        // We try with the deprecated collaborative settlement protocol if the latest version fails
-        if let Err(e0) | Ok(Err(e0)) = res {
-            if let Err(e1) | Ok(Err(e1)) = self
-                .collab_settlement_deprecated
-                .send(daemon::collab_settlement::deprecated::maker::Reject { order_id })
-                .await
-                .map_err(anyhow::Error::new)
-            {
-                bail!(
-                    "Failed to reject collaborative settlement.
-                     Current version error: {e0:#}.
-                     Deprecated version error: {e1:#}"
-                );
-            }
-        }
+            .await??;
 
         Ok(())
     }
@@ -369,7 +316,16 @@ impl Actor {
 
 #[xtra_productivity]
 impl Actor {
-    async fn handle_offer_params(&mut self, offer_params: OfferParams) -> Result<()> {
+    /// Publish offers for the parameters, returning the funding rates that were published.
+    async fn handle_offer_params(&mut self, mut offer_params: OfferParams) -> Result<FundingRates> {
+        if let Some(FundingRates { long, short }) = self
+            .computed_funding_rates(offer_params.contract_symbol)
+            .await
+        {
+            offer_params.funding_rate_long = long;
+            offer_params.funding_rate_short = short;
+        }
+
         // 1. Update internal state for rollovers
         self.udpate_rollover_params(
             offer_params.contract_symbol,
@@ -377,6 +333,10 @@ impl Actor {
             offer_params.funding_rate_short,
             offer_params.tx_fee_rate,
         );
+        let published_rates = FundingRates {
+            long: offer_params.funding_rate_long,
+            short: offer_params.funding_rate_short,
+        };
 
         let offers = offer_params.into_offers(self.settlement_interval);
 
@@ -388,32 +348,13 @@ impl Actor {
         // 3. Broadcast to all peers via offer actor
         if let Err(e) = self
             .offer
-            .send_async_safe(offer::maker::NewOffers::new(offers.clone()))
+            .send_async_safe(offer::maker::NewOffers::new(offers))
             .await
         {
             tracing::warn!("{e:#}");
         }
 
-        // 4. Broadcast to all peers via deprecated offer actor
-        {
-            // Takers on the deprecated version only care (and know how to handle) BTCUSD offers
-            let btcusd_offers = offers
-                .into_iter()
-                .filter(|offer| offer.contract_symbol == ContractSymbol::BtcUsd)
-                .collect::<Vec<_>>();
-
-            if let Some(btcusd_offers) = NonEmpty::from_vec(btcusd_offers) {
-                if let Err(e) = self
-                    .offer_deprecated
-                    .send_async_safe(offer::deprecated::maker::NewOffers::new(btcusd_offers))
-                    .await
-                {
-                    tracing::warn!("{e:#}");
-                }
-            };
-        }
-
-        Ok(())
+        Ok(published_rates)
     }
 
     async fn handle(&mut self, msg: TakerConnected) -> Result<()> {
@@ -437,26 +378,6 @@ impl RatesChannel {
     }
 }
 
-#[async_trait]
-impl rollover::deprecated::protocol::GetRates for RatesChannel {
-    async fn get_rates(
-        &self,
-        contract_symbol: ContractSymbol,
-    ) -> Result<rollover::deprecated::protocol::Rates> {
-        let (FundingRates { long, short }, tx_fee_rate) = self
-            .0
-            .send(GetRolloverParams(contract_symbol))
-            .await
-            .context("CFD actor disconnected")??;
-
-        Ok(rollover::deprecated::protocol::Rates::new(
-            long,
-            short,
-            tx_fee_rate,
-        ))
-    }
-}
-
 #[async_trait]
 impl rollover::protocol::GetRates for RatesChannel {
     async fn get_rates(
@@ -0,0 +1,360 @@
+//! Computes the funding rates of offers and rollovers automatically, instead of taking them from
+//! the offer parameters.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::projection;
+use daemon::projection::CfdState;
+use model::ContractSymbol;
+use model::Contracts;
+use model::FundingRate;
+use model::Position;
+use model::SETTLEMENT_INTERVAL;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::sync::watch;
+use xtra::prelude::MessageChannel;
+use xtra_bitmex_price_feed::GetLatestFundingRates;
+use xtra_bitmex_price_feed::LatestFundingRates;
+use xtra_bitmex_price_feed::FUNDING_INTERVAL_HOURS;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often the funding rates are recomputed.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The period over which [`Config::smoothing`] applies, the funding interval of BitMEX.
+const SMOOTHING_PERIOD: Duration = Duration::from_secs(FUNDING_INTERVAL_HOURS as u64 * 60 * 60);
+
+/// Where the funding rates of offers and rollovers come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Source {
+    /// The rates of the offer parameters.
+    Manual,
+    /// The funding rate of the BitMEX perpetual swap of the contract symbol.
+    Bitmex,
+    /// The imbalance between the long and short positions of takers.
+    ///
+    /// If takers are predominantly long, longs pay shorts and vice versa. The rate reaches the
+    /// bounds if all takers are on the same side.
+    Imbalance,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub source: Source,
+    /// Lower bound of the computed funding rate per settlement interval.
+    pub min_rate: FundingRate,
+    /// Upper bound of the computed funding rate per settlement interval.
+    pub max_rate: FundingRate,
+    /// Weight of a sample in the exponential moving average of the funding rate, if the sample
+    /// prevailed for a whole [`SMOOTHING_PERIOD`].
+    ///
+    /// Has to be between 0 and 1, where 1 disables smoothing.
+    pub smoothing: Decimal,
+}
+
+impl Config {
+    /// Smooth the sample with the previous funding rate and bound the result.
+    ///
+    /// The sample is weighted by the time `elapsed` since the previous rate was computed, hence
+    /// the rate converges at the same pace regardless of how often it is recomputed.
+    fn next_rate(
+        &self,
+        previous: Option<FundingRate>,
+        sample: Decimal,
+        elapsed: Duration,
+    ) -> FundingRate {
+        let smoothed = match previous {
+            None => sample,
+            Some(previous) => {
+                let previous = previous.to_decimal();
+                previous + self.sample_weight(elapsed) * (sample - previous)
+            }
+        };
+
+        let bounded = smoothed.clamp(self.min_rate.to_decimal(), self.max_rate.to_decimal());
+
+        FundingRate::new(bounded).expect("bounded rate to be valid")
+    }
+
+    fn sample_weight(&self, elapsed: Duration) -> Decimal {
+        let smoothing = self.smoothing.to_f64().expect("smoothing to fit into f64");
+        let periods = elapsed.as_secs_f64() / SMOOTHING_PERIOD.as_secs_f64();
+        let weight = 1.0 - (1.0 - smoothing).powf(periods);
+
+        Decimal::from_f64(weight)
+            .expect("weight to fit into decimal")
+            .clamp(Decimal::ZERO, Decimal::ONE)
+    }
+
+    /// The funding rate per settlement interval, derived from the positions of takers.
+    fn imbalance_rate(
+        &self,
+        taker_positions: impl IntoIterator<Item = (Position, Contracts)>,
+    ) -> Decimal {
+        let (long, short) = taker_positions.into_iter().fold(
+            (Decimal::ZERO, Decimal::ZERO),
+            |(long, short), (position, quantity)| match position {
+                Position::Long => (long + quantity.into_decimal(), short),
+                Position::Short => (long, short + quantity.into_decimal()),
+            },
+        );
+
+        let total = long + short;
+        if total.is_zero() {
+            return Decimal::ZERO;
+        }
+
+        let imbalance = (long - short) / total;
+
+        if imbalance.is_sign_positive() {
+            imbalance * self.max_rate.to_decimal()
+        } else {
+            imbalance * self.min_rate.to_decimal().abs()
+        }
+    }
+}
+
+/// Convert a BitMEX funding rate to a funding rate per settlement interval.
+fn bitmex_rate(rate: Decimal) -> Decimal {
+    rate * Decimal::from(SETTLEMENT_INTERVAL.whole_hours()) / Decimal::from(FUNDING_INTERVAL_HOURS)
+}
+
+pub struct Actor {
+    config: Config,
+    price_feed: MessageChannel<GetLatestFundingRates, LatestFundingRates>,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    /// The computed rates, together with when they were computed.
+    rates: HashMap<ContractSymbol, (FundingRate, Instant)>,
+}
+
+impl Actor {
+    pub fn new(
+        config: Config,
+        price_feed: MessageChannel<GetLatestFundingRates, LatestFundingRates>,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    ) -> Self {
+        Self {
+            config,
+            price_feed,
+            cfds,
+            rates: HashMap::new(),
+        }
+    }
+
+    async fn samples(&self) -> Result<HashMap<ContractSymbol, Decimal>> {
+        let samples = match self.config.source {
+            Source::Manual => HashMap::new(),
+            Source::Bitmex => {
+                let latest = self.price_feed.send(GetLatestFundingRates).await?;
+
+                [ContractSymbol::BtcUsd, ContractSymbol::EthUsd]
+                    .into_iter()
+                    .filter_map(|symbol| {
+                        let funding_rate = latest.get(&into_price_feed_symbol(symbol))?;
+
+                        Some((symbol, bitmex_rate(funding_rate.rate)))
+                    })
+                    .collect()
+            }
+            Source::Imbalance => {
+                let cfds = self.cfds.borrow();
+                let cfds = match cfds.as_ref() {
+                    Some(cfds) => cfds,
+                    // CFDs are still being loaded, skip this round to not reset the rate
+                    None => return Ok(HashMap::new()),
+                };
+
+                [ContractSymbol::BtcUsd, ContractSymbol::EthUsd]
+                    .into_iter()
+                    .map(|symbol| {
+                        let taker_positions = cfds
+                            .iter()
+                            .filter(|cfd| cfd.contract_symbol == symbol && is_open(cfd.state))
+                            .map(|cfd| (cfd.position.counter_position(), cfd.quantity));
+
+                        (symbol, self.config.imbalance_rate(taker_positions))
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(samples)
+    }
+}
+
+pub(crate) fn is_open(state: CfdState) -> bool {
+    matches!(
+        state,
+        CfdState::PendingOpen
+            | CfdState::Open
+            | CfdState::IncomingSettlementProposal
+            | CfdState::OutgoingSettlementProposal
+            | CfdState::RolloverSetup
+    )
+}
+
+fn into_price_feed_symbol(symbol: ContractSymbol) -> xtra_bitmex_price_feed::ContractSymbol {
+    match symbol {
+        ContractSymbol::BtcUsd => xtra_bitmex_price_feed::ContractSymbol::BtcUsd,
+        ContractSymbol::EthUsd => xtra_bitmex_price_feed::ContractSymbol::EthUsd,
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: UpdateFundingRates) {
+        let samples = match self.samples().await {
+            Ok(samples) => samples,
+            Err(e) => {
+                tracing::warn!("Failed to sample funding rates: {e:#}");
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        for (symbol, sample) in samples {
+            let (previous, elapsed) = match self.rates.get(&symbol) {
+                Some((rate, computed_at)) => (Some(*rate), now.duration_since(*computed_at)),
+                None => (None, Duration::ZERO),
+            };
+            let rate = self.config.next_rate(previous, sample, elapsed);
+
+            tracing::debug!(%symbol, %sample, %rate, "Updated funding rate");
+
+            self.rates.insert(symbol, (rate, now));
+        }
+    }
+
+    async fn handle(&mut self, GetFundingRate(symbol): GetFundingRate) -> Option<FundingRate> {
+        self.rates.get(&symbol).map(|(rate, _)| *rate)
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+        tokio_extras::spawn(
+            &this.clone(),
+            this.send_interval(
+                UPDATE_INTERVAL,
+                || UpdateFundingRates,
+                xtras::IncludeSpan::Always,
+            ),
+        );
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Message sent to ourselves at an interval to recompute the funding rates.
+#[derive(Clone, Copy)]
+pub struct UpdateFundingRates;
+
+/// Get the computed funding rate per settlement interval for a contract symbol.
+///
+/// Returns `None` if no rate could be computed yet.
+#[derive(Clone, Copy)]
+pub struct GetFundingRate(pub ContractSymbol);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn given_no_previous_rate_then_sample_is_used() {
+        let config = dummy_config(dec!(0.5));
+
+        let rate = config.next_rate(None, dec!(0.001), Duration::ZERO);
+
+        assert_eq!(rate.to_decimal(), dec!(0.001));
+    }
+
+    #[test]
+    fn given_previous_rate_then_sample_is_smoothed() {
+        let config = dummy_config(dec!(0.5));
+        let previous = FundingRate::new(dec!(0.001)).unwrap();
+
+        let rate = config.next_rate(Some(previous), dec!(0.002), SMOOTHING_PERIOD);
+
+        assert_eq!(rate.to_decimal(), dec!(0.0015));
+    }
+
+    #[test]
+    fn given_frequent_updates_then_rate_converges_at_same_pace() {
+        let config = dummy_config(dec!(0.5));
+        let previous = FundingRate::new(dec!(0.001)).unwrap();
+
+        let mut rate = previous;
+        for _ in 0..(SMOOTHING_PERIOD.as_secs() / UPDATE_INTERVAL.as_secs()) {
+            rate = config.next_rate(Some(rate), dec!(0.002), UPDATE_INTERVAL);
+        }
+
+        assert_eq!(rate.to_decimal().round_dp(6), dec!(0.0015));
+    }
+
+    #[test]
+    fn given_sample_exceeds_bounds_then_rate_is_bounded() {
+        let config = dummy_config(Decimal::ONE);
+
+        let upper = config.next_rate(None, dec!(0.1), Duration::ZERO);
+        let lower = config.next_rate(None, dec!(-0.1), Duration::ZERO);
+
+        assert_eq!(upper, config.max_rate);
+        assert_eq!(lower, config.min_rate);
+    }
+
+    #[test]
+    fn given_takers_predominantly_long_then_longs_pay() {
+        let config = dummy_config(Decimal::ONE);
+
+        let rate = config.imbalance_rate([
+            (Position::Long, Contracts::new(300)),
+            (Position::Short, Contracts::new(100)),
+        ]);
+
+        assert_eq!(rate, dec!(0.0015));
+    }
+
+    #[test]
+    fn given_takers_only_short_then_shorts_pay_at_bound() {
+        let config = dummy_config(Decimal::ONE);
+
+        let rate = config.imbalance_rate([(Position::Short, Contracts::new(100))]);
+
+        assert_eq!(rate, config.min_rate.to_decimal());
+    }
+
+    #[test]
+    fn given_no_positions_then_no_imbalance() {
+        let config = dummy_config(Decimal::ONE);
+
+        let rate = config.imbalance_rate([]);
+
+        assert_eq!(rate, Decimal::ZERO);
+    }
+
+    #[test]
+    fn bitmex_rate_is_scaled_to_settlement_interval() {
+        assert_eq!(bitmex_rate(dec!(0.0001)), dec!(0.0003));
+    }
+
+    fn dummy_config(smoothing: Decimal) -> Config {
+        Config {
+            source: Source::Imbalance,
+            min_rate: FundingRate::new(dec!(-0.003)).unwrap(),
+            max_rate: FundingRate::new(dec!(0.003)).unwrap(),
+            smoothing,
+        }
+    }
+}
@@ -1,19 +1,33 @@
+use anyhow::ensure;
+use anyhow::Result;
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
 use clap::Parser;
 use daemon::bdk;
+use model::FundingRate;
+use rust_decimal::Decimal;
 use shared_bin::cli::Network;
 use shared_bin::logger::LevelFilter;
 use shared_bin::logger::LOCAL_COLLECTOR_ENDPOINT;
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::limits::ConnectionLimits;
+use xtra_libp2p::limits::Rate;
 
 pub use actor_system::ActorSystem;
 
 mod actor_system;
 pub mod cfd;
+pub mod funding_rate;
 mod metrics;
+pub mod opening_fee;
+pub mod pnl;
+pub mod rollover_policy;
 pub mod routes;
+mod stored_config;
+pub mod ws;
 
 #[derive(Debug)]
 pub struct Password(String);
@@ -44,10 +58,95 @@ pub struct Opts {
     #[clap(long, default_value = "10000")]
     pub p2p_port: u16,
 
+    /// If enabled, libp2p connections are also accepted on all IPv6 interfaces.
+    #[clap(long)]
+    pub p2p_ipv6: bool,
+
+    /// The address under which takers can reach the maker, advertised through the identify
+    /// protocol, e.g. `/dns4/maker.example.com/tcp/10000` or `/ip6/2001:db8::1/tcp/10000`.
+    ///
+    /// To be reachable through Tor, configure a hidden service that forwards to the p2p port and
+    /// advertise its address, e.g. `/onion3/<address>:10000`.
+    ///
+    /// Can be specified multiple times. If not specified the listen addresses are advertised.
+    #[clap(long = "external-address")]
+    pub external_addresses: Vec<Multiaddr>,
+
+    /// The address of a relay to listen on, including its peer ID, e.g.
+    /// `/dns4/relay.example.com/tcp/10100/p2p/<peer id>`.
+    ///
+    /// Takers can reach the maker through the relay even if it cannot be dialed directly, e.g.
+    /// because it runs behind a NAT on a home connection. The relayed address is advertised in
+    /// addition to the external addresses.
+    ///
+    /// Can be specified multiple times.
+    #[clap(long = "relay")]
+    pub relays: Vec<Multiaddr>,
+
+    /// Maximum number of libp2p connections. Unlimited if not specified.
+    #[clap(long)]
+    pub max_connections: Option<usize>,
+
+    /// Maximum number of libp2p connections from the same IP address. Unlimited if not specified.
+    #[clap(long)]
+    pub max_connections_per_ip: Option<usize>,
+
+    /// Maximum number of concurrent substreams a taker may open for each protocol.
+    ///
+    /// Takers exceeding the limit are banned temporarily. Unlimited if not specified.
+    #[clap(long)]
+    pub max_substreams_per_protocol: Option<usize>,
+
+    /// Maximum number of substreams a taker may open per minute, across all protocols.
+    ///
+    /// Takers exceeding the limit are banned temporarily. Unlimited if not specified.
+    #[clap(long)]
+    pub max_substreams_per_minute: Option<usize>,
+
+    /// How long takers exceeding the substream limits are banned for, in seconds.
+    #[clap(long, default_value = "600")]
+    pub ban_duration_secs: u64,
+
+    /// Only allow takers on the allowlist to connect.
+    ///
+    /// Takers can be added to the allowlist through the HTTP API.
+    #[clap(long)]
+    pub allowlist_only: bool,
+
+    /// Where the funding rates of offers and rollovers come from: `manual`, `bitmex` or
+    /// `imbalance`.
+    ///
+    /// With `manual` the funding rates of the offer parameters are used. With `bitmex` the funding
+    /// rate of the BitMEX perpetual swap is tracked. With `imbalance` the rate is derived from the
+    /// imbalance between long and short positions of takers.
+    #[clap(long, default_value = "manual")]
+    pub funding_rate_source: funding_rate::Source,
+
+    /// Lower bound of computed funding rates, per settlement interval.
+    #[clap(long, default_value = "-0.003", allow_hyphen_values = true)]
+    pub min_funding_rate: FundingRate,
+
+    /// Upper bound of computed funding rates, per settlement interval.
+    #[clap(long, default_value = "0.003")]
+    pub max_funding_rate: FundingRate,
+
+    /// Weight of a sample in the moving average of computed funding rates, between 0 and 1, if
+    /// the sample prevails for a funding interval of 8 hours.
+    ///
+    /// Lower values smooth out short-term changes, 1 disables smoothing.
+    #[clap(long, default_value = "0.2")]
+    pub funding_rate_smoothing: Decimal,
+
     /// The IP address to listen on for the HTTP API.
     #[clap(long, default_value = "127.0.0.1:8001")]
     pub http_address: SocketAddr,
 
+    /// The IP address to listen on for the WebSocket API.
+    ///
+    /// If not specified the WebSocket API is disabled.
+    #[clap(long)]
+    pub ws_address: Option<SocketAddr>,
+
     /// Where to permanently store data, defaults to the current working directory.
     #[clap(long)]
     pub data_dir: Option<PathBuf>,
@@ -113,3 +212,34 @@ pub struct Opts {
     #[clap(subcommand)]
     pub network: Network,
 }
+
+impl Opts {
+    pub fn funding_rate_config(&self) -> Result<funding_rate::Config> {
+        ensure!(
+            self.min_funding_rate.to_decimal() <= self.max_funding_rate.to_decimal(),
+            "Minimum funding rate must not exceed maximum funding rate"
+        );
+        ensure!(
+            self.funding_rate_smoothing > Decimal::ZERO
+                && self.funding_rate_smoothing <= Decimal::ONE,
+            "Funding rate smoothing must be greater than 0 and at most 1"
+        );
+
+        Ok(funding_rate::Config {
+            source: self.funding_rate_source,
+            min_rate: self.min_funding_rate,
+            max_rate: self.max_funding_rate,
+            smoothing: self.funding_rate_smoothing,
+        })
+    }
+
+    pub fn connection_limits(&self) -> ConnectionLimits {
+        ConnectionLimits {
+            max_connections: self.max_connections,
+            max_connections_per_ip: self.max_connections_per_ip,
+            max_substreams_per_protocol: self.max_substreams_per_protocol,
+            max_inbound_substream_rate: self.max_substreams_per_minute.map(Rate::per_minute),
+            ban_duration: Duration::from_secs(self.ban_duration_secs),
+        }
+    }
+}
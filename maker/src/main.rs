@@ -3,6 +3,7 @@ use anyhow::Context;
 use anyhow::Result;
 use clap::StructOpt;
 use daemon::bdk::FeeRate;
+use daemon::event_webhooks;
 use daemon::monitor;
 use daemon::oracle;
 use daemon::projection;
@@ -11,7 +12,12 @@ use daemon::seed::Seed;
 use daemon::wallet;
 use daemon::wallet::MAKER_WALLET_ID;
 use daemon::N_PAYOUTS;
+use maker::funding_rate;
+use maker::opening_fee;
+use maker::pnl;
+use maker::rollover_policy;
 use maker::routes;
+use maker::ws;
 use maker::ActorSystem;
 use maker::Opts;
 use model::olivia;
@@ -24,9 +30,14 @@ use shared_bin::cli::Withdraw;
 use shared_bin::fairings;
 use shared_bin::logger;
 use std::collections::HashSet;
-use std::net::SocketAddr;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::Ipv6Addr;
 use std::path::Path;
+use std::sync::Arc;
 use tokio_extras::Tasks;
+use xtra::Actor as _;
+use xtra_libp2p::libp2p::multiaddr::Protocol;
 use xtra_libp2p::libp2p::PeerId;
 use xtras::supervisor::always_restart;
 use xtras::supervisor::Supervisor;
@@ -116,7 +127,10 @@ async fn main() -> Result<()> {
         .merge(("secret_key", RandomSeed::default().seed()));
 
     let p2p_port = opts.p2p_port;
-    let p2p_socket = format!("0.0.0.0:{p2p_port}").parse::<SocketAddr>().unwrap();
+    let mut p2p_listen_ips = vec![IpAddr::from(Ipv4Addr::UNSPECIFIED)];
+    if opts.p2p_ipv6 {
+        p2p_listen_ips.push(IpAddr::from(Ipv6Addr::UNSPECIFIED));
+    }
 
     let db =
         sqlite_db::connect(data_dir.join("maker.sqlite"), opts.ignore_migration_errors).await?;
@@ -134,9 +148,45 @@ async fn main() -> Result<()> {
         });
 
     // Create actors
-    let endpoint_listen =
-        daemon::libp2p_utils::create_listen_tcp_multiaddr(&p2p_socket.ip(), p2p_socket.port())
-            .expect("to parse properly");
+    let relay_listen = opts
+        .relays
+        .iter()
+        .map(|relay| {
+            if !matches!(relay.iter().last(), Some(Protocol::P2p(_))) {
+                bail!("Relay address {relay} does not contain the peer ID of the relay");
+            }
+
+            Ok(relay.clone().with(Protocol::P2pCircuit))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for address in relay_listen.iter() {
+        tracing::info!("Takers can connect through relay: {address}/p2p/{peer_id}");
+    }
+
+    let tcp_listen = p2p_listen_ips
+        .iter()
+        .map(|ip| {
+            daemon::libp2p_utils::create_listen_tcp_multiaddr(ip, p2p_port)
+                .expect("to parse properly")
+        })
+        .collect::<Vec<_>>();
+    let advertised_addresses = if opts.external_addresses.is_empty() {
+        tcp_listen
+            .iter()
+            .chain(relay_listen.iter())
+            .cloned()
+            .collect()
+    } else {
+        opts.external_addresses
+            .iter()
+            .chain(relay_listen.iter())
+            .cloned()
+            .collect()
+    };
+    let endpoint_listen = tcp_listen
+        .into_iter()
+        .chain(relay_listen)
+        .collect::<Vec<_>>();
 
     let (supervisor, price_feed) = Supervisor::with_policy(
         {
@@ -152,6 +202,7 @@ async fn main() -> Result<()> {
 
     let (supervisor, projection_actor) = Supervisor::new({
         let db = db.clone();
+        let price_feed = price_feed.clone();
         move || {
             projection::Actor::new(
                 db.clone(),
@@ -164,6 +215,58 @@ async fn main() -> Result<()> {
     });
     tasks.add(supervisor.run_log_summary());
 
+    let webhooks_path = data_dir.join("webhooks.toml");
+    let webhook_subscribers = load_webhook_subscribers(&webhooks_path)
+        .await
+        .with_context(|| format!("Failed to load webhook subscribers from {webhooks_path:?}"))?;
+    let event_webhooks = if webhook_subscribers.is_empty() {
+        None
+    } else {
+        tracing::info!(
+            "Delivering CFD events to {} webhook subscriber(s)",
+            webhook_subscribers.len()
+        );
+
+        let event_webhooks =
+            event_webhooks::Actor::new(db.clone(), Role::Maker, webhook_subscribers)
+                .create(None)
+                .spawn(&mut tasks);
+
+        Some(event_webhooks)
+    };
+
+    let funding_rate_config = opts.funding_rate_config()?;
+    let funding_rate_engine = match funding_rate_config.source {
+        funding_rate::Source::Manual => None,
+        source => {
+            tracing::info!(%source, "Computing funding rates automatically");
+
+            let engine = funding_rate::Actor::new(
+                funding_rate_config,
+                price_feed.clone().into(),
+                feed_receivers.cfds.clone(),
+            )
+            .create(None)
+            .spawn(&mut tasks);
+
+            Some(engine)
+        }
+    };
+
+    let rollover_policy = rollover_policy::Actor::new(db.clone(), feed_receivers.cfds.clone())
+        .await?
+        .create(None)
+        .spawn(&mut tasks);
+
+    let opening_fee = opening_fee::Actor::new(db.clone())
+        .await?
+        .create(None)
+        .spawn(&mut tasks);
+
+    let pnl = pnl::Actor::new(db.clone(), feed_receivers.cfds.clone())
+        .create(None)
+        .spawn(&mut tasks);
+
     let maker = ActorSystem::new(
         db.clone(),
         wallet.clone(),
@@ -178,26 +281,58 @@ async fn main() -> Result<()> {
         projection_actor.clone(),
         identities,
         endpoint_listen,
+        advertised_addresses,
         blocked_peers,
+        opts.allowlist_only,
+        opts.connection_limits(),
+        funding_rate_engine,
+        event_webhooks,
+        Some(rollover_policy),
+        Some(opening_fee),
+        Some(pnl),
     )?;
+    maker
+        .sync_peer_access()
+        .await
+        .context("Failed to apply blocked and allowed peers")?;
 
     if let Some(password) = opts.password {
-        db.clone()
-            .update_password(rocket_cookie_auth::user::create_password(
-                password.to_string().as_str(),
-            )?)
-            .await?;
+        let admin = db
+            .load_user(rocket_cookie_auth::DEFAULT_USERNAME)
+            .await?
+            .context("Default user not found")?;
+        db.update_password(
+            admin.id,
+            rocket_cookie_auth::user::create_password(password.to_string().as_str())?,
+        )
+        .await?;
     }
 
     let rocket_auth_db_connection = RocketAuthDbConnection::new(db.clone());
     let users = Users::new(Box::new(rocket_auth_db_connection));
 
+    let maker = Arc::new(maker);
+
+    if let Some(ws_address) = opts.ws_address {
+        let feeds = shared_bin::ws::Feeds::new(&feed_receivers, wallet_feed_receiver.clone(), None);
+        tasks.add_fallible(
+            shared_bin::ws::serve(
+                ws_address,
+                feeds,
+                Arc::new(RocketAuthDbConnection::new(db.clone())),
+                Arc::new(ws::Commands::new(maker.clone(), db.clone())),
+            ),
+            |e| async move { tracing::error!("WebSocket API stopped: {e:#}") },
+        );
+    }
+
     let mission_success = rocket::custom(figment)
         .manage(feed_receivers)
         .manage(wallet_feed_receiver)
         .manage(maker)
         .manage(users)
         .manage(bitcoin_network)
+        .manage(db.clone())
         .mount(
             "/api",
             rocket::routes![
@@ -207,16 +342,65 @@ async fn main() -> Result<()> {
                 routes::post_cfd_action,
                 routes::get_health_check,
                 routes::get_cfds,
+                shared_bin::routes::get_funding_fees,
+                shared_bin::routes::get_funding_rate_history,
                 routes::get_metrics,
                 routes::put_sync_wallet,
                 routes::get_version,
                 routes::change_password,
                 routes::post_login,
                 routes::logout,
+                routes::post_totp_enroll,
+                routes::post_totp_enable,
+                routes::post_totp_disable,
                 routes::is_authenticated,
+                routes::get_api_keys,
+                routes::post_api_key,
+                routes::delete_api_key,
+                routes::update_rollover_configuration,
+                routes::get_rollover_policy,
+                routes::put_rollover_policy,
+                routes::get_rollover_decisions,
+                routes::get_fee_schedule,
+                routes::put_fee_schedule,
+                routes::get_referral_codes,
+                routes::put_referral_code,
+                routes::delete_referral_code,
+                routes::get_referrer_revenue,
+                routes::get_pnl,
+                routes::get_users,
+                routes::post_user,
+                routes::put_user_role,
+                routes::delete_user,
+                routes::get_audit_log,
+                routes::get_bans,
+                routes::post_ban,
+                routes::delete_ban,
+                routes::get_blocked_peers,
+                routes::post_blocked_peer,
+                routes::delete_blocked_peer,
+                routes::get_allowed_peers,
+                routes::post_allowed_peer,
+                routes::delete_allowed_peer,
+                routes::get_takers,
+                routes::get_connection_history,
             ],
         )
         .register("/api", default_catchers())
+        .mount(
+            "/api/v1",
+            rocket::routes![
+                routes::v1::get_offers,
+                routes::v1::put_offer,
+                routes::v1::get_cfds,
+                routes::v1::get_cfd,
+                routes::v1::post_cfd_action,
+                routes::v1::get_quotes,
+                routes::v1::get_wallet,
+                routes::v1::get_openapi_spec,
+            ],
+        )
+        .register("/api/v1", default_catchers())
         .mount("/", rocket::routes![routes::dist, routes::index])
         .register("/", default_catchers())
         .attach(fairings::log_launch())
@@ -244,20 +428,73 @@ impl RocketAuthDbConnection {
 
 #[rocket::async_trait]
 impl rocket_cookie_auth::Database for RocketAuthDbConnection {
-    async fn load_user(&self) -> Result<Option<rocket_cookie_auth::user::User>> {
-        let users = self.inner.clone().load_user().await?;
-        Ok(users.map(|user| rocket_cookie_auth::user::User {
-            id: user.id,
-            password: user.password,
-            auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
-            first_login: user.first_login,
+    async fn load_user(&self, username: String) -> Result<Option<rocket_cookie_auth::user::User>> {
+        let user = self.inner.load_user(&username).await?;
+        user.map(|user| {
+            Ok(rocket_cookie_auth::user::User {
+                id: user.id,
+                username: user.username,
+                role: user.role.parse()?,
+                password: user.password,
+                auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
+                first_login: user.first_login,
+                totp_secret: user.totp_secret,
+                totp_enabled: user.totp_enabled,
+            })
+        })
+        .transpose()
+    }
+
+    async fn load_user_by_id(&self, id: u32) -> Result<Option<rocket_cookie_auth::user::User>> {
+        let user = self.inner.load_user_by_id(id).await?;
+        user.map(|user| {
+            Ok(rocket_cookie_auth::user::User {
+                id: user.id,
+                username: user.username,
+                role: user.role.parse()?,
+                password: user.password,
+                auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
+                first_login: user.first_login,
+                totp_secret: user.totp_secret,
+                totp_enabled: user.totp_enabled,
+            })
+        })
+        .transpose()
+    }
+
+    async fn update_password(&self, id: u32, password: String) -> Result<()> {
+        self.inner.update_password(id, password).await?;
+        Ok(())
+    }
+
+    async fn load_api_key(
+        &self,
+        key_hash: String,
+    ) -> Result<Option<rocket_cookie_auth::api_key::ApiKey>> {
+        let api_key = self.inner.load_api_key(&key_hash).await?;
+        Ok(api_key.map(|api_key| rocket_cookie_auth::api_key::ApiKey {
+            id: api_key.id,
+            name: api_key.name,
         }))
     }
 
-    async fn update_password(&self, password: String) -> Result<()> {
-        self.inner.clone().update_password(password).await?;
+    async fn update_totp(&self, id: u32, secret: Option<String>, enabled: bool) -> Result<()> {
+        self.inner.update_totp(id, secret, enabled).await?;
         Ok(())
     }
+
+    async fn use_totp_step(&self, id: u32, step: u64) -> Result<bool> {
+        self.inner.use_totp_step(id, step).await
+    }
+
+    async fn replace_recovery_codes(&self, id: u32, code_hashes: Vec<String>) -> Result<()> {
+        self.inner.replace_recovery_codes(id, &code_hashes).await?;
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, id: u32, code_hash: String) -> Result<bool> {
+        self.inner.use_recovery_code(id, &code_hash).await
+    }
 }
 
 /// Convenience type to load the blocked peer list from toml
@@ -274,3 +511,21 @@ async fn load_blocked_peers(blocked_peers_path: &Path) -> Result<HashSet<PeerId>
     let raw = tokio::fs::read_to_string(blocked_peers_path).await?;
     Ok(toml::from_str::<BlockedPeers>(&raw)?.blocked)
 }
+
+/// Convenience type to load the webhook subscribers from toml
+#[derive(Deserialize)]
+struct WebhookSubscribers {
+    #[serde(default, rename = "subscriber")]
+    subscribers: Vec<event_webhooks::Subscriber>,
+}
+
+/// Loads the subscribers CFD events are delivered to. Without a config file, no events are
+/// delivered.
+async fn load_webhook_subscribers(path: &Path) -> Result<Vec<event_webhooks::Subscriber>> {
+    if !path.try_exists()? {
+        return Ok(Vec::new());
+    }
+
+    let raw = tokio::fs::read_to_string(path).await?;
+    Ok(toml::from_str::<WebhookSubscribers>(&raw)?.subscribers)
+}
@@ -1 +1,2 @@
+pub mod referrals;
 pub mod time_to_first_position;
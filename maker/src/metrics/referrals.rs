@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use std::time::Duration;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often the revenue per referrer is recomputed.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+const REFERRAL_CODE_LABEL: &str = "referral_code";
+
+static REFERRED_CFDS_GAUGE: conquer_once::Lazy<prometheus::IntGaugeVec> =
+    conquer_once::Lazy::new(|| {
+        prometheus::register_int_gauge_vec!(
+            "referred_cfds_total",
+            "Number of CFDs opened by takers referred by a partner.",
+            &[REFERRAL_CODE_LABEL]
+        )
+        .unwrap()
+    });
+
+static REFERRED_OPENING_FEES_GAUGE: conquer_once::Lazy<prometheus::IntGaugeVec> =
+    conquer_once::Lazy::new(|| {
+        prometheus::register_int_gauge_vec!(
+            "referred_opening_fees_satoshis",
+            "Opening fees paid by takers referred by a partner.",
+            &[REFERRAL_CODE_LABEL]
+        )
+        .unwrap()
+    });
+
+static REFERRED_FUNDING_FEES_GAUGE: conquer_once::Lazy<prometheus::IntGaugeVec> =
+    conquer_once::Lazy::new(|| {
+        prometheus::register_int_gauge_vec!(
+            "referred_funding_fees_satoshis",
+            "Net funding fees received from takers referred by a partner.",
+            &[REFERRAL_CODE_LABEL]
+        )
+        .unwrap()
+    });
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+}
+
+impl Actor {
+    pub fn new(db: sqlite_db::Connection) -> Self {
+        Self { db }
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: UpdateMetrics) {
+        let revenue = match self.db.load_referrer_revenue().await {
+            Ok(revenue) => revenue,
+            Err(e) => {
+                tracing::warn!("Failed to load revenue per referrer: {e:#}");
+                return;
+            }
+        };
+
+        for referrer in revenue {
+            let labels = [referrer.referral_code.as_str()];
+
+            REFERRED_CFDS_GAUGE
+                .with_label_values(&labels)
+                .set(referrer.n_cfds as i64);
+            REFERRED_OPENING_FEES_GAUGE
+                .with_label_values(&labels)
+                .set(referrer.opening_fees.as_sat() as i64);
+            REFERRED_FUNDING_FEES_GAUGE
+                .with_label_values(&labels)
+                .set(referrer.funding_fees.as_sat());
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+        tokio_extras::spawn(
+            &this.clone(),
+            this.send_interval(
+                UPDATE_INTERVAL,
+                || UpdateMetrics,
+                xtras::IncludeSpan::Always,
+            ),
+        );
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Message sent to ourselves at an interval to update the metrics.
+#[derive(Clone, Copy)]
+pub struct UpdateMetrics;
@@ -0,0 +1,321 @@
+//! Computes the opening fee a taker is offered, based on a fee schedule configured by the
+//! operator.
+//!
+//! Partners can be assigned a fixed fee, all other takers are charged according to their trading
+//! volume over the last 30 days. Takers which neither have an override nor reach a volume tier are
+//! offered the fee of the offer as published.
+
+use crate::stored_config;
+use crate::stored_config::StoredConfig;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use bdk::bitcoin::Amount;
+use model::calculate_margin;
+use model::libp2p::PeerId;
+use model::Leverage;
+use model::OpeningFee;
+use model::Timestamp;
+use offer::maker::TailorOffers;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+use xtra_productivity::xtra_productivity;
+
+/// Trading volume is summed over this period.
+const VOLUME_PERIOD: time::Duration = time::Duration::days(30);
+
+/// How long a taker's volume is reused before it is computed again.
+const VOLUME_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeSchedule {
+    /// The taker is charged the fee of the highest tier their volume reaches.
+    pub volume_tiers: Vec<VolumeTier>,
+    /// Fees for individual takers, taking precedence over the volume tiers.
+    pub overrides: HashMap<PeerId, Fee>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeTier {
+    /// Notional value of the positions the taker opened over the last 30 days.
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub min_volume: Amount,
+    pub fee: Fee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fee {
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub flat: Amount,
+    /// Fraction of the notional value of the position, e.g. `0.001` for 0.1%.
+    #[serde(default)]
+    pub rate: Decimal,
+}
+
+impl StoredConfig for FeeSchedule {
+    const NAME: &'static str = "fee schedule";
+
+    fn validate(&self) -> Result<()> {
+        let fees = self
+            .volume_tiers
+            .iter()
+            .map(|tier| tier.fee)
+            .chain(self.overrides.values().copied());
+
+        for fee in fees {
+            ensure!(
+                !fee.rate.is_sign_negative() && fee.rate < Decimal::ONE,
+                "Fee rate {} has to be between 0 and 1",
+                fee.rate
+            );
+        }
+
+        let mut min_volumes = self
+            .volume_tiers
+            .iter()
+            .map(|tier| tier.min_volume)
+            .collect::<Vec<_>>();
+        min_volumes.sort();
+        min_volumes.dedup();
+        ensure!(
+            min_volumes.len() == self.volume_tiers.len(),
+            "Volume tiers must not share the same minimum volume"
+        );
+
+        Ok(())
+    }
+}
+
+impl FeeSchedule {
+    fn override_for(&self, taker: PeerId) -> Option<Fee> {
+        self.overrides.get(&taker).copied()
+    }
+
+    fn tier_for(&self, volume: Amount) -> Option<Fee> {
+        self.volume_tiers
+            .iter()
+            .filter(|tier| tier.min_volume <= volume)
+            .max_by_key(|tier| tier.min_volume)
+            .map(|tier| tier.fee)
+    }
+}
+
+fn apply(fee: Fee, offers: Vec<model::Offer>) -> Vec<model::Offer> {
+    offers
+        .into_iter()
+        .map(|offer| model::Offer {
+            opening_fee: OpeningFee::new(fee.flat),
+            opening_fee_rate: fee.rate,
+            ..offer
+        })
+        .collect()
+}
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+    schedule: FeeSchedule,
+    volumes: HashMap<PeerId, (Instant, Amount)>,
+}
+
+impl Actor {
+    /// Fails if the stored fee schedule cannot be loaded.
+    pub async fn new(db: sqlite_db::Connection) -> Result<Self> {
+        let schedule = stored_config::parse(
+            db.load_fee_schedule()
+                .await
+                .context("Failed to load fee schedule")?,
+        )?;
+
+        Ok(Self {
+            db,
+            schedule,
+            volumes: HashMap::default(),
+        })
+    }
+
+    /// The notional value of all positions the taker opened within the volume period.
+    async fn volume(&mut self, taker: PeerId) -> Result<Amount> {
+        if let Some((computed_at, volume)) = self.volumes.get(&taker) {
+            if computed_at.elapsed() < VOLUME_CACHE_TTL {
+                return Ok(*volume);
+            }
+        }
+
+        let since = Timestamp::new(Timestamp::now().seconds() - VOLUME_PERIOD.whole_seconds());
+        let volume = self
+            .db
+            .load_positions_opened_since(taker, since)
+            .await?
+            .into_iter()
+            .map(|position| {
+                calculate_margin(
+                    position.contract_symbol,
+                    position.initial_price,
+                    position.quantity,
+                    Leverage::ONE,
+                )
+            })
+            .fold(Amount::ZERO, |volume, notional| volume + notional);
+
+        self.volumes.insert(taker, (Instant::now(), volume));
+
+        Ok(volume)
+    }
+
+    async fn fee_for(&mut self, taker: PeerId) -> Result<Option<Fee>> {
+        if let Some(fee) = self.schedule.override_for(taker) {
+            return Ok(Some(fee));
+        }
+
+        if self.schedule.volume_tiers.is_empty() {
+            return Ok(None);
+        }
+
+        let volume = self.volume(taker).await?;
+
+        Ok(self.schedule.tier_for(volume))
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: GetFeeSchedule) -> FeeSchedule {
+        self.schedule.clone()
+    }
+
+    async fn handle(&mut self, msg: UpdateFeeSchedule) -> Result<()> {
+        let schedule = msg.0;
+
+        self.db
+            .save_fee_schedule(&stored_config::to_json(&schedule)?)
+            .await
+            .context("Failed to store fee schedule")?;
+        self.schedule = schedule;
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, msg: TailorOffers) -> Vec<model::Offer> {
+        let taker = PeerId::from(msg.peer_id);
+
+        match self.fee_for(taker).await {
+            Ok(Some(fee)) => apply(fee, msg.offers),
+            Ok(None) => msg.offers,
+            Err(e) => {
+                tracing::warn!(%taker, "Failed to compute opening fee, offering default: {e:#}");
+                msg.offers
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Get the fee schedule currently in effect.
+#[derive(Clone, Copy)]
+pub struct GetFeeSchedule;
+
+/// Replace the fee schedule, effective for the next offers sent to takers.
+pub struct UpdateFeeSchedule(pub FeeSchedule);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn given_volume_between_tiers_then_lower_tier_applies() {
+        let schedule = FeeSchedule {
+            volume_tiers: vec![
+                dummy_tier(Amount::from_btc(10.0).unwrap(), 500),
+                dummy_tier(Amount::from_btc(1.0).unwrap(), 1_000),
+            ],
+            ..FeeSchedule::default()
+        };
+
+        assert_eq!(schedule.tier_for(Amount::from_btc(0.5).unwrap()), None);
+        assert_eq!(
+            schedule.tier_for(Amount::from_btc(5.0).unwrap()),
+            Some(dummy_fee(1_000))
+        );
+        assert_eq!(
+            schedule.tier_for(Amount::from_btc(10.0).unwrap()),
+            Some(dummy_fee(500))
+        );
+    }
+
+    #[test]
+    fn given_fee_rate_above_one_then_schedule_invalid() {
+        let schedule = FeeSchedule {
+            overrides: HashMap::from([(
+                PeerId::random(),
+                Fee {
+                    flat: Amount::ZERO,
+                    rate: dec!(1.5),
+                },
+            )]),
+            ..FeeSchedule::default()
+        };
+
+        assert!(schedule.validate().is_err());
+    }
+
+    #[test]
+    fn given_tiers_with_same_min_volume_then_schedule_invalid() {
+        let schedule = FeeSchedule {
+            volume_tiers: vec![
+                dummy_tier(Amount::from_btc(1.0).unwrap(), 500),
+                dummy_tier(Amount::from_btc(1.0).unwrap(), 1_000),
+            ],
+            ..FeeSchedule::default()
+        };
+
+        assert!(schedule.validate().is_err());
+    }
+
+    #[test]
+    fn schedule_roundtrips_through_json() {
+        let schedule = FeeSchedule {
+            volume_tiers: vec![dummy_tier(Amount::from_btc(1.0).unwrap(), 500)],
+            overrides: HashMap::from([(
+                PeerId::random(),
+                Fee {
+                    flat: Amount::ZERO,
+                    rate: dec!(0.0005),
+                },
+            )]),
+        };
+
+        let json = serde_json::to_string(&schedule).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<FeeSchedule>(&json).unwrap(),
+            schedule
+        );
+    }
+
+    fn dummy_tier(min_volume: Amount, flat_fee_sat: u64) -> VolumeTier {
+        VolumeTier {
+            min_volume,
+            fee: dummy_fee(flat_fee_sat),
+        }
+    }
+
+    fn dummy_fee(flat_fee_sat: u64) -> Fee {
+        Fee {
+            flat: Amount::from_sat(flat_fee_sat),
+            rate: Decimal::ZERO,
+        }
+    }
+}
@@ -0,0 +1,510 @@
+//! Accounts for the profit and loss of the maker, to tell whether market making is profitable.
+//!
+//! Realised PnL is the payout of settled CFDs minus our margin and minus the fees settled with the
+//! payout, hence realised PnL, opening fees and funding fees add up to the net profit of settled
+//! CFDs. Unrealised PnL is what we would be paid out if open CFDs were settled at the current
+//! quote, likewise minus the fees accrued so far.
+//!
+//! CFDs are accounted as realised once they were moved to the closed CFDs, until then they are
+//! accounted as unrealised as long as they are open.
+//!
+//! Closed CFDs only keep the balance of their fee account, the opening fee is what remains after
+//! subtracting the funding fees recorded in the funding fee ledger. The funding fee charged during
+//! contract setup and the funding fees of rollovers which completed before the ledger was
+//! introduced are not recorded, hence they are accounted as opening fees, of open CFDs as well.
+//! The net profit is not affected.
+
+use crate::funding_rate::is_open;
+use anyhow::Result;
+use async_trait::async_trait;
+use bdk::bitcoin::SignedAmount;
+use daemon::projection;
+use model::calculate_margin;
+use model::calculate_profit;
+use model::libp2p::PeerId;
+use model::long_and_short_leverage;
+use model::ContractSymbol;
+use model::OrderId;
+use model::Position;
+use model::Timestamp;
+use serde::Serialize;
+use sqlite_db::pnl::FundingFeeRecord;
+use sqlite_db::pnl::OpeningFeeRecord;
+use sqlite_db::pnl::SettledCfd;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use xtra_productivity::xtra_productivity;
+use xtras::SendInterval;
+
+/// How often the PnL metrics are recomputed.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const SYMBOL_LABEL: &str = "symbol";
+const TYPE_LABEL: &str = "type";
+
+static PNL_GAUGE: conquer_once::Lazy<prometheus::IntGaugeVec> = conquer_once::Lazy::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "maker_pnl_satoshis",
+        "Profit and loss of the maker by type: realised, unrealised, opening_fees and funding_fees.",
+        &[SYMBOL_LABEL, TYPE_LABEL]
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Pnl {
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub realised: SignedAmount,
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub unrealised: SignedAmount,
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub opening_fees: SignedAmount,
+    /// Funding fees received minus funding fees paid.
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    pub funding_fees: SignedAmount,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub total: Pnl,
+    pub symbols: HashMap<ContractSymbol, Pnl>,
+    /// PnL per UTC day, e.g. `2022-10-14`.
+    ///
+    /// PnL is realised on the day of settlement and fees on the day they were charged. Unrealised
+    /// PnL is accounted to the current day.
+    pub days: BTreeMap<String, Pnl>,
+    pub takers: HashMap<PeerId, Pnl>,
+}
+
+impl Report {
+    fn record(
+        &mut self,
+        contract_symbol: ContractSymbol,
+        taker: PeerId,
+        timestamp: Timestamp,
+        update: impl Fn(&mut Pnl),
+    ) {
+        update(&mut self.total);
+        update(self.symbols.entry(contract_symbol).or_default());
+        update(self.days.entry(day(timestamp)).or_default());
+        update(self.takers.entry(taker).or_default());
+    }
+}
+
+/// A CFD which is still open, valued at the current quote.
+struct UnrealisedPnl {
+    order_id: OrderId,
+    contract_symbol: ContractSymbol,
+    taker: PeerId,
+    /// Includes the fees accrued so far.
+    pnl: SignedAmount,
+    /// Balance of our fee account, negative if the counterparty owes us fees.
+    fees: SignedAmount,
+}
+
+fn day(timestamp: Timestamp) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp.seconds())
+        .map(|datetime| datetime.date().to_string())
+        .unwrap_or_else(|_| "invalid".to_owned())
+}
+
+fn compute(
+    settled: &[SettledCfd],
+    opening_fees: &[OpeningFeeRecord],
+    funding_fees: &[FundingFeeRecord],
+    unrealised: &[UnrealisedPnl],
+    now: Timestamp,
+) -> Report {
+    let mut report = Report::default();
+
+    let mut funding_fees_per_cfd = HashMap::new();
+    for record in funding_fees {
+        *funding_fees_per_cfd
+            .entry(record.order_id)
+            .or_insert(SignedAmount::ZERO) += record.fee;
+
+        report.record(
+            record.contract_symbol,
+            record.counterparty_peer_id,
+            record.timestamp,
+            |pnl| pnl.funding_fees += record.fee,
+        );
+    }
+
+    for cfd in settled {
+        let (long_leverage, short_leverage) =
+            long_and_short_leverage(cfd.taker_leverage, cfd.role, cfd.position);
+        let leverage = match cfd.position {
+            Position::Long => long_leverage,
+            Position::Short => short_leverage,
+        };
+        let margin = calculate_margin(
+            cfd.contract_symbol,
+            cfd.initial_price,
+            cfd.quantity,
+            leverage,
+        );
+        let (profit, _) = calculate_profit(cfd.payout, margin);
+
+        // The fee account of a closed CFD includes the opening fee and all funding fees, which
+        // were settled with the payout
+        let fees_received = -cfd.fees;
+        let realised = profit - fees_received;
+
+        report.record(
+            cfd.contract_symbol,
+            cfd.counterparty_peer_id,
+            cfd.settled_at,
+            |pnl| pnl.realised += realised,
+        );
+
+        let funding_fees = funding_fees_per_cfd
+            .get(&cfd.order_id)
+            .copied()
+            .unwrap_or(SignedAmount::ZERO);
+        let opening_fee = fees_received - funding_fees;
+
+        report.record(
+            cfd.contract_symbol,
+            cfd.counterparty_peer_id,
+            cfd.opened_at,
+            |pnl| pnl.opening_fees += opening_fee,
+        );
+    }
+
+    let mut opening_fees_per_cfd = HashMap::new();
+    for record in opening_fees {
+        let opening_fee = record
+            .opening_fee
+            .to_inner()
+            .to_signed()
+            .expect("amount to fit into signed amount");
+        opening_fees_per_cfd.insert(record.order_id, (opening_fee, record.timestamp));
+
+        report.record(
+            record.contract_symbol,
+            record.counterparty_peer_id,
+            record.timestamp,
+            |pnl| pnl.opening_fees += opening_fee,
+        );
+    }
+
+    for cfd in unrealised {
+        let fees_received = -cfd.fees;
+        let unrealised = cfd.pnl - fees_received;
+
+        report.record(cfd.contract_symbol, cfd.taker, now, |pnl| {
+            pnl.unrealised += unrealised
+        });
+
+        // Fees in the fee account which are not recorded on their own, e.g. the funding fee
+        // charged during contract setup
+        let (opening_fee, opened_at) = opening_fees_per_cfd
+            .get(&cfd.order_id)
+            .copied()
+            .unwrap_or((SignedAmount::ZERO, now));
+        let funding_fees = funding_fees_per_cfd
+            .get(&cfd.order_id)
+            .copied()
+            .unwrap_or(SignedAmount::ZERO);
+        let unrecorded_fees = fees_received - opening_fee - funding_fees;
+
+        if unrecorded_fees != SignedAmount::ZERO {
+            report.record(cfd.contract_symbol, cfd.taker, opened_at, |pnl| {
+                pnl.opening_fees += unrecorded_fees
+            });
+        }
+    }
+
+    report
+}
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+}
+
+impl Actor {
+    pub fn new(
+        db: sqlite_db::Connection,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    ) -> Self {
+        Self { db, cfds }
+    }
+
+    fn unrealised(&self) -> Vec<UnrealisedPnl> {
+        let cfds = self.cfds.borrow();
+
+        cfds.iter()
+            .flatten()
+            .filter(|cfd| is_open(cfd.state))
+            .filter_map(|cfd| {
+                Some(UnrealisedPnl {
+                    order_id: cfd.order_id,
+                    contract_symbol: cfd.contract_symbol,
+                    taker: cfd.counterparty_peer_id?,
+                    pnl: cfd.profit_btc?,
+                    fees: cfd.accumulated_fees,
+                })
+            })
+            .collect()
+    }
+
+    async fn report(&self) -> Result<Report> {
+        let settled = self.db.load_settled_cfds().await?;
+        let opening_fees = self.db.load_opening_fees().await?;
+        let funding_fees = self.db.load_funding_fees().await?;
+
+        Ok(compute(
+            &settled,
+            &opening_fees,
+            &funding_fees,
+            &self.unrealised(),
+            Timestamp::now(),
+        ))
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: GetReport) -> Result<Report> {
+        self.report().await
+    }
+
+    async fn handle(&mut self, _: UpdateMetrics) {
+        let report = match self.report().await {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::warn!("Failed to compute PnL: {e:#}");
+                return;
+            }
+        };
+
+        for (symbol, pnl) in report.symbols {
+            let symbol = symbol.to_string();
+
+            for (kind, amount) in [
+                ("realised", pnl.realised),
+                ("unrealised", pnl.unrealised),
+                ("opening_fees", pnl.opening_fees),
+                ("funding_fees", pnl.funding_fees),
+            ] {
+                PNL_GAUGE
+                    .with_label_values(&[symbol.as_str(), kind])
+                    .set(amount.as_sat());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
+        let this = ctx.address().expect("we are alive");
+        tokio_extras::spawn(
+            &this.clone(),
+            this.send_interval(
+                UPDATE_INTERVAL,
+                || UpdateMetrics,
+                xtras::IncludeSpan::Always,
+            ),
+        );
+    }
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Compute the PnL report from the current state of all CFDs.
+#[derive(Clone, Copy)]
+pub struct GetReport;
+
+/// Message sent to ourselves at an interval to update the PnL metrics.
+#[derive(Clone, Copy)]
+pub struct UpdateMetrics;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::Amount;
+    use model::Contracts;
+    use model::Leverage;
+    use model::OpeningFee;
+    use model::Price;
+    use model::Role;
+    use rust_decimal_macros::dec;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn given_settled_cfd_then_realised_pnl_is_payout_minus_margin() {
+        let cfd = dummy_settled_cfd(Amount::from_sat(1_020_000), SignedAmount::ZERO);
+
+        let report = compute(&[cfd], &[], &[], &[], Timestamp::new(10 * DAY));
+
+        // The maker's margin for 100 contracts at 10_000 with leverage one is 0.01 BTC
+        assert_eq!(report.total.realised, SignedAmount::from_sat(20_000));
+        assert_eq!(report.symbols[&ContractSymbol::BtcUsd], report.total);
+        assert_eq!(report.takers[&cfd.counterparty_peer_id], report.total);
+        assert_eq!(report.days["1970-01-03"].realised, report.total.realised);
+    }
+
+    #[test]
+    fn given_settled_cfd_with_funding_fees_then_opening_fee_is_remainder_of_fee_account() {
+        let cfd = dummy_settled_cfd(Amount::from_sat(1_000_000), SignedAmount::from_sat(-1_500));
+        let funding_fee = FundingFeeRecord {
+            order_id: cfd.order_id,
+            contract_symbol: cfd.contract_symbol,
+            counterparty_peer_id: cfd.counterparty_peer_id,
+            fee: SignedAmount::from_sat(500),
+            timestamp: Timestamp::new(DAY + 1),
+        };
+
+        let report = compute(&[cfd], &[], &[funding_fee], &[], Timestamp::new(10 * DAY));
+
+        // The payout equals our margin, so the fees we were paid made up for a trading loss
+        assert_eq!(report.total.realised, SignedAmount::from_sat(-1_500));
+        assert_eq!(report.total.opening_fees, SignedAmount::from_sat(1_000));
+        assert_eq!(report.total.funding_fees, SignedAmount::from_sat(500));
+        assert_eq!(
+            report.days["1970-01-01"].opening_fees,
+            SignedAmount::from_sat(1_000)
+        );
+        assert_eq!(
+            report.days["1970-01-02"].funding_fees,
+            SignedAmount::from_sat(500)
+        );
+    }
+
+    #[test]
+    fn given_settled_cfd_then_realised_pnl_and_fees_add_up_to_net_profit() {
+        let cfd = dummy_settled_cfd(Amount::from_sat(1_020_000), SignedAmount::from_sat(-3_000));
+        let funding_fee = FundingFeeRecord {
+            order_id: cfd.order_id,
+            contract_symbol: cfd.contract_symbol,
+            counterparty_peer_id: cfd.counterparty_peer_id,
+            fee: SignedAmount::from_sat(-1_000),
+            timestamp: Timestamp::new(DAY),
+        };
+
+        let report = compute(&[cfd], &[], &[funding_fee], &[], Timestamp::new(10 * DAY));
+
+        let Pnl {
+            realised,
+            unrealised,
+            opening_fees,
+            funding_fees,
+        } = report.total;
+        assert_eq!(realised, SignedAmount::from_sat(17_000));
+        assert_eq!(
+            realised + unrealised + opening_fees + funding_fees,
+            SignedAmount::from_sat(20_000)
+        );
+    }
+
+    #[test]
+    fn given_open_cfds_then_unrealised_pnl_accounted_to_current_day() {
+        let taker = PeerId::random();
+        let opening_fee = OpeningFeeRecord {
+            order_id: OrderId::default(),
+            contract_symbol: ContractSymbol::EthUsd,
+            counterparty_peer_id: taker,
+            opening_fee: OpeningFee::new(Amount::from_sat(2_000)),
+            timestamp: Timestamp::new(0),
+        };
+        let unrealised = UnrealisedPnl {
+            order_id: OrderId::default(),
+            contract_symbol: ContractSymbol::EthUsd,
+            taker,
+            pnl: SignedAmount::from_sat(-5_000),
+            fees: SignedAmount::from_sat(-2_000),
+        };
+
+        let report = compute(&[], &[opening_fee], &[], &[unrealised], Timestamp::new(DAY));
+
+        assert_eq!(
+            report.takers[&taker],
+            Pnl {
+                realised: SignedAmount::ZERO,
+                unrealised: SignedAmount::from_sat(-7_000),
+                opening_fees: SignedAmount::from_sat(2_000),
+                funding_fees: SignedAmount::ZERO,
+            }
+        );
+        assert_eq!(
+            report.days["1970-01-02"].unrealised,
+            SignedAmount::from_sat(-7_000)
+        );
+    }
+
+    #[test]
+    fn given_open_cfd_with_fees_then_unrealised_pnl_and_fees_add_up_to_value_at_current_quote() {
+        let order_id = OrderId::default();
+        let taker = PeerId::random();
+        let opening_fee = OpeningFeeRecord {
+            order_id,
+            contract_symbol: ContractSymbol::BtcUsd,
+            counterparty_peer_id: taker,
+            opening_fee: OpeningFee::new(Amount::from_sat(2_000)),
+            timestamp: Timestamp::new(0),
+        };
+        let funding_fee = FundingFeeRecord {
+            order_id,
+            contract_symbol: ContractSymbol::BtcUsd,
+            counterparty_peer_id: taker,
+            fee: SignedAmount::from_sat(500),
+            timestamp: Timestamp::new(DAY),
+        };
+        // The fee account additionally holds the funding fee charged during contract setup
+        let unrealised = UnrealisedPnl {
+            order_id,
+            contract_symbol: ContractSymbol::BtcUsd,
+            taker,
+            pnl: SignedAmount::from_sat(10_000),
+            fees: SignedAmount::from_sat(-3_000),
+        };
+
+        let report = compute(
+            &[],
+            &[opening_fee],
+            &[funding_fee],
+            &[unrealised],
+            Timestamp::new(2 * DAY),
+        );
+
+        let Pnl {
+            realised,
+            unrealised,
+            opening_fees,
+            funding_fees,
+        } = report.total;
+        assert_eq!(unrealised, SignedAmount::from_sat(7_000));
+        assert_eq!(opening_fees, SignedAmount::from_sat(2_500));
+        assert_eq!(funding_fees, SignedAmount::from_sat(500));
+        assert_eq!(
+            realised + unrealised + opening_fees + funding_fees,
+            SignedAmount::from_sat(10_000)
+        );
+    }
+
+    fn dummy_settled_cfd(payout: Amount, fees: SignedAmount) -> SettledCfd {
+        SettledCfd {
+            order_id: OrderId::default(),
+            contract_symbol: ContractSymbol::BtcUsd,
+            counterparty_peer_id: PeerId::random(),
+            position: Position::Short,
+            role: Role::Maker,
+            initial_price: Price::new(dec!(10_000)).unwrap(),
+            taker_leverage: Leverage::TWO,
+            quantity: Contracts::new(100),
+            fees,
+            payout,
+            opened_at: Timestamp::new(0),
+            settled_at: Timestamp::new(2 * DAY),
+        }
+    }
+}
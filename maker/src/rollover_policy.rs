@@ -0,0 +1,440 @@
+//! Decides per proposal whether a rollover is accepted, based on rules configured by the operator.
+//!
+//! The rules are stored in the database and every decision is recorded, such that operators can
+//! audit why a rollover was rejected.
+
+use crate::funding_rate::is_open;
+use crate::stored_config;
+use crate::stored_config::StoredConfig;
+use anyhow::ensure;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::projection;
+use model::libp2p::PeerId;
+use model::ContractSymbol;
+use model::Contracts;
+use model::Timestamp;
+use rollover::protocol::RejectReason;
+use rollover::protocol::RolloverDecision;
+use rollover::protocol::RolloverProposal;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlite_db::rollover_rules::RolloverDecisionRecord;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use xtra::prelude::MessageChannel;
+use xtra_productivity::xtra_productivity;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// Maximum number of contracts per symbol a taker may hold across all open CFDs.
+    ///
+    /// Symbols without a limit are not restricted.
+    pub max_taker_exposure: HashMap<ContractSymbol, Contracts>,
+    /// Rollovers are only accepted during this window, if specified.
+    pub window: Option<Window>,
+    /// CFDs of these symbols are not rolled over anymore.
+    pub wound_down_symbols: HashSet<ContractSymbol>,
+    /// Named tiers takers can be assigned to.
+    pub tiers: HashMap<String, Tier>,
+    /// The tier of a taker. Takers without a tier pay the offered funding rates.
+    pub taker_tiers: HashMap<PeerId, String>,
+}
+
+/// Hours of the day in UTC, e.g. `{ "start_hour": 22, "end_hour": 6 }` spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    /// Inclusive.
+    pub start_hour: u8,
+    /// Exclusive.
+    pub end_hour: u8,
+}
+
+impl Window {
+    fn contains(&self, hour: u8) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tier {
+    /// Applied to the offered funding rates the taker pays, e.g. `0.5` halves them.
+    pub funding_rate_multiplier: Decimal,
+}
+
+impl StoredConfig for Rules {
+    const NAME: &'static str = "rollover rules";
+
+    fn validate(&self) -> Result<()> {
+        if let Some(window) = self.window {
+            ensure!(
+                window.start_hour < 24 && window.end_hour < 24,
+                "Window hours have to be between 0 and 23"
+            );
+            ensure!(
+                window.start_hour != window.end_hour,
+                "Window must not be empty"
+            );
+        }
+
+        for (name, tier) in &self.tiers {
+            ensure!(
+                !tier.funding_rate_multiplier.is_sign_negative(),
+                "Funding rate multiplier of tier {name} must not be negative"
+            );
+        }
+
+        for (taker, tier) in &self.taker_tiers {
+            ensure!(
+                self.tiers.contains_key(tier),
+                "Taker {taker} is assigned to unknown tier {tier}"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Rules {
+    /// Decide on a rollover, given the current exposure of the taker and hour of the day.
+    fn evaluate(
+        &self,
+        proposal: &RolloverProposal,
+        exposure: Contracts,
+        hour: u8,
+    ) -> RolloverDecision {
+        if self.wound_down_symbols.contains(&proposal.contract_symbol) {
+            return RolloverDecision::Reject(RejectReason::SymbolWoundDown);
+        }
+
+        if let Some(window) = self.window {
+            if !window.contains(hour) {
+                return RolloverDecision::Reject(RejectReason::OutsideOfWindow);
+            }
+        }
+
+        if let Some(max) = self.max_taker_exposure.get(&proposal.contract_symbol) {
+            if exposure > *max {
+                return RolloverDecision::Reject(RejectReason::ExposureLimitExceeded);
+            }
+        }
+
+        let funding_rate_multiplier = self
+            .taker_tiers
+            .get(&proposal.taker)
+            .and_then(|tier| self.tiers.get(tier))
+            .map(|tier| tier.funding_rate_multiplier)
+            .unwrap_or(Decimal::ONE);
+
+        RolloverDecision::Accept {
+            funding_rate_multiplier,
+        }
+    }
+}
+
+pub struct Actor {
+    db: sqlite_db::Connection,
+    cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    rules: Rules,
+}
+
+impl Actor {
+    /// Fails if the stored rollover rules cannot be loaded.
+    pub async fn new(
+        db: sqlite_db::Connection,
+        cfds: watch::Receiver<Option<Vec<projection::Cfd>>>,
+    ) -> Result<Self> {
+        let rules = stored_config::parse(
+            db.load_rollover_rules()
+                .await
+                .context("Failed to load rollover rules")?,
+        )?;
+
+        Ok(Self { db, cfds, rules })
+    }
+
+    /// The number of contracts of the symbol the taker holds across all open CFDs.
+    fn exposure(&self, taker: PeerId, contract_symbol: ContractSymbol) -> Contracts {
+        let cfds = self.cfds.borrow();
+
+        cfds.iter()
+            .flatten()
+            .filter(|cfd| {
+                cfd.counterparty_peer_id == Some(taker)
+                    && cfd.contract_symbol == contract_symbol
+                    && is_open(cfd.state)
+            })
+            .fold(Contracts::ZERO, |exposure, cfd| exposure + cfd.quantity)
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, _: GetRules) -> Rules {
+        self.rules.clone()
+    }
+
+    async fn handle(&mut self, msg: UpdateRules) -> Result<()> {
+        let rules = msg.0;
+
+        self.db
+            .save_rollover_rules(&stored_config::to_json(&rules)?)
+            .await
+            .context("Failed to store rollover rules")?;
+        self.rules = rules;
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, msg: Decide) -> RolloverDecision {
+        let proposal = msg.0;
+
+        let exposure = self.exposure(proposal.taker, proposal.contract_symbol);
+        let hour = OffsetDateTime::now_utc().hour();
+        let decision = self.rules.evaluate(&proposal, exposure, hour);
+
+        let reason = match decision {
+            RolloverDecision::Accept { .. } => None,
+            RolloverDecision::Reject(reason) => Some(reason.to_string()),
+        };
+
+        tracing::info!(
+            order_id = %proposal.order_id,
+            taker = %proposal.taker,
+            ?decision,
+            "Evaluated rollover policy"
+        );
+
+        if let Err(e) = self
+            .db
+            .insert_rollover_decision(&RolloverDecisionRecord {
+                order_id: proposal.order_id,
+                taker_id: proposal.taker,
+                contract_symbol: proposal.contract_symbol,
+                accepted: reason.is_none(),
+                reason,
+                timestamp: Timestamp::now(),
+            })
+            .await
+        {
+            tracing::warn!(order_id = %proposal.order_id, "Failed to record rollover decision: {e:#}");
+        }
+
+        decision
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Get the rollover rules currently in effect.
+#[derive(Clone, Copy)]
+pub struct GetRules;
+
+/// Replace the rollover rules, effective for the next proposal.
+pub struct UpdateRules(pub Rules);
+
+/// Decide on a rollover proposed by a taker.
+pub struct Decide(pub RolloverProposal);
+
+/// Rollover policy used by the rollover protocol.
+///
+/// Without a policy actor all rollovers are accepted at the offered funding rates.
+#[derive(Clone)]
+pub struct PolicyChannel(Option<MessageChannel<Decide, RolloverDecision>>);
+
+impl PolicyChannel {
+    pub fn new(channel: Option<MessageChannel<Decide, RolloverDecision>>) -> Self {
+        Self(channel)
+    }
+}
+
+#[async_trait]
+impl rollover::protocol::DecideRollover for PolicyChannel {
+    async fn decide(&self, proposal: RolloverProposal) -> Result<RolloverDecision> {
+        let channel = match &self.0 {
+            Some(channel) => channel,
+            None => {
+                return Ok(RolloverDecision::Accept {
+                    funding_rate_multiplier: Decimal::ONE,
+                })
+            }
+        };
+
+        let decision = channel
+            .send(Decide(proposal))
+            .await
+            .context("Rollover policy actor disconnected")?;
+
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::OrderId;
+    use model::Position;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn given_no_rules_then_rollover_accepted_at_offered_rates() {
+        let decision = Rules::default().evaluate(&dummy_proposal(), Contracts::new(100), 12);
+
+        assert_eq!(
+            decision,
+            RolloverDecision::Accept {
+                funding_rate_multiplier: Decimal::ONE
+            }
+        );
+    }
+
+    #[test]
+    fn given_symbol_wound_down_then_rollover_rejected() {
+        let rules = Rules {
+            wound_down_symbols: HashSet::from([ContractSymbol::BtcUsd]),
+            ..Rules::default()
+        };
+
+        let decision = rules.evaluate(&dummy_proposal(), Contracts::new(100), 12);
+
+        assert_eq!(
+            decision,
+            RolloverDecision::Reject(RejectReason::SymbolWoundDown)
+        );
+    }
+
+    #[test]
+    fn given_window_spanning_midnight_then_only_hours_within_accepted() {
+        let rules = Rules {
+            window: Some(Window {
+                start_hour: 22,
+                end_hour: 6,
+            }),
+            ..Rules::default()
+        };
+
+        let decisions = [21, 22, 0, 5, 6]
+            .map(|hour| rules.evaluate(&dummy_proposal(), Contracts::new(100), hour));
+
+        let rejected = RolloverDecision::Reject(RejectReason::OutsideOfWindow);
+        let accepted = RolloverDecision::Accept {
+            funding_rate_multiplier: Decimal::ONE,
+        };
+        assert_eq!(
+            decisions,
+            [rejected, accepted, accepted, accepted, rejected]
+        );
+    }
+
+    #[test]
+    fn given_exposure_above_limit_then_rollover_rejected() {
+        let rules = Rules {
+            max_taker_exposure: HashMap::from([(ContractSymbol::BtcUsd, Contracts::new(500))]),
+            ..Rules::default()
+        };
+
+        let at_limit = rules.evaluate(&dummy_proposal(), Contracts::new(500), 12);
+        let above_limit = rules.evaluate(&dummy_proposal(), Contracts::new(501), 12);
+
+        assert!(matches!(at_limit, RolloverDecision::Accept { .. }));
+        assert_eq!(
+            above_limit,
+            RolloverDecision::Reject(RejectReason::ExposureLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn given_taker_in_tier_then_funding_rate_multiplier_applied() {
+        let proposal = dummy_proposal();
+        let rules = Rules {
+            tiers: HashMap::from([(
+                "vip".to_owned(),
+                Tier {
+                    funding_rate_multiplier: dec!(0.5),
+                },
+            )]),
+            taker_tiers: HashMap::from([(proposal.taker, "vip".to_owned())]),
+            ..Rules::default()
+        };
+
+        let decision = rules.evaluate(&proposal, Contracts::new(100), 12);
+
+        assert_eq!(
+            decision,
+            RolloverDecision::Accept {
+                funding_rate_multiplier: dec!(0.5)
+            }
+        );
+    }
+
+    #[test]
+    fn given_taker_assigned_to_unknown_tier_then_rules_invalid() {
+        let rules = Rules {
+            taker_tiers: HashMap::from([(dummy_proposal().taker, "vip".to_owned())]),
+            ..Rules::default()
+        };
+
+        assert!(rules.validate().is_err());
+    }
+
+    #[test]
+    fn stored_rules_are_rejected_if_invalid() {
+        assert_eq!(
+            stored_config::parse::<Rules>(None).unwrap(),
+            Rules::default()
+        );
+        assert!(stored_config::parse::<Rules>(Some("{".to_owned())).is_err());
+        assert!(stored_config::parse::<Rules>(Some(
+            r#"{ "window": { "start_hour": 8, "end_hour": 8 } }"#.to_owned()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn rules_roundtrip_through_json() {
+        let proposal = dummy_proposal();
+        let rules = Rules {
+            max_taker_exposure: HashMap::from([(ContractSymbol::EthUsd, Contracts::new(1000))]),
+            window: Some(Window {
+                start_hour: 8,
+                end_hour: 20,
+            }),
+            wound_down_symbols: HashSet::from([ContractSymbol::BtcUsd]),
+            tiers: HashMap::from([(
+                "vip".to_owned(),
+                Tier {
+                    funding_rate_multiplier: dec!(0.5),
+                },
+            )]),
+            taker_tiers: HashMap::from([(proposal.taker, "vip".to_owned())]),
+        };
+
+        let json = serde_json::to_string(&rules).unwrap();
+
+        assert_eq!(serde_json::from_str::<Rules>(&json).unwrap(), rules);
+    }
+
+    fn dummy_proposal() -> RolloverProposal {
+        RolloverProposal {
+            order_id: OrderId::default(),
+            taker: PeerId::random(),
+            contract_symbol: ContractSymbol::BtcUsd,
+            taker_position: Position::Long,
+            quantity: Contracts::new(100),
+        }
+    }
+}
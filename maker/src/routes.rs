@@ -1,6 +1,11 @@
 #![allow(clippy::let_unit_value)] // see: https://github.com/SergioBenitez/Rocket/issues/2211
 use crate::actor_system::ActorSystem;
+use crate::opening_fee;
+use crate::pnl;
+use crate::rollover_policy;
 use anyhow::Result;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
 use bdk::sled;
 use daemon::bdk::blockchain::ElectrumBlockchain;
 use daemon::oracle;
@@ -17,32 +22,44 @@ use model::LotSize;
 use model::OpeningFee;
 use model::OrderId;
 use model::Price;
+use model::ReferralCode;
+use model::Timestamp;
 use model::TxFeeRate;
 use model::WalletInfo;
 use rocket::form::Form;
 use rocket::http::ContentType;
 use rocket::http::Status;
-use rocket::request::FromParam;
 use rocket::response::stream::Event;
 use rocket::response::stream::EventStream;
 use rocket::response::Responder;
 use rocket::serde::json::Json;
 use rocket::State;
+use rocket_cookie_auth::api_key::generate_api_key;
 use rocket_cookie_auth::auth::Auth;
 use rocket_cookie_auth::forms::ChangePassword;
 use rocket_cookie_auth::forms::Login;
+use rocket_cookie_auth::role::Admin;
+use rocket_cookie_auth::role::Role;
+use rocket_cookie_auth::role::Trader;
+use rocket_cookie_auth::totp::TotpEnrolment;
+use rocket_cookie_auth::user::create_password;
 use rocket_cookie_auth::user::User;
 use rust_embed::RustEmbed;
 use rust_embed_rocket::EmbeddedFileExt;
 use serde::Deserialize;
 use serde::Serialize;
+use shared_bin::routes::ContractSymbol;
 use shared_bin::ToSseEvent;
 use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::select;
 use tokio::sync::watch;
 use tracing::instrument;
 use uuid::Uuid;
+use xtra_libp2p::libp2p::PeerId;
+
+pub mod v1;
 
 pub type Maker = ActorSystem<oracle::Actor, wallet::Actor<ElectrumBlockchain, sled::Tree>>;
 
@@ -139,71 +156,37 @@ fn default_lot_size() -> LotSize {
 }
 
 #[rocket::put("/offer", data = "<offer_params>")]
-#[instrument(name = "PUT /offer", skip(maker, _user), err)]
+#[instrument(name = "PUT /offer", skip(maker, db, trader), err)]
 pub async fn put_offer_params(
     offer_params: Json<CfdNewOfferParamsRequest>,
-    maker: &State<Maker>,
-    _user: User,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    trader: Trader,
 ) -> Result<(), HttpApiProblem> {
     tracing::warn!("Deprecated /offer was called. Please use /<contract_symbol>/offer from now.");
-    maker
-        .set_offer_params(
-            offer_params.price_long,
-            offer_params.price_short,
-            offer_params.min_quantity,
-            offer_params.max_quantity,
-            offer_params.tx_fee_rate,
-            offer_params.daily_funding_rate_long,
-            offer_params.daily_funding_rate_short,
-            offer_params.opening_fee,
-            offer_params.leverage_choices.clone(),
-            ContractSymbol::BtcUsd.into(),
-            offer_params.lot_size,
-        )
-        .await
-        .map_err(|e| {
-            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
-                .title("Posting offer failed")
-                .detail(format!("{e:#}"))
-        })?;
-
-    Ok(())
-}
-
-#[derive(Debug, Copy, Clone, strum_macros::Display)]
-pub enum ContractSymbol {
-    BtcUsd,
-    EthUsd,
-}
-
-impl From<ContractSymbol> for model::ContractSymbol {
-    fn from(symbol: ContractSymbol) -> Self {
-        match symbol {
-            ContractSymbol::BtcUsd => model::ContractSymbol::BtcUsd,
-            ContractSymbol::EthUsd => model::ContractSymbol::EthUsd,
-        }
-    }
-}
+    let symbol = ContractSymbol::BtcUsd.into();
+    let result = update_offer_params(maker, symbol, &offer_params).await;
 
-impl<'r> FromParam<'r> for ContractSymbol {
-    type Error = anyhow::Error;
+    audit(
+        db,
+        &trader.0.username,
+        "updateOfferParams",
+        format!("symbol={symbol}, params={:?}", offer_params.0),
+        result.is_ok(),
+    )
+    .await;
 
-    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
-        match param.to_lowercase().as_str() {
-            "btcusd" => Ok(ContractSymbol::BtcUsd),
-            "ethusd" => Ok(ContractSymbol::EthUsd),
-            _ => anyhow::bail!("Unknown contract symbol provided: {param}"),
-        }
-    }
+    result
 }
 
 #[rocket::put("/<symbol>/offer", data = "<offer_params>")]
-#[instrument(name = "PUT /offer", skip(maker, _user), err)]
+#[instrument(name = "PUT /offer", skip(maker, db, trader), err)]
 pub async fn put_offer_params_for_symbol(
     symbol: Result<ContractSymbol>,
     offer_params: Json<CfdNewOfferParamsRequest>,
-    maker: &State<Maker>,
-    _user: User,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    trader: Trader,
 ) -> Result<(), HttpApiProblem> {
     // if we use `ContractSymbol` as arg directly the error gets lost. So we need to do this:
     let symbol = symbol.map_err(|e| {
@@ -211,6 +194,26 @@ pub async fn put_offer_params_for_symbol(
             .title("Unknown ContractSymbol provided")
             .detail(format!("{e:#}"))
     })?;
+    let symbol = symbol.into();
+    let result = update_offer_params(maker, symbol, &offer_params).await;
+
+    audit(
+        db,
+        &trader.0.username,
+        "updateOfferParams",
+        format!("symbol={symbol}, params={:?}", offer_params.0),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+pub(crate) async fn update_offer_params(
+    maker: &Maker,
+    symbol: model::ContractSymbol,
+    offer_params: &CfdNewOfferParamsRequest,
+) -> Result<(), HttpApiProblem> {
     maker
         .set_offer_params(
             offer_params.price_long,
@@ -222,7 +225,7 @@ pub async fn put_offer_params_for_symbol(
             offer_params.daily_funding_rate_short,
             offer_params.opening_fee,
             offer_params.leverage_choices.clone(),
-            symbol.into(),
+            symbol,
             offer_params.lot_size,
         )
         .await
@@ -236,14 +239,35 @@ pub async fn put_offer_params_for_symbol(
 }
 
 #[rocket::post("/cfd/<order_id>/<action>")]
-#[instrument(name = "POST /cfd/<order_id>/<action>", skip(maker, _user), err)]
+#[instrument(name = "POST /cfd/<order_id>/<action>", skip(maker, db, trader), err)]
 pub async fn post_cfd_action(
     order_id: Uuid,
     action: String,
-    maker: &State<Maker>,
-    _user: User,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    trader: Trader,
 ) -> Result<(), HttpApiProblem> {
     let order_id = OrderId::from(order_id);
+    let result = execute_cfd_action(maker, order_id, action.clone()).await;
+
+    audit(
+        db,
+        &trader.0.username,
+        &action,
+        format!("order_id={order_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+/// Execute an action on a CFD on behalf of the maker.
+pub(crate) async fn execute_cfd_action(
+    maker: &Maker,
+    order_id: OrderId,
+    action: String,
+) -> Result<(), HttpApiProblem> {
     let action = action.parse().map_err(|_| {
         HttpApiProblem::new(StatusCode::BAD_REQUEST).detail(format!("Invalid action: {}", action))
     })?;
@@ -292,7 +316,7 @@ pub fn index<'r>(_paths: PathBuf) -> impl Responder<'r, 'static> {
 
 #[rocket::put("/sync")]
 #[instrument(name = "PUT /sync", skip_all, err)]
-pub async fn put_sync_wallet(maker: &State<Maker>, _user: User) -> Result<(), HttpApiProblem> {
+pub async fn put_sync_wallet(maker: &State<Arc<Maker>>, _user: User) -> Result<(), HttpApiProblem> {
     maker.sync_wallet().await.map_err(|e| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not sync wallet")
@@ -342,22 +366,308 @@ pub struct RolloverConfig {
 }
 
 #[rocket::post("/rollover/config", data = "<config>")]
-#[instrument(name = "POST /rollover/config", skip(maker), err)]
+#[instrument(name = "POST /rollover/config", skip(maker, db, admin), err)]
 pub async fn update_rollover_configuration(
     config: Json<RolloverConfig>,
-    maker: &State<Maker>,
-    _user: User,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
 ) -> Result<(), HttpApiProblem> {
-    maker
+    let result = maker
         .update_rollover_configuration(config.is_accepting_rollovers)
         .await
         .map_err(|e| {
             HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
                 .title("Updating rollover configuration failed")
                 .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "updateRolloverConfiguration",
+        format!("is_accepting_rollovers={}", config.is_accepting_rollovers),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::get("/rollover/policy")]
+#[instrument(name = "GET /rollover/policy", skip(maker, _user), err)]
+pub async fn get_rollover_policy(
+    maker: &State<Arc<Maker>>,
+    _user: User,
+) -> Result<Json<rollover_policy::Rules>, HttpApiProblem> {
+    let rules = maker.rollover_rules().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load rollover policy")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(rules))
+}
+
+#[rocket::put("/rollover/policy", data = "<rules>")]
+#[instrument(name = "PUT /rollover/policy", skip(maker, db, admin), err)]
+pub async fn put_rollover_policy(
+    rules: Json<rollover_policy::Rules>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let rules = rules.into_inner();
+    let details = serde_json::to_string(&rules).unwrap_or_default();
+
+    let result = maker.update_rollover_rules(rules).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Updating rollover policy failed")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "updateRolloverPolicy",
+        details,
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloverDecision {
+    order_id: OrderId,
+    taker_id: String,
+    contract_symbol: model::ContractSymbol,
+    accepted: bool,
+    reason: Option<String>,
+    timestamp: Timestamp,
+}
+
+/// The most recent decisions of the rollover policy, latest first.
+#[rocket::get("/rollover/decisions?<limit>")]
+#[instrument(name = "GET /rollover/decisions", skip(db, _user), err)]
+pub async fn get_rollover_decisions(
+    limit: Option<u32>,
+    db: &State<sqlite_db::Connection>,
+    _user: User,
+) -> Result<Json<Vec<RolloverDecision>>, HttpApiProblem> {
+    let decisions = db
+        .load_rollover_decisions(limit.unwrap_or(100))
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not load rollover decisions")
+                .detail(format!("{e:#}"))
         })?;
 
-    Ok(())
+    let decisions = decisions
+        .into_iter()
+        .map(|record| RolloverDecision {
+            order_id: record.order_id,
+            taker_id: record.taker_id.to_string(),
+            contract_symbol: record.contract_symbol,
+            accepted: record.accepted,
+            reason: record.reason,
+            timestamp: record.timestamp,
+        })
+        .collect();
+
+    Ok(Json(decisions))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferrerRevenue {
+    referral_code: String,
+    n_cfds: u64,
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    opening_fees: Amount,
+    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
+    funding_fees: SignedAmount,
+}
+
+/// Fees earned with the CFDs of takers referred by partners, per referral code.
+#[rocket::get("/referrals/revenue")]
+#[instrument(name = "GET /referrals/revenue", skip(db, _user), err)]
+pub async fn get_referrer_revenue(
+    db: &State<sqlite_db::Connection>,
+    _user: User,
+) -> Result<Json<Vec<ReferrerRevenue>>, HttpApiProblem> {
+    let revenue = db.load_referrer_revenue().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load revenue per referrer")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let revenue = revenue
+        .into_iter()
+        .map(|referrer| ReferrerRevenue {
+            referral_code: referrer.referral_code,
+            n_cfds: referrer.n_cfds,
+            opening_fees: referrer.opening_fees,
+            funding_fees: referrer.funding_fees,
+        })
+        .collect();
+
+    Ok(Json(revenue))
+}
+
+/// Referral codes handed out to partners.
+///
+/// Only CFDs of takers sending one of these codes are attributed to a partner.
+#[rocket::get("/referral-codes")]
+#[instrument(name = "GET /referral-codes", skip(db, _user), err)]
+pub async fn get_referral_codes(
+    db: &State<sqlite_db::Connection>,
+    _user: User,
+) -> Result<Json<Vec<String>>, HttpApiProblem> {
+    let referral_codes = db.load_referral_codes().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load referral codes")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(referral_codes))
+}
+
+#[rocket::put("/referral-codes/<referral_code>")]
+#[instrument(name = "PUT /referral-codes/<referral_code>", skip(db, admin), err)]
+pub async fn put_referral_code(
+    referral_code: &str,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let referral_code = parse_referral_code(referral_code)?;
+
+    let result = db.insert_referral_code(&referral_code).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not register referral code")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "registerReferralCode",
+        format!("referral_code={referral_code}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::delete("/referral-codes/<referral_code>")]
+#[instrument(name = "DELETE /referral-codes/<referral_code>", skip(db, admin), err)]
+pub async fn delete_referral_code(
+    referral_code: &str,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let referral_code = parse_referral_code(referral_code)?;
+
+    let result = match db.delete_referral_code(&referral_code).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not remove referral code")
+            .detail(format!("Referral code {referral_code} is not registered"))),
+        Err(e) => Err(HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not remove referral code")
+            .detail(format!("{e:#}"))),
+    };
+
+    audit(
+        db,
+        &admin.0.username,
+        "removeReferralCode",
+        format!("referral_code={referral_code}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+/// Profit and loss of the maker in total, per symbol, per day and per taker.
+#[rocket::get("/pnl")]
+#[instrument(name = "GET /pnl", skip(maker, _user), err)]
+pub async fn get_pnl(
+    maker: &State<Arc<Maker>>,
+    _user: User,
+) -> Result<Json<pnl::Report>, HttpApiProblem> {
+    let report = maker.pnl_report().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not compute PnL")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(report))
+}
+
+#[rocket::get("/fee-schedule")]
+#[instrument(name = "GET /fee-schedule", skip(maker, _user), err)]
+pub async fn get_fee_schedule(
+    maker: &State<Arc<Maker>>,
+    _user: User,
+) -> Result<Json<opening_fee::FeeSchedule>, HttpApiProblem> {
+    let schedule = maker.fee_schedule().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load fee schedule")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(schedule))
+}
+
+#[rocket::put("/fee-schedule", data = "<schedule>")]
+#[instrument(name = "PUT /fee-schedule", skip(maker, db, admin), err)]
+pub async fn put_fee_schedule(
+    schedule: Json<opening_fee::FeeSchedule>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let schedule = schedule.into_inner();
+    let details = serde_json::to_string(&schedule).unwrap_or_default();
+
+    let result = maker.update_fee_schedule(schedule).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Updating fee schedule failed")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "updateFeeSchedule",
+        details,
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+/// Record an action in the audit log, attributed to the given actor.
+///
+/// Failing to record the action does not fail the request, as the action was already executed.
+pub(crate) async fn audit(
+    db: &sqlite_db::Connection,
+    actor: &str,
+    action: &str,
+    details: String,
+    succeeded: bool,
+) {
+    if let Err(e) = db
+        .append_audit_log(actor, action, &details, succeeded)
+        .await
+    {
+        tracing::warn!(%actor, %action, "Failed to record action in audit log: {e:#}");
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -396,6 +706,10 @@ pub async fn change_password(
             .detail(format!("{error:#}"))
     })?;
 
+    auth.users
+        .verify_fresh_totp(user.id, form.totp.as_deref())
+        .await?;
+
     user.set_password(&form.password).map_err(|error| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not set password")
@@ -447,3 +761,704 @@ pub fn logout(auth: Auth<'_>) -> Result<(), HttpApiProblem> {
     })?;
     Ok(())
 }
+
+/// Start enrolling a TOTP authenticator, which has to be confirmed with
+/// [`post_totp_enable`] before it is required upon login.
+#[rocket::post("/totp/enroll")]
+pub async fn post_totp_enroll(
+    user: User,
+    auth: Auth<'_>,
+) -> Result<Json<TotpEnrolment>, HttpApiProblem> {
+    let enrolment = auth.users.enroll_totp(user.id).await?;
+    Ok(Json(enrolment))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TotpCode {
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+#[rocket::post("/totp/enable", data = "<request>")]
+pub async fn post_totp_enable(
+    request: Json<TotpCode>,
+    user: User,
+    auth: Auth<'_>,
+) -> Result<Json<RecoveryCodes>, HttpApiProblem> {
+    let recovery_codes = auth.users.enable_totp(user.id, &request.code).await?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+#[rocket::post("/totp/disable", data = "<request>")]
+pub async fn post_totp_disable(
+    request: Json<TotpCode>,
+    user: User,
+    auth: Auth<'_>,
+) -> Result<(), HttpApiProblem> {
+    auth.users.disable_totp(user.id, &request.code).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewApiKeyRequest {
+    name: String,
+}
+
+/// A newly created API key.
+///
+/// This is the only time the key itself is handed out, only its hash is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewApiKey {
+    id: u32,
+    name: String,
+    key: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyInfo {
+    id: u32,
+    name: String,
+    created_at: Timestamp,
+}
+
+#[rocket::get("/api-keys")]
+#[instrument(name = "GET /api-keys", skip_all, err)]
+pub async fn get_api_keys(
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<Vec<ApiKeyInfo>>, HttpApiProblem> {
+    let api_keys = db.load_api_keys().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load API keys")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let api_keys = api_keys
+        .into_iter()
+        .map(|api_key| ApiKeyInfo {
+            id: api_key.id,
+            name: api_key.name,
+            created_at: api_key.created_at,
+        })
+        .collect();
+
+    Ok(Json(api_keys))
+}
+
+#[rocket::post("/api-keys", data = "<request>")]
+#[instrument(name = "POST /api-keys", skip(db, _admin), err)]
+pub async fn post_api_key(
+    request: Json<NewApiKeyRequest>,
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<NewApiKey>, HttpApiProblem> {
+    let (key, key_hash) = generate_api_key();
+
+    let api_key = db
+        .insert_api_key(&request.name, &key_hash)
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not create API key")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(NewApiKey {
+        id: api_key.id,
+        name: api_key.name,
+        key,
+    }))
+}
+
+#[rocket::delete("/api-keys/<id>")]
+#[instrument(name = "DELETE /api-keys/<id>", skip(db, _admin), err)]
+pub async fn delete_api_key(
+    id: u32,
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    match db.revoke_api_key(id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not revoke API key")
+            .detail(format!("No active API key with id {id}"))),
+        Err(e) => Err(HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not revoke API key")
+            .detail(format!("{e:#}"))),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserInfo {
+    id: u32,
+    username: String,
+    role: Role,
+}
+
+#[rocket::get("/users")]
+#[instrument(name = "GET /users", skip_all, err)]
+pub async fn get_users(
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<Vec<UserInfo>>, HttpApiProblem> {
+    let users = db.load_users().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load users")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let users = users
+        .into_iter()
+        .map(|user| {
+            Ok(UserInfo {
+                id: user.id,
+                username: user.username,
+                role: user.role.parse()?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Invalid user in database")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(users))
+}
+
+/// The initial password of a new user, which has to be changed upon first login.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUserRequest {
+    username: String,
+    password: String,
+    role: Role,
+}
+
+#[rocket::post("/users", data = "<request>")]
+#[instrument(name = "POST /users", skip_all, err)]
+pub async fn post_user(
+    request: Json<NewUserRequest>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<Json<UserInfo>, HttpApiProblem> {
+    let password = create_password(&request.password).map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not hash password")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let result = db
+        .insert_user(&request.username, &password, request.role.as_str())
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::BAD_REQUEST)
+                .title("Could not create user")
+                .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "createUser",
+        format!("username={}, role={}", request.username, request.role),
+        result.is_ok(),
+    )
+    .await;
+
+    Ok(Json(UserInfo {
+        id: result?,
+        username: request.username.clone(),
+        role: request.role,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct UpdateRoleRequest {
+    role: Role,
+}
+
+#[rocket::put("/users/<id>/role", data = "<request>")]
+#[instrument(name = "PUT /users/<id>/role", skip(db, admin), err)]
+pub async fn put_user_role(
+    id: u32,
+    request: Json<UpdateRoleRequest>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    if id == admin.0.id {
+        return Err(HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Could not update role")
+            .detail("Admins cannot change their own role"));
+    }
+
+    let result = db
+        .update_role(id, request.role.as_str())
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::NOT_FOUND)
+                .title("Could not update role")
+                .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "updateUserRole",
+        format!("user_id={id}, role={}", request.role),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::delete("/users/<id>")]
+#[instrument(name = "DELETE /users/<id>", skip(db, admin), err)]
+pub async fn delete_user(
+    id: u32,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    if id == admin.0.id {
+        return Err(HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Could not delete user")
+            .detail("Admins cannot delete themselves"));
+    }
+
+    let result = db.delete_user(id).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not delete user")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "deleteUser",
+        format!("user_id={id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditLogEntry {
+    timestamp: Timestamp,
+    actor: String,
+    action: String,
+    details: String,
+    succeeded: bool,
+}
+
+#[rocket::get("/audit-log?<limit>")]
+#[instrument(name = "GET /audit-log", skip(db, _admin), err)]
+pub async fn get_audit_log(
+    limit: Option<u32>,
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<Vec<AuditLogEntry>>, HttpApiProblem> {
+    let entries = db.load_audit_log(limit.unwrap_or(100)).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load audit log")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| AuditLogEntry {
+            timestamp: entry.timestamp,
+            actor: entry.actor,
+            action: entry.action,
+            details: entry.details,
+            succeeded: entry.succeeded,
+        })
+        .collect();
+
+    Ok(Json(entries))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ban {
+    peer_id: String,
+    remaining_secs: u64,
+    reason: String,
+}
+
+#[rocket::get("/bans")]
+#[instrument(name = "GET /bans", skip_all, err)]
+pub async fn get_bans(
+    maker: &State<Arc<Maker>>,
+    _admin: Admin,
+) -> Result<Json<Vec<Ban>>, HttpApiProblem> {
+    let banned_peers = maker.banned_peers().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load banned peers")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let bans = banned_peers
+        .into_iter()
+        .map(|banned| Ban {
+            peer_id: banned.peer_id.to_string(),
+            remaining_secs: banned.remaining.as_secs(),
+            reason: banned.reason,
+        })
+        .collect();
+
+    Ok(Json(bans))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BanRequest {
+    peer_id: String,
+    duration_secs: u64,
+    reason: Option<String>,
+}
+
+#[rocket::post("/bans", data = "<request>")]
+#[instrument(name = "POST /bans", skip(maker, db, admin), err)]
+pub async fn post_ban(
+    request: Json<BanRequest>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let peer_id = parse_peer_id(&request.peer_id)?;
+    let reason = request
+        .reason
+        .clone()
+        .unwrap_or_else(|| format!("Banned by {}", admin.0.username));
+
+    let result = maker
+        .ban_peer(
+            peer_id,
+            std::time::Duration::from_secs(request.duration_secs),
+            reason,
+        )
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not ban peer")
+                .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "banPeer",
+        format!("peer_id={peer_id}, duration_secs={}", request.duration_secs),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::delete("/bans/<peer_id>")]
+#[instrument(name = "DELETE /bans/<peer_id>", skip(maker, db, admin), err)]
+pub async fn delete_ban(
+    peer_id: &str,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let peer_id = parse_peer_id(peer_id)?;
+
+    let result = match maker.unban_peer(peer_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not lift ban")
+            .detail(format!("Peer {peer_id} is not banned"))),
+        Err(e) => Err(HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not lift ban")
+            .detail(format!("{e:#}"))),
+    };
+
+    audit(
+        db,
+        &admin.0.username,
+        "unbanPeer",
+        format!("peer_id={peer_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerListEntry {
+    peer_id: String,
+    note: String,
+    created_at: Timestamp,
+}
+
+impl From<sqlite_db::peer_list::PeerListEntry> for PeerListEntry {
+    fn from(entry: sqlite_db::peer_list::PeerListEntry) -> Self {
+        Self {
+            peer_id: entry.peer_id.to_string(),
+            note: entry.note,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PeerListRequest {
+    peer_id: String,
+    note: Option<String>,
+}
+
+#[rocket::get("/peers/blocked")]
+#[instrument(name = "GET /peers/blocked", skip_all, err)]
+pub async fn get_blocked_peers(
+    maker: &State<Arc<Maker>>,
+    _admin: Admin,
+) -> Result<Json<Vec<PeerListEntry>>, HttpApiProblem> {
+    let entries = maker.blocked_peers().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load blocked peers")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(entries.into_iter().map(PeerListEntry::from).collect()))
+}
+
+#[rocket::post("/peers/blocked", data = "<request>")]
+#[instrument(name = "POST /peers/blocked", skip(maker, db, admin), err)]
+pub async fn post_blocked_peer(
+    request: Json<PeerListRequest>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<Json<PeerListEntry>, HttpApiProblem> {
+    let peer_id = parse_peer_id(&request.peer_id)?;
+    let note = request.note.clone().unwrap_or_default();
+
+    let result = maker
+        .block_peer(peer_id, &note)
+        .await
+        .map(|entry| Json(PeerListEntry::from(entry)))
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not block peer")
+                .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "blockPeer",
+        format!("peer_id={peer_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::delete("/peers/blocked/<peer_id>")]
+#[instrument(name = "DELETE /peers/blocked/<peer_id>", skip(maker, db, admin), err)]
+pub async fn delete_blocked_peer(
+    peer_id: &str,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let peer_id = parse_peer_id(peer_id)?;
+
+    let result = maker.unblock_peer(peer_id).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not unblock peer")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "unblockPeer",
+        format!("peer_id={peer_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::get("/peers/allowed")]
+#[instrument(name = "GET /peers/allowed", skip_all, err)]
+pub async fn get_allowed_peers(
+    maker: &State<Arc<Maker>>,
+    _admin: Admin,
+) -> Result<Json<Vec<PeerListEntry>>, HttpApiProblem> {
+    let entries = maker.allowed_peers().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load allowed peers")
+            .detail(format!("{e:#}"))
+    })?;
+
+    Ok(Json(entries.into_iter().map(PeerListEntry::from).collect()))
+}
+
+#[rocket::post("/peers/allowed", data = "<request>")]
+#[instrument(name = "POST /peers/allowed", skip(maker, db, admin), err)]
+pub async fn post_allowed_peer(
+    request: Json<PeerListRequest>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<Json<PeerListEntry>, HttpApiProblem> {
+    let peer_id = parse_peer_id(&request.peer_id)?;
+    let note = request.note.clone().unwrap_or_default();
+
+    let result = maker
+        .allow_peer(peer_id, &note)
+        .await
+        .map(|entry| Json(PeerListEntry::from(entry)))
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not allow peer")
+                .detail(format!("{e:#}"))
+        });
+
+    audit(
+        db,
+        &admin.0.username,
+        "allowPeer",
+        format!("peer_id={peer_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::delete("/peers/allowed/<peer_id>")]
+#[instrument(name = "DELETE /peers/allowed/<peer_id>", skip(maker, db, admin), err)]
+pub async fn delete_allowed_peer(
+    peer_id: &str,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    let peer_id = parse_peer_id(peer_id)?;
+
+    let result = maker.disallow_peer(peer_id).await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not remove peer from allowlist")
+            .detail(format!("{e:#}"))
+    });
+
+    audit(
+        db,
+        &admin.0.username,
+        "disallowPeer",
+        format!("peer_id={peer_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedTaker {
+    peer_id: String,
+    address: String,
+    daemon_version: Option<String>,
+    environment: Option<String>,
+    latency_ms: Option<u128>,
+}
+
+#[rocket::get("/takers")]
+#[instrument(name = "GET /takers", skip_all, err)]
+pub async fn get_takers(
+    maker: &State<Arc<Maker>>,
+    _user: User,
+) -> Result<Json<Vec<ConnectedTaker>>, HttpApiProblem> {
+    let takers = maker.connected_takers().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load connected takers")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let takers = takers
+        .into_iter()
+        .map(|taker| ConnectedTaker {
+            peer_id: taker.peer_id.to_string(),
+            address: taker.address.to_string(),
+            daemon_version: taker.daemon_version,
+            environment: taker.environment.map(|environment| environment.to_string()),
+            latency_ms: taker.latency.map(|latency| latency.as_millis()),
+        })
+        .collect();
+
+    Ok(Json(takers))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionHistoryEntry {
+    peer_id: String,
+    address: String,
+    connected_at: Timestamp,
+    disconnected_at: Option<Timestamp>,
+    /// Only known once the connection was dropped.
+    duration_secs: Option<i64>,
+}
+
+#[rocket::get("/takers/connection-history?<peer_id>&<limit>")]
+#[instrument(name = "GET /takers/connection-history", skip(maker, _user), err)]
+pub async fn get_connection_history(
+    peer_id: Option<&str>,
+    limit: Option<u32>,
+    maker: &State<Arc<Maker>>,
+    _user: User,
+) -> Result<Json<Vec<ConnectionHistoryEntry>>, HttpApiProblem> {
+    let peer_id = peer_id.map(parse_peer_id).transpose()?;
+
+    let entries = maker
+        .connection_history(peer_id, limit.unwrap_or(100))
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not load connection history")
+                .detail(format!("{e:#}"))
+        })?;
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| ConnectionHistoryEntry {
+            peer_id: entry.peer_id.to_string(),
+            address: entry.address.to_string(),
+            connected_at: entry.connected_at,
+            disconnected_at: entry.disconnected_at,
+            duration_secs: entry
+                .disconnected_at
+                .map(|disconnected_at| disconnected_at.seconds() - entry.connected_at.seconds()),
+        })
+        .collect();
+
+    Ok(Json(entries))
+}
+
+fn parse_peer_id(peer_id: &str) -> Result<PeerId, HttpApiProblem> {
+    peer_id.parse().map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Invalid peer id")
+            .detail(format!("{e}"))
+    })
+}
+
+fn parse_referral_code(referral_code: &str) -> Result<ReferralCode, HttpApiProblem> {
+    referral_code.parse().map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Invalid referral code")
+            .detail(format!("{e:#}"))
+    })
+}
@@ -0,0 +1,167 @@
+//! Versioned HTTP API for programmatic access to the maker.
+//!
+//! In contrast to the routes consumed by the UI, these routes are authenticated by API key and
+//! follow the specification in `maker/openapi.yaml`, which is served under `/openapi.yaml`.
+
+use crate::routes::audit;
+use crate::routes::execute_cfd_action;
+use crate::routes::update_offer_params;
+use crate::routes::CfdNewOfferParamsRequest;
+use crate::routes::ContractSymbol;
+use crate::routes::Maker;
+use anyhow::Result;
+use daemon::projection::Cfd;
+use daemon::projection::CfdOffer;
+use daemon::projection::FeedReceivers;
+use daemon::projection::LatestQuotes;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::OrderId;
+use model::WalletInfo;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use rocket::State;
+use rocket_cookie_auth::api_key::ApiKey;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::instrument;
+use uuid::Uuid;
+
+const OPENAPI_SPEC: &str = include_str!("../../openapi.yaml");
+
+#[rocket::get("/openapi.yaml")]
+pub fn get_openapi_spec() -> (ContentType, &'static str) {
+    (ContentType::new("application", "yaml"), OPENAPI_SPEC)
+}
+
+#[rocket::get("/offers")]
+#[instrument(name = "GET /v1/offers", skip_all)]
+pub fn get_offers(rx: &State<FeedReceivers>, _api_key: ApiKey) -> Json<Vec<CfdOffer>> {
+    let offers = rx.offers.borrow().to_vec();
+    Json(offers)
+}
+
+#[rocket::put("/offers/<symbol>", data = "<offer_params>")]
+#[instrument(name = "PUT /v1/offers/<symbol>", skip(maker, db, api_key), err)]
+pub async fn put_offer(
+    symbol: Result<ContractSymbol>,
+    offer_params: Json<CfdNewOfferParamsRequest>,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    api_key: ApiKey,
+) -> Result<(), HttpApiProblem> {
+    let symbol = symbol.map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Unknown ContractSymbol provided")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let symbol = symbol.into();
+    let result = update_offer_params(maker, symbol, &offer_params).await;
+
+    audit(
+        db,
+        &actor(&api_key),
+        "updateOfferParams",
+        format!("symbol={symbol}, params={:?}", offer_params.0),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::get("/cfds")]
+#[instrument(name = "GET /v1/cfds", skip_all, err)]
+pub fn get_cfds(
+    rx: &State<FeedReceivers>,
+    _api_key: ApiKey,
+) -> Result<Json<Vec<Cfd>>, HttpApiProblem> {
+    let cfds = load_cfds(rx)?;
+    Ok(Json(cfds))
+}
+
+#[rocket::get("/cfds/<order_id>")]
+#[instrument(name = "GET /v1/cfds/<order_id>", skip(rx, _api_key), err)]
+pub fn get_cfd(
+    order_id: Uuid,
+    rx: &State<FeedReceivers>,
+    _api_key: ApiKey,
+) -> Result<Json<Cfd>, HttpApiProblem> {
+    let order_id = OrderId::from(order_id);
+
+    let cfd = load_cfds(rx)?
+        .into_iter()
+        .find(|cfd| cfd.order_id == order_id)
+        .ok_or_else(|| {
+            HttpApiProblem::new(StatusCode::NOT_FOUND)
+                .title("CFD not found")
+                .detail(format!("No CFD with order id {order_id}"))
+        })?;
+
+    Ok(Json(cfd))
+}
+
+#[rocket::post("/cfds/<order_id>/<action>")]
+#[instrument(
+    name = "POST /v1/cfds/<order_id>/<action>",
+    skip(maker, db, api_key),
+    err
+)]
+pub async fn post_cfd_action(
+    order_id: Uuid,
+    action: String,
+    maker: &State<Arc<Maker>>,
+    db: &State<sqlite_db::Connection>,
+    api_key: ApiKey,
+) -> Result<(), HttpApiProblem> {
+    let order_id = OrderId::from(order_id);
+    let result = execute_cfd_action(maker, order_id, action.clone()).await;
+
+    audit(
+        db,
+        &actor(&api_key),
+        &action,
+        format!("order_id={order_id}"),
+        result.is_ok(),
+    )
+    .await;
+
+    result
+}
+
+#[rocket::get("/quotes")]
+#[instrument(name = "GET /v1/quotes", skip_all)]
+pub fn get_quotes(rx: &State<FeedReceivers>, _api_key: ApiKey) -> Json<LatestQuotes> {
+    let quotes = rx.quote.borrow().clone();
+    Json(quotes)
+}
+
+#[rocket::get("/wallet")]
+#[instrument(name = "GET /v1/wallet", skip_all, err)]
+pub fn get_wallet(
+    rx_wallet: &State<watch::Receiver<Option<WalletInfo>>>,
+    _api_key: ApiKey,
+) -> Result<Json<shared_bin::WalletInfo>, HttpApiProblem> {
+    let wallet_info = rx_wallet.borrow();
+    let wallet_info = wallet_info.as_ref().ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("Wallet not yet available")
+            .detail("The wallet has not been synced yet. Please retry later.")
+    })?;
+
+    Ok(Json(shared_bin::WalletInfo::from(wallet_info)))
+}
+
+/// Actions performed with an API key are attributed to the key in the audit log.
+pub(crate) fn actor(api_key: &ApiKey) -> String {
+    format!("api-key:{}", api_key.name)
+}
+
+fn load_cfds(rx: &FeedReceivers) -> Result<Vec<Cfd>, HttpApiProblem> {
+    rx.cfds.borrow().clone().ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("CFDs not yet available")
+            .detail("CFDs are still being loaded from the database. Please retry later.")
+    })
+}
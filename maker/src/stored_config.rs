@@ -0,0 +1,43 @@
+//! Configuration managed by the operator through the HTTP API, stored as a JSON document in the
+//! database.
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub(crate) trait StoredConfig: Default + Serialize + DeserializeOwned {
+    /// How the configuration is referred to in errors.
+    const NAME: &'static str;
+
+    fn validate(&self) -> Result<()>;
+}
+
+/// Parse the stored configuration, the default applies if none was stored.
+///
+/// Fails if the stored document is invalid, rather than silently falling back to the default.
+pub(crate) fn parse<T>(stored: Option<String>) -> Result<T>
+where
+    T: StoredConfig,
+{
+    let config = match stored {
+        Some(json) => serde_json::from_str::<T>(&json)
+            .with_context(|| format!("Failed to parse stored {}", T::NAME))?,
+        None => return Ok(T::default()),
+    };
+    config
+        .validate()
+        .with_context(|| format!("Invalid stored {}", T::NAME))?;
+
+    Ok(config)
+}
+
+/// Validate the configuration and serialize it to be stored.
+pub(crate) fn to_json<T>(config: &T) -> Result<String>
+where
+    T: StoredConfig,
+{
+    config.validate()?;
+
+    Ok(serde_json::to_string(config)?)
+}
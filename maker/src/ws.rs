@@ -0,0 +1,71 @@
+use crate::routes::audit;
+use crate::routes::execute_cfd_action;
+use crate::routes::update_offer_params;
+use crate::routes::v1::actor;
+use crate::routes::CfdNewOfferParamsRequest;
+use crate::routes::Maker;
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::projection::CfdAction;
+use model::ContractSymbol;
+use model::OrderId;
+use rocket_cookie_auth::api_key::ApiKey;
+use serde::Deserialize;
+use shared_bin::ws::problem_to_error;
+use shared_bin::ws::CommandHandler;
+use std::sync::Arc;
+
+/// Commands the maker accepts over the WebSocket API.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    CfdAction {
+        order_id: OrderId,
+        action: CfdAction,
+    },
+    SetOfferParams {
+        symbol: ContractSymbol,
+        params: CfdNewOfferParamsRequest,
+    },
+}
+
+pub struct Commands {
+    maker: Arc<Maker>,
+    db: sqlite_db::Connection,
+}
+
+impl Commands {
+    pub fn new(maker: Arc<Maker>, db: sqlite_db::Connection) -> Self {
+        Self { maker, db }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for Commands {
+    type Command = Command;
+
+    /// Commands are recorded in the audit log like the equivalent requests to the HTTP API.
+    async fn handle(&self, api_key: &ApiKey, command: Command) -> Result<()> {
+        let (action, details, result) = match command {
+            Command::CfdAction { order_id, action } => {
+                let action = action.to_string();
+                let result = execute_cfd_action(&self.maker, order_id, action.clone()).await;
+
+                (action, format!("order_id={order_id}"), result)
+            }
+            Command::SetOfferParams { symbol, params } => {
+                let result = update_offer_params(&self.maker, symbol, &params).await;
+
+                (
+                    "updateOfferParams".to_owned(),
+                    format!("symbol={symbol}, params={params:?}"),
+                    result,
+                )
+            }
+        };
+
+        audit(&self.db, &actor(api_key), &action, details, result.is_ok()).await;
+
+        result.map_err(problem_to_error)
+    }
+}
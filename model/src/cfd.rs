@@ -51,6 +51,7 @@ use maia_core::secp256k1_zkp::ecdsa::Signature;
 use maia_core::secp256k1_zkp::EcdsaAdaptorSignature;
 use maia_core::secp256k1_zkp::SECP256K1;
 use maia_core::TransactionExt;
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::de::Error as _;
@@ -172,6 +173,9 @@ pub struct Offer {
     pub tx_fee_rate: TxFeeRate,
     pub funding_rate: FundingRate,
     pub opening_fee: OpeningFee,
+    /// Fraction of the notional value of the position charged in addition to the `opening_fee`
+    #[serde(default)]
+    pub opening_fee_rate: Decimal,
     pub lot_size: LotSize,
 }
 
@@ -209,10 +213,22 @@ impl Offer {
             tx_fee_rate,
             funding_rate,
             opening_fee,
+            opening_fee_rate: Decimal::ZERO,
             lot_size,
         }
     }
 
+    /// The opening fee the taker pays for a position of the given quantity.
+    pub fn opening_fee_for(&self, quantity: Contracts) -> OpeningFee {
+        let notional = calculate_margin(self.contract_symbol, self.price, quantity, Leverage::ONE);
+        let share_of_notional = (Decimal::from(notional.as_sat()) * self.opening_fee_rate)
+            .floor()
+            .to_u64()
+            .unwrap_or_default();
+
+        OpeningFee::new(self.opening_fee.to_inner() + Amount::from_sat(share_of_notional))
+    }
+
     /// Defines when we consider an order to be outdated
     ///
     /// If the maker's offer creation timestamp is older than `OUTDATED_AFTER_MINS` minutes then we
@@ -279,6 +295,81 @@ pub enum CannotRollover {
     NoEvents,
 }
 
+/// Limits the taker puts on automatically rolling over a CFD.
+///
+/// By default a CFD is rolled over for as long as it is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolloverPolicy {
+    /// If disabled, the CFD is not rolled over and expires through the oracle attestation.
+    pub enabled: bool,
+    /// The CFD is no longer rolled over once the funding fees paid reach this amount.
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_btc::opt")]
+    pub max_funding_fee: Option<Amount>,
+    /// The CFD is no longer rolled over once it was rolled over this many times.
+    pub max_rollovers: Option<u32>,
+    /// The CFD is not rolled over if it would then expire after this point in time.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub until: Option<OffsetDateTime>,
+}
+
+impl Default for RolloverPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_funding_fee: None,
+            max_rollovers: None,
+            until: None,
+        }
+    }
+}
+
+/// The [`RolloverPolicy`] of a CFD as of when an automatic rollover was started.
+///
+/// The maker only tells the taker which funding rate it charges once it accepted the rollover,
+/// hence the policy has to be checked again with that rate, see [`Cfd::check_rollover_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloverPolicyCheck {
+    pub policy: RolloverPolicy,
+    pub completed_rollovers: u32,
+    pub now: OffsetDateTime,
+}
+
+/// Reasons why the [`RolloverPolicy`] of a CFD does not allow rolling it over.
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RolloverPolicyViolation {
+    #[error("Auto-rollover is disabled")]
+    Disabled,
+    #[error("Funding fees paid reached the limit of {max}")]
+    FundingFeeLimitReached { max: Amount },
+    #[error("CFD was already rolled over {max} times")]
+    RolloverLimitReached { max: u32 },
+    #[error("CFD would expire after {until}")]
+    EndDateReached { until: OffsetDateTime },
+}
+
+/// Whether we paid or received a funding fee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FundingFeeDirection {
+    Paid,
+    Received,
+}
+
+/// The funding fee charged for one rollover of a CFD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FundingFeeLedgerEntry {
+    pub timestamp: Timestamp,
+    pub rate: FundingRate,
+    pub hours_charged: u64,
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub fee: Amount,
+    pub direction: FundingFeeDirection,
+    /// Funding fees paid over the lifetime of the CFD after this one was charged.
+    ///
+    /// Negative if we received more funding fees than we paid.
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub balance: SignedAmount,
+}
+
 /// Reasons why we cannot collab close a CFD
 #[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CannotSettleCollaboratively {
@@ -314,8 +405,12 @@ impl CfdEvent {
 ///
 /// Unfortunately, despite being a shared type some of the variants
 /// are only relevant for specific roles.
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, strum_macros::EnumDiscriminants)]
 #[serde(tag = "name", content = "data")]
+#[strum_discriminants(
+    name(EventName),
+    derive(Hash, Serialize, Deserialize, strum_macros::Display)
+)]
 pub enum EventKind {
     ContractSetupStarted,
     ContractSetupCompleted {
@@ -338,6 +433,11 @@ pub enum EventKind {
         /// If the complete fee is available we can use it instead of accumulating fees from
         /// `funding_fee`
         complete_fee: Option<CompleteFee>,
+        /// The hours `funding_fee` was charged for.
+        ///
+        /// Not recorded for rollovers which predate the funding fee ledger.
+        #[serde(default)]
+        hours_charged: Option<u64>,
     },
     RolloverFailed,
 
@@ -488,7 +588,11 @@ impl EventKind {
 /// we apply the event to the aggregate producing a new aggregate (representing the latest state
 /// `version`). To bring a cfd into a certain state version we load all events from the
 /// database and apply them in order (order by version).
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// The aggregate is serializable so it can be persisted as a snapshot. Changing its fields
+/// invalidates existing snapshots because they fail to deserialize.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Cfd {
     version: u32,
 
@@ -517,6 +621,7 @@ pub struct Cfd {
     ///
     /// There is not guarantee that the transaction is confirmed if this is set to `Some`.
     /// However, if this is set to `Some`, there is no need to re-emit it as part of another event.
+    #[serde(with = "hex_transaction::opt")]
     cet: Option<Transaction>,
 
     /// Holds the decrypted commit transaction if we have previously emitted it as part of an
@@ -524,9 +629,12 @@ pub struct Cfd {
     ///
     /// There is not guarantee that the transaction is confirmed if this is set to `Some`.
     /// However, if this is set to `Some`, there is no need to re-emit it as part of another event.
+    #[serde(with = "hex_transaction::opt")]
     commit_tx: Option<Transaction>,
 
+    #[serde(with = "hex_transaction::opt")]
     collaborative_settlement_spend_tx: Option<Transaction>,
+    #[serde(with = "hex_transaction::opt")]
     refund_tx: Option<Transaction>,
 
     lock_finality: bool,
@@ -642,7 +750,7 @@ impl Cfd {
             quantity,
             counterparty_network_identity,
             counterparty_peer_id,
-            offer.opening_fee,
+            offer.opening_fee_for(quantity),
             offer.funding_rate,
             offer.tx_fee_rate,
             offer.contract_symbol,
@@ -708,6 +816,113 @@ impl Cfd {
         Ok((dlc.commit.0.txid(), dlc.settlement_event_id))
     }
 
+    /// Check whether the taker's [`RolloverPolicy`] allows rolling over the CFD once more.
+    ///
+    /// The funding fee of the rollover is estimated with the given `funding_rate`, charged for a
+    /// full settlement interval. It counts towards the funding fee limit, so that the limit is not
+    /// exceeded by the rollover.
+    ///
+    /// Only the limits chosen by the taker are checked, whether the CFD can be rolled over at all
+    /// is decided by [`Cfd::can_auto_rollover_taker`].
+    pub fn check_rollover_policy(
+        &self,
+        policy: &RolloverPolicy,
+        completed_rollovers: u32,
+        funding_rate: FundingRate,
+        now: OffsetDateTime,
+    ) -> Result<Result<(), RolloverPolicyViolation>> {
+        if !policy.enabled {
+            return Ok(Err(RolloverPolicyViolation::Disabled));
+        }
+
+        if let Some(max) = policy.max_funding_fee {
+            let funding_fee = FundingFee::calculate(
+                self.initial_price,
+                self.quantity,
+                self.long_leverage,
+                self.short_leverage,
+                funding_rate,
+                self.settlement_interval.whole_hours(),
+                self.contract_symbol,
+            )?;
+            let funding_fees_paid = self.funding_fees_paid_after(funding_fee);
+
+            if funding_fees_paid > max.to_signed().context("Funding fee limit too large")? {
+                return Ok(Err(RolloverPolicyViolation::FundingFeeLimitReached { max }));
+            }
+        }
+
+        if let Some(max) = policy.max_rollovers {
+            if completed_rollovers >= max {
+                return Ok(Err(RolloverPolicyViolation::RolloverLimitReached { max }));
+            }
+        }
+
+        if let Some(until) = policy.until {
+            if now + self.settlement_interval > until {
+                return Ok(Err(RolloverPolicyViolation::EndDateReached { until }));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// The funding fees we paid so far, negative if we received funding fees.
+    ///
+    /// Does not include the opening fee.
+    pub fn funding_fees_paid(&self) -> SignedAmount {
+        self.funding_fees_paid_in(self.fee_account)
+    }
+
+    /// The funding fees we paid so far and after being charged `funding_fee` once more.
+    fn funding_fees_paid_after(&self, funding_fee: FundingFee) -> SignedAmount {
+        self.funding_fees_paid_in(self.fee_account.add_funding_fee(funding_fee))
+    }
+
+    fn funding_fees_paid_in(&self, fee_account: FeeAccount) -> SignedAmount {
+        let opening_fee = FeeAccount::new(self.position, self.role)
+            .add_opening_fee(self.opening_fee)
+            .balance();
+
+        fee_account.balance() - opening_fee
+    }
+
+    /// Derive the funding fee ledger entry for a `RolloverCompleted` event.
+    ///
+    /// Must be called on the CFD before the event is applied, as the funding fees paid so far are
+    /// derived by applying the event.
+    pub fn funding_fee_ledger_entry(
+        &self,
+        event: &CfdEvent,
+    ) -> Result<Option<FundingFeeLedgerEntry>> {
+        let (funding_fee, hours_charged) = match &event.event {
+            EventKind::RolloverCompleted {
+                funding_fee,
+                hours_charged,
+                ..
+            } => (*funding_fee, *hours_charged),
+            _ => return Ok(None),
+        };
+        let hours_charged = hours_charged.context("Hours charged not recorded")?;
+
+        let direction = if funding_fee.compute_relative(self.position).is_negative() {
+            FundingFeeDirection::Received
+        } else {
+            FundingFeeDirection::Paid
+        };
+
+        let balance = self.clone().apply(event.clone()).funding_fees_paid();
+
+        Ok(Some(FundingFeeLedgerEntry {
+            timestamp: event.timestamp,
+            rate: funding_fee.rate,
+            hours_charged,
+            fee: funding_fee.fee,
+            direction,
+            balance,
+        }))
+    }
+
     fn can_rollover(&self) -> Result<(), CannotRollover> {
         if self.is_closed() {
             return Err(CannotRollover::Closed);
@@ -941,6 +1156,7 @@ impl Cfd {
                 tx_fee_rate,
                 rollover_fee_account,
                 funding_fee,
+                hours_to_charge,
             ),
             self.dlc.clone().context("No DLC present")?,
             self.position,
@@ -1006,6 +1222,7 @@ impl Cfd {
                 tx_fee_rate,
                 self.fee_account,
                 funding_fee,
+                hours_to_charge,
             ),
             self.dlc.clone().context("No DLC present")?,
             self.position,
@@ -1037,43 +1254,10 @@ impl Cfd {
 
     /// Process the taker's collaborative settlement proposal.
     ///
-    /// It generates a local [`SettlementProposal`] setting the maximum payout price to Olivia's
-    /// maximum attestation price. This assumes that the counterparty has also used the same
-    /// configuration.
-    pub fn start_collab_settlement_maker_olivia_max(
-        self,
-        current_price: Price,
-        n_payouts: usize,
-        proposed_settlement_transaction: &Transaction,
-    ) -> Result<(CfdEvent, SettlementTransaction, SettlementProposal)> {
-        self.start_collab_settlement_maker(
-            current_price,
-            n_payouts,
-            proposed_settlement_transaction,
-            InverseMaxPrice::OliviaMax,
-        )
-    }
-
-    /// Process the taker's collaborative settlement proposal.
-    ///
-    /// It generates a local [`SettlementProposal`] setting the maximum payout price to double of
-    /// the value of the inital price used to generate the CFD. This assumes that the counterparty
-    /// has also used the same configuration.
-    pub fn start_collab_settlement_maker_double_initial(
-        self,
-        current_price: Price,
-        n_payouts: usize,
-        proposed_settlement_transaction: &Transaction,
-    ) -> Result<(CfdEvent, SettlementTransaction, SettlementProposal)> {
-        self.start_collab_settlement_maker(
-            current_price,
-            n_payouts,
-            proposed_settlement_transaction,
-            InverseMaxPrice::DoubleOfInitial,
-        )
-    }
-
-    fn start_collab_settlement_maker(
+    /// It generates a local [`SettlementProposal`] with the maximum payout price of inverse payout
+    /// curves configured through `inverse_max_price_config`. This assumes that the counterparty has also
+    /// used the same configuration.
+    pub fn start_collab_settlement_maker(
         self,
         current_price: Price,
         n_payouts: usize,
@@ -1207,6 +1391,7 @@ impl Cfd {
         self,
         dlc: Dlc,
         funding_fee: FundingFee,
+        hours_charged: u64,
         complete_fee: Option<CompleteFee>,
     ) -> CfdEvent {
         match self.can_rollover() {
@@ -1214,6 +1399,7 @@ impl Cfd {
                 dlc: Some(dlc),
                 funding_fee,
                 complete_fee,
+                hours_charged: Some(hours_charged),
             }),
             Err(e) => self.fail_rollover(e.into()),
         }
@@ -1570,6 +1756,7 @@ impl Cfd {
                 dlc,
                 funding_fee,
                 complete_fee,
+                ..
             } => {
                 self.dlc = dlc;
                 self.during_rollover = false;
@@ -2853,6 +3040,7 @@ mod tests {
             dlc: Some(Dlc::dummy(None)),
             funding_fee: FundingFee::new(Amount::ZERO, FundingRate::default()),
             complete_fee: Some(CompleteFee::None),
+            hours_charged: Some(24),
         }
         .to_json();
 
@@ -2997,6 +3185,211 @@ mod tests {
         assert_eq!(cannot_roll_over, CannotRollover::TooRecent)
     }
 
+    #[test]
+    fn given_default_rollover_policy_then_rollover() {
+        let cfd = Cfd::dummy_taker_long();
+
+        let result = cfd
+            .check_rollover_policy(
+                &RolloverPolicy::default(),
+                100,
+                FundingRate::default(),
+                datetime!(2021-11-18 11:00:00).assume_utc(),
+            )
+            .unwrap();
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_disabled_rollover_policy_then_no_rollover() {
+        let cfd = Cfd::dummy_taker_long();
+        let policy = RolloverPolicy {
+            enabled: false,
+            ..RolloverPolicy::default()
+        };
+
+        let violation = cfd
+            .check_rollover_policy(
+                &policy,
+                0,
+                FundingRate::default(),
+                datetime!(2021-11-18 11:00:00).assume_utc(),
+            )
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(violation, RolloverPolicyViolation::Disabled);
+    }
+
+    #[test]
+    fn given_funding_fee_of_rollover_exceeds_limit_then_no_rollover() {
+        let cfd = Cfd::dummy_taker_long();
+        let max = Amount::from_sat(100);
+        let policy = RolloverPolicy {
+            max_funding_fee: Some(max),
+            ..RolloverPolicy::default()
+        };
+        let now = datetime!(2021-11-18 11:00:00).assume_utc();
+
+        let violation = cfd
+            .check_rollover_policy(&policy, 0, FundingRate::new(dec!(0.001)).unwrap(), now)
+            .unwrap()
+            .unwrap_err();
+
+        assert_eq!(
+            violation,
+            RolloverPolicyViolation::FundingFeeLimitReached { max }
+        );
+    }
+
+    #[test]
+    fn given_funding_fee_received_in_rollover_then_rollover_despite_limit() {
+        let cfd = Cfd::dummy_taker_long();
+        let policy = RolloverPolicy {
+            max_funding_fee: Some(Amount::ZERO),
+            ..RolloverPolicy::default()
+        };
+        let now = datetime!(2021-11-18 11:00:00).assume_utc();
+
+        let result = cfd
+            .check_rollover_policy(&policy, 0, FundingRate::new(dec!(-0.001)).unwrap(), now)
+            .unwrap();
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn given_rollover_limit_reached_then_no_rollover() {
+        let cfd = Cfd::dummy_taker_long();
+        let policy = RolloverPolicy {
+            max_rollovers: Some(3),
+            ..RolloverPolicy::default()
+        };
+        let now = datetime!(2021-11-18 11:00:00).assume_utc();
+
+        assert!(cfd
+            .check_rollover_policy(&policy, 2, FundingRate::default(), now)
+            .unwrap()
+            .is_ok());
+        assert_eq!(
+            cfd.check_rollover_policy(&policy, 3, FundingRate::default(), now)
+                .unwrap()
+                .unwrap_err(),
+            RolloverPolicyViolation::RolloverLimitReached { max: 3 }
+        );
+    }
+
+    #[test]
+    fn given_rollover_would_expire_after_end_date_then_no_rollover() {
+        let cfd = Cfd::dummy_taker_long();
+        let until = datetime!(2021-11-20 10:00:00).assume_utc();
+        let policy = RolloverPolicy {
+            until: Some(until),
+            ..RolloverPolicy::default()
+        };
+
+        assert!(cfd
+            .check_rollover_policy(
+                &policy,
+                0,
+                FundingRate::default(),
+                datetime!(2021-11-19 10:00:00).assume_utc()
+            )
+            .unwrap()
+            .is_ok());
+        assert_eq!(
+            cfd.check_rollover_policy(
+                &policy,
+                0,
+                FundingRate::default(),
+                datetime!(2021-11-19 10:00:01).assume_utc()
+            )
+            .unwrap()
+            .unwrap_err(),
+            RolloverPolicyViolation::EndDateReached { until }
+        );
+    }
+
+    #[test]
+    fn given_opening_fee_rate_then_opening_fee_includes_share_of_notional() {
+        let offer = Offer {
+            opening_fee: OpeningFee::new(Amount::from_sat(1_000)),
+            opening_fee_rate: dec!(0.001),
+            ..Offer::dummy_btc_usd_short().with_price(Price::new(dec!(20_000)).unwrap())
+        };
+
+        // 1000 contracts at 20_000 are worth 0.05 BTC, 0.1% of that is 5_000 sats
+        let opening_fee = offer.opening_fee_for(Contracts::new(1_000));
+
+        assert_eq!(opening_fee, OpeningFee::new(Amount::from_sat(6_000)));
+    }
+
+    #[test]
+    fn given_new_cfd_then_funding_fees_paid_exclude_opening_fee() {
+        let cfd = Cfd::dummy_taker_long();
+        let initial_funding_fee = FundingFee::calculate(
+            cfd.initial_price,
+            cfd.quantity,
+            cfd.long_leverage,
+            cfd.short_leverage,
+            cfd.initial_funding_rate,
+            SETTLEMENT_INTERVAL.whole_hours(),
+            cfd.contract_symbol,
+        )
+        .unwrap();
+        let expected = FeeAccount::new(cfd.position, cfd.role)
+            .add_funding_fee(initial_funding_fee)
+            .balance();
+
+        assert_eq!(cfd.funding_fees_paid(), expected);
+    }
+
+    #[test]
+    fn given_taker_long_and_positive_funding_rate_then_ledger_entry_records_paid_fee() {
+        let now = datetime!(2021-11-19 10:00:00).assume_utc();
+        let from_event_id =
+            BitMexPriceEventId::with_20_digits(now + 12.hours(), ContractSymbol::BtcUsd);
+        let to_event_id =
+            BitMexPriceEventId::with_20_digits(now + 36.hours(), ContractSymbol::BtcUsd);
+        let cfd = Cfd::dummy_taker_long().dummy_open(from_event_id);
+        let funding_fees_paid_before = cfd.funding_fees_paid();
+
+        let event = CfdEvent {
+            timestamp: Timestamp::new(now.unix_timestamp()),
+            id: cfd.id,
+            event: EventKind::RolloverCompleted {
+                dlc: Some(Dlc::dummy(Some(to_event_id))),
+                funding_fee: FundingFee::new(
+                    Amount::from_sat(500),
+                    FundingRate::new(dec!(0.0005)).unwrap(),
+                ),
+                complete_fee: None,
+                hours_charged: Some(24),
+            },
+        };
+        let entry = cfd.funding_fee_ledger_entry(&event).unwrap().unwrap();
+
+        assert_eq!(entry.hours_charged, 24);
+        assert_eq!(entry.fee, Amount::from_sat(500));
+        assert_eq!(entry.direction, FundingFeeDirection::Paid);
+        assert_eq!(
+            entry.balance,
+            funding_fees_paid_before + SignedAmount::from_sat(500)
+        );
+    }
+
+    #[test]
+    fn given_event_other_than_rollover_completed_then_no_ledger_entry() {
+        let cfd = Cfd::dummy_taker_long().dummy_open(dummy_event_id());
+
+        let entry = cfd
+            .funding_fee_ledger_entry(&CfdEvent::new(cfd.id, EventKind::RolloverFailed))
+            .unwrap();
+
+        assert!(entry.is_none());
+    }
+
     #[test]
     fn given_cfd_not_locked_then_no_rollover() {
         let cfd = Cfd::dummy_not_open_yet();
@@ -3102,7 +3495,7 @@ mod tests {
             .with_lock(taker_keys, maker_keys)
             .dummy_collab_settlement_taker(opening_price, maker_cfd);
 
-        let rollover_event = cfd.complete_rollover(Dlc::dummy(None), FundingFee::dummy(), None);
+        let rollover_event = cfd.complete_rollover(Dlc::dummy(None), FundingFee::dummy(), 24, None);
 
         assert_eq!(rollover_event.event, EventKind::RolloverFailed);
     }
@@ -3119,7 +3512,7 @@ mod tests {
             .dummy_open(dummy_event_id())
             .dummy_start_collab_settlement();
 
-        let rollover_event = cfd.complete_rollover(Dlc::dummy(None), FundingFee::dummy(), None);
+        let rollover_event = cfd.complete_rollover(Dlc::dummy(None), FundingFee::dummy(), 24, None);
 
         assert_eq!(rollover_event.event, EventKind::RolloverFailed);
     }
@@ -3937,6 +4330,7 @@ mod tests {
                             rate: FundingRate::new(funding_rate).unwrap(),
                         },
                         complete_fee: None,
+                        hours_charged: Some(24),
                     },
                 },
             ]
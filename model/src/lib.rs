@@ -42,6 +42,7 @@ pub mod transaction_ext;
 
 pub use cfd::*;
 pub use contract_setup::SetupParams;
+pub use payout_curve::InverseMaxPrice;
 pub use payout_curve::OraclePayouts;
 pub use payout_curve::Payouts;
 pub use rollover::BaseDlcParams;
@@ -573,6 +574,43 @@ impl Default for OpeningFee {
     }
 }
 
+/// Identifies the partner which referred a taker to the maker.
+///
+/// Takers send the code along with their orders, allowing the maker to attribute revenue to
+/// partners bundling the taker.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ReferralCode(String);
+
+impl ReferralCode {
+    const MAX_LEN: usize = 64;
+}
+
+impl str::FromStr for ReferralCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ensure!(!s.is_empty(), "Referral code must not be empty");
+        ensure!(
+            s.len() <= Self::MAX_LEN,
+            "Referral code must not be longer than {} characters",
+            Self::MAX_LEN
+        );
+        ensure!(
+            s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "Referral code may only contain letters, digits, '-' and '_'"
+        );
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl fmt::Display for ReferralCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// Fee paid between takers and makers periodically.
 ///
 /// The `fee` field represents the absolute value of this fee.
@@ -703,8 +741,9 @@ impl CompleteFee {
 /// The balance being positive means we owe this amount to the other party.
 /// The balance being negative means that the other party owes this amount to us.
 /// The counterparty fee-account balance is always the inverse of the balance.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FeeAccount {
+    #[serde(with = "::bdk::bitcoin::util::amount::serde::as_sat")]
     balance: SignedAmount,
     position: Position,
     role: Role,
@@ -1530,6 +1569,14 @@ mod tests {
         assert_eq!(complete_fee, expected_complete_fee)
     }
 
+    #[test]
+    fn referral_code_only_allows_url_safe_characters() {
+        assert!("partner-app_1".parse::<ReferralCode>().is_ok());
+        assert!("".parse::<ReferralCode>().is_err());
+        assert!("partner app".parse::<ReferralCode>().is_err());
+        assert!("a".repeat(65).parse::<ReferralCode>().is_err());
+    }
+
     fn dummy_amount() -> Amount {
         Amount::from_sat(500)
     }
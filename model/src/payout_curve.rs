@@ -68,50 +68,10 @@ pub struct Payouts {
 }
 
 impl Payouts {
-    /// Generate the inverse payout curve discretised [`Payouts`], with the maximum price set to
-    /// Olivia's maximum attestation price.
-    pub fn new_inverse_olivia_max(
-        (position, role): (Position, Role),
-        initial_price: Price,
-        quantity: Contracts,
-        (leverage_long, leverage_short): (Leverage, Leverage),
-        n_payouts: usize,
-        fee: CompleteFee,
-    ) -> Result<Self> {
-        Self::new_inverse(
-            (position, role),
-            initial_price,
-            quantity,
-            (leverage_long, leverage_short),
-            n_payouts,
-            fee,
-            InverseMaxPrice::OliviaMax,
-        )
-    }
-
-    /// Generate the inverse payout curve discretised [`Payouts`], with the maximum price set to
-    /// double the value of the `initial_price`.
-    pub fn new_inverse_double_initial(
-        (position, role): (Position, Role),
-        initial_price: Price,
-        quantity: Contracts,
-        (leverage_long, leverage_short): (Leverage, Leverage),
-        n_payouts: usize,
-        fee: CompleteFee,
-    ) -> Result<Self> {
-        Self::new_inverse(
-            (position, role),
-            initial_price,
-            quantity,
-            (leverage_long, leverage_short),
-            n_payouts,
-            fee,
-            InverseMaxPrice::DoubleOfInitial,
-        )
-    }
-
+    /// Generate the inverse payout curve discretised [`Payouts`], with the maximum price
+    /// configured through `inverse_max_price_config`.
     #[tracing::instrument(err)]
-    pub(crate) fn new_inverse(
+    pub fn new_inverse(
         (position, role): (Position, Role),
         price: Price,
         quantity: Contracts,
@@ -219,7 +179,7 @@ impl Payouts {
 
 /// Configure the maximum price supported by the inverse payout curve.
 #[derive(Debug, Copy, Clone)]
-pub(crate) enum InverseMaxPrice {
+pub enum InverseMaxPrice {
     /// Set the maximum price to the maximum value Olivia can attest to.
     OliviaMax,
     /// Set the maximum price to double the value of the initial price.
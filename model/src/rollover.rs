@@ -31,6 +31,7 @@ pub struct RolloverParams {
     pub fee_rate: TxFeeRate,
     pub fee_account: FeeAccount,
     pub current_fee: FundingFee,
+    pub hours_charged: u64,
 }
 
 impl RolloverParams {
@@ -44,6 +45,7 @@ impl RolloverParams {
         fee_rate: TxFeeRate,
         fee_account: FeeAccount,
         current_fee: FundingFee,
+        hours_charged: u64,
     ) -> Self {
         Self {
             price,
@@ -54,6 +56,7 @@ impl RolloverParams {
             fee_rate,
             fee_account,
             current_fee,
+            hours_charged,
         }
     }
 
@@ -61,6 +64,11 @@ impl RolloverParams {
         &self.current_fee
     }
 
+    /// The hours the funding fee of the rollover is charged for.
+    pub fn hours_charged(&self) -> u64 {
+        self.hours_charged
+    }
+
     pub fn complete_fee_before_rollover(&self) -> CompleteFee {
         self.fee_account.settle()
     }
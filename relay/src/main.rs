@@ -0,0 +1,169 @@
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use libp2p_core::identity::ed25519;
+use libp2p_core::identity::Keypair;
+use libp2p_core::Multiaddr;
+use libp2p_tcp::TokioTcpConfig;
+use ping_pong::pong;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio_extras::Tasks;
+use xtra::Actor as _;
+use xtra_libp2p::endpoint;
+use xtra_libp2p::limits::ConnectionLimits;
+use xtra_libp2p::listener;
+use xtra_libp2p::relay;
+use xtra_libp2p::Endpoint;
+use xtras::supervisor::always_restart_after;
+use xtras::supervisor::Supervisor;
+
+const ENDPOINT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
+const RESTART_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Relays connections to peers which cannot be dialed directly, e.g. a maker behind a NAT.
+#[derive(Parser)]
+struct Opts {
+    /// The IP address to listen on.
+    #[clap(long, default_value_t = IpAddr::from(Ipv4Addr::UNSPECIFIED))]
+    ip: IpAddr,
+
+    /// The port to listen on.
+    #[clap(long, default_value = "10100")]
+    port: u16,
+
+    /// Where to store the identity of the relay, defaults to the current working directory.
+    #[clap(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Maximum number of circuits relayed at the same time. Unlimited if not specified.
+    #[clap(long)]
+    max_circuits: Option<usize>,
+
+    /// Circuits are closed after this many seconds. Unlimited if not specified.
+    #[clap(long)]
+    max_circuit_duration_secs: Option<u64>,
+
+    /// Maximum number of connections. Unlimited if not specified.
+    #[clap(long)]
+    max_connections: Option<usize>,
+
+    /// Maximum number of connections from the same IP address. Unlimited if not specified.
+    #[clap(long)]
+    max_connections_per_ip: Option<usize>,
+
+    /// Maximum number of circuits a single peer may request at the same time. Unlimited if not
+    /// specified.
+    #[clap(long)]
+    max_circuits_per_peer: Option<usize>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter("info,xtra_libp2p=debug,relay=debug")
+        .init();
+
+    let data_dir = opts
+        .data_dir
+        .unwrap_or_else(|| std::env::current_dir().expect("unable to get cwd"));
+    if !data_dir.exists() {
+        tokio::fs::create_dir_all(&data_dir).await?;
+    }
+
+    let identity = load_or_create_identity(&data_dir.join("relay_identity")).await?;
+    let peer_id = identity.public().to_peer_id();
+
+    let ip_protocol = match opts.ip {
+        IpAddr::V4(_) => "ip4",
+        IpAddr::V6(_) => "ip6",
+    };
+    let listen_address = format!("/{ip_protocol}/{}/tcp/{}", opts.ip, opts.port)
+        .parse::<Multiaddr>()
+        .context("failed to construct listen address")?;
+
+    let mut tasks = Tasks::default();
+
+    let (endpoint_addr, endpoint_context) = xtra::Context::new(None);
+
+    let relay_server = relay::server::Actor::new(
+        endpoint_addr.clone(),
+        relay::server::Config {
+            max_circuits: opts.max_circuits,
+            max_circuit_duration: opts.max_circuit_duration_secs.map(Duration::from_secs),
+        },
+    )
+    .create(None)
+    .spawn(&mut tasks);
+
+    // Takers and makers ping the relay, which keeps the NAT mapping of their connection alive
+    let pong_address = pong::Actor.create(None).spawn(&mut tasks);
+
+    let (listener_supervisor, listener_actor) = Supervisor::<_, listener::Error>::with_policy(
+        {
+            let endpoint_addr = endpoint_addr.clone();
+            let listen_address = listen_address.clone();
+            move || listener::Actor::new(endpoint_addr.clone(), listen_address.clone())
+        },
+        always_restart_after(RESTART_INTERVAL),
+    );
+
+    let endpoint = Endpoint::new(
+        Box::new(TokioTcpConfig::new),
+        identity,
+        ENDPOINT_CONNECTION_TIMEOUT,
+        [
+            (ping_pong::PROTOCOL, pong_address.into()),
+            (relay::HOP_PROTOCOL, relay_server.into()),
+        ],
+        endpoint::Subscribers::new(vec![], vec![], vec![], vec![listener_actor.into()]),
+        HashSet::new(),
+        ConnectionLimits {
+            max_connections: opts.max_connections,
+            max_connections_per_ip: opts.max_connections_per_ip,
+            max_substreams_per_protocol: opts.max_circuits_per_peer,
+            ..ConnectionLimits::default()
+        },
+    );
+
+    tasks.add(endpoint_context.run(endpoint));
+    tasks.add(listener_supervisor.run_log_summary());
+
+    tracing::info!(
+        "Relay listening on {listen_address}/p2p/{peer_id}, peers behind a NAT can listen on \
+         <address>/p2p/{peer_id}/p2p-circuit"
+    );
+
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+/// Loads the identity of the relay, generating a new one if none exists yet.
+///
+/// The identity needs to be stable, as it is part of the address peers use to reach the relay.
+async fn load_or_create_identity(path: &Path) -> Result<Keypair> {
+    if path.exists() {
+        let mut bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("failed to read identity from {}", path.display()))?;
+        let keypair = ed25519::Keypair::decode(&mut bytes).context("invalid identity")?;
+
+        return Ok(Keypair::Ed25519(keypair));
+    }
+
+    let keypair = ed25519::Keypair::generate();
+    tokio::fs::write(path, keypair.encode())
+        .await
+        .with_context(|| format!("failed to write identity to {}", path.display()))?;
+
+    tracing::info!("Generated new identity in {}", path.display());
+
+    Ok(Keypair::Ed25519(keypair))
+}
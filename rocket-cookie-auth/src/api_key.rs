@@ -0,0 +1,110 @@
+use crate::error::Error;
+use crate::users::Users;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use rocket::http::Status;
+use rocket::request::FromRequest;
+use rocket::request::Outcome;
+use rocket::Request;
+use rocket::State;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+/// Header to pass the API key in.
+///
+/// Alternatively, the key can be passed as bearer token in the `Authorization` header.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+const API_KEY_LENGTH: usize = 40;
+
+/// A request guard that authenticates requests to the programmatic API by API key.
+///
+/// In contrast to [`crate::user::User`] this does not rely on a session cookie, which makes it
+/// suitable for bots and scripts.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ApiKey {
+    pub id: u32,
+    pub name: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<ApiKey, Self::Error> {
+        let key = match extract_key(request) {
+            Some(key) => key,
+            None => return Outcome::Failure((Status::Unauthorized, Error::Unauthenticated)),
+        };
+
+        let users = match request.guard::<&State<Users>>().await {
+            Outcome::Success(users) => users,
+            _ => return Outcome::Failure((Status::InternalServerError, Error::UnmanagedState)),
+        };
+
+        match users.authenticate_api_key(key).await {
+            Ok(api_key) => Outcome::Success(api_key),
+            Err(e) => {
+                tracing::debug!("Rejecting request with invalid API key: {e:#}");
+                Outcome::Failure((Status::Unauthorized, Error::Unauthorized))
+            }
+        }
+    }
+}
+
+fn extract_key<'r>(request: &'r Request<'_>) -> Option<&'r str> {
+    if let Some(key) = request.headers().get_one(API_KEY_HEADER) {
+        return Some(key);
+    }
+
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Generate a new random API key.
+///
+/// Returns the key, which is to be handed out to the client, and its hash, which is to be
+/// persisted.
+pub fn generate_api_key() -> (String, String) {
+    let key = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_LENGTH)
+        .map(char::from)
+        .collect::<String>();
+    let hash = hash_api_key(&key);
+
+    (key, hash)
+}
+
+/// Hash an API key for storage and lookup.
+///
+/// API keys are long random strings, hence a fast hash function is sufficient and allows to look
+/// up keys by their hash.
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_api_key_matches_its_hash() {
+        let (key, hash) = generate_api_key();
+
+        assert_eq!(key.len(), API_KEY_LENGTH);
+        assert_eq!(hash_api_key(&key), hash);
+        assert_ne!(key, hash);
+    }
+
+    #[test]
+    fn different_api_keys_are_generated() {
+        let (key1, _) = generate_api_key();
+        let (key2, _) = generate_api_key();
+
+        assert_ne!(key1, key2);
+    }
+}
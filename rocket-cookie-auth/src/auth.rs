@@ -90,12 +90,10 @@ impl<'a> Auth<'a> {
             return Ok(None);
         }
         let id = self.session.as_ref().context("Could not get session")?.id;
-        if let Ok(Some(user)) = self.users.get_by_id().await {
+        if let Ok(Some(user)) = self.users.get_by_id(id).await {
             Ok(Some(User {
-                id,
-                password: user.password,
                 auth_key: "NONE".to_string(),
-                first_login: user.first_login,
+                ..user
             }))
         } else {
             Ok(None)
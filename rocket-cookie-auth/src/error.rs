@@ -23,6 +23,25 @@ pub enum Error {
     #[error("Incorrect password")]
     Unauthorized,
 
+    /// This error occurs when the user is authenticated, but their role does not permit the
+    /// operation.
+    #[error("The user's role does not permit this operation.")]
+    Forbidden,
+
+    /// This error occurs when the user enabled two-factor authentication but did not provide a
+    /// code.
+    #[error("A TOTP code is required")]
+    TotpRequired,
+
+    #[error("Invalid TOTP code")]
+    InvalidTotp,
+
+    #[error("Two-factor authentication is already enabled")]
+    TotpAlreadyEnabled,
+
+    #[error("Two-factor authentication has not been enrolled")]
+    TotpNotEnrolled,
+
     /// A wrapper around [`argon2::Error`].
     #[error("Argon2ParsingError: {0}")]
     Argon2Parsing(#[from] argon2::Error),
@@ -39,7 +58,9 @@ use self::Error::*;
 impl Error {
     fn message(&self) -> String {
         match self {
-            Unauthorized | UserNotFound => format!("{}", self),
+            Unauthorized | UserNotFound | Forbidden | TotpRequired | InvalidTotp => {
+                format!("{}", self)
+            }
             #[cfg(debug_assertions)]
             e => format!("{}", e),
             #[allow(unreachable_patterns)]
@@ -66,6 +87,18 @@ impl From<Error> for HttpApiProblem {
             Unauthorized => HttpApiProblem::new(StatusCode::UNAUTHORIZED)
                 .title("User not authorized")
                 .detail(format!("{error:#}")),
+            Forbidden => HttpApiProblem::new(StatusCode::FORBIDDEN)
+                .title("Insufficient role")
+                .detail(format!("{error:#}")),
+            TotpRequired => HttpApiProblem::new(StatusCode::UNAUTHORIZED)
+                .title("TOTP required")
+                .detail(format!("{error:#}")),
+            InvalidTotp => HttpApiProblem::new(StatusCode::UNAUTHORIZED)
+                .title("Invalid TOTP code")
+                .detail(format!("{error:#}")),
+            TotpAlreadyEnabled | TotpNotEnrolled => HttpApiProblem::new(StatusCode::BAD_REQUEST)
+                .title("Two-factor authentication error")
+                .detail(format!("{error:#}")),
             Argon2Parsing(e) => HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
                 .title("Internal server error")
                 .detail(format!("{e:#}")),
@@ -5,13 +5,19 @@ use serde::Deserialize;
 /// The `Login` form is used along with the [`Auth`] guard to authenticate users.
 #[derive(FromForm, Deserialize, Clone, Hash, PartialEq, Eq)]
 pub struct Login {
+    /// Defaults to [`crate::DEFAULT_USERNAME`] if not provided.
+    pub(crate) username: Option<String>,
     pub(crate) password: String,
+    /// TOTP or recovery code, required if the user enabled two-factor authentication.
+    pub(crate) totp: Option<String>,
 }
 
 /// The `ChangePassword` form is used along with the [`User`] guard to change a user's password
 #[derive(FromForm, Deserialize, Clone, Hash, PartialEq, Eq)]
 pub struct ChangePassword {
     pub password: String,
+    /// Required if the user enabled two-factor authentication.
+    pub totp: Option<String>,
 }
 
 impl ChangePassword {
@@ -1,21 +1,50 @@
 #[macro_use]
 extern crate rocket;
+use crate::api_key::ApiKey;
+use crate::role::Role;
 use crate::user::User;
 use anyhow::Result;
 use rocket::async_trait;
 
+pub mod api_key;
 pub mod auth;
 pub mod error;
 pub mod forms;
+pub mod role;
 mod session;
+pub mod totp;
 pub mod user;
 pub mod users;
 
 /// Temporary value if no authentication key is set
 pub const NO_AUTH_KEY_SET: &str = "NONE";
 
+/// The name of the user that exists from the start.
+///
+/// Used for logins that do not specify a username, e.g. from clients that predate multiple users.
+pub const DEFAULT_USERNAME: &str = "admin";
+
+/// The role of the user that exists from the start.
+pub const DEFAULT_ROLE: Role = Role::Admin;
+
 #[async_trait]
 pub trait Database: Send + Sync {
-    async fn load_user(&self) -> Result<Option<User>>;
-    async fn update_password(&self, password: String) -> Result<()>;
+    async fn load_user(&self, username: String) -> Result<Option<User>>;
+    async fn load_user_by_id(&self, id: u32) -> Result<Option<User>>;
+    async fn update_password(&self, id: u32, password: String) -> Result<()>;
+    /// Load the API key with the given hash, if it exists and has not been revoked.
+    async fn load_api_key(&self, key_hash: String) -> Result<Option<ApiKey>>;
+    /// Store the user's TOTP secret and whether it is required upon login.
+    async fn update_totp(&self, id: u32, secret: Option<String>, enabled: bool) -> Result<()>;
+    /// Record the time step of a used TOTP code.
+    ///
+    /// Returns `false` if a code of this or a later time step was already used, i.e. the code
+    /// must not be accepted again.
+    async fn use_totp_step(&self, id: u32, step: u64) -> Result<bool>;
+    /// Replace all recovery codes of the user with the given hashes.
+    async fn replace_recovery_codes(&self, id: u32, code_hashes: Vec<String>) -> Result<()>;
+    /// Mark the recovery code as used.
+    ///
+    /// Returns `false` if the code does not exist or was already used.
+    async fn use_recovery_code(&self, id: u32, code_hash: String) -> Result<bool>;
 }
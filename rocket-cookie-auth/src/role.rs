@@ -0,0 +1,119 @@
+use crate::error::Error;
+use crate::user::User;
+use anyhow::bail;
+use rocket::http::Status;
+use rocket::outcome::Outcome::*;
+use rocket::request::FromRequest;
+use rocket::request::Outcome;
+use rocket::Request;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// The role of a user, determining which actions they are allowed to perform.
+///
+/// Roles are ordered, i.e. a user is allowed to do everything that a user with a lower role is
+/// allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can only look at the state of the application.
+    Viewer,
+    /// Can additionally trade, e.g. accept and reject orders and update offers.
+    Trader,
+    /// Can additionally manage the application, e.g. users, API keys and withdrawals.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Trader => "trader",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let role = match s {
+            "viewer" => Role::Viewer,
+            "trader" => Role::Trader,
+            "admin" => Role::Admin,
+            _ => bail!("Unknown role: {s}"),
+        };
+
+        Ok(role)
+    }
+}
+
+/// A request guard that only lets through users with at least the [`Role::Trader`] role.
+#[derive(Debug, Clone)]
+pub struct Trader(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Trader {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Trader, Self::Error> {
+        require_role(request, Role::Trader).await.map(Trader)
+    }
+}
+
+/// A request guard that only lets through users with the [`Role::Admin`] role.
+#[derive(Debug, Clone)]
+pub struct Admin(pub User);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Admin, Self::Error> {
+        require_role(request, Role::Admin).await.map(Admin)
+    }
+}
+
+async fn require_role(request: &Request<'_>, role: Role) -> Outcome<User, Error> {
+    let user = match request.guard::<User>().await {
+        Success(user) => user,
+        Failure(x) => return Failure(x),
+        Forward(x) => return Forward(x),
+    };
+
+    if user.role >= role {
+        Success(user)
+    } else {
+        tracing::debug!(username = %user.username, %role, "User lacks required role");
+        Failure((Status::Forbidden, Error::Forbidden))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered_by_privileges() {
+        assert!(Role::Viewer < Role::Trader);
+        assert!(Role::Trader < Role::Admin);
+    }
+
+    #[test]
+    fn role_roundtrips_through_string() {
+        for role in [Role::Viewer, Role::Trader, Role::Admin] {
+            assert_eq!(role.to_string().parse::<Role>().unwrap(), role);
+        }
+
+        assert!("superuser".parse::<Role>().is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Time-based one-time passwords (TOTP) as second factor, see [RFC 6238](https://www.rfc-editor.org/rfc/rfc6238).
+//!
+//! Codes are derived with HMAC-SHA1, the only algorithm common authenticator apps support.
+
+use crate::api_key::hash_api_key;
+use anyhow::Context;
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use hmac::Hmac;
+use hmac::Mac;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+use sha1::Sha1;
+
+/// Duration of a time step in seconds, i.e. how long a code is valid.
+pub const TOTP_STEP_SECS: u64 = 30;
+
+const TOTP_DIGITS: u32 = 6;
+
+const SECRET_LENGTH: usize = 20;
+
+/// Number of time steps before and after the current one that are still accepted, to allow for
+/// clock drift between server and authenticator.
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+
+const ISSUER: &str = "ItchySats";
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Everything a user needs to add the secret to their authenticator app.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TotpEnrolment {
+    pub secret: String,
+    /// `otpauth://` URI, usually rendered as QR code.
+    pub provisioning_uri: String,
+}
+
+impl TotpEnrolment {
+    pub(crate) fn new(username: &str) -> Self {
+        let secret = generate_secret();
+        let provisioning_uri = provisioning_uri(&secret, username);
+
+        Self {
+            secret,
+            provisioning_uri,
+        }
+    }
+}
+
+/// Generate a random base32 encoded secret.
+fn generate_secret() -> String {
+    let secret = rand::thread_rng().gen::<[u8; SECRET_LENGTH]>();
+    BASE32_NOPAD.encode(&secret)
+}
+
+fn provisioning_uri(secret: &str, username: &str) -> String {
+    let label = percent_encode(&format!("{ISSUER}:{username}"));
+
+    format!(
+        "otpauth://totp/{label}?secret={secret}&issuer={ISSUER}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP_SECS}"
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b':' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// Check the code against the secret at the given unix timestamp.
+///
+/// Returns the time step the code belongs to, which has to be recorded to prevent the code from
+/// being used again.
+pub(crate) fn verify_code(secret: &str, code: &str, unix_timestamp: u64) -> Result<Option<u64>> {
+    let secret = BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .context("Invalid TOTP secret")?;
+    let code = match code.trim().parse::<u32>() {
+        Ok(code) => code,
+        Err(_) => return Ok(None),
+    };
+
+    let current_step = unix_timestamp / TOTP_STEP_SECS;
+    let matching_step = (current_step.saturating_sub(ALLOWED_DRIFT_STEPS)
+        ..=current_step + ALLOWED_DRIFT_STEPS)
+        .find(|step| hotp(&secret, *step, TOTP_DIGITS) == code);
+
+    Ok(matching_step)
+}
+
+/// HMAC-based one-time password, see [RFC 4226](https://www.rfc-editor.org/rfc/rfc4226).
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC to accept keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([
+        hash[offset],
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]) & 0x7fff_ffff;
+
+    truncated % 10u32.pow(digits)
+}
+
+/// Generate single-use codes that can be used instead of a TOTP code, e.g. if the
+/// authenticator was lost.
+pub(crate) fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(RECOVERY_CODE_LENGTH)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect()
+        })
+        .collect()
+}
+
+/// Only the hash of recovery codes is stored, like for API keys.
+pub(crate) fn hash_recovery_code(code: &str) -> String {
+    hash_api_key(&code.trim().to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from RFC 6238, Appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn hotp_matches_rfc_6238_test_vectors() {
+        for (time, expected) in [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+        ] {
+            assert_eq!(hotp(RFC_SECRET, time / TOTP_STEP_SECS, 8), expected);
+        }
+    }
+
+    #[test]
+    fn code_is_accepted_within_allowed_drift() {
+        let secret = generate_secret();
+        let decoded = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+        let now = 1_650_000_000;
+        let step = now / TOTP_STEP_SECS;
+
+        let code = format!("{:06}", hotp(&decoded, step, TOTP_DIGITS));
+        let previous_code = format!("{:06}", hotp(&decoded, step - 1, TOTP_DIGITS));
+        let old_code = format!("{:06}", hotp(&decoded, step - 2, TOTP_DIGITS));
+
+        assert_eq!(verify_code(&secret, &code, now).unwrap(), Some(step));
+        assert_eq!(
+            verify_code(&secret, &previous_code, now).unwrap(),
+            Some(step - 1)
+        );
+        assert_eq!(verify_code(&secret, &old_code, now).unwrap(), None);
+        assert_eq!(verify_code(&secret, "not a code", now).unwrap(), None);
+    }
+
+    #[test]
+    fn provisioning_uri_contains_escaped_label_and_secret() {
+        let uri = provisioning_uri("SECRET", "alice smith");
+
+        assert_eq!(
+            uri,
+            "otpauth://totp/ItchySats:alice%20smith?secret=SECRET&issuer=ItchySats&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_code_hash_ignores_case_and_whitespace() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&format!(" {} ", codes[0].to_uppercase()))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::auth::rand_string;
 use crate::error::Error;
+use crate::role::Role;
 use anyhow::Result;
 use argon2::verify_encoded;
 use rocket::http::Status;
@@ -13,10 +14,16 @@ use serde::Serialize;
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Hash, PartialOrd, Ord)]
 pub struct User {
     pub id: u32,
+    pub username: String,
+    pub role: Role,
     #[serde(skip_serializing)]
     pub password: String,
     pub auth_key: String,
     pub first_login: bool,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    /// Whether a TOTP code is required in addition to the password.
+    pub totp_enabled: bool,
 }
 
 #[rocket::async_trait]
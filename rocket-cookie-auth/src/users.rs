@@ -1,11 +1,18 @@
+use crate::api_key::hash_api_key;
+use crate::api_key::ApiKey;
 use crate::auth::rand_string;
 use crate::error::Error;
 use crate::forms::Login;
 use crate::session::Session;
 use crate::session::SessionManager;
+use crate::totp;
+use crate::totp::generate_recovery_codes;
+use crate::totp::hash_recovery_code;
+use crate::totp::TotpEnrolment;
 use crate::user::verify_password;
 use crate::user::User;
 use crate::Database;
+use crate::DEFAULT_USERNAME;
 use anyhow::Context;
 use anyhow::Result;
 
@@ -34,25 +41,26 @@ impl Users {
     }
 
     pub(crate) async fn login(&self, form: &Login) -> Result<User, Error> {
+        let username = form.username.as_deref().unwrap_or(DEFAULT_USERNAME);
         let user = self
             .db
-            .load_user()
+            .load_user(username.to_owned())
             .await
             .map_err(Error::Other)?
             .context(Error::UserNotFound)?;
 
         let user_pwd = &user.password;
-        if verify_password(user_pwd, form.password.as_str())? {
-            let auth_key = self.set_auth_key(user.id);
-            Ok(User {
-                id: user.id,
-                password: user.password,
-                auth_key,
-                first_login: user.first_login,
-            })
-        } else {
-            Err(Error::Unauthorized)
+        if !verify_password(user_pwd, form.password.as_str())? {
+            return Err(Error::Unauthorized);
+        }
+
+        if user.totp_enabled {
+            self.verify_second_factor(&user, form.totp.as_deref())
+                .await?;
         }
+
+        let auth_key = self.set_auth_key(user.id);
+        Ok(User { auth_key, ..user })
     }
 
     pub(crate) fn logout(&self, session: &Session) -> Result<()> {
@@ -69,12 +77,139 @@ impl Users {
         key
     }
 
-    pub async fn get_by_id(&self) -> Result<Option<User>> {
-        let maybe_user = self.db.load_user().await?;
+    pub(crate) async fn authenticate_api_key(&self, key: &str) -> Result<ApiKey, Error> {
+        let api_key = self
+            .db
+            .load_api_key(hash_api_key(key))
+            .await
+            .map_err(Error::Other)?
+            .ok_or(Error::Unauthorized)?;
+
+        Ok(api_key)
+    }
+
+    pub async fn get_by_id(&self, id: u32) -> Result<Option<User>> {
+        let maybe_user = self.db.load_user_by_id(id).await?;
         Ok(maybe_user)
     }
     pub async fn update_user(&self, user: User) -> Result<()> {
-        self.db.update_password(user.password).await?;
+        self.db.update_password(user.id, user.password).await?;
+        Ok(())
+    }
+
+    /// Generate a new TOTP secret for the user.
+    ///
+    /// Two-factor authentication only becomes mandatory once a code of the new secret was
+    /// confirmed through [`Users::enable_totp`].
+    pub async fn enroll_totp(&self, user_id: u32) -> Result<TotpEnrolment, Error> {
+        let user = self.load_existing_user(user_id).await?;
+        if user.totp_enabled {
+            return Err(Error::TotpAlreadyEnabled);
+        }
+
+        let enrolment = TotpEnrolment::new(&user.username);
+        self.db
+            .update_totp(user.id, Some(enrolment.secret.clone()), false)
+            .await?;
+
+        Ok(enrolment)
+    }
+
+    /// Enable two-factor authentication after verifying a code of the enrolled secret.
+    ///
+    /// Returns the recovery codes, which are only shown to the user this one time.
+    pub async fn enable_totp(&self, user_id: u32, code: &str) -> Result<Vec<String>, Error> {
+        let user = self.load_existing_user(user_id).await?;
+        if user.totp_enabled {
+            return Err(Error::TotpAlreadyEnabled);
+        }
+        let secret = user.totp_secret.ok_or(Error::TotpNotEnrolled)?;
+
+        self.verify_totp_code(user.id, &secret, code).await?;
+
+        let recovery_codes = generate_recovery_codes();
+        self.db
+            .replace_recovery_codes(
+                user.id,
+                recovery_codes
+                    .iter()
+                    .map(|code| hash_recovery_code(code))
+                    .collect(),
+            )
+            .await?;
+        self.db.update_totp(user.id, Some(secret), true).await?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Disable two-factor authentication, which requires either a TOTP or a recovery code.
+    pub async fn disable_totp(&self, user_id: u32, code: &str) -> Result<(), Error> {
+        let user = self.load_existing_user(user_id).await?;
+        if !user.totp_enabled {
+            return Err(Error::TotpNotEnrolled);
+        }
+
+        self.verify_second_factor(&user, Some(code)).await?;
+
+        self.db.update_totp(user.id, None, false).await?;
+        self.db.replace_recovery_codes(user.id, Vec::new()).await?;
+
         Ok(())
     }
+
+    /// Require a fresh TOTP code for sensitive operations, e.g. changing the password.
+    ///
+    /// Does nothing if the user did not enable two-factor authentication.
+    pub async fn verify_fresh_totp(&self, user_id: u32, code: Option<&str>) -> Result<(), Error> {
+        let user = self.load_existing_user(user_id).await?;
+        if !user.totp_enabled {
+            return Ok(());
+        }
+
+        let code = code.ok_or(Error::TotpRequired)?;
+        let secret = user.totp_secret.context("Missing TOTP secret")?;
+
+        self.verify_totp_code(user.id, &secret, code).await
+    }
+
+    /// Accepts either a TOTP code or one of the user's recovery codes.
+    async fn verify_second_factor(&self, user: &User, code: Option<&str>) -> Result<(), Error> {
+        let code = code.ok_or(Error::TotpRequired)?;
+        let secret = user.totp_secret.as_deref().context("Missing TOTP secret")?;
+
+        match self.verify_totp_code(user.id, secret, code).await {
+            Err(Error::InvalidTotp) => {
+                let used = self
+                    .db
+                    .use_recovery_code(user.id, hash_recovery_code(code))
+                    .await?;
+
+                if used {
+                    tracing::info!(user_id = %user.id, "Logged in with recovery code");
+                    Ok(())
+                } else {
+                    Err(Error::InvalidTotp)
+                }
+            }
+            result => result,
+        }
+    }
+
+    async fn verify_totp_code(&self, user_id: u32, secret: &str, code: &str) -> Result<(), Error> {
+        let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+        let step = totp::verify_code(secret, code, now)?.ok_or(Error::InvalidTotp)?;
+
+        if !self.db.use_totp_step(user_id, step).await? {
+            return Err(Error::InvalidTotp);
+        }
+
+        Ok(())
+    }
+
+    async fn load_existing_user(&self, user_id: u32) -> Result<User, Error> {
+        self.db
+            .load_user_by_id(user_id)
+            .await?
+            .ok_or(Error::UserNotFound)
+    }
 }
@@ -2,7 +2,9 @@ pub mod catchers;
 pub mod cli;
 pub mod fairings;
 pub mod logger;
+pub mod routes;
 mod to_sse_event;
+pub mod ws;
 
 pub use crate::to_sse_event::*;
 
@@ -0,0 +1,107 @@
+//! Routes which are served by both maker and taker.
+
+use anyhow::Result;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::FundingFeeLedgerEntry;
+use model::FundingRate;
+use model::OrderId;
+use model::Timestamp;
+use rocket::request::FromParam;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use rocket_cookie_auth::user::User;
+use serde::Serialize;
+use tracing::instrument;
+
+#[derive(Debug, Copy, Clone, strum_macros::Display)]
+pub enum ContractSymbol {
+    BtcUsd,
+    EthUsd,
+}
+
+impl From<ContractSymbol> for model::ContractSymbol {
+    fn from(symbol: ContractSymbol) -> Self {
+        match symbol {
+            ContractSymbol::BtcUsd => model::ContractSymbol::BtcUsd,
+            ContractSymbol::EthUsd => model::ContractSymbol::EthUsd,
+        }
+    }
+}
+
+impl<'r> FromParam<'r> for ContractSymbol {
+    type Error = anyhow::Error;
+
+    fn from_param(param: &'r str) -> Result<Self, Self::Error> {
+        match param.to_lowercase().as_str() {
+            "btcusd" => Ok(ContractSymbol::BtcUsd),
+            "ethusd" => Ok(ContractSymbol::EthUsd),
+            _ => anyhow::bail!("Unknown contract symbol provided: {param}"),
+        }
+    }
+}
+
+#[rocket::get("/cfd/<order_id>/fees")]
+#[instrument(name = "GET /cfd/<order_id>/fees", skip(db, _user), err)]
+pub async fn get_funding_fees(
+    order_id: Uuid,
+    db: &State<sqlite_db::Connection>,
+    _user: User,
+) -> Result<Json<Vec<FundingFeeLedgerEntry>>, HttpApiProblem> {
+    let ledger = db
+        .load_funding_fee_ledger(OrderId::from(order_id))
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not load funding fees")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(ledger))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishedFundingRates {
+    funding_rate_long: FundingRate,
+    funding_rate_short: FundingRate,
+    published_at: Timestamp,
+}
+
+/// The funding rates published by the maker, latest first.
+///
+/// The taker records the funding rates of the offers it receives, hence its history only starts
+/// once it connected to the maker.
+#[rocket::get("/<symbol>/funding-rates")]
+#[instrument(name = "GET /<symbol>/funding-rates", skip(db, _user), err)]
+pub async fn get_funding_rate_history(
+    symbol: Result<ContractSymbol>,
+    db: &State<sqlite_db::Connection>,
+    _user: User,
+) -> Result<Json<Vec<PublishedFundingRates>>, HttpApiProblem> {
+    let symbol = symbol.map_err(|e| {
+        HttpApiProblem::new(StatusCode::BAD_REQUEST)
+            .title("Unknown ContractSymbol provided")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let history = db
+        .load_funding_rate_history(symbol.into())
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not load funding rates")
+                .detail(format!("{e:#}"))
+        })?;
+
+    let history = history
+        .into_iter()
+        .map(|record| PublishedFundingRates {
+            funding_rate_long: record.funding_rate_long,
+            funding_rate_short: record.funding_rate_short,
+            published_at: record.timestamp,
+        })
+        .collect();
+
+    Ok(Json(history))
+}
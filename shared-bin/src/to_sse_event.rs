@@ -61,30 +61,35 @@ impl From<(Network, &daemon::bdk::TransactionDetails)> for TransactionDetails {
     }
 }
 
+impl From<&model::WalletInfo> for WalletInfo {
+    fn from(wallet_info: &model::WalletInfo) -> Self {
+        let transaction_details = wallet_info
+            .transactions
+            .iter()
+            .map(|tx| (wallet_info.network, tx).into())
+            .collect();
+
+        WalletInfo {
+            balance: wallet_info.balance,
+            address: wallet_info.address.to_string(),
+            last_updated_at: wallet_info.last_updated_at,
+            transactions: transaction_details,
+        }
+    }
+}
+
 impl ToSseEvent for Option<model::WalletInfo> {
     fn to_sse_event(&self) -> Event {
-        let wallet_info = self.as_ref().map(|wallet_info| {
-            let transaction_details = wallet_info
-                .transactions
-                .iter()
-                .map(|tx| (wallet_info.network, tx).into())
-                .collect();
-
-            WalletInfo {
-                balance: wallet_info.balance,
-                address: wallet_info.address.to_string(),
-                last_updated_at: wallet_info.last_updated_at,
-                transactions: transaction_details,
-            }
-        });
+        let wallet_info = self.as_ref().map(WalletInfo::from);
 
         Event::json(&wallet_info).event("wallet")
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ConnectionStatus {
     online: bool,
+    address: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -93,12 +98,24 @@ pub enum ConnectionCloseReason {
     TakerVersionOutdated,
 }
 
+impl From<online_status::ConnectionStatus> for ConnectionStatus {
+    fn from(status: online_status::ConnectionStatus) -> Self {
+        match status {
+            online_status::ConnectionStatus::Online { address } => ConnectionStatus {
+                online: true,
+                address: Some(address.to_string()),
+            },
+            online_status::ConnectionStatus::Offline => ConnectionStatus {
+                online: false,
+                address: None,
+            },
+        }
+    }
+}
+
 impl ToSseEvent for online_status::ConnectionStatus {
     fn to_sse_event(&self) -> Event {
-        let connected = match self {
-            online_status::ConnectionStatus::Online => ConnectionStatus { online: true },
-            online_status::ConnectionStatus::Offline => ConnectionStatus { online: false },
-        };
+        let connected = ConnectionStatus::from(self.clone());
 
         Event::json(&connected).event("maker_status")
     }
@@ -0,0 +1,593 @@
+//! WebSocket API for streaming updates to programmatic clients.
+//!
+//! In contrast to the SSE `/feed`, clients subscribe to the topics they are interested in and
+//! receive updates of individual CFDs instead of the full list of CFDs upon every change. The
+//! same socket can be used to send commands to the daemon.
+//!
+//! Clients authenticate with an API key, passed in the `X-Api-Key` header, as bearer token in
+//! the `Authorization` header or, for browser clients, in the `api_key` query parameter.
+//!
+//! All messages are JSON encoded text frames tagged by `type`, e.g.:
+//!
+//! ```json
+//! {"type": "subscribe", "topics": ["cfds", "quotes"]}
+//! {"type": "command", "id": 1, "command": {"command": "cfd_action", "order_id": "...", "action": "commit"}}
+//! ```
+
+use crate::ConnectionStatus;
+use crate::WalletInfo;
+use anyhow::Context;
+use anyhow::Result;
+use async_trait::async_trait;
+use daemon::online_status;
+use daemon::projection::Cfd;
+use daemon::projection::CfdOffer;
+use daemon::projection::FeedReceivers;
+use daemon::projection::LatestQuotes;
+use daemon::projection::MakerOffers;
+use daemon::projection::Quote;
+use futures::SinkExt;
+use futures::StreamExt;
+use http_api_problem::HttpApiProblem;
+use model::ContractSymbol;
+use model::OrderId;
+use rocket_cookie_auth::api_key::hash_api_key;
+use rocket_cookie_auth::api_key::ApiKey;
+use rocket_cookie_auth::api_key::API_KEY_HEADER;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::watch;
+use tokio_extras::Tasks;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use tokio_tungstenite::tungstenite::handshake::server::Response;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+const API_KEY_QUERY_PARAM: &str = "api_key";
+
+/// Topics a client can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Topic {
+    #[serde(rename = "cfds")]
+    Cfds,
+    #[serde(rename = "offers.btcusd")]
+    BtcUsdOffers,
+    #[serde(rename = "offers.ethusd")]
+    EthUsdOffers,
+    #[serde(rename = "quotes")]
+    Quotes,
+    #[serde(rename = "wallet")]
+    Wallet,
+    /// Connection status to the maker, only available on the taker.
+    #[serde(rename = "connection_status")]
+    ConnectionStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(bound = "C: DeserializeOwned")]
+enum ClientMessage<C> {
+    Subscribe { topics: Vec<Topic> },
+    Unsubscribe { topics: Vec<Topic> },
+    Command { id: u64, command: C },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed {
+        topics: Vec<Topic>,
+    },
+    /// The full list of CFDs, sent upon subscribing to [`Topic::Cfds`].
+    Cfds {
+        cfds: &'a [Cfd],
+    },
+    CfdUpdated {
+        cfd: &'a Cfd,
+    },
+    CfdRemoved {
+        order_id: OrderId,
+    },
+    Offers {
+        symbol: ContractSymbol,
+        long: &'a Option<CfdOffer>,
+        short: &'a Option<CfdOffer>,
+    },
+    Quote {
+        symbol: ContractSymbol,
+        quote: Quote,
+    },
+    Wallet {
+        wallet: Option<WalletInfo>,
+    },
+    ConnectionStatus {
+        status: ConnectionStatus,
+    },
+    CommandResult {
+        id: u64,
+        error: Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Executes commands that clients send over the socket.
+#[async_trait]
+pub trait CommandHandler: Send + Sync + 'static {
+    type Command: DeserializeOwned + Send;
+
+    /// Execute the command on behalf of the client authenticated with the given API key.
+    async fn handle(&self, api_key: &ApiKey, command: Self::Command) -> Result<()>;
+}
+
+/// Convert the error returned by an HTTP route into an error for a [`CommandHandler`].
+pub fn problem_to_error(problem: HttpApiProblem) -> anyhow::Error {
+    match problem.detail {
+        Some(detail) => anyhow::anyhow!("{}: {detail}", problem.title),
+        None => anyhow::anyhow!("{}", problem.title),
+    }
+}
+
+/// The feeds that can be subscribed to.
+#[derive(Clone)]
+pub struct Feeds {
+    pub quote: watch::Receiver<LatestQuotes>,
+    pub offers: watch::Receiver<MakerOffers>,
+    pub cfds: watch::Receiver<Option<Vec<Cfd>>>,
+    pub wallet: watch::Receiver<Option<model::WalletInfo>>,
+    pub connection_status: Option<watch::Receiver<online_status::ConnectionStatus>>,
+}
+
+impl Feeds {
+    pub fn new(
+        feed_receivers: &FeedReceivers,
+        wallet: watch::Receiver<Option<model::WalletInfo>>,
+        connection_status: Option<watch::Receiver<online_status::ConnectionStatus>>,
+    ) -> Self {
+        Self {
+            quote: feed_receivers.quote.clone(),
+            offers: feed_receivers.offers.clone(),
+            cfds: feed_receivers.cfds.clone(),
+            wallet,
+            connection_status,
+        }
+    }
+}
+
+/// Accept WebSocket connections on the given address until the listener fails.
+pub async fn serve<H>(
+    address: SocketAddr,
+    feeds: Feeds,
+    db: Arc<dyn rocket_cookie_auth::Database>,
+    handler: Arc<H>,
+) -> Result<()>
+where
+    H: CommandHandler,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to bind WebSocket API to {address}"))?;
+
+    tracing::info!("WebSocket API listening on {address}");
+
+    let mut tasks = Tasks::default();
+    loop {
+        let (stream, peer) = listener.accept().await?;
+
+        let feeds = feeds.clone();
+        let db = db.clone();
+        let handler = handler.clone();
+        tasks.add(async move {
+            if let Err(e) = handle_connection(stream, feeds, db, handler).await {
+                tracing::debug!(%peer, "WebSocket connection failed: {e:#}");
+            }
+        });
+    }
+}
+
+async fn handle_connection<H>(
+    stream: TcpStream,
+    feeds: Feeds,
+    db: Arc<dyn rocket_cookie_auth::Database>,
+    handler: Arc<H>,
+) -> Result<()>
+where
+    H: CommandHandler,
+{
+    let mut api_key = None;
+    let mut ws = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response| {
+        api_key = extract_api_key(request);
+        Ok::<Response, _>(response)
+    })
+    .await
+    .context("WebSocket handshake failed")?;
+
+    let key_hash = api_key.map(|key| hash_api_key(&key));
+    let authenticated = match &key_hash {
+        Some(key_hash) => db.load_api_key(key_hash.clone()).await?.is_some(),
+        None => false,
+    };
+    let key_hash = match key_hash {
+        Some(key_hash) if authenticated => key_hash,
+        _ => {
+            ws.close(Some(CloseFrame {
+                code: CloseCode::Policy,
+                reason: Cow::Borrowed("Invalid or missing API key"),
+            }))
+            .await?;
+            return Ok(());
+        }
+    };
+
+    Connection::new(ws, feeds, db, key_hash)
+        .run(handler.as_ref())
+        .await
+}
+
+fn extract_api_key(request: &Request) -> Option<String> {
+    let headers = request.headers();
+
+    if let Some(key) = headers.get(API_KEY_HEADER) {
+        return key.to_str().ok().map(ToOwned::to_owned);
+    }
+
+    if let Some(key) = headers
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(key.to_owned());
+    }
+
+    request.uri().query()?.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == API_KEY_QUERY_PARAM).then(|| value.to_owned())
+    })
+}
+
+struct Connection {
+    ws: WebSocketStream<TcpStream>,
+    feeds: Feeds,
+    db: Arc<dyn rocket_cookie_auth::Database>,
+    /// Hash of the API key the client authenticated with.
+    key_hash: String,
+    topics: HashSet<Topic>,
+    /// The CFDs as last sent to the client, used to only send the CFDs that changed.
+    sent_cfds: HashMap<OrderId, Cfd>,
+}
+
+impl Connection {
+    fn new(
+        ws: WebSocketStream<TcpStream>,
+        feeds: Feeds,
+        db: Arc<dyn rocket_cookie_auth::Database>,
+        key_hash: String,
+    ) -> Self {
+        Self {
+            ws,
+            feeds,
+            db,
+            key_hash,
+            topics: HashSet::new(),
+            sent_cfds: HashMap::new(),
+        }
+    }
+
+    async fn run<H>(mut self, handler: &H) -> Result<()>
+    where
+        H: CommandHandler,
+    {
+        loop {
+            select! {
+                message = self.ws.next() => {
+                    let message = match message {
+                        Some(message) => message?,
+                        None => return Ok(()),
+                    };
+
+                    match message {
+                        Message::Text(text) => self.on_client_message(&text, handler).await?,
+                        Message::Close(_) => return Ok(()),
+                        Message::Binary(_) => {
+                            self.send(ServerMessage::Error {
+                                message: "Binary messages are not supported".to_owned(),
+                            })
+                            .await?
+                        }
+                        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+                    }
+                }
+                Ok(()) = self.feeds.cfds.changed() => {
+                    if self.topics.contains(&Topic::Cfds) {
+                        self.send_cfd_updates().await?;
+                    }
+                }
+                Ok(()) = self.feeds.offers.changed() => {
+                    for topic in [Topic::BtcUsdOffers, Topic::EthUsdOffers] {
+                        if self.topics.contains(&topic) {
+                            self.send_offers(topic).await?;
+                        }
+                    }
+                }
+                Ok(()) = self.feeds.quote.changed() => {
+                    if self.topics.contains(&Topic::Quotes) {
+                        self.send_quotes().await?;
+                    }
+                }
+                Ok(()) = self.feeds.wallet.changed() => {
+                    if self.topics.contains(&Topic::Wallet) {
+                        self.send_wallet().await?;
+                    }
+                }
+                Ok(()) = connection_status_changed(&mut self.feeds.connection_status) => {
+                    if self.topics.contains(&Topic::ConnectionStatus) {
+                        self.send_connection_status().await?;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn on_client_message<H>(&mut self, text: &str, handler: &H) -> Result<()>
+    where
+        H: CommandHandler,
+    {
+        let message = match serde_json::from_str::<ClientMessage<H::Command>>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return self
+                    .send(ServerMessage::Error {
+                        message: format!("Invalid message: {e}"),
+                    })
+                    .await;
+            }
+        };
+
+        match message {
+            ClientMessage::Subscribe { topics } => {
+                if topics.contains(&Topic::ConnectionStatus)
+                    && self.feeds.connection_status.is_none()
+                {
+                    return self
+                        .send(ServerMessage::Error {
+                            message: "Topic connection_status is not available".to_owned(),
+                        })
+                        .await;
+                }
+
+                let new_topics = topics
+                    .into_iter()
+                    .filter(|topic| self.topics.insert(*topic))
+                    .collect::<Vec<_>>();
+
+                self.send(ServerMessage::Subscribed {
+                    topics: self.topics.iter().copied().collect(),
+                })
+                .await?;
+
+                for topic in new_topics {
+                    self.send_current_state(topic).await?;
+                }
+            }
+            ClientMessage::Unsubscribe { topics } => {
+                for topic in topics {
+                    self.topics.remove(&topic);
+
+                    if topic == Topic::Cfds {
+                        self.sent_cfds.clear();
+                    }
+                }
+
+                self.send(ServerMessage::Subscribed {
+                    topics: self.topics.iter().copied().collect(),
+                })
+                .await?;
+            }
+            ClientMessage::Command { id, command } => {
+                // The API key is checked again for every command, as it might have been revoked
+                // since the client connected
+                let error = match self.db.load_api_key(self.key_hash.clone()).await {
+                    Ok(Some(api_key)) => handler.handle(&api_key, command).await.err(),
+                    Ok(None) => Some(anyhow::anyhow!("API key has been revoked")),
+                    Err(e) => Some(e.context("Failed to load API key")),
+                }
+                .map(|e| format!("{e:#}"));
+
+                self.send(ServerMessage::CommandResult { id, error })
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn send_current_state(&mut self, topic: Topic) -> Result<()> {
+        match topic {
+            Topic::Cfds => {
+                let cfds = self.feeds.cfds.borrow().clone().unwrap_or_default();
+                self.sent_cfds = cfds.iter().map(|cfd| (cfd.order_id, cfd.clone())).collect();
+
+                self.send(ServerMessage::Cfds { cfds: &cfds }).await
+            }
+            Topic::BtcUsdOffers | Topic::EthUsdOffers => self.send_offers(topic).await,
+            Topic::Quotes => self.send_quotes().await,
+            Topic::Wallet => self.send_wallet().await,
+            Topic::ConnectionStatus => self.send_connection_status().await,
+        }
+    }
+
+    async fn send_cfd_updates(&mut self) -> Result<()> {
+        let cfds = match self.feeds.cfds.borrow().clone() {
+            Some(cfds) => cfds,
+            None => return Ok(()),
+        };
+
+        let Diff { updated, removed } = diff(&mut self.sent_cfds, cfds, |cfd: &Cfd| cfd.order_id);
+
+        for cfd in updated.iter() {
+            self.send(ServerMessage::CfdUpdated { cfd }).await?;
+        }
+        for order_id in removed {
+            self.send(ServerMessage::CfdRemoved { order_id }).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_offers(&mut self, topic: Topic) -> Result<()> {
+        let offers = self.feeds.offers.borrow().clone();
+
+        let message = match topic {
+            Topic::BtcUsdOffers => ServerMessage::Offers {
+                symbol: ContractSymbol::BtcUsd,
+                long: &offers.btcusd_long,
+                short: &offers.btcusd_short,
+            },
+            Topic::EthUsdOffers => ServerMessage::Offers {
+                symbol: ContractSymbol::EthUsd,
+                long: &offers.ethusd_long,
+                short: &offers.ethusd_short,
+            },
+            _ => unreachable!("only called for offer topics"),
+        };
+
+        self.send(message).await
+    }
+
+    async fn send_quotes(&mut self) -> Result<()> {
+        let quotes = self.feeds.quote.borrow().clone();
+
+        for (symbol, quote) in quotes {
+            self.send(ServerMessage::Quote { symbol, quote }).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_wallet(&mut self) -> Result<()> {
+        let wallet = self.feeds.wallet.borrow().as_ref().map(WalletInfo::from);
+
+        self.send(ServerMessage::Wallet { wallet }).await
+    }
+
+    async fn send_connection_status(&mut self) -> Result<()> {
+        let status = match &self.feeds.connection_status {
+            Some(rx) => ConnectionStatus::from(rx.borrow().clone()),
+            None => return Ok(()),
+        };
+
+        self.send(ServerMessage::ConnectionStatus { status }).await
+    }
+
+    async fn send(&mut self, message: ServerMessage<'_>) -> Result<()> {
+        let text = serde_json::to_string(&message)?;
+        self.ws.send(Message::Text(text)).await?;
+
+        Ok(())
+    }
+}
+
+/// Wait for a change of the connection status, never resolves if there is none.
+async fn connection_status_changed(
+    rx: &mut Option<watch::Receiver<online_status::ConnectionStatus>>,
+) -> Result<(), watch::error::RecvError> {
+    match rx {
+        Some(rx) => rx.changed().await,
+        None => futures::future::pending().await,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Diff<K, V> {
+    updated: Vec<V>,
+    removed: Vec<K>,
+}
+
+/// Compute which items changed compared to what was previously sent and remember the new state.
+fn diff<K, V>(previous: &mut HashMap<K, V>, current: Vec<V>, key: impl Fn(&V) -> K) -> Diff<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: PartialEq + Clone,
+{
+    let current = current
+        .into_iter()
+        .map(|value| (key(&value), value))
+        .collect::<HashMap<_, _>>();
+
+    let updated = current
+        .iter()
+        .filter(|(key, value)| previous.get(*key) != Some(*value))
+        .map(|(_, value)| value.clone())
+        .collect();
+
+    let removed = previous
+        .keys()
+        .filter(|key| !current.contains_key(*key))
+        .cloned()
+        .collect();
+
+    *previous = current;
+
+    Diff { updated, removed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn diff_contains_only_changed_and_removed_items() {
+        let mut previous = HashMap::from([(1, (1, "a")), (2, (2, "b")), (3, (3, "c"))]);
+
+        let diff = diff(
+            &mut previous,
+            vec![(1, "a"), (2, "changed"), (4, "new")],
+            |(id, _)| *id,
+        );
+
+        let mut updated = diff.updated;
+        updated.sort();
+        assert_eq!(updated, vec![(2, "changed"), (4, "new")]);
+        assert_eq!(diff.removed, vec![3]);
+        assert_eq!(
+            previous,
+            HashMap::from([(1, (1, "a")), (2, (2, "changed")), (4, (4, "new"))])
+        );
+    }
+
+    #[test]
+    fn deserialize_client_messages() {
+        let subscribe = serde_json::from_str::<ClientMessage<String>>(
+            r#"{"type":"subscribe","topics":["cfds","offers.btcusd","connection_status"]}"#,
+        )
+        .unwrap();
+        let command = serde_json::from_str::<ClientMessage<String>>(
+            r#"{"type":"command","id":42,"command":"commit"}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            subscribe,
+            ClientMessage::Subscribe { topics }
+                if topics == vec![Topic::Cfds, Topic::BtcUsdOffers, Topic::ConnectionStatus]
+        ));
+        assert!(matches!(
+            command,
+            ClientMessage::Command { id: 42, command } if command == "commit"
+        ));
+    }
+}
@@ -0,0 +1,11 @@
+-- Snapshots of CFD aggregates to avoid replaying all events upon loading
+CREATE TABLE IF NOT EXISTS aggregate_snapshots (
+    id integer PRIMARY KEY autoincrement,
+    cfd_id integer NOT NULL,
+    aggregate text NOT NULL,
+    schema_version integer NOT NULL,
+    version integer NOT NULL,
+    data text NOT NULL,
+    FOREIGN KEY (cfd_id) REFERENCES cfds (id) ON DELETE CASCADE,
+    UNIQUE (cfd_id, aggregate)
+);
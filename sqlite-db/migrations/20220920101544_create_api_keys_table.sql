@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS api_keys (
+    id integer PRIMARY KEY autoincrement,
+    name text NOT NULL,
+    key_hash text UNIQUE NOT NULL,
+    created_at integer NOT NULL,
+    revoked_at integer NULL
+);
@@ -0,0 +1,14 @@
+-- Support multiple named users with roles, the existing user becomes the admin
+ALTER TABLE login_details ADD COLUMN username text NOT NULL DEFAULT 'admin';
+ALTER TABLE login_details ADD COLUMN role text NOT NULL DEFAULT 'admin';
+CREATE UNIQUE INDEX IF NOT EXISTS login_details_username ON login_details (username);
+
+-- Actions performed by users, e.g. accepting orders or updating offers
+CREATE TABLE IF NOT EXISTS audit_log (
+    id integer PRIMARY KEY autoincrement,
+    timestamp integer NOT NULL,
+    actor text NOT NULL,
+    action text NOT NULL,
+    details text NOT NULL,
+    succeeded boolean NOT NULL
+);
@@ -0,0 +1,14 @@
+-- Optional TOTP two-factor authentication, only required once enabled
+ALTER TABLE login_details ADD COLUMN totp_secret text;
+ALTER TABLE login_details ADD COLUMN totp_enabled boolean NOT NULL DEFAULT FALSE;
+-- The time step of the last accepted TOTP code, to reject replayed codes
+ALTER TABLE login_details ADD COLUMN totp_last_step integer;
+
+-- Single-use codes that can be used instead of a TOTP code, only the hashes are stored
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id integer PRIMARY KEY autoincrement,
+    user_id integer NOT NULL,
+    code_hash text NOT NULL,
+    used_at integer,
+    FOREIGN KEY (user_id) REFERENCES login_details (id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS blocked_peers (
+    peer_id text PRIMARY KEY NOT NULL,
+    note text NOT NULL,
+    created_at integer NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS allowed_peers (
+    peer_id text PRIMARY KEY NOT NULL,
+    note text NOT NULL,
+    created_at integer NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS connection_history (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    peer_id text NOT NULL,
+    address text NOT NULL,
+    connected_at integer NOT NULL,
+    disconnected_at integer
+);
+
+CREATE INDEX IF NOT EXISTS connection_history_peer_id ON connection_history (peer_id);
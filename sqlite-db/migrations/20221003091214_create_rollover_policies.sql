@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS rollover_policies (
+    order_id text PRIMARY KEY NOT NULL,
+    enabled boolean NOT NULL,
+    max_funding_fee_sat integer,
+    max_rollovers integer,
+    until integer
+);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS funding_fee_ledger (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    order_id text NOT NULL,
+    timestamp integer NOT NULL,
+    funding_rate text NOT NULL,
+    hours_charged integer NOT NULL,
+    fee_sat integer NOT NULL,
+    direction text NOT NULL,
+    balance_sat integer NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS funding_fee_ledger_order_id ON funding_fee_ledger (order_id);
+
+CREATE TABLE IF NOT EXISTS funding_rates (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    contract_symbol text NOT NULL,
+    funding_rate_long text NOT NULL,
+    funding_rate_short text NOT NULL,
+    timestamp integer NOT NULL
+);
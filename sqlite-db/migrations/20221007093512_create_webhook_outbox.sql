@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS webhook_outbox (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    url text NOT NULL,
+    payload text NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    next_attempt_at integer NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_outbox_next_attempt_at ON webhook_outbox (next_attempt_at);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS rollover_rules (
+    id integer PRIMARY KEY CHECK (id = 1),
+    rules text NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS rollover_decisions (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    order_id text NOT NULL,
+    taker_id text NOT NULL,
+    contract_symbol text NOT NULL,
+    accepted boolean NOT NULL,
+    reason text,
+    timestamp integer NOT NULL
+);
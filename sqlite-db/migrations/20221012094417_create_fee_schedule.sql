@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS fee_schedule (
+    id integer PRIMARY KEY CHECK (id = 1),
+    schedule text NOT NULL
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS referrals (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    order_id text UNIQUE NOT NULL,
+    referral_code text NOT NULL,
+    opening_fee_sat integer NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS referrals_referral_code ON referrals (referral_code);
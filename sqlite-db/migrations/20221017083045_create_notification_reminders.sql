@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS expiry_reminders (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    order_id text UNIQUE NOT NULL,
+    expiry integer NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS maker_offline_reminders (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    notified_at integer NOT NULL,
+    maker_online_at integer
+);
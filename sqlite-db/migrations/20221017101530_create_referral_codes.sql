@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS referral_codes (
+    id integer PRIMARY KEY AUTOINCREMENT,
+    referral_code text UNIQUE NOT NULL
+);
@@ -0,0 +1,164 @@
+use crate::models;
+use crate::Connection;
+use anyhow::bail;
+use anyhow::Result;
+use model::Timestamp;
+
+/// An API key that grants access to the programmatic HTTP API.
+///
+/// Only the hash of the key is stored, the key itself is handed out once upon creation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey {
+    pub id: u32,
+    pub name: String,
+    pub created_at: Timestamp,
+}
+
+impl Connection {
+    pub async fn insert_api_key(&self, name: &str, key_hash: &str) -> Result<ApiKey> {
+        let mut conn = self.inner.acquire().await?;
+
+        let created_at = Timestamp::now();
+        let created_at_db = models::Timestamp::from(created_at);
+        let query_result = sqlx::query!(
+            r#"
+            insert into api_keys (
+                name,
+                key_hash,
+                created_at
+            ) values ($1, $2, $3)
+            "#,
+            name,
+            key_hash,
+            created_at_db
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("failed to insert api key");
+        }
+
+        Ok(ApiKey {
+            id: query_result.last_insert_rowid() as u32,
+            name: name.to_owned(),
+            created_at,
+        })
+    }
+
+    /// Load the API key matching the given hash, unless it has been revoked.
+    pub async fn load_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let api_key = sqlx::query!(
+            r#"
+            select
+                id as "id!: u32",
+                name,
+                created_at as "created_at: models::Timestamp"
+            from
+                api_keys
+            where
+                key_hash = $1 and revoked_at is null
+            "#,
+            key_hash
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|row| ApiKey {
+            id: row.id,
+            name: row.name,
+            created_at: row.created_at.into(),
+        });
+
+        Ok(api_key)
+    }
+
+    /// Load all API keys that have not been revoked.
+    pub async fn load_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let api_keys = sqlx::query!(
+            r#"
+            select
+                id as "id!: u32",
+                name,
+                created_at as "created_at: models::Timestamp"
+            from
+                api_keys
+            where
+                revoked_at is null
+            order by
+                id
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| ApiKey {
+            id: row.id,
+            name: row.name,
+            created_at: row.created_at.into(),
+        })
+        .collect();
+
+        Ok(api_keys)
+    }
+
+    /// Revoke the API key with the given id.
+    ///
+    /// Returns `false` if there is no active API key with that id.
+    pub async fn revoke_api_key(&self, id: u32) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+
+        let revoked_at = models::Timestamp::from(Timestamp::now());
+        let query_result = sqlx::query!(
+            r#"
+            update
+                api_keys
+            set
+                revoked_at = $1
+            where
+                id = $2 and revoked_at is null
+            "#,
+            revoked_at,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(query_result.rows_affected() == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_inserted_api_key_then_can_be_loaded_by_hash() {
+        let db = memory().await.unwrap();
+
+        let api_key = db.insert_api_key("bot", "hash").await.unwrap();
+
+        let loaded = db.load_api_key("hash").await.unwrap();
+        assert_eq!(loaded, Some(api_key.clone()));
+
+        let all = db.load_api_keys().await.unwrap();
+        assert_eq!(all, vec![api_key]);
+    }
+
+    #[tokio::test]
+    async fn given_revoked_api_key_then_cannot_be_loaded() {
+        let db = memory().await.unwrap();
+
+        let api_key = db.insert_api_key("bot", "hash").await.unwrap();
+        assert!(db.revoke_api_key(api_key.id).await.unwrap());
+
+        assert_eq!(db.load_api_key("hash").await.unwrap(), None);
+        assert!(db.load_api_keys().await.unwrap().is_empty());
+        assert!(!db.revoke_api_key(api_key.id).await.unwrap());
+    }
+}
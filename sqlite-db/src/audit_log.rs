@@ -0,0 +1,116 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::Timestamp;
+
+/// An action performed through the HTTP API, attributed to the acting user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLogEntry {
+    pub id: u32,
+    pub timestamp: Timestamp,
+    /// The username, or the name of the API key, of whoever performed the action.
+    pub actor: String,
+    pub action: String,
+    /// Free-form description of the action's parameters, e.g. the affected order id.
+    pub details: String,
+    pub succeeded: bool,
+}
+
+impl Connection {
+    pub async fn append_audit_log(
+        &self,
+        actor: &str,
+        action: &str,
+        details: &str,
+        succeeded: bool,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let timestamp = models::Timestamp::from(Timestamp::now());
+        sqlx::query!(
+            r#"
+            insert into audit_log (
+                timestamp,
+                actor,
+                action,
+                details,
+                succeeded
+            ) values ($1, $2, $3, $4, $5)
+            "#,
+            timestamp,
+            actor,
+            action,
+            details,
+            succeeded
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the most recent audit log entries, newest first.
+    pub async fn load_audit_log(&self, limit: u32) -> Result<Vec<AuditLogEntry>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let entries = sqlx::query!(
+            r#"
+            select
+                id as "id!: u32",
+                timestamp as "timestamp: models::Timestamp",
+                actor,
+                action,
+                details,
+                succeeded
+            from
+                audit_log
+            order by
+                id desc
+            limit $1
+            "#,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| AuditLogEntry {
+            id: row.id,
+            timestamp: row.timestamp.into(),
+            actor: row.actor,
+            action: row.action,
+            details: row.details,
+            succeeded: row.succeeded,
+        })
+        .collect();
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn audit_log_is_loaded_newest_first() {
+        let db = memory().await.unwrap();
+
+        db.append_audit_log("alice", "acceptOrder", "order_id=1", true)
+            .await
+            .unwrap();
+        db.append_audit_log("bob", "rejectOrder", "order_id=2", false)
+            .await
+            .unwrap();
+
+        let entries = db.load_audit_log(10).await.unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actor, "bob");
+        assert_eq!(entries[0].action, "rejectOrder");
+        assert!(!entries[0].succeeded);
+        assert_eq!(entries[1].actor, "alice");
+
+        assert_eq!(db.load_audit_log(1).await.unwrap().len(), 1);
+    }
+}
@@ -310,6 +310,7 @@ impl ClosedCfdInputAggregate {
                 dlc,
                 funding_fee,
                 complete_fee,
+                ..
             } => {
                 self.fee_account = match complete_fee {
                     None => self.fee_account.add_funding_fee(funding_fee),
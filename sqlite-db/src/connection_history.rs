@@ -0,0 +1,220 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Context;
+use anyhow::Result;
+use libp2p_core::Multiaddr;
+use model::libp2p::PeerId;
+use model::Timestamp;
+
+/// A single connection of a peer, which is still open if `disconnected_at` is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionHistoryEntry {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+    pub connected_at: Timestamp,
+    pub disconnected_at: Option<Timestamp>,
+}
+
+impl Connection {
+    pub async fn insert_connection_established(
+        &self,
+        peer_id: PeerId,
+        address: &Multiaddr,
+        connected_at: Timestamp,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let peer_id = models::PeerId::from(peer_id);
+        let address = address.to_string();
+        let connected_at = models::Timestamp::from(connected_at);
+        sqlx::query!(
+            r#"
+            insert into connection_history (
+                peer_id,
+                address,
+                connected_at
+            ) values ($1, $2, $3)
+            "#,
+            peer_id,
+            address,
+            connected_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark the open connections of the peer as disconnected.
+    pub async fn update_connection_dropped(
+        &self,
+        peer_id: PeerId,
+        disconnected_at: Timestamp,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let peer_id = models::PeerId::from(peer_id);
+        let disconnected_at = models::Timestamp::from(disconnected_at);
+        sqlx::query!(
+            r#"
+            update connection_history
+            set disconnected_at = $2
+            where peer_id = $1 and disconnected_at is null
+            "#,
+            peer_id,
+            disconnected_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark all open connections as disconnected.
+    ///
+    /// Connections which were still open when the application stopped will never be marked as
+    /// dropped, so this is to be called upon startup.
+    pub async fn close_open_connections(&self, disconnected_at: Timestamp) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let disconnected_at = models::Timestamp::from(disconnected_at);
+        sqlx::query!(
+            r#"
+            update connection_history
+            set disconnected_at = $1
+            where disconnected_at is null
+            "#,
+            disconnected_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the most recent connections, newest first, optionally only for the given peer.
+    pub async fn load_connection_history(
+        &self,
+        peer_id: Option<PeerId>,
+        limit: u32,
+    ) -> Result<Vec<ConnectionHistoryEntry>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let peer_id = peer_id.map(models::PeerId::from);
+        sqlx::query!(
+            r#"
+            select
+                peer_id as "peer_id: models::PeerId",
+                address,
+                connected_at as "connected_at: models::Timestamp",
+                disconnected_at as "disconnected_at: models::Timestamp"
+            from
+                connection_history
+            where
+                $1 is null or peer_id = $1
+            order by
+                id desc
+            limit $2
+            "#,
+            peer_id,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(ConnectionHistoryEntry {
+                peer_id: row.peer_id.into(),
+                address: row
+                    .address
+                    .parse()
+                    .context("Invalid address in connection history")?,
+                connected_at: row.connected_at.into(),
+                disconnected_at: row.disconnected_at.map(Into::into),
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_dropped_connection_then_history_contains_both_times() {
+        let db = memory().await.unwrap();
+        let peer_id = PeerId::random();
+        let address = "/ip4/127.0.0.1/tcp/10000".parse::<Multiaddr>().unwrap();
+
+        db.insert_connection_established(peer_id, &address, Timestamp::new(100))
+            .await
+            .unwrap();
+        db.update_connection_dropped(peer_id, Timestamp::new(160))
+            .await
+            .unwrap();
+        db.insert_connection_established(peer_id, &address, Timestamp::new(200))
+            .await
+            .unwrap();
+
+        let history = db.load_connection_history(Some(peer_id), 10).await.unwrap();
+
+        assert_eq!(
+            history,
+            vec![
+                ConnectionHistoryEntry {
+                    peer_id,
+                    address: address.clone(),
+                    connected_at: Timestamp::new(200),
+                    disconnected_at: None,
+                },
+                ConnectionHistoryEntry {
+                    peer_id,
+                    address,
+                    connected_at: Timestamp::new(100),
+                    disconnected_at: Some(Timestamp::new(160)),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn given_filter_then_only_history_of_peer_is_loaded() {
+        let db = memory().await.unwrap();
+        let (alice, bob) = (PeerId::random(), PeerId::random());
+        let address = "/ip4/127.0.0.1/tcp/10000".parse::<Multiaddr>().unwrap();
+
+        db.insert_connection_established(alice, &address, Timestamp::new(100))
+            .await
+            .unwrap();
+        db.insert_connection_established(bob, &address, Timestamp::new(100))
+            .await
+            .unwrap();
+
+        let alice_history = db.load_connection_history(Some(alice), 10).await.unwrap();
+        let all_history = db.load_connection_history(None, 10).await.unwrap();
+
+        assert_eq!(alice_history.len(), 1);
+        assert_eq!(alice_history[0].peer_id, alice);
+        assert_eq!(all_history.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn given_open_connections_when_closing_all_then_all_are_disconnected() {
+        let db = memory().await.unwrap();
+        let address = "/ip4/127.0.0.1/tcp/10000".parse::<Multiaddr>().unwrap();
+
+        db.insert_connection_established(PeerId::random(), &address, Timestamp::new(100))
+            .await
+            .unwrap();
+        db.close_open_connections(Timestamp::new(300))
+            .await
+            .unwrap();
+
+        let history = db.load_connection_history(None, 10).await.unwrap();
+
+        assert_eq!(history[0].disconnected_at, Some(Timestamp::new(300)));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::libp2p::PeerId;
+use model::ContractSymbol;
+use model::Contracts;
+use model::Price;
+use model::Timestamp;
+
+/// A position opened with a taker, used to compute the taker's trading volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenedPosition {
+    pub contract_symbol: ContractSymbol,
+    pub initial_price: Price,
+    pub quantity: Contracts,
+}
+
+impl Connection {
+    /// Store the maker's opening fee schedule, replacing the previous one.
+    ///
+    /// The schedule is stored as a JSON document, the database does not interpret it.
+    pub async fn save_fee_schedule(&self, schedule: &str) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            insert into fee_schedule (id, schedule) values (1, $1)
+            on conflict (id) do update set schedule = $1
+            "#,
+            schedule
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the maker's opening fee schedule, `None` if it was never stored.
+    pub async fn load_fee_schedule(&self) -> Result<Option<String>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let row = sqlx::query!(
+            r#"
+            select schedule from fee_schedule where id = 1
+            "#
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(row.map(|row| row.schedule))
+    }
+
+    /// Load all positions with the given taker whose contract setup completed at or after
+    /// `since`, including positions that have been closed in the meantime.
+    pub async fn load_positions_opened_since(
+        &self,
+        peer_id: PeerId,
+        since: Timestamp,
+    ) -> Result<Vec<OpenedPosition>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let peer_id = models::PeerId::from(peer_id);
+        let since = models::Timestamp::from(since);
+        sqlx::query!(
+            r#"
+            select
+                cfds.contract_symbol as "contract_symbol!: models::ContractSymbol",
+                cfds.initial_price as "initial_price!: models::Price",
+                cfds.contracts as "quantity!: models::Contracts"
+            from
+                cfds
+            join
+                events on events.cfd_id = cfds.id
+            where
+                cfds.counterparty_peer_id = $1 and
+                events.name = 'ContractSetupCompleted' and
+                cast(events.created_at as integer) >= $2
+            union all
+            select
+                closed_cfds.contract_symbol as contract_symbol,
+                closed_cfds.initial_price as initial_price,
+                cast(closed_cfds.n_contracts as text) as quantity
+            from
+                closed_cfds
+            join
+                event_log on event_log.cfd_id = closed_cfds.id
+            where
+                closed_cfds.counterparty_peer_id = $1 and
+                event_log.name = 'ContractSetupCompleted' and
+                event_log.created_at >= $2
+            "#,
+            peer_id,
+            since
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(OpenedPosition {
+                contract_symbol: row.contract_symbol.into(),
+                initial_price: row.initial_price.into(),
+                quantity: row.quantity.try_into()?,
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use crate::tests::dummy_taker_with_counterparty_peer_id;
+    use model::CfdEvent;
+    use model::EventKind;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_schedule_saved_twice_then_latest_is_loaded() {
+        let db = memory().await.unwrap();
+
+        assert_eq!(db.load_fee_schedule().await.unwrap(), None);
+
+        db.save_fee_schedule(r#"{"a":1}"#).await.unwrap();
+        db.save_fee_schedule(r#"{"a":2}"#).await.unwrap();
+
+        assert_eq!(
+            db.load_fee_schedule().await.unwrap(),
+            Some(r#"{"a":2}"#.to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn given_contract_setup_completed_before_and_after_cutoff_then_only_later_is_loaded() {
+        let db = memory().await.unwrap();
+
+        let old = dummy_taker_with_counterparty_peer_id();
+        let peer_id = old.counterparty_peer_id().unwrap();
+        let new = dummy_taker_with_peer_id(peer_id);
+        let other_taker = dummy_taker_with_counterparty_peer_id();
+
+        for (cfd, timestamp) in [(&old, 100), (&new, 200), (&other_taker, 200)] {
+            db.insert_cfd(cfd).await.unwrap();
+            db.append_event(CfdEvent {
+                timestamp: Timestamp::new(timestamp),
+                id: cfd.id(),
+                event: EventKind::ContractSetupCompleted { dlc: None },
+            })
+            .await
+            .unwrap();
+        }
+
+        let positions = db
+            .load_positions_opened_since(peer_id, Timestamp::new(150))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            positions,
+            vec![OpenedPosition {
+                contract_symbol: new.contract_symbol(),
+                initial_price: new.initial_price(),
+                quantity: new.quantity(),
+            }]
+        );
+    }
+
+    fn dummy_taker_with_peer_id(peer_id: PeerId) -> model::Cfd {
+        let cfd = dummy_taker_with_counterparty_peer_id();
+
+        model::Cfd::new(
+            model::OrderId::default(),
+            model::OfferId::default(),
+            cfd.position(),
+            cfd.initial_price(),
+            cfd.taker_leverage(),
+            cfd.settlement_time_interval_hours(),
+            cfd.role(),
+            cfd.quantity(),
+            cfd.counterparty_network_identity(),
+            Some(peer_id),
+            cfd.opening_fee(),
+            cfd.initial_funding_rate(),
+            cfd.initial_tx_fee_rate(),
+            cfd.contract_symbol(),
+        )
+    }
+}
@@ -0,0 +1,298 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Context;
+use anyhow::Result;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
+use model::ContractSymbol;
+use model::FundingFeeLedgerEntry;
+use model::FundingRate;
+use model::OrderId;
+use model::Timestamp;
+use sqlx::Acquire;
+
+/// The funding rates a maker published for a contract symbol from `timestamp` onwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundingRateRecord {
+    pub contract_symbol: ContractSymbol,
+    pub funding_rate_long: FundingRate,
+    pub funding_rate_short: FundingRate,
+    pub timestamp: Timestamp,
+}
+
+impl Connection {
+    pub async fn insert_funding_fee_ledger_entry(
+        &self,
+        order_id: OrderId,
+        entry: &FundingFeeLedgerEntry,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        let timestamp = models::Timestamp::from(entry.timestamp);
+        let funding_rate = models::FundingRate::from(entry.rate);
+        let hours_charged = i64::try_from(entry.hours_charged).context("Too many hours charged")?;
+        let fee_sat = i64::try_from(entry.fee.as_sat()).context("Funding fee too large")?;
+        let direction = models::FundingFeeDirection::from(entry.direction);
+        let balance_sat = entry.balance.as_sat();
+
+        sqlx::query!(
+            r#"
+            insert into funding_fee_ledger (
+                order_id,
+                timestamp,
+                funding_rate,
+                hours_charged,
+                fee_sat,
+                direction,
+                balance_sat
+            ) values ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            order_id,
+            timestamp,
+            funding_rate,
+            hours_charged,
+            fee_sat,
+            direction,
+            balance_sat
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the funding fees charged for the CFD, in the order they were charged.
+    pub async fn load_funding_fee_ledger(
+        &self,
+        order_id: OrderId,
+    ) -> Result<Vec<FundingFeeLedgerEntry>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        sqlx::query!(
+            r#"
+            select
+                timestamp as "timestamp: models::Timestamp",
+                funding_rate as "funding_rate: models::FundingRate",
+                hours_charged,
+                fee_sat,
+                direction as "direction: models::FundingFeeDirection",
+                balance_sat
+            from
+                funding_fee_ledger
+            where
+                order_id = $1
+            order by id
+            "#,
+            order_id
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(FundingFeeLedgerEntry {
+                timestamp: row.timestamp.into(),
+                rate: row.funding_rate.into(),
+                hours_charged: u64::try_from(row.hours_charged)
+                    .context("Negative hours charged")?,
+                fee: Amount::from_sat(u64::try_from(row.fee_sat).context("Negative funding fee")?),
+                direction: row.direction.into(),
+                balance: SignedAmount::from_sat(row.balance_sat),
+            })
+        })
+        .collect()
+    }
+
+    /// Record the funding rates published for a contract symbol.
+    ///
+    /// Offers are republished frequently, hence the rates are only recorded if they differ from
+    /// the latest ones recorded for the symbol. Returns whether the rates were recorded.
+    pub async fn record_funding_rates(
+        &self,
+        contract_symbol: ContractSymbol,
+        funding_rate_long: FundingRate,
+        funding_rate_short: FundingRate,
+        timestamp: Timestamp,
+    ) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+        let mut db_tx = conn.begin().await?;
+
+        let contract_symbol = models::ContractSymbol::from(contract_symbol);
+        let funding_rate_long = models::FundingRate::from(funding_rate_long);
+        let funding_rate_short = models::FundingRate::from(funding_rate_short);
+        let timestamp = models::Timestamp::from(timestamp);
+
+        let latest = sqlx::query!(
+            r#"
+            select
+                funding_rate_long as "funding_rate_long: models::FundingRate",
+                funding_rate_short as "funding_rate_short: models::FundingRate"
+            from
+                funding_rates
+            where
+                contract_symbol = $1
+            order by id desc
+            limit 1
+            "#,
+            contract_symbol
+        )
+        .fetch_optional(&mut db_tx)
+        .await?;
+
+        if let Some(latest) = latest {
+            if latest.funding_rate_long == funding_rate_long
+                && latest.funding_rate_short == funding_rate_short
+            {
+                return Ok(false);
+            }
+        }
+
+        sqlx::query!(
+            r#"
+            insert into funding_rates (
+                contract_symbol,
+                funding_rate_long,
+                funding_rate_short,
+                timestamp
+            ) values ($1, $2, $3, $4)
+            "#,
+            contract_symbol,
+            funding_rate_long,
+            funding_rate_short,
+            timestamp
+        )
+        .execute(&mut db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Load the history of funding rates published for a contract symbol, latest first.
+    pub async fn load_funding_rate_history(
+        &self,
+        contract_symbol: ContractSymbol,
+    ) -> Result<Vec<FundingRateRecord>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let contract_symbol_db = models::ContractSymbol::from(contract_symbol);
+        let history = sqlx::query!(
+            r#"
+            select
+                funding_rate_long as "funding_rate_long: models::FundingRate",
+                funding_rate_short as "funding_rate_short: models::FundingRate",
+                timestamp as "timestamp: models::Timestamp"
+            from
+                funding_rates
+            where
+                contract_symbol = $1
+            order by id desc
+            "#,
+            contract_symbol_db
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| FundingRateRecord {
+            contract_symbol,
+            funding_rate_long: row.funding_rate_long.into(),
+            funding_rate_short: row.funding_rate_short.into(),
+            timestamp: row.timestamp.into(),
+        })
+        .collect();
+
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use model::FundingFeeDirection;
+    use pretty_assertions::assert_eq;
+    use rust_decimal_macros::dec;
+
+    #[tokio::test]
+    async fn given_ledger_entries_then_loaded_in_order_of_insertion() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let paid = dummy_ledger_entry(FundingFeeDirection::Paid, 500);
+        let received = dummy_ledger_entry(FundingFeeDirection::Received, 200);
+
+        db.insert_funding_fee_ledger_entry(order_id, &paid)
+            .await
+            .unwrap();
+        db.insert_funding_fee_ledger_entry(order_id, &received)
+            .await
+            .unwrap();
+        db.insert_funding_fee_ledger_entry(OrderId::default(), &paid)
+            .await
+            .unwrap();
+
+        let ledger = db.load_funding_fee_ledger(order_id).await.unwrap();
+
+        assert_eq!(ledger, vec![paid, received]);
+    }
+
+    #[tokio::test]
+    async fn given_unchanged_funding_rates_then_only_recorded_once() {
+        let db = memory().await.unwrap();
+        let long = FundingRate::new(dec!(0.0005)).unwrap();
+        let short = FundingRate::new(dec!(-0.0005)).unwrap();
+        let changed_long = FundingRate::new(dec!(0.001)).unwrap();
+
+        let recorded = [
+            (long, Timestamp::new(1)),
+            (long, Timestamp::new(2)),
+            (changed_long, Timestamp::new(3)),
+        ];
+        let mut was_recorded = Vec::new();
+        for (long, timestamp) in recorded {
+            was_recorded.push(
+                db.record_funding_rates(ContractSymbol::BtcUsd, long, short, timestamp)
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        assert_eq!(was_recorded, vec![true, false, true]);
+        assert_eq!(
+            db.load_funding_rate_history(ContractSymbol::BtcUsd)
+                .await
+                .unwrap(),
+            vec![
+                FundingRateRecord {
+                    contract_symbol: ContractSymbol::BtcUsd,
+                    funding_rate_long: changed_long,
+                    funding_rate_short: short,
+                    timestamp: Timestamp::new(3),
+                },
+                FundingRateRecord {
+                    contract_symbol: ContractSymbol::BtcUsd,
+                    funding_rate_long: long,
+                    funding_rate_short: short,
+                    timestamp: Timestamp::new(1),
+                },
+            ]
+        );
+        assert!(db
+            .load_funding_rate_history(ContractSymbol::EthUsd)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    fn dummy_ledger_entry(direction: FundingFeeDirection, fee_sat: u64) -> FundingFeeLedgerEntry {
+        FundingFeeLedgerEntry {
+            timestamp: Timestamp::now(),
+            rate: FundingRate::new(dec!(0.0005)).unwrap(),
+            hours_charged: 24,
+            fee: Amount::from_sat(fee_sat),
+            direction,
+            balance: SignedAmount::from_sat(-300),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::Snapshot;
+use anyhow::Result;
 use model::CfdEvent;
 
 impl crate::CfdAggregate for model::Cfd {
@@ -47,4 +49,12 @@ impl crate::CfdAggregate for model::Cfd {
     fn version(&self) -> u32 {
         self.version()
     }
+
+    fn snapshot(&self) -> Result<Option<Snapshot>> {
+        Ok(Some(Snapshot::new(self)?))
+    }
+
+    fn restore(&self, snapshot: &Snapshot) -> Option<Self> {
+        snapshot.restore()
+    }
 }
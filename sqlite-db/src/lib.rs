@@ -37,15 +37,29 @@ use time::Duration;
 pub use closed::*;
 pub use failed::*;
 use model::EventKind::RolloverCompleted;
+pub use snapshot::Snapshot;
 
+pub mod api_key;
+pub mod audit_log;
 pub mod closed;
+pub mod connection_history;
 pub mod event_log;
 pub mod failed;
+pub mod fee_schedule;
+pub mod funding_fee;
 mod impls;
 mod models;
+pub mod notification_reminders;
+pub mod peer_list;
+pub mod pnl;
+pub mod referral;
 mod rollover;
+mod rollover_policy;
+pub mod rollover_rules;
+mod snapshot;
 pub mod time_to_first_position;
 pub mod user;
+pub mod webhook_outbox;
 
 #[derive(Clone)]
 pub struct Connection {
@@ -265,6 +279,7 @@ impl Connection {
                 dlc: Some(dlc),
                 funding_fee,
                 complete_fee,
+                ..
             } => {
                 rollover::overwrite(
                     &mut db_tx,
@@ -307,8 +322,23 @@ impl Connection {
                 // No cache entry? Load the CFD row. Version will be 0 because we haven't applied
                 // any events, thus all events will be loaded.
                 let cfd = load_cfd_row(&mut db_tx, id).await?;
-
-                C::new(args, cfd)
+                let cfd = C::new(args, cfd);
+
+                // Unless we have a compatible snapshot, in which case only the events after the
+                // snapshot's version will be loaded.
+                match snapshot::load(&mut db_tx, id, aggregate).await? {
+                    Some((snapshot_version, snapshot)) => match cfd.restore(&snapshot) {
+                        Some(restored) if restored.version() == snapshot_version => restored,
+                        _ => {
+                            tracing::debug!(target = "aggregate", order_id = %id, %aggregate, "Discarding incompatible snapshot");
+
+                            snapshot::delete(&mut db_tx, id, aggregate).await?;
+
+                            cfd
+                        }
+                    },
+                    None => cfd,
+                }
             }
             Some(cfd) => {
                 // Got a cache entry: Downcast it to the type at hand.
@@ -328,6 +358,12 @@ impl Connection {
 
         let cfd = events.into_iter().fold(cfd, C::apply);
 
+        if snapshot::is_due(cfd_version, cfd.version()) {
+            if let Err(e) = save_snapshot(&mut db_tx, id, aggregate, &cfd).await {
+                tracing::warn!(order_id = %id, %aggregate, "Failed to save snapshot: {e:#}");
+            }
+        }
+
         self.aggregate_cache
             .insert(cache_key, Box::new(cfd.clone()))
             .await;
@@ -562,6 +598,43 @@ pub trait CfdAggregate: Clone + Send + Sync + 'static {
     fn new(args: Self::CtorArgs, cfd: Cfd) -> Self;
     fn apply(self, event: CfdEvent) -> Self;
     fn version(&self) -> u32;
+
+    /// Capture the state of the aggregate so it can be persisted.
+    ///
+    /// Aggregates that do not support snapshots return `None` and are always rebuilt from all
+    /// events.
+    fn snapshot(&self) -> Result<Option<Snapshot>> {
+        Ok(None)
+    }
+
+    /// Restore the aggregate from a snapshot.
+    ///
+    /// `self` is the aggregate as constructed from the `cfds` table. Returns `None` if the
+    /// snapshot is not compatible with the current version of the aggregate.
+    fn restore(&self, _snapshot: &Snapshot) -> Option<Self> {
+        None
+    }
+}
+
+async fn save_snapshot<C>(
+    conn: &mut SqliteConnection,
+    id: OrderId,
+    aggregate: &str,
+    cfd: &C,
+) -> Result<()>
+where
+    C: CfdAggregate,
+{
+    let snapshot = match cfd.snapshot()? {
+        Some(snapshot) => snapshot,
+        None => return Ok(()),
+    };
+
+    snapshot::save(conn, id, aggregate, cfd.version(), snapshot).await?;
+
+    tracing::trace!(target = "aggregate", order_id = %id, %aggregate, version = %cfd.version(), "Saved snapshot");
+
+    Ok(())
 }
 
 async fn load_cfd_row(conn: &mut SqliteConnection, id: OrderId) -> Result<Cfd, Error> {
@@ -703,7 +776,7 @@ async fn load_cfd_events(
     .collect::<Result<Vec<(i64, i64, CfdEvent)>>>()?;
 
     for (cfd_row_id, event_row_id, event) in events.iter_mut() {
-        if let RolloverCompleted { .. } = event.event {
+        if let RolloverCompleted { hours_charged, .. } = event.event {
             if let Some((dlc, funding_fee, complete_fee)) =
                 rollover::load(&mut *conn, *cfd_row_id, *event_row_id).await?
             {
@@ -711,6 +784,7 @@ async fn load_cfd_events(
                     dlc: Some(dlc),
                     funding_fee,
                     complete_fee,
+                    hours_charged,
                 }
             }
         }
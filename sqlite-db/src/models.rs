@@ -940,6 +940,30 @@ pub enum FeeFlow {
     None,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type)]
+pub enum FundingFeeDirection {
+    Paid,
+    Received,
+}
+
+impl From<model::FundingFeeDirection> for FundingFeeDirection {
+    fn from(direction: model::FundingFeeDirection) -> Self {
+        match direction {
+            model::FundingFeeDirection::Paid => FundingFeeDirection::Paid,
+            model::FundingFeeDirection::Received => FundingFeeDirection::Received,
+        }
+    }
+}
+
+impl From<FundingFeeDirection> for model::FundingFeeDirection {
+    fn from(direction: FundingFeeDirection) -> Self {
+        match direction {
+            FundingFeeDirection::Paid => model::FundingFeeDirection::Paid,
+            FundingFeeDirection::Received => model::FundingFeeDirection::Received,
+        }
+    }
+}
+
 pub fn into_complete_fee(
     complete_fee_flow: Option<FeeFlow>,
     complete_fee: Option<i64>,
@@ -1011,8 +1035,12 @@ impl From<ContractSymbol> for model::ContractSymbol {
 #[derive(Debug)]
 pub struct User {
     pub id: u32,
+    pub username: String,
+    pub role: String,
     pub password: String,
     pub first_login: bool,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
 }
 
 #[cfg(test)]
@@ -0,0 +1,158 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::OrderId;
+use model::Timestamp;
+use std::collections::HashMap;
+
+impl Connection {
+    /// Record that the user was reminded of the expiry of the CFD, replacing a previous reminder.
+    pub async fn upsert_expiry_reminder(&self, order_id: OrderId, expiry: Timestamp) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        let expiry = models::Timestamp::from(expiry);
+        sqlx::query!(
+            r#"
+            insert into expiry_reminders (
+                order_id,
+                expiry
+            ) values ($1, $2)
+            on conflict (order_id) do update set expiry = $2
+            "#,
+            order_id,
+            expiry
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the expiry the user was last reminded of per CFD.
+    pub async fn load_expiry_reminders(&self) -> Result<HashMap<OrderId, Timestamp>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let reminders = sqlx::query!(
+            r#"
+            select
+                order_id as "order_id: models::OrderId",
+                expiry as "expiry: models::Timestamp"
+            from
+                expiry_reminders
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.order_id.into(), row.expiry.into()))
+        .collect();
+
+        Ok(reminders)
+    }
+
+    /// Record that the user was notified about the maker being offline.
+    pub async fn insert_maker_offline_reminder(&self, notified_at: Timestamp) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let notified_at = models::Timestamp::from(notified_at);
+        sqlx::query!(
+            r#"
+            insert into maker_offline_reminders (
+                notified_at
+            ) values ($1)
+            "#,
+            notified_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark the outage the user was notified about as over.
+    pub async fn update_maker_online(&self, maker_online_at: Timestamp) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let maker_online_at = models::Timestamp::from(maker_online_at);
+        sqlx::query!(
+            r#"
+            update maker_offline_reminders
+            set maker_online_at = $1
+            where maker_online_at is null
+            "#,
+            maker_online_at
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether the user was notified about the maker being offline and it has not been online
+    /// since.
+    pub async fn is_maker_offline_notified(&self) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+
+        let row = sqlx::query!(
+            r#"
+            select
+                count(*) as "n!: i64"
+            from
+                maker_offline_reminders
+            where
+                maker_online_at is null
+            "#
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(row.n > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_expiry_reminders_then_latest_expiry_per_cfd_loaded() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let other_order_id = OrderId::default();
+
+        db.upsert_expiry_reminder(order_id, Timestamp::new(100))
+            .await
+            .unwrap();
+        db.upsert_expiry_reminder(other_order_id, Timestamp::new(100))
+            .await
+            .unwrap();
+        db.upsert_expiry_reminder(order_id, Timestamp::new(200))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db.load_expiry_reminders().await.unwrap(),
+            HashMap::from([
+                (order_id, Timestamp::new(200)),
+                (other_order_id, Timestamp::new(100))
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn maker_offline_reminder_is_cleared_once_maker_online() {
+        let db = memory().await.unwrap();
+        assert!(!db.is_maker_offline_notified().await.unwrap());
+
+        db.insert_maker_offline_reminder(Timestamp::new(100))
+            .await
+            .unwrap();
+        assert!(db.is_maker_offline_notified().await.unwrap());
+
+        db.update_maker_online(Timestamp::new(200)).await.unwrap();
+        assert!(!db.is_maker_offline_notified().await.unwrap());
+    }
+}
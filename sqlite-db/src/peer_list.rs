@@ -0,0 +1,237 @@
+use crate::models;
+use crate::Connection;
+use anyhow::bail;
+use anyhow::Result;
+use model::libp2p::PeerId;
+use model::Timestamp;
+
+/// A list of peers the maker treats specially when they connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerList {
+    /// Peers that are not allowed to connect.
+    Blocked,
+    /// Peers that are allowed to connect, if only allowed peers can connect.
+    Allowed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerListEntry {
+    pub peer_id: PeerId,
+    /// Free-form note, e.g. why the peer was blocked.
+    pub note: String,
+    pub created_at: Timestamp,
+}
+
+impl Connection {
+    pub async fn insert_peer_list_entry(
+        &self,
+        list: PeerList,
+        peer_id: PeerId,
+        note: &str,
+    ) -> Result<PeerListEntry> {
+        let mut conn = self.inner.acquire().await?;
+
+        let created_at = Timestamp::now();
+        let peer_id_db = models::PeerId::from(peer_id);
+        let created_at_db = models::Timestamp::from(created_at);
+        let query_result = match list {
+            PeerList::Blocked => {
+                sqlx::query!(
+                    r#"
+                    insert into blocked_peers (
+                        peer_id,
+                        note,
+                        created_at
+                    ) values ($1, $2, $3)
+                    on conflict do nothing
+                    "#,
+                    peer_id_db,
+                    note,
+                    created_at_db
+                )
+                .execute(&mut *conn)
+                .await?
+            }
+            PeerList::Allowed => {
+                sqlx::query!(
+                    r#"
+                    insert into allowed_peers (
+                        peer_id,
+                        note,
+                        created_at
+                    ) values ($1, $2, $3)
+                    on conflict do nothing
+                    "#,
+                    peer_id_db,
+                    note,
+                    created_at_db
+                )
+                .execute(&mut *conn)
+                .await?
+            }
+        };
+
+        if query_result.rows_affected() != 1 {
+            bail!("Peer {peer_id} is already on the {list:?} list");
+        }
+
+        Ok(PeerListEntry {
+            peer_id,
+            note: note.to_owned(),
+            created_at,
+        })
+    }
+
+    pub async fn delete_peer_list_entry(&self, list: PeerList, peer_id: PeerId) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let peer_id_db = models::PeerId::from(peer_id);
+        let query_result = match list {
+            PeerList::Blocked => {
+                sqlx::query!(
+                    r#"
+                    delete from blocked_peers where peer_id = $1
+                    "#,
+                    peer_id_db
+                )
+                .execute(&mut *conn)
+                .await?
+            }
+            PeerList::Allowed => {
+                sqlx::query!(
+                    r#"
+                    delete from allowed_peers where peer_id = $1
+                    "#,
+                    peer_id_db
+                )
+                .execute(&mut *conn)
+                .await?
+            }
+        };
+
+        if query_result.rows_affected() != 1 {
+            bail!("Peer {peer_id} is not on the {list:?} list");
+        }
+
+        Ok(())
+    }
+
+    pub async fn load_peer_list(&self, list: PeerList) -> Result<Vec<PeerListEntry>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let entries = match list {
+            PeerList::Blocked => sqlx::query!(
+                r#"
+                select
+                    peer_id as "peer_id: models::PeerId",
+                    note,
+                    created_at as "created_at: models::Timestamp"
+                from
+                    blocked_peers
+                order by
+                    created_at
+                "#
+            )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| PeerListEntry {
+                peer_id: row.peer_id.into(),
+                note: row.note,
+                created_at: row.created_at.into(),
+            })
+            .collect(),
+            PeerList::Allowed => sqlx::query!(
+                r#"
+                select
+                    peer_id as "peer_id: models::PeerId",
+                    note,
+                    created_at as "created_at: models::Timestamp"
+                from
+                    allowed_peers
+                order by
+                    created_at
+                "#
+            )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| PeerListEntry {
+                peer_id: row.peer_id.into(),
+                note: row.note,
+                created_at: row.created_at.into(),
+            })
+            .collect(),
+        };
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_inserted_peers_then_lists_are_separate() {
+        let db = memory().await.unwrap();
+
+        let blocked = db
+            .insert_peer_list_entry(PeerList::Blocked, PeerId::random(), "spam")
+            .await
+            .unwrap();
+        let allowed = db
+            .insert_peer_list_entry(PeerList::Allowed, PeerId::random(), "otc desk")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            db.load_peer_list(PeerList::Blocked).await.unwrap(),
+            vec![blocked]
+        );
+        assert_eq!(
+            db.load_peer_list(PeerList::Allowed).await.unwrap(),
+            vec![allowed]
+        );
+    }
+
+    #[tokio::test]
+    async fn given_peer_on_list_then_cannot_be_inserted_twice() {
+        let db = memory().await.unwrap();
+        let peer_id = PeerId::random();
+
+        db.insert_peer_list_entry(PeerList::Blocked, peer_id, "spam")
+            .await
+            .unwrap();
+
+        assert!(db
+            .insert_peer_list_entry(PeerList::Blocked, peer_id, "spam")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn given_deleted_peer_then_no_longer_on_list() {
+        let db = memory().await.unwrap();
+        let peer_id = PeerId::random();
+
+        db.insert_peer_list_entry(PeerList::Blocked, peer_id, "spam")
+            .await
+            .unwrap();
+        db.delete_peer_list_entry(PeerList::Blocked, peer_id)
+            .await
+            .unwrap();
+
+        assert!(db
+            .load_peer_list(PeerList::Blocked)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .delete_peer_list_entry(PeerList::Blocked, peer_id)
+            .await
+            .is_err());
+    }
+}
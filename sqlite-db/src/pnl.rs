@@ -0,0 +1,296 @@
+//! Data for accounting the profit and loss of CFDs.
+
+use crate::models;
+use crate::Connection;
+use anyhow::Context;
+use anyhow::Result;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
+use model::libp2p::PeerId;
+use model::ContractSymbol;
+use model::Contracts;
+use model::Leverage;
+use model::OpeningFee;
+use model::OrderId;
+use model::Position;
+use model::Price;
+use model::Role;
+use model::Timestamp;
+
+/// A closed CFD and what we were paid out when it was settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettledCfd {
+    pub order_id: OrderId,
+    pub contract_symbol: ContractSymbol,
+    pub counterparty_peer_id: PeerId,
+    pub position: Position,
+    pub role: Role,
+    pub initial_price: Price,
+    pub taker_leverage: Leverage,
+    pub quantity: Contracts,
+    /// Balance of our fee account, negative if the counterparty owed us fees.
+    pub fees: SignedAmount,
+    pub payout: Amount,
+    pub opened_at: Timestamp,
+    pub settled_at: Timestamp,
+}
+
+/// The opening fee of an open CFD for which contract setup completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpeningFeeRecord {
+    pub order_id: OrderId,
+    pub contract_symbol: ContractSymbol,
+    pub counterparty_peer_id: PeerId,
+    pub opening_fee: OpeningFee,
+    pub timestamp: Timestamp,
+}
+
+/// A funding fee charged for an open or closed CFD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundingFeeRecord {
+    pub order_id: OrderId,
+    pub contract_symbol: ContractSymbol,
+    pub counterparty_peer_id: PeerId,
+    /// Positive if we received the fee, negative if we paid it.
+    pub fee: SignedAmount,
+    pub timestamp: Timestamp,
+}
+
+impl Connection {
+    pub async fn load_settled_cfds(&self) -> Result<Vec<SettledCfd>> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            select
+                closed_cfds.order_id as "order_id!: models::OrderId",
+                closed_cfds.contract_symbol as "contract_symbol!: models::ContractSymbol",
+                closed_cfds.counterparty_peer_id as "counterparty_peer_id!: models::PeerId",
+                closed_cfds.position as "position!: models::Position",
+                closed_cfds.role as "role!: models::Role",
+                closed_cfds.initial_price as "initial_price!: models::Price",
+                closed_cfds.taker_leverage as "taker_leverage!: models::Leverage",
+                cast(closed_cfds.n_contracts as text) as "quantity!: models::Contracts",
+                closed_cfds.fees as "fees!: models::Fees",
+                coalesce(
+                    collaborative_settlement_txs.payout,
+                    closed_cets.payout,
+                    closed_refund_txs.payout
+                ) as "payout: i64",
+                (
+                    select
+                        min(event_log.created_at)
+                    from
+                        event_log
+                    where
+                        event_log.cfd_id = closed_cfds.id and
+                        event_log.name = 'ContractSetupCompleted'
+                ) as "opened_at: i64",
+                (
+                    select
+                        max(event_log.created_at)
+                    from
+                        event_log
+                    where
+                        event_log.cfd_id = closed_cfds.id
+                ) as "settled_at: i64"
+            from
+                closed_cfds
+            left join
+                collaborative_settlement_txs on collaborative_settlement_txs.cfd_id = closed_cfds.id
+            left join
+                closed_cets on closed_cets.cfd_id = closed_cfds.id
+            left join
+                closed_refund_txs on closed_refund_txs.cfd_id = closed_cfds.id
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let order_id: OrderId = row.order_id.into();
+            let payout = row
+                .payout
+                .with_context(|| format!("Closed CFD {order_id} was not settled"))?;
+            let settled_at = row
+                .settled_at
+                .with_context(|| format!("Closed CFD {order_id} has no events"))?;
+
+            Ok(SettledCfd {
+                order_id,
+                contract_symbol: row.contract_symbol.into(),
+                counterparty_peer_id: row.counterparty_peer_id.into(),
+                position: row.position.into(),
+                role: row.role.into(),
+                initial_price: row.initial_price.into(),
+                taker_leverage: row.taker_leverage.into(),
+                quantity: row.quantity.try_into()?,
+                fees: row.fees.into(),
+                payout: Amount::from_sat(u64::try_from(payout).context("Negative payout")?),
+                // CFDs closed before contract setup completed do not exist, fall back to the
+                // settlement in case the event is missing
+                opened_at: Timestamp::new(row.opened_at.unwrap_or(settled_at)),
+                settled_at: Timestamp::new(settled_at),
+            })
+        })
+        .collect()
+    }
+
+    /// Load the opening fees of open CFDs.
+    ///
+    /// Closed CFDs only keep the balance of their fee account, which includes the opening fee.
+    pub async fn load_opening_fees(&self) -> Result<Vec<OpeningFeeRecord>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let opening_fees = sqlx::query!(
+            r#"
+            select
+                cfds.order_id as "order_id!: models::OrderId",
+                cfds.contract_symbol as "contract_symbol!: models::ContractSymbol",
+                cfds.counterparty_peer_id as "counterparty_peer_id!: models::PeerId",
+                cfds.opening_fee as "opening_fee!: models::OpeningFee",
+                cast(events.created_at as integer) as "timestamp!: models::Timestamp"
+            from
+                cfds
+            join
+                events on events.cfd_id = cfds.id
+            where
+                events.name = 'ContractSetupCompleted'
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| OpeningFeeRecord {
+            order_id: row.order_id.into(),
+            contract_symbol: row.contract_symbol.into(),
+            counterparty_peer_id: row.counterparty_peer_id.into(),
+            opening_fee: row.opening_fee.into(),
+            timestamp: row.timestamp.into(),
+        })
+        .collect();
+
+        Ok(opening_fees)
+    }
+
+    /// Load the funding fees charged for all open and closed CFDs.
+    pub async fn load_funding_fees(&self) -> Result<Vec<FundingFeeRecord>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let funding_fees = sqlx::query!(
+            r#"
+            select
+                funding_fee_ledger.order_id as "order_id!: models::OrderId",
+                coalesce(
+                    cfds.contract_symbol,
+                    closed_cfds.contract_symbol
+                ) as "contract_symbol!: models::ContractSymbol",
+                coalesce(
+                    cfds.counterparty_peer_id,
+                    closed_cfds.counterparty_peer_id
+                ) as "counterparty_peer_id!: models::PeerId",
+                case funding_fee_ledger.direction
+                    when 'Received' then funding_fee_ledger.fee_sat
+                    else -funding_fee_ledger.fee_sat
+                end as "fee_sat!: i64",
+                funding_fee_ledger.timestamp as "timestamp!: models::Timestamp"
+            from
+                funding_fee_ledger
+            left join
+                cfds on cfds.order_id = funding_fee_ledger.order_id
+            left join
+                closed_cfds on closed_cfds.order_id = funding_fee_ledger.order_id
+            where
+                cfds.id is not null or closed_cfds.id is not null
+            order by
+                funding_fee_ledger.id
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| FundingFeeRecord {
+            order_id: row.order_id.into(),
+            contract_symbol: row.contract_symbol.into(),
+            counterparty_peer_id: row.counterparty_peer_id.into(),
+            fee: SignedAmount::from_sat(row.fee_sat),
+            timestamp: row.timestamp.into(),
+        })
+        .collect();
+
+        Ok(funding_fees)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use crate::tests::dummy_taker_with_counterparty_peer_id;
+    use model::CfdEvent;
+    use model::EventKind;
+    use model::FundingFeeDirection;
+    use model::FundingFeeLedgerEntry;
+    use model::FundingRate;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_open_cfd_with_funding_fees_then_fees_are_loaded() {
+        let db = memory().await.unwrap();
+
+        let cfd = dummy_taker_with_counterparty_peer_id();
+        let pending = dummy_taker_with_counterparty_peer_id();
+        db.insert_cfd(&cfd).await.unwrap();
+        db.insert_cfd(&pending).await.unwrap();
+        db.append_event(CfdEvent {
+            timestamp: Timestamp::new(100),
+            id: cfd.id(),
+            event: EventKind::ContractSetupCompleted { dlc: None },
+        })
+        .await
+        .unwrap();
+
+        for (fee_sat, direction, timestamp) in [
+            (300, FundingFeeDirection::Received, 200),
+            (100, FundingFeeDirection::Paid, 300),
+        ] {
+            db.insert_funding_fee_ledger_entry(
+                cfd.id(),
+                &FundingFeeLedgerEntry {
+                    timestamp: Timestamp::new(timestamp),
+                    rate: FundingRate::default(),
+                    hours_charged: 8,
+                    fee: Amount::from_sat(fee_sat),
+                    direction,
+                    balance: SignedAmount::ZERO,
+                },
+            )
+            .await
+            .unwrap();
+        }
+
+        let opening_fees = db.load_opening_fees().await.unwrap();
+        let funding_fees = db.load_funding_fees().await.unwrap();
+
+        assert_eq!(
+            opening_fees,
+            vec![OpeningFeeRecord {
+                order_id: cfd.id(),
+                contract_symbol: cfd.contract_symbol(),
+                counterparty_peer_id: cfd.counterparty_peer_id().unwrap(),
+                opening_fee: cfd.opening_fee(),
+                timestamp: Timestamp::new(100),
+            }]
+        );
+        assert_eq!(
+            funding_fees
+                .iter()
+                .map(|record| (record.order_id, record.fee, record.timestamp))
+                .collect::<Vec<_>>(),
+            vec![
+                (cfd.id(), SignedAmount::from_sat(300), Timestamp::new(200)),
+                (cfd.id(), SignedAmount::from_sat(-100), Timestamp::new(300)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Context;
+use anyhow::Result;
+use bdk::bitcoin::Amount;
+use bdk::bitcoin::SignedAmount;
+use model::OpeningFee;
+use model::OrderId;
+use model::ReferralCode;
+
+/// Revenue of the CFDs attributed to a referrer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferrerRevenue {
+    pub referral_code: String,
+    /// Number of CFDs for which contract setup completed.
+    pub n_cfds: u64,
+    pub opening_fees: Amount,
+    /// Funding fees received minus funding fees paid.
+    pub funding_fees: SignedAmount,
+}
+
+impl Connection {
+    /// Register a referral code handed out to a partner.
+    ///
+    /// Registering a code which is already registered has no effect.
+    pub async fn insert_referral_code(&self, referral_code: &ReferralCode) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let referral_code = referral_code.to_string();
+
+        sqlx::query!(
+            r#"
+            insert into referral_codes (
+                referral_code
+            ) values ($1)
+            on conflict (referral_code) do nothing
+            "#,
+            referral_code
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove a registered referral code.
+    ///
+    /// Returns `false` if the code was not registered. CFDs already attributed to the code are
+    /// kept.
+    pub async fn delete_referral_code(&self, referral_code: &ReferralCode) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+
+        let referral_code = referral_code.to_string();
+
+        let query_result = sqlx::query!(
+            r#"
+            delete from
+                referral_codes
+            where
+                referral_code = $1
+            "#,
+            referral_code
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(query_result.rows_affected() == 1)
+    }
+
+    pub async fn load_referral_codes(&self) -> Result<Vec<String>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let referral_codes = sqlx::query!(
+            r#"
+            select
+                referral_code
+            from
+                referral_codes
+            order by
+                referral_code
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| row.referral_code)
+        .collect();
+
+        Ok(referral_codes)
+    }
+
+    pub async fn is_referral_code_registered(&self, referral_code: &ReferralCode) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+
+        let referral_code = referral_code.to_string();
+
+        let row = sqlx::query!(
+            r#"
+            select
+                count(*) as "n!: i64"
+            from
+                referral_codes
+            where
+                referral_code = $1
+            "#,
+            referral_code
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(row.n > 0)
+    }
+
+    /// Attribute the CFD to the partner which referred the taker.
+    pub async fn insert_referral(
+        &self,
+        order_id: OrderId,
+        referral_code: &ReferralCode,
+        opening_fee: OpeningFee,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let opening_fee_sat =
+            i64::try_from(opening_fee.to_inner().as_sat()).context("Opening fee too large")?;
+
+        let order_id = models::OrderId::from(order_id);
+        let referral_code = referral_code.to_string();
+
+        sqlx::query!(
+            r#"
+            insert into referrals (
+                order_id,
+                referral_code,
+                opening_fee_sat
+            ) values ($1, $2, $3)
+            "#,
+            order_id,
+            referral_code,
+            opening_fee_sat
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sum up the fees earned with the CFDs of each referrer.
+    ///
+    /// Only CFDs for which contract setup completed are taken into account, regardless of whether
+    /// they are still open or have been closed already.
+    pub async fn load_referrer_revenue(&self) -> Result<Vec<ReferrerRevenue>> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            select
+                referrals.referral_code as referral_code,
+                count(*) as "n_cfds!: i64",
+                sum(referrals.opening_fee_sat) as "opening_fees_sat!: i64",
+                sum(
+                    coalesce(
+                        (
+                            select
+                                sum(
+                                    case funding_fee_ledger.direction
+                                        when 'Received' then funding_fee_ledger.fee_sat
+                                        else -funding_fee_ledger.fee_sat
+                                    end
+                                )
+                            from
+                                funding_fee_ledger
+                            where
+                                funding_fee_ledger.order_id = referrals.order_id
+                        ),
+                        0
+                    )
+                ) as "funding_fees_sat!: i64"
+            from
+                referrals
+            where
+                referrals.order_id in (
+                    select
+                        cfds.order_id
+                    from
+                        cfds
+                    join
+                        events on events.cfd_id = cfds.id
+                    where
+                        events.name = 'ContractSetupCompleted'
+                    union
+                    select
+                        closed_cfds.order_id
+                    from
+                        closed_cfds
+                    join
+                        event_log on event_log.cfd_id = closed_cfds.id
+                    where
+                        event_log.name = 'ContractSetupCompleted'
+                )
+            group by
+                referrals.referral_code
+            order by
+                referrals.referral_code
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(ReferrerRevenue {
+                referral_code: row.referral_code,
+                n_cfds: u64::try_from(row.n_cfds)?,
+                opening_fees: Amount::from_sat(
+                    u64::try_from(row.opening_fees_sat).context("Negative opening fees")?,
+                ),
+                funding_fees: SignedAmount::from_sat(row.funding_fees_sat),
+            })
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use crate::tests::dummy_cfd;
+    use model::CfdEvent;
+    use model::EventKind;
+    use model::FundingFeeDirection;
+    use model::FundingFeeLedgerEntry;
+    use model::FundingRate;
+    use model::Timestamp;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_referred_cfds_then_revenue_summed_per_referrer() {
+        let db = memory().await.unwrap();
+        let partner = "partner".parse::<ReferralCode>().unwrap();
+
+        let open = insert_cfd(&db, true).await;
+        let other_open = insert_cfd(&db, true).await;
+        let failed = insert_cfd(&db, false).await;
+
+        for order_id in [open, other_open, failed] {
+            db.insert_referral(order_id, &partner, OpeningFee::new(Amount::from_sat(1_000)))
+                .await
+                .unwrap();
+        }
+
+        db.insert_funding_fee_ledger_entry(
+            open,
+            &dummy_ledger_entry(300, FundingFeeDirection::Received),
+        )
+        .await
+        .unwrap();
+        db.insert_funding_fee_ledger_entry(
+            other_open,
+            &dummy_ledger_entry(100, FundingFeeDirection::Paid),
+        )
+        .await
+        .unwrap();
+
+        let revenue = db.load_referrer_revenue().await.unwrap();
+
+        assert_eq!(
+            revenue,
+            vec![ReferrerRevenue {
+                referral_code: "partner".to_owned(),
+                n_cfds: 2,
+                opening_fees: Amount::from_sat(2_000),
+                funding_fees: SignedAmount::from_sat(200),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn given_registered_referral_code_when_deleted_then_no_longer_registered() {
+        let db = memory().await.unwrap();
+        let partner = "partner".parse::<ReferralCode>().unwrap();
+
+        db.insert_referral_code(&partner).await.unwrap();
+        db.insert_referral_code(&partner).await.unwrap();
+
+        assert!(db.is_referral_code_registered(&partner).await.unwrap());
+        assert_eq!(
+            db.load_referral_codes().await.unwrap(),
+            vec!["partner".to_owned()]
+        );
+
+        assert!(db.delete_referral_code(&partner).await.unwrap());
+        assert!(!db.delete_referral_code(&partner).await.unwrap());
+        assert!(!db.is_referral_code_registered(&partner).await.unwrap());
+    }
+
+    async fn insert_cfd(db: &Connection, contract_setup_completed: bool) -> OrderId {
+        let cfd = dummy_cfd();
+        db.insert_cfd(&cfd).await.unwrap();
+
+        if contract_setup_completed {
+            db.append_event(CfdEvent::new(
+                cfd.id(),
+                EventKind::ContractSetupCompleted { dlc: None },
+            ))
+            .await
+            .unwrap();
+        }
+
+        cfd.id()
+    }
+
+    fn dummy_ledger_entry(fee_sat: u64, direction: FundingFeeDirection) -> FundingFeeLedgerEntry {
+        FundingFeeLedgerEntry {
+            timestamp: Timestamp::now(),
+            rate: FundingRate::default(),
+            hours_charged: 8,
+            fee: Amount::from_sat(fee_sat),
+            direction,
+            balance: SignedAmount::ZERO,
+        }
+    }
+}
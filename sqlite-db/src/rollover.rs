@@ -321,6 +321,7 @@ mod tests {
                 dlc: Some(mut dlc),
                 funding_fee,
                 complete_fee,
+                hours_charged,
             } => {
                 dlc.settlement_event_id =
                     BitMexPriceEventId::with_20_digits(settlement_event_timestamp, contract_symbol);
@@ -332,6 +333,7 @@ mod tests {
                         dlc: Some(dlc),
                         funding_fee,
                         complete_fee,
+                        hours_charged,
                     },
                 })
             }
@@ -347,6 +349,7 @@ mod tests {
                 dlc: Some(dlc),
                 funding_fee,
                 complete_fee,
+                ..
             } => (dlc, funding_fee, complete_fee),
             _ => panic!("Expected RolloverCompleted event with DLC"),
         }
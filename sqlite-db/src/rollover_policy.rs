@@ -0,0 +1,220 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Context;
+use anyhow::Result;
+use bdk::bitcoin::Amount;
+use model::EventKind;
+use model::OrderId;
+use model::RolloverPolicy;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+impl Connection {
+    /// Store the policy for automatically rolling over the CFD, replacing a previous one.
+    pub async fn upsert_rollover_policy(
+        &self,
+        order_id: OrderId,
+        policy: &RolloverPolicy,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        let max_funding_fee_sat = policy
+            .max_funding_fee
+            .map(|fee| i64::try_from(fee.as_sat()))
+            .transpose()
+            .context("Funding fee limit too large")?;
+        let until = policy.until.map(OffsetDateTime::unix_timestamp);
+
+        sqlx::query!(
+            r#"
+            insert into rollover_policies (
+                order_id,
+                enabled,
+                max_funding_fee_sat,
+                max_rollovers,
+                until
+            ) values ($1, $2, $3, $4, $5)
+            on conflict (order_id) do update set
+                enabled = $2,
+                max_funding_fee_sat = $3,
+                max_rollovers = $4,
+                until = $5
+            "#,
+            order_id,
+            policy.enabled,
+            max_funding_fee_sat,
+            policy.max_rollovers,
+            until
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the policy for automatically rolling over the CFD.
+    ///
+    /// CFDs without a stored policy are rolled over according to the default policy.
+    pub async fn load_rollover_policy(&self, order_id: OrderId) -> Result<RolloverPolicy> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        let row = sqlx::query!(
+            r#"
+            select
+                enabled,
+                max_funding_fee_sat,
+                max_rollovers as "max_rollovers: u32",
+                until
+            from
+                rollover_policies
+            where
+                order_id = $1
+            "#,
+            order_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match row {
+            Some(row) => rollover_policy(
+                row.enabled,
+                row.max_funding_fee_sat,
+                row.max_rollovers,
+                row.until,
+            ),
+            None => Ok(RolloverPolicy::default()),
+        }
+    }
+
+    /// Load the policies of all CFDs that have one stored.
+    pub async fn load_rollover_policies(&self) -> Result<HashMap<OrderId, RolloverPolicy>> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            select
+                order_id as "order_id: models::OrderId",
+                enabled,
+                max_funding_fee_sat,
+                max_rollovers as "max_rollovers: u32",
+                until
+            from
+                rollover_policies
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let policy = rollover_policy(
+                row.enabled,
+                row.max_funding_fee_sat,
+                row.max_rollovers,
+                row.until,
+            )?;
+
+            Ok((row.order_id.into(), policy))
+        })
+        .collect()
+    }
+
+    /// The number of times the CFD was rolled over.
+    pub async fn count_completed_rollovers(&self, order_id: OrderId) -> Result<u32> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(order_id);
+        let row = sqlx::query!(
+            r#"
+            select
+                count(*) as "rollovers!: u32"
+            from
+                events
+            join
+                cfds c on c.id = events.cfd_id
+            where
+                c.order_id = $1 and events.name = $2
+            "#,
+            order_id,
+            EventKind::ROLLOVER_COMPLETED_EVENT
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok(row.rollovers)
+    }
+}
+
+fn rollover_policy(
+    enabled: bool,
+    max_funding_fee_sat: Option<i64>,
+    max_rollovers: Option<u32>,
+    until: Option<i64>,
+) -> Result<RolloverPolicy> {
+    Ok(RolloverPolicy {
+        enabled,
+        max_funding_fee: max_funding_fee_sat
+            .map(|sat| u64::try_from(sat).map(Amount::from_sat))
+            .transpose()
+            .context("Negative funding fee limit")?,
+        max_rollovers,
+        until: until
+            .map(OffsetDateTime::from_unix_timestamp)
+            .transpose()
+            .context("Invalid end date")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use crate::tests::dummy_cfd;
+    use crate::tests::lock_confirmed;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_no_policy_then_default_policy() {
+        let db = memory().await.unwrap();
+
+        let policy = db.load_rollover_policy(OrderId::default()).await.unwrap();
+
+        assert_eq!(policy, RolloverPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn given_updated_policy_then_latest_policy_is_loaded() {
+        let db = memory().await.unwrap();
+        let order_id = OrderId::default();
+        let policy = RolloverPolicy {
+            enabled: true,
+            max_funding_fee: Some(Amount::from_sat(10_000)),
+            max_rollovers: Some(7),
+            until: Some(OffsetDateTime::from_unix_timestamp(1_667_217_600).unwrap()),
+        };
+
+        db.upsert_rollover_policy(order_id, &RolloverPolicy::default())
+            .await
+            .unwrap();
+        db.upsert_rollover_policy(order_id, &policy).await.unwrap();
+
+        assert_eq!(db.load_rollover_policy(order_id).await.unwrap(), policy);
+        assert_eq!(
+            db.load_rollover_policies().await.unwrap(),
+            HashMap::from([(order_id, policy)])
+        );
+    }
+
+    #[tokio::test]
+    async fn given_cfd_without_rollovers_then_no_completed_rollovers() {
+        let db = memory().await.unwrap();
+        let cfd = dummy_cfd();
+        db.insert_cfd(&cfd).await.unwrap();
+        db.append_event(lock_confirmed(&cfd)).await.unwrap();
+
+        let rollovers = db.count_completed_rollovers(cfd.id()).await.unwrap();
+
+        assert_eq!(rollovers, 0);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::models;
+use crate::Connection;
+use anyhow::Result;
+use model::libp2p::PeerId;
+use model::ContractSymbol;
+use model::OrderId;
+use model::Timestamp;
+
+/// The outcome of evaluating the maker's rollover rules for a proposal of a taker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolloverDecisionRecord {
+    pub order_id: OrderId,
+    pub taker_id: PeerId,
+    pub contract_symbol: ContractSymbol,
+    pub accepted: bool,
+    /// Why the rollover was rejected, `None` if it was accepted.
+    pub reason: Option<String>,
+    pub timestamp: Timestamp,
+}
+
+impl Connection {
+    /// Store the maker's rollover rules, replacing the previous ones.
+    ///
+    /// The rules are stored as a JSON document, the database does not interpret them.
+    pub async fn save_rollover_rules(&self, rules: &str) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            insert into rollover_rules (id, rules) values (1, $1)
+            on conflict (id) do update set rules = $1
+            "#,
+            rules
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the maker's rollover rules, `None` if they were never stored.
+    pub async fn load_rollover_rules(&self) -> Result<Option<String>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let row = sqlx::query!(
+            r#"
+            select rules from rollover_rules where id = 1
+            "#
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(row.map(|row| row.rules))
+    }
+
+    pub async fn insert_rollover_decision(&self, decision: &RolloverDecisionRecord) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let order_id = models::OrderId::from(decision.order_id);
+        let taker_id = models::PeerId::from(decision.taker_id);
+        let contract_symbol = models::ContractSymbol::from(decision.contract_symbol);
+        let reason = decision.reason.as_deref();
+        let timestamp = models::Timestamp::from(decision.timestamp);
+
+        sqlx::query!(
+            r#"
+            insert into rollover_decisions (
+                order_id,
+                taker_id,
+                contract_symbol,
+                accepted,
+                reason,
+                timestamp
+            ) values ($1, $2, $3, $4, $5, $6)
+            "#,
+            order_id,
+            taker_id,
+            contract_symbol,
+            decision.accepted,
+            reason,
+            timestamp
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Load the most recent rollover decisions, latest first.
+    pub async fn load_rollover_decisions(&self, limit: u32) -> Result<Vec<RolloverDecisionRecord>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let decisions = sqlx::query!(
+            r#"
+            select
+                order_id as "order_id: models::OrderId",
+                taker_id as "taker_id: models::PeerId",
+                contract_symbol as "contract_symbol: models::ContractSymbol",
+                accepted,
+                reason,
+                timestamp as "timestamp: models::Timestamp"
+            from
+                rollover_decisions
+            order by id desc
+            limit $1
+            "#,
+            limit
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| RolloverDecisionRecord {
+            order_id: row.order_id.into(),
+            taker_id: row.taker_id.into(),
+            contract_symbol: row.contract_symbol.into(),
+            accepted: row.accepted,
+            reason: row.reason,
+            timestamp: row.timestamp.into(),
+        })
+        .collect();
+
+        Ok(decisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_rules_saved_twice_then_latest_are_loaded() {
+        let db = memory().await.unwrap();
+
+        assert_eq!(db.load_rollover_rules().await.unwrap(), None);
+
+        db.save_rollover_rules(r#"{"a":1}"#).await.unwrap();
+        db.save_rollover_rules(r#"{"a":2}"#).await.unwrap();
+
+        assert_eq!(
+            db.load_rollover_rules().await.unwrap(),
+            Some(r#"{"a":2}"#.to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn given_decisions_then_latest_are_loaded_first() {
+        let db = memory().await.unwrap();
+        let accepted = dummy_decision(None, Timestamp::new(1));
+        let rejected = dummy_decision(Some("symbol wound down"), Timestamp::new(2));
+
+        db.insert_rollover_decision(&accepted).await.unwrap();
+        db.insert_rollover_decision(&rejected).await.unwrap();
+
+        assert_eq!(
+            db.load_rollover_decisions(10).await.unwrap(),
+            vec![rejected.clone(), accepted]
+        );
+        assert_eq!(db.load_rollover_decisions(1).await.unwrap(), vec![rejected]);
+    }
+
+    fn dummy_decision(reason: Option<&str>, timestamp: Timestamp) -> RolloverDecisionRecord {
+        RolloverDecisionRecord {
+            order_id: OrderId::default(),
+            taker_id: PeerId::random(),
+            contract_symbol: ContractSymbol::BtcUsd,
+            accepted: reason.is_none(),
+            reason: reason.map(ToOwned::to_owned),
+            timestamp,
+        }
+    }
+}
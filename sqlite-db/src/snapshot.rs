@@ -0,0 +1,343 @@
+use crate::models;
+use anyhow::Context;
+use anyhow::Result;
+use model::OrderId;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::SqliteConnection;
+
+/// Number of events after which a new snapshot of an aggregate is persisted.
+const SNAPSHOT_INTERVAL: u32 = 50;
+
+/// Version of the snapshot schema, shared by all aggregates.
+///
+/// Snapshots whose fields differ from the fields of the aggregate are discarded regardless of
+/// their version. Bump this if the semantics of an aggregate change without changing its fields.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The serialized state of an aggregate.
+///
+/// If the schema version does not match upon loading, the snapshot fails to deserialize or its
+/// fields differ from the fields of the aggregate, the snapshot is discarded and the aggregate is
+/// rebuilt from all events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    schema_version: u32,
+    data: String,
+}
+
+impl Snapshot {
+    pub fn new<T>(state: &T) -> Result<Self>
+    where
+        T: Serialize,
+    {
+        let data = serde_json::to_string(state).context("Failed to serialize snapshot")?;
+
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
+            data,
+        })
+    }
+
+    /// Deserialize the state captured in the snapshot.
+    ///
+    /// Returns `None` if the snapshot was taken with a different schema version, can no longer be
+    /// deserialized or was taken from an aggregate with different fields. The latter prevents
+    /// fields which were added since, e.g. optional ones, from being silently restored with their
+    /// default value.
+    pub fn restore<T>(&self) -> Option<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        if self.schema_version != SCHEMA_VERSION {
+            tracing::debug!(
+                snapshot_schema_version = %self.schema_version,
+                schema_version = %SCHEMA_VERSION,
+                "Ignoring snapshot with outdated schema"
+            );
+            return None;
+        }
+
+        let restore = || -> Result<Option<T>> {
+            let data = serde_json::from_str::<Value>(&self.data)?;
+            let state = serde_json::from_value::<T>(data.clone())?;
+
+            if !same_fields(&data, &serde_json::to_value(&state)?) {
+                return Ok(None);
+            }
+
+            Ok(Some(state))
+        };
+
+        match restore() {
+            Ok(Some(state)) => Some(state),
+            Ok(None) => {
+                tracing::debug!("Ignoring snapshot with fields that differ from the aggregate");
+                None
+            }
+            Err(e) => {
+                tracing::debug!("Ignoring snapshot that failed to deserialize: {e:#}");
+                None
+            }
+        }
+    }
+}
+
+/// Whether both values consist of objects with the same fields.
+///
+/// Only compares the structure, not the values. Elements of arrays of different length, e.g.
+/// serialized sets, cannot be matched up and are not compared.
+fn same_fields(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(key, a)| match b.get(key) {
+                    Some(b) => same_fields(a, b),
+                    None => false,
+                })
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            a.iter().zip(b).all(|(a, b)| same_fields(a, b))
+        }
+        _ => true,
+    }
+}
+
+/// Whether applying events from `from_version` to `to_version` warrants a new snapshot.
+pub(crate) fn is_due(from_version: u32, to_version: u32) -> bool {
+    from_version / SNAPSHOT_INTERVAL < to_version / SNAPSHOT_INTERVAL
+}
+
+/// Load the latest snapshot of the given aggregate, together with the version it was taken at.
+pub(crate) async fn load(
+    conn: &mut SqliteConnection,
+    id: OrderId,
+    aggregate: &str,
+) -> Result<Option<(u32, Snapshot)>> {
+    let id = models::OrderId::from(id);
+
+    let row = sqlx::query!(
+        r#"
+        select
+            snapshots.schema_version as "schema_version: u32",
+            snapshots.version as "version: u32",
+            snapshots.data
+        from
+            aggregate_snapshots as snapshots
+        join
+            cfds c on c.id = snapshots.cfd_id
+        where
+            c.order_id = $1 and snapshots.aggregate = $2
+        "#,
+        id,
+        aggregate
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let snapshot = row.map(|row| {
+        let snapshot = Snapshot {
+            schema_version: row.schema_version,
+            data: row.data,
+        };
+
+        (row.version, snapshot)
+    });
+
+    Ok(snapshot)
+}
+
+/// Persist a snapshot of the given aggregate, replacing any previous one.
+pub(crate) async fn save(
+    conn: &mut SqliteConnection,
+    id: OrderId,
+    aggregate: &str,
+    version: u32,
+    snapshot: Snapshot,
+) -> Result<()> {
+    let id = models::OrderId::from(id);
+
+    sqlx::query!(
+        r#"
+        insert into aggregate_snapshots (
+            cfd_id,
+            aggregate,
+            schema_version,
+            version,
+            data
+        ) values (
+            (select id from cfds where cfds.order_id = $1),
+            $2, $3, $4, $5
+        )
+        on conflict (cfd_id, aggregate) do update set
+            schema_version = excluded.schema_version,
+            version = excluded.version,
+            data = excluded.data
+        "#,
+        id,
+        aggregate,
+        snapshot.schema_version,
+        version,
+        snapshot.data
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete the snapshot of the given aggregate.
+pub(crate) async fn delete(
+    conn: &mut SqliteConnection,
+    id: OrderId,
+    aggregate: &str,
+) -> Result<()> {
+    let id = models::OrderId::from(id);
+
+    sqlx::query!(
+        r#"
+        delete from
+            aggregate_snapshots
+        where
+            cfd_id = (select id from cfds where cfds.order_id = $1) and
+            aggregate = $2
+        "#,
+        id,
+        aggregate
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use crate::tests::dummy_cfd;
+    use crate::tests::lock_confirmed;
+    use crate::CfdAggregate;
+    use crate::Connection;
+    use model::CfdEvent;
+    use model::EventKind;
+    use model::Timestamp;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn snapshot_is_due_when_crossing_interval() {
+        assert!(!is_due(0, SNAPSHOT_INTERVAL - 1));
+        assert!(is_due(0, SNAPSHOT_INTERVAL));
+        assert!(is_due(SNAPSHOT_INTERVAL - 1, SNAPSHOT_INTERVAL + 1));
+        assert!(!is_due(SNAPSHOT_INTERVAL, SNAPSHOT_INTERVAL * 2 - 1));
+    }
+
+    #[test]
+    fn snapshot_with_different_schema_version_is_ignored() {
+        let snapshot = Snapshot::new(&42u32).unwrap();
+        assert_eq!(snapshot.restore::<u32>(), Some(42));
+
+        let outdated = Snapshot {
+            schema_version: SCHEMA_VERSION + 1,
+            ..snapshot
+        };
+        assert_eq!(outdated.restore::<u32>(), None);
+    }
+
+    #[test]
+    fn snapshot_with_incompatible_data_is_ignored() {
+        let snapshot = Snapshot::new(&"not a number").unwrap();
+
+        assert_eq!(snapshot.restore::<u32>(), None);
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Aggregate {
+        version: u32,
+        nested: Nested,
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Nested {
+        price: Option<u64>,
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct ChangedAggregate {
+        version: u32,
+        nested: ChangedNested,
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct ChangedNested {
+        price: Option<u64>,
+        added: Option<u64>,
+    }
+
+    #[test]
+    fn snapshot_with_changed_fields_is_ignored() {
+        let snapshot = Snapshot::new(&Aggregate {
+            version: 1,
+            nested: Nested { price: Some(42) },
+        })
+        .unwrap();
+
+        assert_eq!(
+            snapshot.restore::<Aggregate>(),
+            Some(Aggregate {
+                version: 1,
+                nested: Nested { price: Some(42) },
+            })
+        );
+        assert_eq!(snapshot.restore::<ChangedAggregate>(), None);
+
+        let snapshot = Snapshot::new(&ChangedAggregate {
+            version: 1,
+            nested: ChangedNested {
+                price: Some(42),
+                added: None,
+            },
+        })
+        .unwrap();
+
+        assert_eq!(snapshot.restore::<Aggregate>(), None);
+    }
+
+    #[tokio::test]
+    async fn given_many_events_when_loading_cfd_then_snapshot_saved_and_restored() {
+        let db = memory().await.unwrap();
+
+        let cfd = dummy_cfd();
+        db.insert_cfd(&cfd).await.unwrap();
+
+        for _ in 0..SNAPSHOT_INTERVAL {
+            db.append_event(lock_confirmed(&cfd)).await.unwrap();
+        }
+
+        let loaded = db.load_open_cfd::<model::Cfd>(cfd.id(), ()).await.unwrap();
+
+        let mut conn = db.inner.acquire().await.unwrap();
+        let (version, snapshot) = load(&mut conn, cfd.id(), std::any::type_name::<model::Cfd>())
+            .await
+            .unwrap()
+            .expect("snapshot to be saved");
+        assert_eq!(version, SNAPSHOT_INTERVAL);
+
+        let restored = <model::Cfd as CfdAggregate>::restore(&cfd, &snapshot).unwrap();
+        assert_eq!(restored, loaded);
+
+        let event = CfdEvent {
+            timestamp: Timestamp::now(),
+            id: cfd.id(),
+            event: EventKind::RevokeConfirmed,
+        };
+        db.append_event(event.clone()).await.unwrap();
+
+        // a fresh connection starts with an empty aggregate cache
+        let db = Connection::new(db.inner.clone());
+        let loaded = db.load_open_cfd::<model::Cfd>(cfd.id(), ()).await.unwrap();
+
+        assert_eq!(loaded, restored.apply(event));
+    }
+}
@@ -1,45 +1,327 @@
+use crate::models;
 use crate::models::User;
 use crate::Connection;
+use anyhow::bail;
 use anyhow::Result;
-
-// we only want to have max 1 user, hence, we hardcode its ID to 1
-const USER_ID: u8 = 1;
+use model::Timestamp;
+use sqlx::Acquire;
 
 impl Connection {
-    pub async fn load_user(self) -> Result<Option<User>> {
+    pub async fn load_user(&self, username: &str) -> Result<Option<User>> {
         let mut conn = self.inner.acquire().await?;
-        let row = sqlx::query!(
+        let user = sqlx::query_as!(
+            User,
             r#"
-            SELECT * from login_details where id = $1
+            SELECT
+                id as "id!: u32",
+                username,
+                role,
+                PASSWORD as password,
+                first_login,
+                totp_secret,
+                totp_enabled
+            from login_details where username = $1
             "#,
-            USER_ID
+            username
         )
         .fetch_optional(&mut *conn)
         .await?;
 
-        match row {
-            Some(row) => Ok(Some(User {
-                id: row.id as u32,
-                password: row.PASSWORD,
-                first_login: row.first_login,
-            })),
-            None => Ok(None),
-        }
+        Ok(user)
     }
 
-    pub async fn update_password(self, password: String) -> Result<()> {
+    pub async fn load_user_by_id(&self, id: u32) -> Result<Option<User>> {
         let mut conn = self.inner.acquire().await?;
-        sqlx::query!(
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT
+                id as "id!: u32",
+                username,
+                role,
+                PASSWORD as password,
+                first_login,
+                totp_secret,
+                totp_enabled
+            from login_details where id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        Ok(user)
+    }
+
+    pub async fn load_users(&self) -> Result<Vec<User>> {
+        let mut conn = self.inner.acquire().await?;
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT
+                id as "id!: u32",
+                username,
+                role,
+                PASSWORD as password,
+                first_login,
+                totp_secret,
+                totp_enabled
+            from login_details order by id
+            "#
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(users)
+    }
+
+    /// Insert a new user that has to change their password upon first login.
+    pub async fn insert_user(&self, username: &str, password: &str, role: &str) -> Result<u32> {
+        let mut conn = self.inner.acquire().await?;
+        let query_result = sqlx::query!(
+            r#"
+            INSERT INTO login_details (username, PASSWORD, role, first_login)
+            VALUES ($1, $2, $3, TRUE)
+            "#,
+            username,
+            password,
+            role
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(query_result.last_insert_rowid() as u32)
+    }
+
+    pub async fn update_password(&self, id: u32, password: String) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let query_result = sqlx::query!(
             r#"
             UPDATE login_details
             SET password = $1, first_login = false
             WHERE id = $2
             "#,
             password,
-            USER_ID
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("No user with id {id}");
+        }
+
+        Ok(())
+    }
+
+    pub async fn update_role(&self, id: u32, role: &str) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let query_result = sqlx::query!(
+            r#"
+            UPDATE login_details
+            SET role = $1
+            WHERE id = $2
+            "#,
+            role,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("No user with id {id}");
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, id: u32) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let query_result = sqlx::query!(
+            r#"
+            DELETE FROM login_details WHERE id = $1
+            "#,
+            id
         )
         .execute(&mut *conn)
         .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("No user with id {id}");
+        }
+
         Ok(())
     }
+
+    pub async fn update_totp(&self, id: u32, secret: Option<String>, enabled: bool) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let query_result = sqlx::query!(
+            r#"
+            UPDATE login_details
+            SET totp_secret = $1, totp_enabled = $2
+            WHERE id = $3
+            "#,
+            secret,
+            enabled,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("No user with id {id}");
+        }
+
+        Ok(())
+    }
+
+    /// Record the time step of an accepted TOTP code.
+    ///
+    /// Returns `false` if a code of the same or a later time step was already accepted.
+    pub async fn use_totp_step(&self, id: u32, step: u64) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+        let step = step as i64;
+        let query_result = sqlx::query!(
+            r#"
+            UPDATE login_details
+            SET totp_last_step = $1
+            WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+            "#,
+            step,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(query_result.rows_affected() == 1)
+    }
+
+    pub async fn replace_recovery_codes(&self, user_id: u32, code_hashes: &[String]) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let mut db_tx = conn.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut db_tx)
+        .await?;
+
+        for code_hash in code_hashes {
+            sqlx::query!(
+                r#"
+                INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)
+                "#,
+                user_id,
+                code_hash
+            )
+            .execute(&mut db_tx)
+            .await?;
+        }
+
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Mark the recovery code as used.
+    ///
+    /// Returns `false` if the user has no such code or it was already used.
+    pub async fn use_recovery_code(&self, user_id: u32, code_hash: &str) -> Result<bool> {
+        let mut conn = self.inner.acquire().await?;
+        let used_at = models::Timestamp::from(Timestamp::now());
+        let query_result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = $1
+            WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL
+            "#,
+            used_at,
+            user_id,
+            code_hash
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(query_result.rows_affected() >= 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_migrated_db_then_default_admin_exists() {
+        let db = memory().await.unwrap();
+
+        let user = db.load_user("admin").await.unwrap().unwrap();
+
+        assert_eq!(user.id, 1);
+        assert_eq!(user.role, "admin");
+        assert!(user.first_login);
+    }
+
+    #[tokio::test]
+    async fn given_inserted_user_then_can_be_loaded_updated_and_deleted() {
+        let db = memory().await.unwrap();
+
+        let id = db.insert_user("alice", "hash", "viewer").await.unwrap();
+        db.update_role(id, "trader").await.unwrap();
+        db.update_password(id, "new-hash".to_owned()).await.unwrap();
+
+        let user = db.load_user_by_id(id).await.unwrap().unwrap();
+        assert_eq!(user.username, "alice");
+        assert_eq!(user.role, "trader");
+        assert_eq!(user.password, "new-hash");
+        assert!(!user.first_login);
+        assert_eq!(db.load_users().await.unwrap().len(), 2);
+
+        db.delete_user(id).await.unwrap();
+        assert!(db.load_user("alice").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn totp_steps_cannot_be_reused() {
+        let db = memory().await.unwrap();
+        db.update_totp(1, Some("SECRET".to_owned()), true)
+            .await
+            .unwrap();
+
+        assert!(db.use_totp_step(1, 100).await.unwrap());
+        assert!(!db.use_totp_step(1, 100).await.unwrap());
+        assert!(!db.use_totp_step(1, 99).await.unwrap());
+        assert!(db.use_totp_step(1, 101).await.unwrap());
+
+        let user = db.load_user_by_id(1).await.unwrap().unwrap();
+        assert_eq!(user.totp_secret.as_deref(), Some("SECRET"));
+        assert!(user.totp_enabled);
+    }
+
+    #[tokio::test]
+    async fn recovery_codes_can_only_be_used_once() {
+        let db = memory().await.unwrap();
+        db.replace_recovery_codes(1, &["a".to_owned(), "b".to_owned()])
+            .await
+            .unwrap();
+
+        assert!(db.use_recovery_code(1, "a").await.unwrap());
+        assert!(!db.use_recovery_code(1, "a").await.unwrap());
+        assert!(!db.use_recovery_code(1, "unknown").await.unwrap());
+
+        db.replace_recovery_codes(1, &[]).await.unwrap();
+        assert!(!db.use_recovery_code(1, "b").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn usernames_are_unique() {
+        let db = memory().await.unwrap();
+
+        let result = db.insert_user("admin", "hash", "viewer").await;
+
+        assert!(result.is_err());
+    }
 }
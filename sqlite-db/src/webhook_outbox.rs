@@ -0,0 +1,183 @@
+use crate::models;
+use crate::Connection;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use model::Timestamp;
+use sqlx::Acquire;
+
+/// A webhook which has not been delivered yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub url: String,
+    pub payload: String,
+    /// Number of failed delivery attempts so far.
+    pub attempts: u32,
+}
+
+impl Connection {
+    /// Queue the payload for delivery to each of the URLs.
+    pub async fn enqueue_webhooks(
+        &self,
+        urls: &[String],
+        payload: &str,
+        timestamp: Timestamp,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+        let mut db_tx = conn.begin().await?;
+
+        let timestamp = models::Timestamp::from(timestamp);
+        for url in urls {
+            sqlx::query!(
+                r#"
+                insert into webhook_outbox (
+                    url,
+                    payload,
+                    next_attempt_at
+                ) values ($1, $2, $3)
+                "#,
+                url,
+                payload,
+                timestamp
+            )
+            .execute(&mut db_tx)
+            .await?;
+        }
+
+        db_tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Load the webhooks due for a delivery attempt, in the order they were queued.
+    pub async fn load_due_webhooks(&self, now: Timestamp) -> Result<Vec<OutboxEntry>> {
+        let mut conn = self.inner.acquire().await?;
+
+        let now = models::Timestamp::from(now);
+        sqlx::query!(
+            r#"
+            select
+                id as "id!",
+                url,
+                payload,
+                attempts
+            from
+                webhook_outbox
+            where
+                next_attempt_at <= $1
+            order by id
+            "#,
+            now
+        )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok(OutboxEntry {
+                id: row.id,
+                url: row.url,
+                payload: row.payload,
+                attempts: u32::try_from(row.attempts).context("Invalid number of attempts")?,
+            })
+        })
+        .collect()
+    }
+
+    /// Record a failed delivery attempt and when to try again.
+    pub async fn reschedule_webhook(
+        &self,
+        id: i64,
+        attempts: u32,
+        next_attempt_at: Timestamp,
+    ) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        let next_attempt_at = models::Timestamp::from(next_attempt_at);
+        let query_result = sqlx::query!(
+            r#"
+            update webhook_outbox
+            set
+                attempts = $1,
+                next_attempt_at = $2
+            where
+                id = $3
+            "#,
+            attempts,
+            next_attempt_at,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        if query_result.rows_affected() != 1 {
+            bail!("Webhook {id} is not in the outbox");
+        }
+
+        Ok(())
+    }
+
+    /// Remove a webhook from the outbox, either because it was delivered or because we gave up.
+    pub async fn delete_webhook(&self, id: i64) -> Result<()> {
+        let mut conn = self.inner.acquire().await?;
+
+        sqlx::query!(
+            r#"
+            delete from webhook_outbox where id = $1
+            "#,
+            id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn given_queued_webhooks_then_only_due_ones_are_loaded() {
+        let db = memory().await.unwrap();
+        let urls = [
+            "https://a.example".to_owned(),
+            "https://b.example".to_owned(),
+        ];
+
+        db.enqueue_webhooks(&urls, "{}", Timestamp::new(100))
+            .await
+            .unwrap();
+
+        let due = db.load_due_webhooks(Timestamp::new(100)).await.unwrap();
+        assert_eq!(
+            due.iter()
+                .map(|entry| entry.url.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://a.example", "https://b.example"]
+        );
+
+        db.reschedule_webhook(due[0].id, 1, Timestamp::new(200))
+            .await
+            .unwrap();
+        db.delete_webhook(due[1].id).await.unwrap();
+
+        assert!(db
+            .load_due_webhooks(Timestamp::new(199))
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.load_due_webhooks(Timestamp::new(200)).await.unwrap(),
+            vec![OutboxEntry {
+                id: due[0].id,
+                url: "https://a.example".to_owned(),
+                payload: "{}".to_owned(),
+                attempts: 1,
+            }]
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use neon::prelude::*;
 use once_cell::sync::OnceCell;
+use std::sync::Arc;
 use taker::Opts;
 use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
 
 // Return a global tokio runtime or create one if it doesn't exist.
 // Throws a JavaScript exception if the `Runtime` fails to create.
@@ -13,6 +15,9 @@ fn runtime<'a, C: Context<'a>>(cx: &mut C) -> NeonResult<&'static Runtime> {
 
 /// Starts the itchysats taker daemon.
 /// returns a `Promise`and executes asynchronously on the `tokio` thread pool
+///
+/// Notifications are passed to the callback given as third argument with their title and message.
+/// The optional fourth argument is the referral code sent along with every order.
 pub fn start(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let rt = runtime(&mut cx)?;
     let channel = cx.channel();
@@ -24,6 +29,35 @@ pub fn start(mut cx: FunctionContext) -> JsResult<JsPromise> {
 
     let network = cx.argument::<JsString>(0)?.value(&mut cx);
     let data_dir = cx.argument::<JsString>(1)?.value(&mut cx);
+    let notify = Arc::new(cx.argument::<JsFunction>(2)?.root(&mut cx));
+    let referral_code = match cx.argument_opt(3) {
+        Some(arg) if arg.is_a::<JsString, _>(&mut cx) => Some(
+            arg.downcast_or_throw::<JsString, _>(&mut cx)?
+                .value(&mut cx),
+        ),
+        _ => None,
+    };
+
+    let (notification_sender, mut notification_receiver) = mpsc::unbounded_channel();
+    rt.spawn({
+        let channel = channel.clone();
+        async move {
+            while let Some(notification) = notification_receiver.recv().await {
+                let notify = notify.clone();
+                channel.send(move |mut cx| {
+                    let notify = notify.to_inner(&mut cx);
+                    let this = cx.undefined();
+                    let args = [
+                        cx.string(notification.title).upcast::<JsValue>(),
+                        cx.string(notification.message).upcast(),
+                    ];
+                    notify.call(&mut cx, this, args)?;
+
+                    Ok(())
+                });
+            }
+        }
+    });
 
     // Spawn an `async` task on the tokio runtime. Only Rust types that are
     // `Send` may be moved into this block. `Context` may not be passed and all
@@ -32,8 +66,13 @@ pub fn start(mut cx: FunctionContext) -> JsResult<JsPromise> {
     // This task will _not_ block the JavaScript main thread.
     rt.spawn(async move {
         // Inside this block, it is possible to `await` Rust `Future`
-        let opts = Opts::new(network, data_dir).expect("valid options");
-        let result = taker::run(opts).await;
+        let result = match Opts::new(network, data_dir, referral_code) {
+            Ok(mut opts) => {
+                opts.desktop_notifications = Some(notification_sender);
+                taker::run(opts).await
+            }
+            Err(e) => Err(e),
+        };
 
         // Settle the promise from the result of a closure. JavaScript exceptions
         // will be converted to a Promise rejection.
@@ -1,4 +1,4 @@
-import { app, BrowserWindow, net } from "electron";
+import { app, BrowserWindow, net, Notification } from "electron";
 import * as logger from "electron-log";
 import * as path from "path";
 /* eslint-disable @typescript-eslint/no-var-requires */
@@ -31,8 +31,14 @@ export default class Main {
         logger.info(`Network: ${network}`);
         logger.info(`Data Dir: ${dataDir}`);
 
-        // start itchysats taker
-        itchysats(network, dataDir).then(() => {
+        // e.g. `--referral-code=partner`, empty if not given
+        const referralCode = app.commandLine.getSwitchValue("referral-code") || null;
+        if (referralCode) {
+            logger.info(`Referral Code: ${referralCode}`);
+        }
+
+        // start itchysats taker, showing its notifications on the desktop
+        itchysats(network, dataDir, Main.notify, referralCode).then(() => {
             logger.info("Stopped ItchySats.");
         }).catch((error: Error) => logger.error(error));
 
@@ -61,6 +67,13 @@ export default class Main {
         setTimeout(Main.alive, 500, 200);
     }
 
+    private static notify(title: string, body: string) {
+        logger.info(`Notification: ${title}: ${body}`);
+        if (Notification.isSupported()) {
+            new Notification({ title, body }).show();
+        }
+    }
+
     // checks if the itchysats taker user interface is available.
     private static alive(timeout: number) {
         const request = net.request("http://127.0.0.1:8000");
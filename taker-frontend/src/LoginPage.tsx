@@ -21,10 +21,11 @@ export default function Login() {
     const [show, setShow] = useState(false);
     const handleClick = () => setShow(!show);
     const [password, setPassword] = useState("");
+    const [totp, setTotp] = useState("");
 
     function handleSubmit(event: FormEvent<HTMLFormElement>) {
         event.preventDefault();
-        login(password);
+        login(password, totp);
     }
 
     return (
@@ -47,6 +48,13 @@ export default function Login() {
                             </Button>
                         </InputRightElement>
                     </InputGroup>
+                    <Input
+                        size="md"
+                        placeholder="2FA code (if enabled)"
+                        autoComplete="one-time-code"
+                        value={totp}
+                        onChange={(e) => setTotp(e.target.value)}
+                    />
 
                     <Button disabled={loading} variant={"solid"} colorScheme={"blue"} isLoading={loading} type="submit">
                         Submit
@@ -12,10 +12,14 @@ export interface HttpError {
 
 export async function login(params: {
     password: string;
+    totp?: string;
 }): Promise<User> {
     try {
         const loginParams = new URLSearchParams();
         loginParams.append("password", params.password);
+        if (params.totp) {
+            loginParams.append("totp", params.totp);
+        }
 
         const response: AxiosResponse<User> = await axios.post("/api/login", loginParams);
         if (response.status === 200) {
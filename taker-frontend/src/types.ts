@@ -294,6 +294,7 @@ export interface WithdrawRequest {
 
 export interface ConnectionStatus {
     online: boolean;
+    address?: string;
 }
 
 export interface IdentityInfo {
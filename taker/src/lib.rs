@@ -6,8 +6,10 @@ use anyhow::Result;
 use clap::Parser;
 use daemon::bdk::bitcoin;
 use daemon::bdk::FeeRate;
-use daemon::libp2p_utils::create_connect_tcp_multiaddr;
+use daemon::libp2p_utils::parse_connect_tcp_multiaddr;
 use daemon::monitor;
+use daemon::notifications;
+use daemon::notifications::Notification;
 use daemon::oracle;
 use daemon::projection;
 use daemon::seed::AppSeed;
@@ -21,6 +23,7 @@ use daemon::N_PAYOUTS;
 use libp2p_core::PeerId;
 use model::olivia;
 use model::Identity;
+use model::ReferralCode;
 use model::Role;
 use model::SETTLEMENT_INTERVAL;
 use rocket::async_trait;
@@ -43,11 +46,13 @@ use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
 use tokio_extras::Tasks;
 use xtras::supervisor::always_restart;
 use xtras::supervisor::Supervisor;
 
 mod routes;
+pub mod ws;
 
 pub const ANNOUNCEMENT_LOOKAHEAD: time::Duration = time::Duration::hours(24);
 
@@ -84,11 +89,19 @@ impl std::fmt::Display for Password {
 
 #[derive(Parser)]
 pub struct Opts {
-    /// The IP address or hostname of the other party (i.e. the maker).
+    /// The address of the other party (i.e. the maker).
+    ///
+    /// Either `host:port`, where host is an IPv4 address, an IPv6 address in brackets or a
+    /// hostname, or a multiaddr such as `/dns6/maker.example.com/tcp/10000`. Hostnames are
+    /// resolved upon every connection attempt.
+    ///
+    /// Can be specified multiple times. The addresses are tried in order until a connection can be
+    /// established; the address that worked last is preferred afterwards. Further addresses
+    /// advertised by the maker are added automatically.
     ///
     /// If not specified it defaults to the itchysats maker for the mainnet or testnet.
     #[clap(long)]
-    maker: Option<String>,
+    maker: Vec<String>,
 
     /// The public key of the maker as a 32 byte hex string.
     ///
@@ -102,10 +115,24 @@ pub struct Opts {
     #[clap(long)]
     maker_peer_id: Option<PeerId>,
 
+    /// The SOCKS5 proxy of a Tor daemon, e.g. `127.0.0.1:9050`.
+    ///
+    /// If specified, the connection to the maker is established through Tor, which hides the
+    /// taker's IP address from the maker. Use together with the maker's onion address, e.g.
+    /// `--maker /onion3/<address>:<port>`.
+    #[clap(long)]
+    tor_socks_proxy: Option<SocketAddr>,
+
     /// The IP address to listen on for the HTTP API.
     #[clap(long, default_value = "127.0.0.1:8000")]
     http_address: SocketAddr,
 
+    /// The IP address to listen on for the WebSocket API.
+    ///
+    /// If not specified the WebSocket API is disabled.
+    #[clap(long)]
+    ws_address: Option<SocketAddr>,
+
     /// Where to permanently store data, defaults to the current working directory.
     #[clap(long)]
     data_dir: Option<PathBuf>,
@@ -168,6 +195,45 @@ pub struct Opts {
     /// keys will be derived according to Bip84.
     #[clap(short, long)]
     pub wallet_xprv: Option<ExtendedPrivKey>,
+
+    /// Notify if a CFD expires within this many hours without having been rolled over.
+    #[clap(long, default_value = "12")]
+    expiry_reminder_hours: u32,
+
+    /// Notify if the price is within this percentage of the liquidation price of a CFD.
+    #[clap(long, default_value = "10")]
+    liquidation_warning_percent: u8,
+
+    /// URL notifications are `POST`ed to as JSON.
+    #[clap(long)]
+    notification_webhook: Option<notifications::Url>,
+
+    /// SMTP server to send notifications by email through, as `ip:port`.
+    ///
+    /// The server has to run on the same machine and accept mail without authentication, e.g. a
+    /// local relay.
+    #[clap(
+        long,
+        requires_all = &["email_from", "email_to"],
+        parse(try_from_str = notifications::parse_smtp_server)
+    )]
+    smtp_server: Option<SocketAddr>,
+
+    /// Sender address of notification emails.
+    #[clap(long)]
+    email_from: Option<String>,
+
+    /// Recipient address of notification emails.
+    #[clap(long)]
+    email_to: Option<String>,
+
+    /// Code of the partner which referred you to the maker, sent along with every order.
+    #[clap(long)]
+    referral_code: Option<ReferralCode>,
+
+    /// Receives notifications to be shown on the desktop.
+    #[clap(skip)]
+    pub desktop_notifications: Option<mpsc::UnboundedSender<Notification>>,
 }
 
 impl Opts {
@@ -175,18 +241,23 @@ impl Opts {
         Opts::parse()
     }
 
-    pub fn new(network: String, data_dir: String) -> Result<Self> {
+    pub fn new(network: String, data_dir: String, referral_code: Option<String>) -> Result<Self> {
         let network = PublicNetwork::from_str(&network)?;
+        let referral_code = referral_code
+            .map(|referral_code| referral_code.parse())
+            .transpose()?;
 
         let maker = Self::maker_url(&network);
         let maker_id = Self::maker_id(&network);
         let maker_peer_id = Self::maker_peer_id(&network);
 
         Ok(Self {
-            maker: Some(maker),
+            maker: vec![maker],
             maker_id: Some(maker_id),
             maker_peer_id: Some(maker_peer_id),
+            tor_socks_proxy: None,
             http_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8000),
+            ws_address: None,
             data_dir: Some(PathBuf::from(data_dir)),
             json: false,
             json_span_list: false,
@@ -201,6 +272,14 @@ impl Opts {
             network: Some(network.into()),
             app_seed: None,
             wallet_xprv: None,
+            expiry_reminder_hours: 12,
+            liquidation_warning_percent: 10,
+            notification_webhook: None,
+            smtp_server: None,
+            email_from: None,
+            email_to: None,
+            referral_code,
+            desktop_notifications: None,
         })
     }
 
@@ -208,13 +287,14 @@ impl Opts {
         self.network.clone().unwrap_or_default()
     }
 
-    fn maker(&self) -> Result<(String, x25519_dalek::PublicKey, PeerId)> {
+    fn maker(&self) -> Result<(Vec<String>, x25519_dalek::PublicKey, PeerId)> {
         let network = PublicNetwork::try_from(self.network())?;
 
-        let maker_url = self
-            .maker
-            .clone()
-            .unwrap_or_else(|| Self::maker_url(&network));
+        let maker_urls = if self.maker.is_empty() {
+            vec![Self::maker_url(&network)]
+        } else {
+            self.maker.clone()
+        };
 
         let maker_id = self.maker_id.unwrap_or_else(|| Self::maker_id(&network));
 
@@ -222,7 +302,25 @@ impl Opts {
             .maker_peer_id
             .unwrap_or_else(|| Self::maker_peer_id(&network));
 
-        Ok((maker_url, maker_id, maker_peer_id))
+        Ok((maker_urls, maker_id, maker_peer_id))
+    }
+
+    fn notifications_config(&self) -> notifications::Config {
+        let email = match (&self.smtp_server, &self.email_from, &self.email_to) {
+            (Some(smtp_server), Some(from), Some(to)) => Some(notifications::EmailConfig {
+                smtp_server: *smtp_server,
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        };
+
+        notifications::Config {
+            expiry_reminder: time::Duration::hours(self.expiry_reminder_hours.into()),
+            liquidation_warning_percent: self.liquidation_warning_percent,
+            webhook: self.notification_webhook.clone(),
+            email,
+        }
     }
 
     fn maker_url(network: &PublicNetwork) -> String {
@@ -304,7 +402,7 @@ fn parse_app_seed(s: &str) -> Result<[u8; 32]> {
 }
 
 pub async fn run(opts: Opts) -> Result<()> {
-    let (maker_url, maker_id, maker_peer_id) = opts.maker()?;
+    let (maker_urls, maker_id, maker_peer_id) = opts.maker()?;
 
     logger::init(
         opts.log_level,
@@ -394,14 +492,11 @@ pub async fn run(opts: Opts) -> Result<()> {
 
     // Create actors
 
-    let possible_addresses = resolve_maker_addresses(maker_url.as_str()).await?;
-
-    // Assume that the first resolved ipv4 address is good enough for libp2p.
-    let maker_libp2p_address = possible_addresses
+    let maker_multiaddrs = maker_urls
         .iter()
-        .find(|x| x.is_ipv4())
-        .context("Could not resolve maker URL")?;
-    let maker_multiaddr = create_connect_tcp_multiaddr(maker_libp2p_address, maker_peer_id)?;
+        .map(|maker_url| parse_connect_tcp_multiaddr(maker_url, maker_peer_id))
+        .collect::<Result<Vec<_>>>()?;
+    tracing::debug!(?maker_multiaddrs, "Connecting to maker");
 
     let hex_pk = hex::encode(identities.identity_pk.to_bytes());
     let peer_id = identities.libp2p.public().to_peer_id().to_string();
@@ -447,6 +542,8 @@ pub async fn run(opts: Opts) -> Result<()> {
     });
     tasks.add(supervisor.run_log_summary());
 
+    let (notifications_addr, notifications_ctx) = xtra::Context::new(None);
+
     let taker = TakerActorSystem::new(
         db.clone(),
         wallet.clone(),
@@ -462,21 +559,56 @@ pub async fn run(opts: Opts) -> Result<()> {
         Duration::from_secs(10),
         projection_actor.clone(),
         maker_identity,
-        maker_multiaddr,
+        maker_multiaddrs,
+        opts.tor_socks_proxy,
         environment,
+        Some(notifications_addr.into()),
+        opts.referral_code.clone(),
     )?;
 
+    tasks.add(notifications_ctx.run(notifications::Actor::new(
+        db.clone(),
+        opts.notifications_config(),
+        opts.desktop_notifications.clone(),
+        feed_receivers.cfds.clone(),
+        feed_receivers.quote.clone(),
+        taker.maker_online_status_feed_receiver.clone(),
+    )));
+
     if let Some(password) = opts.password {
-        db.clone()
-            .update_password(rocket_cookie_auth::user::create_password(
-                password.to_string().as_str(),
-            )?)
-            .await?;
+        let admin = db
+            .load_user(rocket_cookie_auth::DEFAULT_USERNAME)
+            .await?
+            .context("Default user not found")?;
+        db.update_password(
+            admin.id,
+            rocket_cookie_auth::user::create_password(password.to_string().as_str())?,
+        )
+        .await?;
     }
 
     let rocket_auth_db_connection = RocketAuthDbConnection::new(db.clone());
     let users = Users::new(Box::new(rocket_auth_db_connection));
 
+    let taker = Arc::new(taker);
+
+    if let Some(ws_address) = opts.ws_address {
+        let feeds = shared_bin::ws::Feeds::new(
+            &feed_receivers,
+            wallet_feed_receiver.clone(),
+            Some(taker.maker_online_status_feed_receiver.clone()),
+        );
+        tasks.add_fallible(
+            shared_bin::ws::serve(
+                ws_address,
+                feeds,
+                Arc::new(RocketAuthDbConnection::new(db.clone())),
+                Arc::new(ws::Commands::new(taker.clone())),
+            ),
+            |e| async move { tracing::error!("WebSocket API stopped: {e:#}") },
+        );
+    }
+
     let mission_success = rocket::custom(figment)
         .manage(feed_receivers)
         .manage(wallet_feed_receiver)
@@ -485,6 +617,7 @@ pub async fn run(opts: Opts) -> Result<()> {
         .manage(taker.maker_online_status_feed_receiver.clone())
         .manage(taker.identify_info_feed_receiver.clone())
         .manage(taker)
+        .manage(db.clone())
         .mount(
             "/api",
             rocket::routes![
@@ -492,6 +625,9 @@ pub async fn run(opts: Opts) -> Result<()> {
                 routes::post_order_request,
                 routes::get_health_check,
                 routes::post_cfd_action,
+                routes::put_rollover_policy,
+                shared_bin::routes::get_funding_fees,
+                shared_bin::routes::get_funding_rate_history,
                 routes::post_withdraw_request,
                 routes::get_metrics,
                 routes::put_sync_wallet,
@@ -499,10 +635,31 @@ pub async fn run(opts: Opts) -> Result<()> {
                 routes::change_password,
                 routes::post_login,
                 routes::logout,
-                routes::is_authenticated
+                routes::post_totp_enroll,
+                routes::post_totp_enable,
+                routes::post_totp_disable,
+                routes::is_authenticated,
+                routes::get_api_keys,
+                routes::post_api_key,
+                routes::delete_api_key,
             ],
         )
         .register("/api", default_catchers())
+        .mount(
+            "/api/v1",
+            rocket::routes![
+                routes::v1::get_offers,
+                routes::v1::get_cfds,
+                routes::v1::get_cfd,
+                routes::v1::post_order,
+                routes::v1::post_cfd_action,
+                routes::v1::put_rollover_policy,
+                routes::v1::get_quotes,
+                routes::v1::get_wallet,
+                routes::v1::get_openapi_spec,
+            ],
+        )
+        .register("/api/v1", default_catchers())
         .manage(users)
         .mount("/", rocket::routes![routes::dist, routes::index])
         .register("/", default_catchers())
@@ -519,19 +676,6 @@ pub async fn run(opts: Opts) -> Result<()> {
     Ok(())
 }
 
-async fn resolve_maker_addresses(maker_addr: &str) -> Result<Vec<SocketAddr>> {
-    let possible_addresses = tokio::net::lookup_host(maker_addr)
-        .await?
-        .collect::<Vec<_>>();
-
-    tracing::debug!(
-        "Resolved {} to [{}]",
-        maker_addr,
-        itertools::join(possible_addresses.iter(), ",")
-    );
-    Ok(possible_addresses)
-}
-
 struct RocketAuthDbConnection {
     inner: sqlite_db::Connection,
 }
@@ -544,18 +688,71 @@ impl RocketAuthDbConnection {
 
 #[async_trait]
 impl rocket_cookie_auth::Database for RocketAuthDbConnection {
-    async fn load_user(&self) -> Result<Option<rocket_cookie_auth::user::User>> {
-        let users = self.inner.clone().load_user().await?;
-        Ok(users.map(|user| rocket_cookie_auth::user::User {
-            id: user.id,
-            password: user.password,
-            auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
-            first_login: user.first_login,
+    async fn load_user(&self, username: String) -> Result<Option<rocket_cookie_auth::user::User>> {
+        let user = self.inner.load_user(&username).await?;
+        user.map(|user| {
+            Ok(rocket_cookie_auth::user::User {
+                id: user.id,
+                username: user.username,
+                role: user.role.parse()?,
+                password: user.password,
+                auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
+                first_login: user.first_login,
+                totp_secret: user.totp_secret,
+                totp_enabled: user.totp_enabled,
+            })
+        })
+        .transpose()
+    }
+
+    async fn load_user_by_id(&self, id: u32) -> Result<Option<rocket_cookie_auth::user::User>> {
+        let user = self.inner.load_user_by_id(id).await?;
+        user.map(|user| {
+            Ok(rocket_cookie_auth::user::User {
+                id: user.id,
+                username: user.username,
+                role: user.role.parse()?,
+                password: user.password,
+                auth_key: rocket_cookie_auth::NO_AUTH_KEY_SET.to_string(),
+                first_login: user.first_login,
+                totp_secret: user.totp_secret,
+                totp_enabled: user.totp_enabled,
+            })
+        })
+        .transpose()
+    }
+
+    async fn update_password(&self, id: u32, password: String) -> Result<()> {
+        self.inner.update_password(id, password).await?;
+        Ok(())
+    }
+
+    async fn load_api_key(
+        &self,
+        key_hash: String,
+    ) -> Result<Option<rocket_cookie_auth::api_key::ApiKey>> {
+        let api_key = self.inner.load_api_key(&key_hash).await?;
+        Ok(api_key.map(|api_key| rocket_cookie_auth::api_key::ApiKey {
+            id: api_key.id,
+            name: api_key.name,
         }))
     }
 
-    async fn update_password(&self, password: String) -> Result<()> {
-        self.inner.clone().update_password(password).await?;
+    async fn update_totp(&self, id: u32, secret: Option<String>, enabled: bool) -> Result<()> {
+        self.inner.update_totp(id, secret, enabled).await?;
         Ok(())
     }
+
+    async fn use_totp_step(&self, id: u32, step: u64) -> Result<bool> {
+        self.inner.use_totp_step(id, step).await
+    }
+
+    async fn replace_recovery_codes(&self, id: u32, code_hashes: Vec<String>) -> Result<()> {
+        self.inner.replace_recovery_codes(id, &code_hashes).await?;
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, id: u32, code_hash: String) -> Result<bool> {
+        self.inner.use_recovery_code(id, &code_hash).await
+    }
 }
@@ -18,6 +18,7 @@ use model::Contracts;
 use model::Leverage;
 use model::OrderId;
 use model::Price;
+use model::RolloverPolicy;
 use model::Timestamp;
 use model::WalletInfo;
 use rocket::form::Form;
@@ -29,9 +30,12 @@ use rocket::response::Responder;
 use rocket::serde::json::Json;
 use rocket::serde::uuid::Uuid;
 use rocket::State;
+use rocket_cookie_auth::api_key::generate_api_key;
 use rocket_cookie_auth::auth::Auth;
 use rocket_cookie_auth::forms::ChangePassword;
 use rocket_cookie_auth::forms::Login;
+use rocket_cookie_auth::role::Admin;
+use rocket_cookie_auth::totp::TotpEnrolment;
 use rocket_cookie_auth::user::User;
 use rust_embed::RustEmbed;
 use rust_embed_rocket::EmbeddedFileExt;
@@ -40,10 +44,13 @@ use serde::Serialize;
 use shared_bin::ToSseEvent;
 use std::borrow::Cow;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::select;
 use tokio::sync::watch;
 use tracing::instrument;
 
+pub mod v1;
+
 type Taker = TakerActorSystem<
     oracle::Actor,
     wallet::Actor<ElectrumBlockchain, sled::Tree>,
@@ -166,8 +173,15 @@ pub struct CfdOrderRequest {
 #[instrument(name = "POST /cfd/order", skip(taker, _user), err)]
 pub async fn post_order_request(
     cfd_order_request: Json<CfdOrderRequest>,
-    taker: &State<Taker>,
+    taker: &State<Arc<Taker>>,
     _user: User,
+) -> Result<(), HttpApiProblem> {
+    place_order(taker, &cfd_order_request).await
+}
+
+pub(crate) async fn place_order(
+    taker: &Taker,
+    cfd_order_request: &CfdOrderRequest,
 ) -> Result<(), HttpApiProblem> {
     taker
         .place_order(
@@ -190,10 +204,18 @@ pub async fn post_order_request(
 pub async fn post_cfd_action(
     order_id: Uuid,
     action: String,
-    taker: &State<Taker>,
+    taker: &State<Arc<Taker>>,
     _user: User,
 ) -> Result<(), HttpApiProblem> {
-    let order_id = OrderId::from(order_id);
+    execute_cfd_action(taker, OrderId::from(order_id), action).await
+}
+
+/// Execute an action on a CFD on behalf of the taker.
+pub(crate) async fn execute_cfd_action(
+    taker: &Taker,
+    order_id: OrderId,
+    action: String,
+) -> Result<(), HttpApiProblem> {
     let action = action.parse().map_err(|_| {
         HttpApiProblem::new(StatusCode::BAD_REQUEST).detail(format!("Invalid action: {}", action))
     })?;
@@ -219,6 +241,34 @@ pub async fn post_cfd_action(
     Ok(())
 }
 
+#[rocket::put("/cfd/<order_id>/rollover-policy", data = "<policy>")]
+#[instrument(name = "PUT /cfd/<order_id>/rollover-policy", skip(taker, _user), err)]
+pub async fn put_rollover_policy(
+    order_id: Uuid,
+    policy: Json<RolloverPolicy>,
+    taker: &State<Arc<Taker>>,
+    _user: User,
+) -> Result<(), HttpApiProblem> {
+    update_rollover_policy(taker, OrderId::from(order_id), policy.into_inner()).await
+}
+
+/// Update the policy according to which a CFD is rolled over automatically.
+pub(crate) async fn update_rollover_policy(
+    taker: &Taker,
+    order_id: OrderId,
+    policy: RolloverPolicy,
+) -> Result<(), HttpApiProblem> {
+    match taker.set_rollover_policy(order_id, policy).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not update rollover policy")
+            .detail(format!("No open CFD with order id {order_id}"))),
+        Err(e) => Err(HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not update rollover policy")
+            .detail(format!("{e:#}"))),
+    }
+}
+
 #[rocket::get("/alive")]
 #[instrument(name = "GET /alive")]
 pub fn get_health_check() {}
@@ -268,16 +318,24 @@ pub struct WithdrawRequest {
     #[serde(with = "bdk::bitcoin::util::amount::serde::as_btc")]
     amount: Amount,
     fee: f32,
+    /// Required if the user enabled two-factor authentication.
+    #[serde(default)]
+    totp: Option<String>,
 }
 
 #[rocket::post("/withdraw", data = "<withdraw_request>")]
-#[instrument(name = "POST /withdraw", skip(taker, _user), err)]
+#[instrument(name = "POST /withdraw", skip(taker, admin, auth), err)]
 pub async fn post_withdraw_request(
     withdraw_request: Json<WithdrawRequest>,
-    taker: &State<Taker>,
+    taker: &State<Arc<Taker>>,
     network: &State<Network>,
-    _user: User,
+    admin: Admin,
+    auth: Auth<'_>,
 ) -> Result<String, HttpApiProblem> {
+    auth.users
+        .verify_fresh_totp(admin.0.id, withdraw_request.totp.as_deref())
+        .await?;
+
     let amount =
         (withdraw_request.amount != bdk::bitcoin::Amount::ZERO).then(|| withdraw_request.amount);
 
@@ -315,7 +373,7 @@ pub async fn get_metrics<'r>() -> Result<String, HttpApiProblem> {
 
 #[rocket::put("/sync")]
 #[instrument(name = "PUT /sync", skip_all, err)]
-pub async fn put_sync_wallet(taker: &State<Taker>, _user: User) -> Result<(), HttpApiProblem> {
+pub async fn put_sync_wallet(taker: &State<Arc<Taker>>, _user: User) -> Result<(), HttpApiProblem> {
     taker.sync_wallet().await.map_err(|e| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not sync wallet")
@@ -361,6 +419,10 @@ pub async fn change_password(
             .detail(format!("{error:#}"))
     })?;
 
+    auth.users
+        .verify_fresh_totp(user.id, form.totp.as_deref())
+        .await?;
+
     user.set_password(&form.password).map_err(|error| {
         HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
             .title("Could not set password")
@@ -413,6 +475,136 @@ pub fn logout(auth: Auth<'_>) -> Result<(), HttpApiProblem> {
     Ok(())
 }
 
+/// Start enrolling a TOTP authenticator, which has to be confirmed with
+/// [`post_totp_enable`] before it is required upon login.
+#[rocket::post("/totp/enroll")]
+pub async fn post_totp_enroll(
+    user: User,
+    auth: Auth<'_>,
+) -> Result<Json<TotpEnrolment>, HttpApiProblem> {
+    let enrolment = auth.users.enroll_totp(user.id).await?;
+    Ok(Json(enrolment))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TotpCode {
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+#[rocket::post("/totp/enable", data = "<request>")]
+pub async fn post_totp_enable(
+    request: Json<TotpCode>,
+    user: User,
+    auth: Auth<'_>,
+) -> Result<Json<RecoveryCodes>, HttpApiProblem> {
+    let recovery_codes = auth.users.enable_totp(user.id, &request.code).await?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+#[rocket::post("/totp/disable", data = "<request>")]
+pub async fn post_totp_disable(
+    request: Json<TotpCode>,
+    user: User,
+    auth: Auth<'_>,
+) -> Result<(), HttpApiProblem> {
+    auth.users.disable_totp(user.id, &request.code).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewApiKeyRequest {
+    name: String,
+}
+
+/// A newly created API key.
+///
+/// This is the only time the key itself is handed out, only its hash is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewApiKey {
+    id: u32,
+    name: String,
+    key: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyInfo {
+    id: u32,
+    name: String,
+    created_at: Timestamp,
+}
+
+#[rocket::get("/api-keys")]
+#[instrument(name = "GET /api-keys", skip_all, err)]
+pub async fn get_api_keys(
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<Vec<ApiKeyInfo>>, HttpApiProblem> {
+    let api_keys = db.load_api_keys().await.map_err(|e| {
+        HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not load API keys")
+            .detail(format!("{e:#}"))
+    })?;
+
+    let api_keys = api_keys
+        .into_iter()
+        .map(|api_key| ApiKeyInfo {
+            id: api_key.id,
+            name: api_key.name,
+            created_at: api_key.created_at,
+        })
+        .collect();
+
+    Ok(Json(api_keys))
+}
+
+#[rocket::post("/api-keys", data = "<request>")]
+#[instrument(name = "POST /api-keys", skip(db, _admin), err)]
+pub async fn post_api_key(
+    request: Json<NewApiKeyRequest>,
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<Json<NewApiKey>, HttpApiProblem> {
+    let (key, key_hash) = generate_api_key();
+
+    let api_key = db
+        .insert_api_key(&request.name, &key_hash)
+        .await
+        .map_err(|e| {
+            HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+                .title("Could not create API key")
+                .detail(format!("{e:#}"))
+        })?;
+
+    Ok(Json(NewApiKey {
+        id: api_key.id,
+        name: api_key.name,
+        key,
+    }))
+}
+
+#[rocket::delete("/api-keys/<id>")]
+#[instrument(name = "DELETE /api-keys/<id>", skip(db, _admin), err)]
+pub async fn delete_api_key(
+    id: u32,
+    db: &State<sqlite_db::Connection>,
+    _admin: Admin,
+) -> Result<(), HttpApiProblem> {
+    match db.revoke_api_key(id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpApiProblem::new(StatusCode::NOT_FOUND)
+            .title("Could not revoke API key")
+            .detail(format!("No active API key with id {id}"))),
+        Err(e) => Err(HttpApiProblem::new(StatusCode::INTERNAL_SERVER_ERROR)
+            .title("Could not revoke API key")
+            .detail(format!("{e:#}"))),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,139 @@
+//! Versioned HTTP API for programmatic access to the taker.
+//!
+//! In contrast to the routes consumed by the UI, these routes are authenticated by API key and
+//! follow the specification in `taker/openapi.yaml`, which is served under `/openapi.yaml`.
+
+use crate::routes::execute_cfd_action;
+use crate::routes::place_order;
+use crate::routes::update_rollover_policy;
+use crate::routes::CfdOrderRequest;
+use crate::routes::Taker;
+use daemon::projection::Cfd;
+use daemon::projection::CfdOffer;
+use daemon::projection::FeedReceivers;
+use daemon::projection::LatestQuotes;
+use http_api_problem::HttpApiProblem;
+use http_api_problem::StatusCode;
+use model::OrderId;
+use model::RolloverPolicy;
+use model::WalletInfo;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use rocket::serde::uuid::Uuid;
+use rocket::State;
+use rocket_cookie_auth::api_key::ApiKey;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::instrument;
+
+const OPENAPI_SPEC: &str = include_str!("../../openapi.yaml");
+
+#[rocket::get("/openapi.yaml")]
+pub fn get_openapi_spec() -> (ContentType, &'static str) {
+    (ContentType::new("application", "yaml"), OPENAPI_SPEC)
+}
+
+#[rocket::get("/offers")]
+#[instrument(name = "GET /v1/offers", skip_all)]
+pub fn get_offers(rx: &State<FeedReceivers>, _api_key: ApiKey) -> Json<Vec<CfdOffer>> {
+    let offers = rx.offers.borrow().to_vec();
+    Json(offers)
+}
+
+#[rocket::get("/cfds")]
+#[instrument(name = "GET /v1/cfds", skip_all, err)]
+pub fn get_cfds(
+    rx: &State<FeedReceivers>,
+    _api_key: ApiKey,
+) -> Result<Json<Vec<Cfd>>, HttpApiProblem> {
+    let cfds = load_cfds(rx)?;
+    Ok(Json(cfds))
+}
+
+#[rocket::get("/cfds/<order_id>")]
+#[instrument(name = "GET /v1/cfds/<order_id>", skip(rx, _api_key), err)]
+pub fn get_cfd(
+    order_id: Uuid,
+    rx: &State<FeedReceivers>,
+    _api_key: ApiKey,
+) -> Result<Json<Cfd>, HttpApiProblem> {
+    let order_id = OrderId::from(order_id);
+
+    let cfd = load_cfds(rx)?
+        .into_iter()
+        .find(|cfd| cfd.order_id == order_id)
+        .ok_or_else(|| {
+            HttpApiProblem::new(StatusCode::NOT_FOUND)
+                .title("CFD not found")
+                .detail(format!("No CFD with order id {order_id}"))
+        })?;
+
+    Ok(Json(cfd))
+}
+
+#[rocket::post("/orders", data = "<cfd_order_request>")]
+#[instrument(name = "POST /v1/orders", skip(taker, _api_key), err)]
+pub async fn post_order(
+    cfd_order_request: Json<CfdOrderRequest>,
+    taker: &State<Arc<Taker>>,
+    _api_key: ApiKey,
+) -> Result<(), HttpApiProblem> {
+    place_order(taker, &cfd_order_request).await
+}
+
+#[rocket::post("/cfds/<order_id>/<action>")]
+#[instrument(name = "POST /v1/cfds/<order_id>/<action>", skip(taker, _api_key), err)]
+pub async fn post_cfd_action(
+    order_id: Uuid,
+    action: String,
+    taker: &State<Arc<Taker>>,
+    _api_key: ApiKey,
+) -> Result<(), HttpApiProblem> {
+    execute_cfd_action(taker, OrderId::from(order_id), action).await
+}
+
+#[rocket::put("/cfds/<order_id>/rollover-policy", data = "<policy>")]
+#[instrument(
+    name = "PUT /v1/cfds/<order_id>/rollover-policy",
+    skip(taker, _api_key),
+    err
+)]
+pub async fn put_rollover_policy(
+    order_id: Uuid,
+    policy: Json<RolloverPolicy>,
+    taker: &State<Arc<Taker>>,
+    _api_key: ApiKey,
+) -> Result<(), HttpApiProblem> {
+    update_rollover_policy(taker, OrderId::from(order_id), policy.into_inner()).await
+}
+
+#[rocket::get("/quotes")]
+#[instrument(name = "GET /v1/quotes", skip_all)]
+pub fn get_quotes(rx: &State<FeedReceivers>, _api_key: ApiKey) -> Json<LatestQuotes> {
+    let quotes = rx.quote.borrow().clone();
+    Json(quotes)
+}
+
+#[rocket::get("/wallet")]
+#[instrument(name = "GET /v1/wallet", skip_all, err)]
+pub fn get_wallet(
+    rx_wallet: &State<watch::Receiver<Option<WalletInfo>>>,
+    _api_key: ApiKey,
+) -> Result<Json<shared_bin::WalletInfo>, HttpApiProblem> {
+    let wallet_info = rx_wallet.borrow();
+    let wallet_info = wallet_info.as_ref().ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("Wallet not yet available")
+            .detail("The wallet has not been synced yet. Please retry later.")
+    })?;
+
+    Ok(Json(shared_bin::WalletInfo::from(wallet_info)))
+}
+
+fn load_cfds(rx: &FeedReceivers) -> Result<Vec<Cfd>, HttpApiProblem> {
+    rx.cfds.borrow().clone().ok_or_else(|| {
+        HttpApiProblem::new(StatusCode::SERVICE_UNAVAILABLE)
+            .title("CFDs not yet available")
+            .detail("CFDs are still being loaded from the database. Please retry later.")
+    })
+}
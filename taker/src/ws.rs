@@ -0,0 +1,49 @@
+use crate::routes::execute_cfd_action;
+use crate::routes::place_order;
+use crate::routes::CfdOrderRequest;
+use crate::routes::Taker;
+use anyhow::Result;
+use daemon::projection::CfdAction;
+use model::OrderId;
+use rocket::async_trait;
+use rocket_cookie_auth::api_key::ApiKey;
+use serde::Deserialize;
+use shared_bin::ws::problem_to_error;
+use shared_bin::ws::CommandHandler;
+use std::sync::Arc;
+
+/// Commands the taker accepts over the WebSocket API.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    PlaceOrder(CfdOrderRequest),
+    CfdAction {
+        order_id: OrderId,
+        action: CfdAction,
+    },
+}
+
+pub struct Commands {
+    taker: Arc<Taker>,
+}
+
+impl Commands {
+    pub fn new(taker: Arc<Taker>) -> Self {
+        Self { taker }
+    }
+}
+
+#[async_trait]
+impl CommandHandler for Commands {
+    type Command = Command;
+
+    async fn handle(&self, _: &ApiKey, command: Command) -> Result<()> {
+        match command {
+            Command::PlaceOrder(order) => place_order(&self.taker, &order).await,
+            Command::CfdAction { order_id, action } => {
+                execute_cfd_action(&self.taker, order_id, action.to_string()).await
+            }
+        }
+        .map_err(problem_to_error)
+    }
+}
@@ -12,9 +12,13 @@ use xtra_productivity::xtra_productivity;
 
 pub const QUOTE_INTERVAL_MINUTES: i64 = 1;
 
-/// Subscribes to BitMEX and retrieves latest quotes for BTCUSD and ETHUSD.
+/// The interval BitMEX charges funding fees of its perpetual swaps at.
+pub const FUNDING_INTERVAL_HOURS: i64 = 8;
+
+/// Subscribes to BitMEX and retrieves latest quotes and funding rates for BTCUSD and ETHUSD.
 pub struct Actor {
     latest_quotes: LatestQuotes,
+    latest_funding_rates: LatestFundingRates,
 
     /// Contains the reason we are stopping.
     stop_reason: Option<Error>,
@@ -25,6 +29,7 @@ impl Actor {
     pub fn new(network: Network) -> Self {
         Self {
             latest_quotes: HashMap::new(),
+            latest_funding_rates: HashMap::new(),
             stop_reason: None,
             network,
         }
@@ -49,6 +54,8 @@ impl xtra::Actor for Actor {
                         [
                             format!("quoteBin{QUOTE_INTERVAL_MINUTES}m:XBTUSD"),
                             format!("quoteBin{QUOTE_INTERVAL_MINUTES}m:ETHUSD"),
+                            "instrument:XBTUSD".to_owned(),
+                            "instrument:ETHUSD".to_owned(),
                         ],
                         network,
                     );
@@ -84,7 +91,26 @@ impl xtra::Actor for Actor {
                                 }
                             }
                             None => {
-                                continue;
+                                let funding_rates = FundingRate::from_str(&text)
+                                    .map_err(|e| Error::FailedToParseFundingRate { source: e })?;
+
+                                for funding_rate in funding_rates {
+                                    let span = tracing::debug_span!(
+                                        "Received new funding rate",
+                                        rate = %funding_rate.rate,
+                                        symbol = %funding_rate.symbol,
+                                    );
+
+                                    let is_our_address_disconnected = this
+                                        .send(NewFundingRateReceived(funding_rate))
+                                        .instrument(span)
+                                        .await
+                                        .is_err();
+
+                                    if is_our_address_disconnected {
+                                        return Ok(());
+                                    }
+                                }
                             }
                         }
                     }
@@ -117,6 +143,14 @@ impl Actor {
     async fn handle(&mut self, _msg: GetLatestQuotes) -> LatestQuotes {
         self.latest_quotes.clone()
     }
+
+    async fn handle(&mut self, msg: NewFundingRateReceived) {
+        self.latest_funding_rates.insert(msg.0.symbol, msg.0);
+    }
+
+    async fn handle(&mut self, _msg: GetLatestFundingRates) -> LatestFundingRates {
+        self.latest_funding_rates.clone()
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -127,6 +161,8 @@ pub enum Error {
     StreamEnded,
     #[error("Failed to parse quote")]
     FailedToParseQuote { source: anyhow::Error },
+    #[error("Failed to parse funding rate")]
+    FailedToParseFundingRate { source: anyhow::Error },
     #[error("Stop reason was not specified")]
     Unspecified,
 }
@@ -141,6 +177,58 @@ pub struct GetLatestQuotes;
 
 pub type LatestQuotes = HashMap<ContractSymbol, Quote>;
 
+/// Private message to update our internal state with the latest funding rate.
+#[derive(Debug)]
+struct NewFundingRateReceived(FundingRate);
+
+/// Request the latest funding rates of the BitMEX perpetual swaps.
+#[derive(Debug, Clone, Copy)]
+pub struct GetLatestFundingRates;
+
+pub type LatestFundingRates = HashMap<ContractSymbol, FundingRate>;
+
+/// The funding rate of a BitMEX perpetual swap.
+///
+/// The rate applies to a funding interval of [`FUNDING_INTERVAL_HOURS`]. If positive, longs pay
+/// shorts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundingRate {
+    pub rate: Decimal,
+    pub symbol: ContractSymbol,
+    pub received_at: OffsetDateTime,
+}
+
+impl FundingRate {
+    /// Extract the funding rates from an `instrument` table message.
+    ///
+    /// Most updates of an instrument do not change the funding rate, hence an empty list is
+    /// returned for those.
+    fn from_str(text: &str) -> Result<Vec<Self>> {
+        let instrument_message = match serde_json::from_str::<wire::InstrumentMessage>(text) {
+            Ok(instrument_message) if instrument_message.table == "instrument" => {
+                instrument_message
+            }
+            _ => {
+                tracing::trace!(%text, "Not an 'instrument' message, skipping...");
+                return Ok(Vec::new());
+            }
+        };
+
+        instrument_message
+            .data
+            .into_iter()
+            .filter_map(|instrument| Some((instrument.symbol, instrument.funding_rate?)))
+            .map(|(symbol, rate)| {
+                Ok(Self {
+                    rate,
+                    symbol: ContractSymbol::from_str(symbol.as_str())?,
+                    received_at: OffsetDateTime::now_utc(),
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Quote {
     pub timestamp: OffsetDateTime,
@@ -234,6 +322,20 @@ mod wire {
         #[serde(with = "time::serde::rfc3339")]
         pub timestamp: OffsetDateTime,
     }
+
+    #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+    pub struct InstrumentMessage {
+        pub table: String,
+        pub data: Vec<InstrumentData>,
+    }
+
+    #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+    #[serde(rename_all = "camelCase")]
+    pub struct InstrumentData {
+        pub symbol: String,
+        #[serde(default, with = "rust_decimal::serde::float_option")]
+        pub funding_rate: Option<Decimal>,
+    }
 }
 
 #[cfg(test)]
@@ -252,6 +354,29 @@ mod tests {
         assert_eq!(quote.symbol, ContractSymbol::BtcUsd)
     }
 
+    #[test]
+    fn can_deserialize_funding_rate_from_instrument_message() {
+        let funding_rates = FundingRate::from_str(r#"{"table":"instrument","action":"update","data":[{"symbol":"XBTUSD","fundingRate":0.0001,"indicativeFundingRate":0.000125,"timestamp":"2022-10-06T04:00:00.000Z"}]}"#).unwrap();
+
+        assert_eq!(funding_rates.len(), 1);
+        assert_eq!(funding_rates[0].rate, dec!(0.0001));
+        assert_eq!(funding_rates[0].symbol, ContractSymbol::BtcUsd);
+    }
+
+    #[test]
+    fn instrument_update_without_funding_rate_is_skipped() {
+        let funding_rates = FundingRate::from_str(r#"{"table":"instrument","action":"update","data":[{"symbol":"XBTUSD","lastPrice":19950.5,"timestamp":"2022-10-06T04:00:01.000Z"}]}"#).unwrap();
+
+        assert!(funding_rates.is_empty());
+    }
+
+    #[test]
+    fn quote_message_is_not_a_funding_rate() {
+        let funding_rates = FundingRate::from_str(r#"{"table":"quoteBin1m","action":"insert","data":[{"timestamp":"2021-09-21T02:40:00.000Z","symbol":"XBTUSD","bidSize":50200,"bidPrice":42640.5,"askPrice":42641,"askSize":363600}]}"#).unwrap();
+
+        assert!(funding_rates.is_empty());
+    }
+
     #[test]
     fn quote_from_now_is_not_old() {
         let quote = dummy_quote_at(OffsetDateTime::now_utc());
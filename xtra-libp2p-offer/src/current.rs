@@ -1,5 +0,0 @@
-pub mod maker;
-mod protocol;
-pub mod taker;
-
-pub const PROTOCOL: &str = "/itchysats/offer/2.0.0";
@@ -1,4 +0,0 @@
-pub mod maker;
-pub mod protocol;
-
-pub const PROTOCOL: &str = "/itchysats/offer/1.0.0";
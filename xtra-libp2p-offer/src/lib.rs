@@ -1,7 +1,31 @@
-mod current;
-pub mod deprecated;
-
-pub use current::*;
+pub mod maker;
+mod protocol;
+pub mod taker;
+
+pub use protocol::OfferProtocol;
+
+use xtra_libp2p::versions::Versions;
+
+/// The supported versions of the offer protocol, newest first.
+///
+/// Takers only listen for the latest version, the older versions are kept for the maker to
+/// reach takers which have not been updated yet.
+pub const VERSIONS: Versions<Version> = Versions::new(&[
+    ("/itchysats/offer/3.0.0", Version::V3),
+    ("/itchysats/offer/2.0.0", Version::V2),
+    ("/itchysats/offer/1.0.0", Version::V1),
+]);
+
+/// The versions of the offer protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// Only the long and short BTCUSD offers are sent.
+    V1,
+    /// All offers are sent.
+    V2,
+    /// All offers are sent through [`OfferProtocol`], the taker acknowledges them.
+    V3,
+}
 
 #[cfg(test)]
 mod tests {
@@ -22,7 +46,6 @@ mod tests {
     use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
     use std::collections::HashSet;
-    use std::sync::Arc;
     use std::time::Duration;
     use time::macros::datetime;
     use tracing_subscriber::util::SubscriberInitExt;
@@ -36,6 +59,7 @@ mod tests {
     use xtra_libp2p::libp2p::transport::MemoryTransport;
     use xtra_libp2p::libp2p::Multiaddr;
     use xtra_libp2p::libp2p::PeerId;
+    use xtra_libp2p::limits::ConnectionLimits;
     use xtra_libp2p::Connect;
     use xtra_libp2p::Endpoint;
     use xtra_libp2p::ListenOn;
@@ -156,7 +180,7 @@ mod tests {
         let (endpoint_addr, endpoint_context) = Context::new(None);
 
         let id = Keypair::generate_ed25519();
-        let offer_maker_addr = crate::maker::Actor::new(endpoint_addr.clone())
+        let offer_maker_addr = crate::maker::Actor::new(endpoint_addr.clone(), None)
             .create(None)
             .spawn_global();
 
@@ -171,7 +195,8 @@ mod tests {
                 vec![],
                 vec![],
             ),
-            Arc::new(HashSet::default()),
+            HashSet::default(),
+            ConnectionLimits::default(),
         );
 
         #[allow(clippy::disallowed_methods)]
@@ -183,17 +208,20 @@ mod tests {
     fn create_endpoint_with_offer_taker() -> (Address<OffersReceiver>, Address<Endpoint>) {
         let offers_receiver_addr = OffersReceiver::new().create(None).spawn_global();
 
-        let offer_taker_addr = crate::taker::Actor::new(offers_receiver_addr.clone().into())
-            .create(None)
-            .spawn_global();
+        let offer_taker_addr = crate::taker::Actor::new(crate::taker::Handler::new(
+            offers_receiver_addr.clone().into(),
+        ))
+        .create(None)
+        .spawn_global();
 
         let endpoint_addr = Endpoint::new(
             Box::new(MemoryTransport::default),
             Keypair::generate_ed25519(),
             Duration::from_secs(10),
-            [(PROTOCOL, offer_taker_addr.into())],
+            [(VERSIONS.latest().0, offer_taker_addr.into())],
             Subscribers::default(),
-            Arc::new(HashSet::default()),
+            HashSet::default(),
+            ConnectionLimits::default(),
         )
         .create(None)
         .spawn_global();
@@ -275,6 +303,7 @@ mod tests {
             tx_fee_rate: TxFeeRate::default(),
             funding_rate: FundingRate::new(Decimal::ONE).unwrap(),
             opening_fee: Default::default(),
+            opening_fee_rate: Decimal::ZERO,
             lot_size: LotSize::new(100),
         }
     }
@@ -1,5 +1,7 @@
-use crate::current::protocol;
-use crate::current::PROTOCOL;
+use crate::protocol;
+use crate::OfferProtocol;
+use crate::Version;
+use crate::VERSIONS;
 use async_trait::async_trait;
 use model::ContractSymbol;
 use model::Position;
@@ -8,6 +10,7 @@ use std::collections::HashSet;
 use std::time::Duration;
 use tokio_extras::spawn_fallible;
 use tracing::Instrument;
+use xtra::prelude::MessageChannel;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::libp2p::PeerId;
 use xtra_libp2p::Endpoint;
@@ -19,33 +22,88 @@ pub struct Actor {
     endpoint: xtra::Address<Endpoint>,
     connected_peers: HashSet<PeerId>,
     current_offers: Offers,
+    /// The offers as they were last sent to each taker, after tailoring them.
+    sent_offers: HashMap<PeerId, Offers>,
+    tailor_offers: Option<MessageChannel<TailorOffers, Vec<model::Offer>>>,
 }
 
 impl Actor {
-    pub fn new(endpoint: xtra::Address<Endpoint>) -> Self {
+    /// Offers are sent to all takers alike, unless `tailor_offers` adjusts them per taker.
+    pub fn new(
+        endpoint: xtra::Address<Endpoint>,
+        tailor_offers: Option<MessageChannel<TailorOffers, Vec<model::Offer>>>,
+    ) -> Self {
         Self {
             endpoint,
             connected_peers: HashSet::default(),
             current_offers: Offers::default(),
+            sent_offers: HashMap::default(),
+            tailor_offers,
         }
     }
 
+    async fn offers_for(
+        &self,
+        peer_id: PeerId,
+        offers: Vec<model::Offer>,
+    ) -> anyhow::Result<Vec<model::Offer>> {
+        let offers = match &self.tailor_offers {
+            Some(tailor_offers) => tailor_offers.send(TailorOffers { peer_id, offers }).await?,
+            None => offers,
+        };
+
+        Ok(offers)
+    }
+
+    /// Tailor the offers to the taker and send them.
+    ///
+    /// The offers are recorded as sent before they are sent, such that orders referring to them
+    /// can be matched against the offers the taker actually received.
     #[tracing::instrument(name = "Broadcast offers to taker", skip(self, offers, ctx))]
     async fn send_offers(
-        &self,
+        &mut self,
         peer_id: PeerId,
         offers: Vec<model::Offer>,
         ctx: &mut xtra::Context<Self>,
     ) {
+        let offers = match self.offers_for(peer_id, offers).await {
+            Ok(offers) => offers,
+            Err(e) => {
+                tracing::warn!(%peer_id, "Failed to tailor offers: {e:#}");
+                return;
+            }
+        };
+        self.sent_offers
+            .entry(peer_id)
+            .or_default()
+            .update(offers.clone());
+
         let endpoint = self.endpoint.clone();
 
         let task = async move {
-            let stream = endpoint
-                .send(OpenSubstream::single_protocol(peer_id, PROTOCOL))
+            let (negotiated_protocol, stream) = endpoint
+                .send(OpenSubstream::multiple_protocols(
+                    peer_id,
+                    VERSIONS.protocols(),
+                ))
                 .await??
                 .await?;
 
-            protocol::send(stream, offers.into()).await?;
+            match VERSIONS.get(negotiated_protocol) {
+                Some(Version::V3) => {
+                    xtra_libp2p_request_response::request_on_substream::<OfferProtocol>(
+                        stream,
+                        offers.into(),
+                    )
+                    .await?;
+                    protocol::MESSAGES_SENT.inc();
+                }
+                Some(Version::V2) => protocol::send(stream, offers.into()).await?,
+                Some(Version::V1) => {
+                    protocol::v1::send(stream, protocol::v1::MakerOffers::new(&offers)).await?
+                }
+                None => anyhow::bail!("Negotiated unsupported offer protocol"),
+            }
 
             anyhow::Ok(())
         };
@@ -77,7 +135,7 @@ impl Actor {
         self.current_offers.update(msg.0.clone());
 
         let quiet = quiet_spans::sometimes_quiet_children();
-        for peer_id in self.connected_peers.iter().copied() {
+        for peer_id in self.connected_peers.clone() {
             self.send_offers(peer_id, msg.0.clone(), ctx)
                 .instrument(quiet.clone())
                 .await
@@ -87,6 +145,13 @@ impl Actor {
     async fn handle(&mut self, _: GetLatestOffers) -> Vec<model::Offer> {
         self.current_offers.to_vec()
     }
+
+    async fn handle(&mut self, msg: GetOffersFor) -> Vec<model::Offer> {
+        self.sent_offers
+            .get(&msg.0)
+            .map(Offers::to_vec)
+            .unwrap_or_default()
+    }
 }
 
 #[xtra_productivity]
@@ -105,6 +170,7 @@ impl Actor {
     async fn handle_connection_dropped(&mut self, msg: endpoint::ConnectionDropped) {
         tracing::trace!("Remove dropped connection: {:?}", msg.peer_id);
         self.connected_peers.remove(&msg.peer_id);
+        self.sent_offers.remove(&msg.peer_id);
     }
 }
 
@@ -121,6 +187,20 @@ impl NewOffers {
 #[derive(Clone, Copy)]
 pub struct GetLatestOffers;
 
+/// Get the latest offers as they were sent to the given taker.
+///
+/// Empty if no offers were sent to the taker since it connected.
+#[derive(Clone, Copy)]
+pub struct GetOffersFor(pub PeerId);
+
+/// Adjust the offers sent to a taker, e.g. to charge them a different opening fee.
+///
+/// Has to return the offers with the same ids, as takers refer to them by id when placing orders.
+pub struct TailorOffers {
+    pub peer_id: PeerId,
+    pub offers: Vec<model::Offer>,
+}
+
 #[derive(Clone, Default)]
 struct Offers(HashMap<(ContractSymbol, Position), model::Offer>);
 
@@ -1,11 +1,10 @@
-use asynchronous_codec::FramedRead;
+pub(crate) mod v1;
+
 use asynchronous_codec::FramedWrite;
 use asynchronous_codec::JsonCodec;
 use asynchronous_codec::JsonCodecError;
-use futures::AsyncReadExt;
 use futures::AsyncWriteExt;
 use futures::SinkExt;
-use futures::StreamExt;
 use model::olivia::BitMexPriceEventId;
 use model::ContractSymbol;
 use model::Contracts;
@@ -18,11 +17,28 @@ use model::Position;
 use model::Price;
 use model::Timestamp;
 use model::TxFeeRate;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt;
 use time::Duration;
+use xtra_libp2p_request_response::Json;
+use xtra_libp2p_request_response::Protocol;
+
+/// The offer protocol as of [`Version::V3`](crate::Version::V3).
+///
+/// The maker sends all offers as request, the taker acknowledges them with an empty response.
+pub struct OfferProtocol;
+
+impl Protocol for OfferProtocol {
+    type Request = Offers;
+    type Response = ();
+    type Codec = Json;
+
+    const VERSIONS: &'static [&'static str] = &[crate::VERSIONS.latest().0];
+}
 
+/// Send the offers to a taker speaking [`Version::V2`](crate::Version::V2).
 pub(crate) async fn send<S>(sink: S, offers: Offers) -> Result<(), JsonCodecError>
 where
     S: AsyncWriteExt + Unpin,
@@ -34,24 +50,11 @@ where
     Ok(())
 }
 
-pub(crate) async fn recv<S>(stream: S) -> Result<Offers, ReceiveError>
-where
-    S: AsyncReadExt + Unpin,
-{
-    let mut framed = FramedRead::new(stream, JsonCodec::<(), Offers>::new());
-
-    let offers = framed.next().await.ok_or(ReceiveError::Terminated)??;
-
-    MESSAGES_RECEIVED.inc();
-
-    Ok(offers)
-}
-
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
-pub(crate) struct Offers(Vec<Offer>);
+pub struct Offers(Vec<Offer>);
 
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
-pub(crate) struct Offer {
+struct Offer {
     id: OfferId,
     contract_symbol: ContractSymbol,
     position_maker: Position,
@@ -65,6 +68,9 @@ pub(crate) struct Offer {
     tx_fee_rate: TxFeeRate,
     funding_rate: FundingRate,
     opening_fee: OpeningFee,
+    /// Not sent by makers which predate fee schedules.
+    #[serde(default)]
+    opening_fee_rate: Decimal,
     lot_size: LotSize,
 }
 
@@ -84,6 +90,7 @@ impl From<model::Offer> for Offer {
             tx_fee_rate: offer.tx_fee_rate,
             funding_rate: offer.funding_rate,
             opening_fee: offer.opening_fee,
+            opening_fee_rate: offer.opening_fee_rate,
             lot_size: offer.lot_size,
         }
     }
@@ -105,6 +112,7 @@ impl From<Offer> for model::Offer {
             tx_fee_rate: offer.tx_fee_rate,
             funding_rate: offer.funding_rate,
             opening_fee: offer.opening_fee,
+            opening_fee_rate: offer.opening_fee_rate,
             lot_size: offer.lot_size,
         }
     }
@@ -132,23 +140,16 @@ impl fmt::Debug for Offer {
     }
 }
 
-#[derive(Debug, thiserror::Error)]
-pub(crate) enum ReceiveError {
-    #[error("The stream has terminated")]
-    Terminated,
-    #[error("Failed to decode maker offers")]
-    Decode(#[from] JsonCodecError),
-}
-
-static MESSAGES_SENT: conquer_once::Lazy<prometheus::IntCounter> = conquer_once::Lazy::new(|| {
-    prometheus::register_int_counter!(
-        "offer_messages_sent_total_v2",
-        "The number of offer messages sent over the libp2p connection.",
-    )
-    .unwrap()
-});
+pub(crate) static MESSAGES_SENT: conquer_once::Lazy<prometheus::IntCounter> =
+    conquer_once::Lazy::new(|| {
+        prometheus::register_int_counter!(
+            "offer_messages_sent_total_v2",
+            "The number of offer messages sent over the libp2p connection.",
+        )
+        .unwrap()
+    });
 
-static MESSAGES_RECEIVED: conquer_once::Lazy<prometheus::IntCounter> =
+pub(crate) static MESSAGES_RECEIVED: conquer_once::Lazy<prometheus::IntCounter> =
     conquer_once::Lazy::new(|| {
         prometheus::register_int_counter!(
             "offer_messages_received_total",
@@ -161,6 +162,8 @@ static MESSAGES_RECEIVED: conquer_once::Lazy<prometheus::IntCounter> =
 mod tests {
     use super::*;
     use crate::tests::dummy_offers;
+    use asynchronous_codec::FramedRead;
+    use futures::StreamExt;
     use sluice::pipe::pipe;
 
     #[tokio::test]
@@ -169,10 +172,16 @@ mod tests {
 
         let maker_offers = dummy_offers();
 
-        let (send_res, recv_res) =
-            tokio::join!(send(sink, Offers::from(maker_offers.clone())), recv(stream));
+        let mut framed = FramedRead::new(stream, JsonCodec::<(), Offers>::new());
+        let (send_res, recv_res) = tokio::join!(
+            send(sink, Offers::from(maker_offers.clone())),
+            framed.next()
+        );
 
         assert!(send_res.is_ok());
-        assert_eq!(maker_offers, Vec::<model::Offer>::from(recv_res.unwrap()))
+        assert_eq!(
+            maker_offers,
+            Vec::<model::Offer>::from(recv_res.unwrap().unwrap())
+        )
     }
 }
@@ -15,7 +15,6 @@ use model::Position;
 use model::Price;
 use model::Timestamp;
 use model::TxFeeRate;
-use nonempty::NonEmpty;
 use serde::Deserialize;
 use serde::Serialize;
 use time::Duration;
@@ -86,23 +85,24 @@ impl From<model::Offer> for Offer {
 }
 
 impl MakerOffers {
-    pub(crate) fn new(offers: NonEmpty<model::Offer>) -> Option<Self> {
-        // We can safely assume that the new offers all have the same `tx_fee_rate`, because this
-        // field is redundant across offers
-        let tx_fee_rate = offers.first().tx_fee_rate;
-
+    pub(crate) fn new(offers: &[model::Offer]) -> Option<Self> {
         // This version of the protocol caters to takers that only support BTCUSD CFDs
-        let mut offers = offers
-            .iter()
-            .filter(|offer| offer.contract_symbol == ContractSymbol::BtcUsd);
+        let btcusd_offer = |position_maker| {
+            offers
+                .iter()
+                .find(|offer| {
+                    offer.contract_symbol == ContractSymbol::BtcUsd
+                        && offer.position_maker == position_maker
+                })
+                .map(|offer| Offer::from(offer.clone()))
+        };
 
-        let long = offers.find_map(|offer| {
-            (offer.position_maker == Position::Long).then(|| Offer::from(offer.clone()))
-        })?;
-        let short = offers.find_map(|offer| {
-            (offer.position_maker == Position::Short).then(|| Offer::from(offer.clone()))
-        })?;
+        let long = btcusd_offer(Position::Long)?;
+        let short = btcusd_offer(Position::Short)?;
 
+        // We can safely assume that the offers all have the same `tx_fee_rate`, because this
+        // field is redundant across offers
+        let tx_fee_rate = long.tx_fee_rate;
         let funding_rate_long = long.funding_rate;
         let funding_rate_short = short.funding_rate;
 
@@ -123,3 +123,20 @@ static MESSAGES_SENT: conquer_once::Lazy<prometheus::IntCounter> = conquer_once:
     )
     .unwrap()
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::dummy_offers;
+
+    #[test]
+    fn picks_btcusd_offers_regardless_of_order() {
+        let mut offers = dummy_offers();
+        offers.reverse();
+
+        let maker_offers = MakerOffers::new(&offers).unwrap();
+
+        assert_eq!(maker_offers.long.unwrap().position_maker, Position::Long);
+        assert_eq!(maker_offers.short.unwrap().position_maker, Position::Short);
+    }
+}
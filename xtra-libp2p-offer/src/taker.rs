@@ -0,0 +1,48 @@
+use crate::protocol;
+use crate::OfferProtocol;
+use async_trait::async_trait;
+use tracing::Instrument;
+use xtra::prelude::MessageChannel;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p_request_response::listener;
+use xtra_libp2p_request_response::listener::RequestHandler;
+
+/// Receives the maker's offers, to be registered as handler for the latest offer protocol.
+pub type Actor = listener::Actor<OfferProtocol, Handler>;
+
+/// Forwards the offers received from the maker.
+pub struct Handler {
+    maker_offers: MessageChannel<LatestOffers, ()>,
+}
+
+impl Handler {
+    pub fn new(maker_offers: MessageChannel<LatestOffers, ()>) -> Self {
+        Self { maker_offers }
+    }
+}
+
+#[async_trait]
+impl RequestHandler<OfferProtocol> for Handler {
+    async fn handle(
+        &mut self,
+        peer_id: PeerId,
+        _: &'static str,
+        offers: protocol::Offers,
+    ) -> anyhow::Result<()> {
+        protocol::MESSAGES_RECEIVED.inc();
+
+        tracing::debug!(?offers, "Received offers");
+
+        let span = tracing::debug_span!("Received new offers from maker", %peer_id);
+        self.maker_offers
+            .send(LatestOffers(offers.into()))
+            .instrument(span)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Message used to inform other actors about the maker's latest
+/// offers.
+pub struct LatestOffers(pub Vec<model::Offer>);
@@ -13,7 +13,6 @@ mod tests {
     use futures::Future;
     use futures::FutureExt;
     use std::collections::HashSet;
-    use std::sync::Arc;
     use std::time::Duration;
     use tracing_subscriber::util::SubscriberInitExt;
     use xtra::spawn::TokioGlobalSpawnExt;
@@ -26,6 +25,7 @@ mod tests {
     use xtra_libp2p::libp2p::transport::MemoryTransport;
     use xtra_libp2p::libp2p::Multiaddr;
     use xtra_libp2p::libp2p::PeerId;
+    use xtra_libp2p::limits::ConnectionLimits;
     use xtra_libp2p::Connect;
     use xtra_libp2p::Endpoint;
     use xtra_libp2p::ListenOn;
@@ -103,7 +103,8 @@ mod tests {
                 vec![],
                 vec![],
             ),
-            Arc::new(HashSet::default()),
+            HashSet::default(),
+            ConnectionLimits::default(),
         );
 
         #[allow(clippy::disallowed_methods)]
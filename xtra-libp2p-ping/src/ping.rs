@@ -95,10 +95,11 @@ struct RecordLatency {
     latency: Duration,
 }
 
-/// Private message to get the latency of a peer.
-///
-/// Primarily used for testing. May be exposed publicly at some point.
-pub(crate) struct GetLatency(pub PeerId);
+/// Get the latency of a peer, as measured by the last ping.
+pub struct GetLatency(pub PeerId);
+
+/// Get the latencies of all peers which responded to the last ping.
+pub struct GetLatencies;
 
 #[xtra_productivity]
 impl Actor {
@@ -156,6 +157,10 @@ impl Actor {
     async fn handle(&mut self, GetLatency(peer): GetLatency) -> Option<Duration> {
         return self.latencies.get(&peer).copied();
     }
+
+    async fn handle(&mut self, _: GetLatencies) -> HashMap<PeerId, Duration> {
+        self.latencies.clone()
+    }
 }
 
 #[xtra_productivity]
@@ -0,0 +1,186 @@
+use crate::Error;
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The serialisation format of the messages of a [`Protocol`](crate::Protocol).
+pub trait Codec: Send + Sync + 'static {
+    fn encode<T: Serialize>(item: &T) -> anyhow::Result<Vec<u8>>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T>;
+}
+
+/// Encodes messages as JSON, which is easy to debug but verbose.
+#[derive(Clone, Copy, Debug)]
+pub enum Json {}
+
+impl Codec for Json {
+    fn encode<T: Serialize>(item: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(item)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Encodes messages in the compact binary format of `bincode`.
+///
+/// Messages are not self-describing, hence any change to a message type requires a new protocol
+/// version.
+#[derive(Clone, Copy, Debug)]
+pub enum Bincode {}
+
+impl Codec for Bincode {
+    fn encode<T: Serialize>(item: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(bincode::serialize(item)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Encodes messages as CBOR, which is self-describing like JSON but considerably more compact,
+/// especially for binary data such as keys and signatures.
+#[derive(Clone, Copy, Debug)]
+pub enum Cbor {}
+
+impl Codec for Cbor {
+    fn encode<T: Serialize>(item: &T) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(item, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+        Ok(ciborium::de::from_reader(bytes)?)
+    }
+}
+
+/// Write a single message, prefixed with its length as big-endian `u32`.
+pub(crate) async fn write<C, T, S>(stream: &mut S, item: &T, max_size: usize) -> Result<(), Error>
+where
+    C: Codec,
+    T: Serialize,
+    S: AsyncWrite + Unpin,
+{
+    let bytes = C::encode(item).map_err(Error::Encode)?;
+    let length = check_size(bytes.len(), max_size)?;
+
+    stream.write_all(&length.to_be_bytes()).await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Read a single message written by [`write`].
+pub(crate) async fn read<C, T, S>(stream: &mut S, max_size: usize) -> Result<T, Error>
+where
+    C: Codec,
+    T: DeserializeOwned,
+    S: AsyncRead + Unpin,
+{
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length).await?;
+    let length = u32::from_be_bytes(length) as usize;
+    check_size(length, max_size)?;
+
+    let mut bytes = vec![0u8; length];
+    stream.read_exact(&mut bytes).await?;
+
+    C::decode(&bytes).map_err(Error::Decode)
+}
+
+pub(crate) fn check_size(size: usize, max_size: usize) -> Result<u32, Error> {
+    if size > max_size {
+        return Err(Error::MessageTooLarge { size, max_size });
+    }
+
+    u32::try_from(size).map_err(|_| Error::MessageTooLarge { size, max_size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        text: String,
+        numbers: Vec<u64>,
+    }
+
+    fn message() -> Message {
+        Message {
+            text: "hello".to_owned(),
+            numbers: vec![1, 2, 3],
+        }
+    }
+
+    #[tokio::test]
+    async fn roundtrip_json() {
+        let mut stream = Cursor::new(Vec::new());
+
+        write::<Json, _, _>(&mut stream, &message(), 1024)
+            .await
+            .unwrap();
+        stream.set_position(0);
+        let decoded = read::<Json, Message, _>(&mut stream, 1024).await.unwrap();
+
+        assert_eq!(decoded, message());
+    }
+
+    #[tokio::test]
+    async fn roundtrip_bincode() {
+        let mut stream = Cursor::new(Vec::new());
+
+        write::<Bincode, _, _>(&mut stream, &message(), 1024)
+            .await
+            .unwrap();
+        stream.set_position(0);
+        let decoded = read::<Bincode, Message, _>(&mut stream, 1024)
+            .await
+            .unwrap();
+
+        assert_eq!(decoded, message());
+    }
+
+    #[tokio::test]
+    async fn roundtrip_cbor() {
+        let mut stream = Cursor::new(Vec::new());
+
+        write::<Cbor, _, _>(&mut stream, &message(), 1024)
+            .await
+            .unwrap();
+        stream.set_position(0);
+        let decoded = read::<Cbor, Message, _>(&mut stream, 1024).await.unwrap();
+
+        assert_eq!(decoded, message());
+    }
+
+    #[tokio::test]
+    async fn rejects_messages_above_max_size() {
+        let mut stream = Cursor::new(Vec::new());
+
+        write::<Json, _, _>(&mut stream, &message(), 1024)
+            .await
+            .unwrap();
+        stream.set_position(0);
+        let result = read::<Json, Message, _>(&mut stream, 4).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::MessageTooLarge { max_size: 4, .. })
+        ));
+        assert!(matches!(
+            write::<Json, _, _>(&mut Cursor::new(Vec::new()), &message(), 4).await,
+            Err(Error::MessageTooLarge { max_size: 4, .. })
+        ));
+    }
+}
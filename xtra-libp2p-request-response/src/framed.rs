@@ -0,0 +1,200 @@
+//! Codecs for [`asynchronous_codec::Framed`], for protocols exchanging more than a single request
+//! and response over a substream.
+
+use crate::codec::check_size;
+use crate::codec::Cbor;
+use crate::codec::Codec;
+use asynchronous_codec::BytesMut;
+use asynchronous_codec::Decoder;
+use asynchronous_codec::Encoder;
+use asynchronous_codec::JsonCodec;
+use asynchronous_codec::JsonCodecError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+
+/// The maximum size of a single CBOR message.
+///
+/// DLC messages carry adaptor signatures for every payout of every announcement, hence this is
+/// rather generous.
+pub const MAX_CBOR_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
+/// How the messages of a substream are encoded, usually determined by the negotiated protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Plain JSON without any framing, compatible with [`asynchronous_codec::JsonCodec`].
+    Json,
+    /// [`Cbor`], prefixed with the length of the message.
+    Cbor,
+}
+
+/// Encodes `Enc` and decodes `Dec` in the given [`Encoding`].
+pub struct FramedCodec<Enc, Dec> {
+    inner: Inner<Enc, Dec>,
+}
+
+enum Inner<Enc, Dec> {
+    Json(JsonCodec<Enc, Dec>),
+    Cbor(PhantomData<(Enc, Dec)>),
+}
+
+impl<Enc, Dec> FramedCodec<Enc, Dec> {
+    pub fn new(encoding: Encoding) -> Self {
+        let inner = match encoding {
+            Encoding::Json => Inner::Json(JsonCodec::new()),
+            Encoding::Cbor => Inner::Cbor(PhantomData),
+        };
+
+        Self { inner }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Json(#[from] JsonCodecError),
+    #[error("Failed to read or write message")]
+    Io(#[from] std::io::Error),
+    #[error("Message of {size} bytes exceeds maximum of {max_size} bytes")]
+    MessageTooLarge { size: usize, max_size: usize },
+    #[error("Failed to encode message")]
+    Encode(#[source] anyhow::Error),
+    #[error("Failed to decode message")]
+    Decode(#[source] anyhow::Error),
+}
+
+impl From<crate::Error> for Error {
+    fn from(e: crate::Error) -> Self {
+        match e {
+            crate::Error::MessageTooLarge { size, max_size } => {
+                Error::MessageTooLarge { size, max_size }
+            }
+            e => Error::Encode(e.into()),
+        }
+    }
+}
+
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+impl<Enc, Dec> Encoder for FramedCodec<Enc, Dec>
+where
+    Enc: Serialize + 'static,
+    Dec: DeserializeOwned + 'static,
+{
+    type Item = Enc;
+    type Error = Error;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match &mut self.inner {
+            Inner::Json(codec) => codec.encode(item, dst)?,
+            Inner::Cbor(_) => {
+                let bytes = Cbor::encode(&item).map_err(Error::Encode)?;
+                let length = check_size(bytes.len(), MAX_CBOR_MESSAGE_SIZE)?;
+
+                dst.reserve(LENGTH_PREFIX_SIZE + bytes.len());
+                dst.extend_from_slice(&length.to_be_bytes());
+                dst.extend_from_slice(&bytes);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<Enc, Dec> Decoder for FramedCodec<Enc, Dec>
+where
+    Enc: Serialize + 'static,
+    Dec: DeserializeOwned + 'static,
+{
+    type Item = Dec;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match &mut self.inner {
+            Inner::Json(codec) => Ok(codec.decode(src)?),
+            Inner::Cbor(_) => {
+                if src.len() < LENGTH_PREFIX_SIZE {
+                    return Ok(None);
+                }
+
+                let mut length = [0u8; LENGTH_PREFIX_SIZE];
+                length.copy_from_slice(&src[..LENGTH_PREFIX_SIZE]);
+                let length = u32::from_be_bytes(length) as usize;
+                check_size(length, MAX_CBOR_MESSAGE_SIZE)?;
+
+                if src.len() < LENGTH_PREFIX_SIZE + length {
+                    src.reserve(LENGTH_PREFIX_SIZE + length - src.len());
+                    return Ok(None);
+                }
+
+                let frame = src.split_to(LENGTH_PREFIX_SIZE + length);
+                let item = Cbor::decode(&frame[LENGTH_PREFIX_SIZE..]).map_err(Error::Decode)?;
+
+                Ok(Some(item))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Hello { name: String },
+        Signature(Vec<u8>),
+    }
+
+    #[test]
+    fn roundtrip_both_encodings() {
+        for encoding in [Encoding::Json, Encoding::Cbor] {
+            let mut codec = FramedCodec::<Message, Message>::new(encoding);
+            let mut buffer = BytesMut::new();
+
+            codec
+                .encode(
+                    Message::Hello {
+                        name: "Bob".to_owned(),
+                    },
+                    &mut buffer,
+                )
+                .unwrap();
+            codec
+                .encode(Message::Signature(vec![1; 64]), &mut buffer)
+                .unwrap();
+
+            assert_eq!(
+                codec.decode(&mut buffer).unwrap(),
+                Some(Message::Hello {
+                    name: "Bob".to_owned()
+                })
+            );
+            assert_eq!(
+                codec.decode(&mut buffer).unwrap(),
+                Some(Message::Signature(vec![1; 64]))
+            );
+            assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn cbor_waits_for_complete_frame() {
+        let mut codec = FramedCodec::<Message, Message>::new(Encoding::Cbor);
+        let mut buffer = BytesMut::new();
+        codec
+            .encode(Message::Signature(vec![1; 64]), &mut buffer)
+            .unwrap();
+        let mut partial = buffer.split_to(buffer.len() / 2);
+
+        assert_eq!(codec.decode(&mut partial).unwrap(), None);
+
+        partial.unsplit(buffer);
+
+        assert_eq!(
+            codec.decode(&mut partial).unwrap(),
+            Some(Message::Signature(vec![1; 64]))
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Typed request/response protocols on top of [`xtra_libp2p`].
+//!
+//! A protocol is defined by implementing [`Protocol`], which ties together the request and
+//! response types, the [`Codec`] used to serialise them and the supported protocol versions.
+//!
+//! The dialer sends a single request per substream through [`request`] and waits for the
+//! response. Dialers which also speak older versions of a protocol not built on this crate
+//! negotiate the substream themselves and use [`request_on_substream`] if the peer supports one
+//! of the [`Protocol::VERSIONS`]. The listener registers a [`listener::Actor`] as inbound substream handler for all
+//! [`Protocol::VERSIONS`], which hands every request to a [`listener::RequestHandler`].
+//!
+//! Messages are framed with a length prefix, thus the wire format is _not_ compatible with the
+//! protocols using `asynchronous_codec::JsonCodec`. Protocols exchanging several messages over
+//! one substream can use the codecs in [`framed`] instead.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Duration;
+use tokio_extras::FutureExt;
+use xtra::Address;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::OpenSubstream;
+use xtra_libp2p::Substream;
+
+pub mod codec;
+pub mod framed;
+pub mod listener;
+
+pub use codec::Bincode;
+pub use codec::Cbor;
+pub use codec::Codec;
+pub use codec::Json;
+
+/// How long to wait for the response to a request, unless the protocol specifies otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The maximum size of a single message, unless the protocol specifies otherwise.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// A protocol consisting of a single request, answered by a single response.
+pub trait Protocol: Send + Sync + 'static {
+    type Request: Serialize + DeserializeOwned + Send + 'static;
+    type Response: Serialize + DeserializeOwned + Send + 'static;
+    type Codec: Codec;
+
+    /// The supported versions of the protocol, e.g. `/itchysats/foo/1.0.0`.
+    ///
+    /// The dialer tries the versions **in order**, hence the preferred version should come first.
+    const VERSIONS: &'static [&'static str];
+
+    /// How long to wait for a message before giving up.
+    const TIMEOUT: Duration = DEFAULT_TIMEOUT;
+
+    /// Messages exceeding this size are rejected, protecting the receiver from running out of
+    /// memory.
+    const MAX_MESSAGE_SIZE: usize = DEFAULT_MAX_MESSAGE_SIZE;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to open substream")]
+    OpenSubstream(#[from] xtra_libp2p::Error),
+    #[error("Endpoint is disconnected")]
+    EndpointDisconnected(#[from] xtra::Error),
+    #[error("No response within {0:?}")]
+    Timeout(Duration),
+    #[error("Failed to read or write message")]
+    Io(#[from] std::io::Error),
+    #[error("Message of {size} bytes exceeds maximum of {max_size} bytes")]
+    MessageTooLarge { size: usize, max_size: usize },
+    #[error("Failed to encode message")]
+    Encode(#[source] anyhow::Error),
+    #[error("Failed to decode message")]
+    Decode(#[source] anyhow::Error),
+    #[error("Peer failed to handle request: {0}")]
+    Rejected(String),
+}
+
+/// Send a request to the peer, using the first of the [`Protocol::VERSIONS`] the peer supports.
+///
+/// Returns the negotiated protocol version together with the response. Fails if no response is
+/// received within [`Protocol::TIMEOUT`].
+pub async fn request<P>(
+    endpoint: &Address<Endpoint>,
+    peer_id: PeerId,
+    request: P::Request,
+) -> Result<(&'static str, P::Response), Error>
+where
+    P: Protocol,
+{
+    request_with_timeout::<P>(endpoint, peer_id, request, P::TIMEOUT).await
+}
+
+/// Like [`request`], overriding [`Protocol::TIMEOUT`] for this request.
+pub async fn request_with_timeout<P>(
+    endpoint: &Address<Endpoint>,
+    peer_id: PeerId,
+    request: P::Request,
+    timeout: Duration,
+) -> Result<(&'static str, P::Response), Error>
+where
+    P: Protocol,
+{
+    async {
+        let (version, stream) = endpoint
+            .send(OpenSubstream::multiple_protocols(
+                peer_id,
+                P::VERSIONS.to_vec(),
+            ))
+            .await??
+            .await?;
+
+        let response = exchange::<P>(stream, request).await?;
+
+        Ok((version, response))
+    }
+    .timeout(timeout, || tracing::debug_span!("Request"))
+    .await
+    .map_err(|_| Error::Timeout(timeout))?
+}
+
+/// Send a request over a substream which was negotiated for one of the [`Protocol::VERSIONS`].
+///
+/// Fails if no response is received within [`Protocol::TIMEOUT`].
+pub async fn request_on_substream<P>(
+    stream: Substream,
+    request: P::Request,
+) -> Result<P::Response, Error>
+where
+    P: Protocol,
+{
+    exchange::<P>(stream, request)
+        .timeout(P::TIMEOUT, || tracing::debug_span!("Request"))
+        .await
+        .map_err(|_| Error::Timeout(P::TIMEOUT))?
+}
+
+async fn exchange<P>(mut stream: Substream, request: P::Request) -> Result<P::Response, Error>
+where
+    P: Protocol,
+{
+    codec::write::<P::Codec, _, _>(&mut stream, &request, P::MAX_MESSAGE_SIZE).await?;
+    let response =
+        codec::read::<P::Codec, Result<P::Response, String>, _>(&mut stream, P::MAX_MESSAGE_SIZE)
+            .await?
+            .map_err(Error::Rejected)?;
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listener::RequestHandler;
+    use async_trait::async_trait;
+    use serde::Deserialize;
+    use std::collections::HashSet;
+    use xtra::spawn::TokioGlobalSpawnExt;
+    use xtra::Actor as _;
+    use xtra::Context;
+    use xtra_libp2p::endpoint::Subscribers;
+    use xtra_libp2p::libp2p::identity::Keypair;
+    use xtra_libp2p::libp2p::multiaddr::Protocol as MultiaddrProtocol;
+    use xtra_libp2p::libp2p::transport::MemoryTransport;
+    use xtra_libp2p::libp2p::Multiaddr;
+    use xtra_libp2p::limits::ConnectionLimits;
+    use xtra_libp2p::Connect;
+    use xtra_libp2p::GetConnectionStats;
+    use xtra_libp2p::ListenOn;
+
+    struct Greet;
+
+    impl Protocol for Greet {
+        type Request = GreetRequest;
+        type Response = GreetResponse;
+        type Codec = Json;
+
+        const VERSIONS: &'static [&'static str] = &["/greet/2.0.0", "/greet/1.0.0"];
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct GreetRequest {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct GreetResponse {
+        greeting: String,
+    }
+
+    struct Greeter;
+
+    #[async_trait]
+    impl RequestHandler<Greet> for Greeter {
+        async fn handle(
+            &mut self,
+            _: PeerId,
+            version: &'static str,
+            request: GreetRequest,
+        ) -> anyhow::Result<GreetResponse> {
+            anyhow::ensure!(!request.name.is_empty(), "Name must not be empty");
+
+            Ok(GreetResponse {
+                greeting: format!("Hello {} via {version}!", request.name),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn request_is_answered_through_handler() {
+        let (alice, bob) = connected_alice_and_bob(["/greet/2.0.0", "/greet/1.0.0"]).await;
+
+        let (version, response) = request::<Greet>(
+            &bob,
+            alice,
+            GreetRequest {
+                name: "Bob".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(version, "/greet/2.0.0");
+        assert_eq!(
+            response,
+            GreetResponse {
+                greeting: "Hello Bob via /greet/2.0.0!".to_owned()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_older_version() {
+        let (alice, bob) = connected_alice_and_bob(["/greet/1.0.0"]).await;
+
+        let (version, _) = request::<Greet>(
+            &bob,
+            alice,
+            GreetRequest {
+                name: "Bob".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(version, "/greet/1.0.0");
+    }
+
+    #[tokio::test]
+    async fn handler_error_is_returned_to_dialer() {
+        let (alice, bob) = connected_alice_and_bob(["/greet/2.0.0", "/greet/1.0.0"]).await;
+
+        let result = request::<Greet>(
+            &bob,
+            alice,
+            GreetRequest {
+                name: "".to_owned(),
+            },
+        )
+        .await;
+
+        assert!(
+            matches!(result, Err(Error::Rejected(reason)) if reason == "Name must not be empty")
+        );
+    }
+
+    /// Returns the peer ID of alice, which listens for the given versions, and the endpoint of
+    /// bob, connected to alice.
+    async fn connected_alice_and_bob<const N: usize>(
+        alice_versions: [&'static str; N],
+    ) -> (PeerId, Address<Endpoint>) {
+        let port = rand::random::<u64>();
+        let alice_id = Keypair::generate_ed25519();
+        let alice_peer_id = alice_id.public().to_peer_id();
+
+        let greeter = listener::Actor::<Greet, _>::new(Greeter)
+            .create(None)
+            .spawn_global();
+        let (alice, alice_context) = Context::new(None);
+        spawn_endpoint(
+            alice_context,
+            alice_id,
+            alice_versions.map(|version| (version, greeter.clone().into())),
+        );
+        alice
+            .send(ListenOn(
+                Multiaddr::empty().with(MultiaddrProtocol::Memory(port)),
+            ))
+            .await
+            .unwrap();
+
+        let (bob, bob_context) = Context::new(None);
+        spawn_endpoint(bob_context, Keypair::generate_ed25519(), []);
+        bob.send(Connect(
+            Multiaddr::empty()
+                .with(MultiaddrProtocol::Memory(port))
+                .with(MultiaddrProtocol::P2p(alice_peer_id.into())),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+
+        while !bob
+            .send(GetConnectionStats)
+            .await
+            .unwrap()
+            .connected_peers
+            .contains(&alice_peer_id)
+        {
+            tokio_extras::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        (alice_peer_id, bob)
+    }
+
+    fn spawn_endpoint<const N: usize>(
+        context: Context<Endpoint>,
+        identity: Keypair,
+        handlers: [(
+            &'static str,
+            xtra::message_channel::MessageChannel<xtra_libp2p::NewInboundSubstream, ()>,
+        ); N],
+    ) {
+        let endpoint = Endpoint::new(
+            Box::new(MemoryTransport::default),
+            identity,
+            Duration::from_secs(10),
+            handlers,
+            Subscribers::default(),
+            HashSet::default(),
+            ConnectionLimits::default(),
+        );
+
+        #[allow(clippy::disallowed_methods)]
+        tokio::spawn(context.run(endpoint));
+    }
+}
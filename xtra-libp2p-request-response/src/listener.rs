@@ -0,0 +1,114 @@
+use crate::codec;
+use crate::Protocol;
+use async_trait::async_trait;
+use std::marker::PhantomData;
+use tokio_extras::FutureExt;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_productivity::xtra_productivity;
+
+/// Answers the requests of a [`Protocol`].
+#[async_trait]
+pub trait RequestHandler<P: Protocol>: Send + 'static {
+    /// Handle a request received through the given protocol `version`.
+    ///
+    /// An error is sent to the dialer instead of a response.
+    async fn handle(
+        &mut self,
+        peer_id: PeerId,
+        version: &'static str,
+        request: P::Request,
+    ) -> anyhow::Result<P::Response>;
+}
+
+/// Permanent actor to handle incoming substreams of a [`Protocol`].
+///
+/// The actor has to be registered as inbound substream handler for each of the
+/// [`Protocol::VERSIONS`] the listener supports.
+///
+/// Reading requests and writing responses happens in separate tasks, only the [`RequestHandler`]
+/// is invoked on the actor itself. Requests are thus handled one after the other.
+pub struct Actor<P, H> {
+    handler: H,
+    _protocol: PhantomData<P>,
+}
+
+impl<P, H> Actor<P, H> {
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler,
+            _protocol: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<P, H> xtra::Actor for Actor<P, H>
+where
+    P: Protocol,
+    H: RequestHandler<P>,
+{
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+/// Private message to handle a request which was read from a substream.
+struct RequestReceived<R> {
+    peer_id: PeerId,
+    version: &'static str,
+    request: R,
+}
+
+#[xtra_productivity]
+impl<P, H> Actor<P, H>
+where
+    P: Protocol,
+    H: RequestHandler<P>,
+{
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut xtra::Context<Self>) {
+        let NewInboundSubstream {
+            peer_id,
+            mut stream,
+        } = msg;
+        let this = ctx.address().expect("we are alive");
+        let version = stream.protocol();
+
+        tokio_extras::spawn_fallible(
+            &this.clone(),
+            async move {
+                let request =
+                    codec::read::<P::Codec, P::Request, _>(&mut stream, P::MAX_MESSAGE_SIZE)
+                        .timeout(P::TIMEOUT, || tracing::debug_span!("Receive request"))
+                        .await
+                        .map_err(|_| crate::Error::Timeout(P::TIMEOUT))??;
+
+                let response = this
+                    .send(RequestReceived {
+                        peer_id,
+                        version,
+                        request,
+                    })
+                    .await?
+                    .map_err(|e| format!("{e:#}"));
+
+                codec::write::<P::Codec, _, _>(&mut stream, &response, P::MAX_MESSAGE_SIZE).await?;
+
+                anyhow::Ok(())
+            },
+            move |e| async move {
+                tracing::warn!(%peer_id, %version, "Failed to answer request: {e:#}")
+            },
+        );
+    }
+
+    async fn handle(&mut self, msg: RequestReceived<P::Request>) -> anyhow::Result<P::Response> {
+        let RequestReceived {
+            peer_id,
+            version,
+            request,
+        } = msg;
+
+        self.handler.handle(peer_id, version, request).await
+    }
+}
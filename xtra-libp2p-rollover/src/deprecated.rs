@@ -1,5 +0,0 @@
-pub mod maker;
-pub mod protocol;
-pub mod taker;
-
-pub const PROTOCOL: &str = "/itchysats/rollover/2.0.0";
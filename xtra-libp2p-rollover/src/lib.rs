@@ -1,4 +1,43 @@
-mod current;
-pub mod deprecated;
+pub mod maker;
+pub mod protocol;
+pub mod taker;
 
-pub use current::*;
+use model::InverseMaxPrice;
+use xtra_libp2p::versions::Versions;
+use xtra_libp2p_request_response::framed::Encoding;
+
+/// The supported versions of the rollover protocol, newest first.
+pub const VERSIONS: Versions<Version> = Versions::new(&[
+    ("/itchysats/rollover/4.0.0", Version::V4),
+    ("/itchysats/rollover/3.0.0", Version::V3),
+    ("/itchysats/rollover/2.0.0", Version::V2),
+]);
+
+/// The versions of the rollover protocol.
+///
+/// All versions exchange the same messages, they only differ in how the messages are encoded and
+/// how the payouts are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// Inverse payout curves are capped at double the initial price.
+    V2,
+    V3,
+    /// Messages are encoded in CBOR instead of JSON.
+    V4,
+}
+
+impl Version {
+    pub(crate) fn encoding(&self) -> Encoding {
+        match self {
+            Version::V2 | Version::V3 => Encoding::Json,
+            Version::V4 => Encoding::Cbor,
+        }
+    }
+
+    pub(crate) fn inverse_max_price(&self) -> InverseMaxPrice {
+        match self {
+            Version::V2 => InverseMaxPrice::DoubleOfInitial,
+            Version::V3 | Version::V4 => InverseMaxPrice::OliviaMax,
+        }
+    }
+}
@@ -1,8 +1,9 @@
-use crate::current::protocol::*;
+use crate::protocol::*;
+use crate::Version;
+use crate::VERSIONS;
 use anyhow::Context;
 use async_trait::async_trait;
 use asynchronous_codec::Framed;
-use asynchronous_codec::JsonCodec;
 use bdk_ext::keypair;
 use futures::SinkExt;
 use futures::StreamExt;
@@ -15,6 +16,7 @@ use model::Role;
 use tokio_extras::FutureExt;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_libp2p::Substream;
+use xtra_libp2p_request_response::framed::FramedCodec;
 use xtra_productivity::xtra_productivity;
 
 /// Permanent actor to handle incoming substreams for the `/itchysats/rollover/2.0.0`
@@ -22,21 +24,23 @@ use xtra_productivity::xtra_productivity;
 ///
 /// There is only one instance of this actor for all connections, meaning we must always spawn a
 /// task whenever we interact with a substream to not block the execution of other connections.
-pub struct Actor<E, O, R> {
+pub struct Actor<E, O, R, P> {
     oracle_pk: XOnlyPublicKey,
     oracle: O,
     n_payouts: usize,
     executor: E,
     rates: R,
+    policy: P,
     is_accepting_rollovers: bool,
 }
 
-impl<E, O, R> Actor<E, O, R> {
+impl<E, O, R, P> Actor<E, O, R, P> {
     pub fn new(
         executor: E,
         oracle_pk: XOnlyPublicKey,
         oracle: O,
         rates: R,
+        policy: P,
         n_payouts: usize,
     ) -> Self {
         Self {
@@ -45,17 +49,19 @@ impl<E, O, R> Actor<E, O, R> {
             n_payouts,
             executor,
             rates,
+            policy,
             is_accepting_rollovers: true,
         }
     }
 }
 
 #[async_trait]
-impl<E, O, R> xtra::Actor for Actor<E, O, R>
+impl<E, O, R, P> xtra::Actor for Actor<E, O, R, P>
 where
     E: Send + Sync + 'static,
     O: Send + Sync + 'static,
     R: Send + Sync + 'static,
+    P: Send + Sync + 'static,
 {
     type Stop = ();
 
@@ -63,11 +69,12 @@ where
 }
 
 #[xtra_productivity]
-impl<E, O, R> Actor<E, O, R>
+impl<E, O, R, P> Actor<E, O, R, P>
 where
     E: ExecuteOnCfd + Clone + Send + Sync + 'static,
     O: GetAnnouncements + Clone + Send + Sync + 'static,
     R: GetRates + Clone + Send + Sync + 'static,
+    P: DecideRollover + Clone + Send + Sync + 'static,
 {
     async fn handle(&mut self, msg: UpdateConfiguration) {
         self.is_accepting_rollovers = msg.is_accepting_rollovers;
@@ -75,11 +82,12 @@ where
 }
 
 #[xtra_productivity]
-impl<E, O, R> Actor<E, O, R>
+impl<E, O, R, P> Actor<E, O, R, P>
 where
     E: ExecuteOnCfd + Clone + Send + Sync + 'static,
     O: GetAnnouncements + Clone + Send + Sync + 'static,
     R: GetRates + Clone + Send + Sync + 'static,
+    P: DecideRollover + Clone + Send + Sync + 'static,
 {
     async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut xtra::Context<Self>) {
         let NewInboundSubstream { peer_id, stream } = msg;
@@ -88,8 +96,13 @@ where
         tokio_extras::spawn_fallible(
             &address.clone(),
             async move {
-                let mut framed =
-                    Framed::new(stream, JsonCodec::<ListenerMessage, DialerMessage>::new());
+                let version = VERSIONS
+                    .get(stream.protocol())
+                    .context("Unsupported rollover protocol")?;
+                let mut framed = Framed::new(
+                    stream,
+                    FramedCodec::<ListenerMessage, DialerMessage>::new(version.encoding()),
+                );
 
                 let propose = framed
                     .next()
@@ -103,6 +116,7 @@ where
                         propose,
                         framed,
                         peer_id,
+                        version,
                     })
                     .await?;
 
@@ -119,19 +133,26 @@ where
             propose,
             mut framed,
             peer_id,
+            version,
         } = msg;
         let order_id = propose.order_id;
 
-        let (base_dlc_params, contract_symbol) = match self
+        let (base_dlc_params, proposal) = match self
             .executor
             .execute(order_id, |cfd| {
                 cfd.verify_counterparty_peer_id(&peer_id.into())?;
 
                 let (event, base_dlc_params) =
                     cfd.start_rollover_maker(propose.from_commit_txid)?;
-                let contract_symbol = cfd.contract_symbol();
+                let proposal = RolloverProposal {
+                    order_id,
+                    taker: peer_id.into(),
+                    contract_symbol: cfd.contract_symbol(),
+                    taker_position: cfd.position().counter_position(),
+                    quantity: cfd.quantity(),
+                };
 
-                Ok((event, base_dlc_params, contract_symbol))
+                Ok((event, base_dlc_params, proposal))
             })
             .await
             .context("Rollover failed after handling taker proposal")
@@ -147,9 +168,12 @@ where
             }
         };
 
+        let contract_symbol = proposal.contract_symbol;
+
         let this = ctx.address().expect("we are alive");
         if !self.is_accepting_rollovers {
-            emit_rejected(order_id, &self.executor).await;
+            let reason = RejectReason::NotAcceptingRollovers;
+            emit_rejected(order_id, Some(reason), &self.executor).await;
 
             tokio_extras::spawn_fallible(
                 &this,
@@ -157,6 +181,7 @@ where
                     framed
                         .send(ListenerMessage::Decision(Decision::Reject(Reject {
                             order_id,
+                            reason: Some(reason),
                         })))
                         .await
                 },
@@ -176,9 +201,33 @@ where
             let executor = self.executor.clone();
             let oracle = self.oracle.clone();
             let rates = self.rates.clone();
+            let policy = self.policy.clone();
             let oracle_pk = self.oracle_pk;
             let n_payouts = self.n_payouts;
             async move {
+                let funding_rate_multiplier = match policy
+                    .decide(proposal)
+                    .await
+                    .context("Failed to evaluate rollover policy")?
+                {
+                    RolloverDecision::Accept {
+                        funding_rate_multiplier,
+                    } => funding_rate_multiplier,
+                    RolloverDecision::Reject(reason) => {
+                        emit_rejected(order_id, Some(reason), &executor).await;
+
+                        framed
+                            .send(ListenerMessage::Decision(Decision::Reject(Reject {
+                                order_id,
+                                reason: Some(reason),
+                            })))
+                            .await
+                            .context("Failed to send reject rollover to the taker")?;
+
+                        return Ok(());
+                    }
+                };
+
                 let Rates {
                     funding_rate_long,
                     funding_rate_short,
@@ -186,7 +235,8 @@ where
                 } = rates
                     .get_rates(contract_symbol)
                     .await
-                    .context("Failed to get rates")?;
+                    .context("Failed to get rates")?
+                    .with_funding_rate_multiplier(funding_rate_multiplier)?;
 
                 let (rollover_params, dlc, position, oracle_event_ids, funding_rate) = executor
                     .execute(order_id, |cfd| {
@@ -232,6 +282,7 @@ where
                 let settlement_event_id = announcements.last().context("Empty to_event_ids")?.id;
 
                 let funding_fee = *rollover_params.funding_fee();
+                let hours_charged = rollover_params.hours_charged();
 
                 let our_role = Role::Maker;
                 let our_position = position;
@@ -278,6 +329,7 @@ where
                     punish_params,
                     Role::Maker,
                     contract_symbol,
+                    version,
                 )
                 .await?;
 
@@ -373,7 +425,15 @@ where
                     refund_timelock: rollover_params.refund_timelock,
                 };
 
-                emit_completed(order_id, dlc, funding_fee, complete_fee, &executor).await;
+                emit_completed(
+                    order_id,
+                    dlc,
+                    funding_fee,
+                    hours_charged,
+                    complete_fee,
+                    &executor,
+                )
+                .await;
 
                 Ok(())
             }
@@ -405,6 +465,7 @@ impl UpdateConfiguration {
 
 struct ProposeReceived {
     propose: Propose,
-    framed: Framed<Substream, JsonCodec<ListenerMessage, DialerMessage>>,
+    framed: Framed<Substream, FramedCodec<ListenerMessage, DialerMessage>>,
     peer_id: PeerId,
+    version: Version,
 }
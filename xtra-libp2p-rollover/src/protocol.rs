@@ -1,3 +1,4 @@
+use crate::Version;
 use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Context;
@@ -26,6 +27,7 @@ use model::shared_protocol::verify_cets;
 use model::shared_protocol::verify_signature;
 use model::Cet;
 use model::ContractSymbol;
+use model::Contracts;
 use model::Dlc;
 use model::ExecuteOnCfd;
 use model::FundingFee;
@@ -40,9 +42,11 @@ use model::Timestamp;
 use model::TransactionExt;
 use model::TxFeeRate;
 use model::CET_TIMELOCK;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::RangeInclusive;
 use std::time::Duration;
 
@@ -128,11 +132,40 @@ pub struct Confirm {
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Reject {
     pub order_id: OrderId,
+    /// Not sent by makers which predate rollover policies.
+    #[serde(default)]
+    pub reason: Option<RejectReason>,
+}
+
+/// Why the maker rejected a rollover.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// The maker does not accept any rollovers at the moment.
+    NotAcceptingRollovers,
+    /// The taker holds more contracts of the symbol than the maker is willing to roll over.
+    ExposureLimitExceeded,
+    /// Rollovers are only accepted during certain hours.
+    OutsideOfWindow,
+    /// The symbol is being wound down, CFDs are no longer extended.
+    SymbolWoundDown,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            RejectReason::NotAcceptingRollovers => "not accepting rollovers",
+            RejectReason::ExposureLimitExceeded => "exposure limit exceeded",
+            RejectReason::OutsideOfWindow => "outside of rollover window",
+            RejectReason::SymbolWoundDown => "symbol wound down",
+        };
+
+        f.write_str(reason)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
-pub(crate) enum RolloverMsg {
+pub enum RolloverMsg {
     Msg0(RolloverMsg0),
     Msg1(RolloverMsg1),
     Msg2(RolloverMsg2),
@@ -165,25 +198,25 @@ impl RolloverMsg {
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
-pub(crate) struct RolloverMsg0 {
+pub struct RolloverMsg0 {
     pub revocation_pk: PublicKey,
     pub publish_pk: PublicKey,
 }
 
 #[derive(Serialize, Deserialize)]
-pub(crate) struct RolloverMsg1 {
+pub struct RolloverMsg1 {
     pub commit: EcdsaAdaptorSignature,
     pub cets: HashMap<String, Vec<(RangeInclusive<u64>, EcdsaAdaptorSignature)>>,
     pub refund: Signature,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
-pub(crate) struct RolloverMsg2 {
+pub struct RolloverMsg2 {
     pub revocation_sk: SecretKey,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
-pub(crate) struct RolloverMsg3;
+pub struct RolloverMsg3;
 
 impl From<CfdTransactions> for RolloverMsg1 {
     fn from(txs: CfdTransactions) -> Self {
@@ -245,6 +278,7 @@ pub(crate) async fn emit_completed<E>(
     order_id: OrderId,
     dlc: Dlc,
     funding_fee: FundingFee,
+    hours_charged: u64,
     complete_fee: model::CompleteFee,
     executor: &E,
 ) where
@@ -252,7 +286,7 @@ pub(crate) async fn emit_completed<E>(
 {
     if let Err(e) = executor
         .execute(order_id, |cfd| {
-            Ok(cfd.complete_rollover(dlc, funding_fee, Some(complete_fee)))
+            Ok(cfd.complete_rollover(dlc, funding_fee, hours_charged, Some(complete_fee)))
         })
         .await
     {
@@ -260,14 +294,17 @@ pub(crate) async fn emit_completed<E>(
     }
 }
 
-pub(crate) async fn emit_rejected<E>(order_id: OrderId, executor: &E)
+pub(crate) async fn emit_rejected<E>(order_id: OrderId, reason: Option<RejectReason>, executor: &E)
 where
     E: ExecuteOnCfd,
 {
+    let reason = match reason {
+        Some(reason) => anyhow!("maker decision: {reason}"),
+        None => anyhow!("maker decision"),
+    };
+
     if let Err(e) = executor
-        .execute(order_id, |cfd| {
-            Ok(cfd.reject_rollover(anyhow!("maker decision")))
-        })
+        .execute(order_id, |cfd| Ok(cfd.reject_rollover(reason)))
         .await
     {
         tracing::error!(%order_id, "Failed to execute rollover rejected: {e:#}")
@@ -324,6 +361,7 @@ pub(crate) async fn build_own_cfd_transactions(
     punish_params: PunishParams,
     role: Role,
     contract_symbol: ContractSymbol,
+    version: Version,
 ) -> Result<CfdTransactions> {
     let sk = dlc.identity;
 
@@ -331,7 +369,7 @@ pub(crate) async fn build_own_cfd_transactions(
     let taker_lock_amount = dlc.taker_lock_amount;
 
     let payouts = match contract_symbol {
-        ContractSymbol::BtcUsd => Payouts::new_inverse_olivia_max(
+        ContractSymbol::BtcUsd => Payouts::new_inverse(
             (our_position, role),
             rollover_params.price,
             rollover_params.quantity,
@@ -341,6 +379,7 @@ pub(crate) async fn build_own_cfd_transactions(
             ),
             n_payouts,
             complete_fee,
+            version.inverse_max_price(),
         )?,
         ContractSymbol::EthUsd => Payouts::new_quanto(
             (our_position, role),
@@ -565,6 +604,31 @@ pub trait GetRates {
     async fn get_rates(&self, contract_symbol: ContractSymbol) -> Result<Rates>;
 }
 
+/// Decides whether to accept a rollover proposal, on top of the maker accepting rollovers at all.
+#[async_trait]
+pub trait DecideRollover {
+    async fn decide(&self, proposal: RolloverProposal) -> Result<RolloverDecision>;
+}
+
+/// A rollover proposed by a taker.
+#[derive(Debug, Clone, Copy)]
+pub struct RolloverProposal {
+    pub order_id: OrderId,
+    pub taker: model::libp2p::PeerId,
+    pub contract_symbol: ContractSymbol,
+    pub taker_position: Position,
+    pub quantity: Contracts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RolloverDecision {
+    Accept {
+        /// Applied to the funding rate the maker currently offers, if the taker pays it.
+        funding_rate_multiplier: Decimal,
+    },
+    Reject(RejectReason),
+}
+
 /// Set of rates needed to accept rollover proposals.
 #[derive(Clone, Copy)]
 pub struct Rates {
@@ -574,6 +638,32 @@ pub struct Rates {
 }
 
 impl Rates {
+    /// Scale the funding rates the taker pays, e.g. to give a taker a discount.
+    ///
+    /// `funding_rate_long` applies if the maker is long and hence the taker short, which pays if
+    /// the rate is negative. Conversely, the taker pays a positive `funding_rate_short`. Rates the
+    /// taker receives are left as they are, as a discount must not cut what the taker is paid.
+    pub(crate) fn with_funding_rate_multiplier(self, multiplier: Decimal) -> Result<Self> {
+        let scale = |rate: FundingRate| FundingRate::new(rate.to_decimal() * multiplier);
+
+        let funding_rate_long = if self.funding_rate_long.short_pays_long() {
+            scale(self.funding_rate_long)?
+        } else {
+            self.funding_rate_long
+        };
+        let funding_rate_short = if self.funding_rate_short.short_pays_long() {
+            self.funding_rate_short
+        } else {
+            scale(self.funding_rate_short)?
+        };
+
+        Ok(Self {
+            funding_rate_long,
+            funding_rate_short,
+            tx_fee_rate: self.tx_fee_rate,
+        })
+    }
+
     pub fn new(
         long_funding_rate: FundingRate,
         short_funding_rate: FundingRate,
@@ -1,5 +1,7 @@
-use crate::deprecated;
-use crate::deprecated::protocol::*;
+use crate::protocol::*;
+use crate::Version;
+use crate::VERSIONS;
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -14,6 +16,7 @@ use model::Dlc;
 use model::ExecuteOnCfd;
 use model::OrderId;
 use model::Role;
+use model::RolloverPolicyCheck;
 use model::Timestamp;
 use std::time::Duration;
 use tokio_extras::FutureExt;
@@ -21,6 +24,7 @@ use xtra::Address;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::OpenSubstream;
 use xtra_libp2p::Substream;
+use xtra_libp2p_request_response::framed::FramedCodec;
 use xtra_productivity::xtra_productivity;
 
 /// The duration that the taker waits until a decision (accept/reject) is expected from the maker
@@ -55,6 +59,9 @@ pub struct ProposeRollover {
     pub maker_peer_id: PeerId,
     pub from_commit_txid: Txid,
     pub from_settlement_event_id: BitMexPriceEventId,
+    /// The policy of an automatic rollover, which fails if the funding rate charged by the maker
+    /// violates it.
+    pub policy: Option<RolloverPolicyCheck>,
 }
 
 impl<E, O> Actor<E, O> {
@@ -76,20 +83,23 @@ impl<E, O> Actor<E, O> {
 }
 
 impl<E, O> Actor<E, O> {
-    async fn open_substream(&self, peer_id: PeerId) -> Result<Substream> {
-        let substream = self
+    async fn open_substream(&self, peer_id: PeerId) -> Result<(Version, Substream)> {
+        let (protocol, substream) = self
             .endpoint
-            .send(OpenSubstream::single_protocol(
+            .send(OpenSubstream::multiple_protocols(
                 peer_id.inner(),
-                deprecated::PROTOCOL,
+                VERSIONS.protocols(),
             ))
             .await
             .context("Endpoint is disconnected")?
             .context("No connection to peer")?
             .await
             .context("Failed to open substream")?;
+        let version = VERSIONS
+            .get(protocol)
+            .context("Negotiated unsupported rollover protocol")?;
 
-        Ok(substream)
+        Ok((version, substream))
     }
 }
 
@@ -105,14 +115,15 @@ where
             maker_peer_id,
             from_commit_txid,
             from_settlement_event_id,
+            policy,
         } = msg;
 
-        let substream = match self
+        let (version, substream) = match self
             .open_substream(maker_peer_id)
             .await
             .context("Failed to start rollover")
         {
-            Ok(substream) => substream,
+            Ok(opened) => opened,
             Err(e) => {
                 emit_failed(order_id, e, &self.executor).await;
                 return;
@@ -129,7 +140,7 @@ where
                 async move {
                     let mut framed = asynchronous_codec::Framed::new(
                         substream,
-                        asynchronous_codec::JsonCodec::<DialerMessage, ListenerMessage>::new(),
+                        FramedCodec::<DialerMessage, ListenerMessage>::new(version.encoding()),
                     );
 
                     let contract_symbol = executor
@@ -175,6 +186,22 @@ where
                         }) => {
                             let (rollover_params, dlc, position) = executor
                                 .execute(order_id, |cfd| {
+                                    if let Some(RolloverPolicyCheck {
+                                        policy,
+                                        completed_rollovers,
+                                        now,
+                                    }) = policy
+                                    {
+                                        if let Err(violation) = cfd.check_rollover_policy(
+                                            &policy,
+                                            completed_rollovers,
+                                            funding_rate,
+                                            now,
+                                        )? {
+                                            bail!("Funding rate charged by the maker violates the rollover policy: {violation}");
+                                        }
+                                    }
+
                                     cfd.handle_rollover_accepted_taker(
                                         tx_fee_rate,
                                         funding_rate,
@@ -194,6 +221,7 @@ where
                             tracing::info!(%order_id, "Rollover proposal got accepted");
 
                             let funding_fee = *rollover_params.funding_fee();
+                            let hours_charged = rollover_params.hours_charged();
                             let complete_fee_before_rollover =
                                 rollover_params.complete_fee_before_rollover();
                             let our_role = Role::Taker;
@@ -249,6 +277,7 @@ where
                                 punish_params,
                                 Role::Taker,
                                 contract_symbol,
+                                version,
                             )
                             .await?;
 
@@ -345,13 +374,14 @@ where
                                 order_id,
                                 dlc,
                                 funding_fee,
+                                hours_charged,
                                 complete_fee.into(),
                                 &executor,
                             )
                             .await;
                         }
-                        Decision::Reject(_) => {
-                            emit_rejected(order_id, &executor).await;
+                        Decision::Reject(Reject { reason, .. }) => {
+                            emit_rejected(order_id, reason, &executor).await;
                         }
                     }
                     Ok(())
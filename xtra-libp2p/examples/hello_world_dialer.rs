@@ -9,12 +9,12 @@ use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
 use libp2p_tcp::TokioTcpConfig;
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 use xtra::prelude::*;
 use xtra::spawn::TokioGlobalSpawnExt;
 use xtra_libp2p::dialer;
 use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::OpenSubstream;
 use xtras::supervisor::always_restart;
@@ -43,7 +43,8 @@ async fn main() -> Result<()> {
         Duration::from_secs(20),
         [],
         Subscribers::default(),
-        Arc::new(HashSet::default()),
+        HashSet::default(),
+        ConnectionLimits::default(),
     )
     .create(None)
     .spawn_global();
@@ -8,12 +8,12 @@ use libp2p_core::identity::Keypair;
 use libp2p_core::Multiaddr;
 use libp2p_tcp::TokioTcpConfig;
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 use tracing::Level;
 use xtra::prelude::*;
 use xtra::spawn::TokioGlobalSpawnExt;
 use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::listener;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::NewInboundSubstream;
@@ -58,7 +58,8 @@ async fn main() -> Result<()> {
         Duration::from_secs(30),
         [("/hello-world/1.0.0", hello_world_addr.clone().into())],
         Subscribers::default(),
-        Arc::new(HashSet::default()),
+        HashSet::default(),
+        ConnectionLimits::default(),
     )
     .create(None)
     .spawn_global();
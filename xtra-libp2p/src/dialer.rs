@@ -7,8 +7,11 @@ use anyhow::anyhow;
 use anyhow::ensure;
 use anyhow::Result;
 use async_trait::async_trait;
+use libp2p_core::multiaddr::Protocol;
 use libp2p_core::Multiaddr;
 use libp2p_core::PeerId;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use tracing::instrument;
 use xtra::Address;
@@ -18,23 +21,160 @@ use xtras::SendAsyncNext;
 /// If we're not connected by this time, stop the actor.
 pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Delay before dialing again once every address in the [`AddressBook`] failed once.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Upper bound for the delay between two rounds of dialing attempts.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// The addresses under which the dialer can reach the other party.
+///
+/// Shared between all instances of the dialer created by a supervisor, which allows us to rotate
+/// through the addresses across restarts and to remember which address worked last. Once all
+/// addresses failed, dialing backs off exponentially up to [`MAX_BACKOFF`].
+#[derive(Clone, Debug)]
+pub struct AddressBook {
+    inner: Arc<Mutex<AddressBookInner>>,
+}
+
+#[derive(Debug)]
+struct AddressBookInner {
+    addresses: Vec<Multiaddr>,
+    next: usize,
+    consecutive_failures: u32,
+}
+
+impl AddressBook {
+    pub fn new(addresses: Vec<Multiaddr>) -> Self {
+        let mut unique = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            if !unique.contains(&address) {
+                unique.push(address);
+            }
+        }
+
+        Self {
+            inner: Arc::new(Mutex::new(AddressBookInner {
+                addresses: unique,
+                next: 0,
+                consecutive_failures: 0,
+            })),
+        }
+    }
+
+    /// Add an address to the end of the rotation.
+    ///
+    /// Addresses without a peer id get the peer id of the known addresses appended. Returns
+    /// `false` if the address was already known or belongs to a different peer.
+    pub fn add(&self, mut address: Multiaddr) -> bool {
+        let mut inner = self.inner.lock().expect("lock not to be poisoned");
+
+        let peer_id = inner
+            .addresses
+            .first()
+            .and_then(|known| known.clone().extract_peer_id());
+
+        match (address.clone().extract_peer_id(), peer_id) {
+            (Some(address_peer_id), Some(peer_id)) if address_peer_id != peer_id => return false,
+            (None, Some(peer_id)) => address.push(Protocol::P2p(peer_id.into())),
+            _ => {}
+        }
+
+        if inner.addresses.contains(&address) {
+            return false;
+        }
+
+        tracing::debug!(%address, "Added address to address book");
+        inner.addresses.push(address);
+
+        true
+    }
+
+    pub fn addresses(&self) -> Vec<Multiaddr> {
+        self.inner
+            .lock()
+            .expect("lock not to be poisoned")
+            .addresses
+            .clone()
+    }
+
+    /// The address to dial next.
+    ///
+    /// This is the address that worked last, as long as dialing it did not fail since.
+    pub fn next(&self) -> Option<Multiaddr> {
+        let inner = self.inner.lock().expect("lock not to be poisoned");
+
+        inner.addresses.get(inner.next).cloned()
+    }
+
+    pub fn record_success(&self, address: &Multiaddr) {
+        let mut inner = self.inner.lock().expect("lock not to be poisoned");
+
+        if let Some(index) = inner.addresses.iter().position(|known| known == address) {
+            inner.next = index;
+        }
+        inner.consecutive_failures = 0;
+    }
+
+    /// Move on to the next address after dialing `address` failed.
+    pub fn record_failure(&self, address: &Multiaddr) {
+        let mut inner = self.inner.lock().expect("lock not to be poisoned");
+
+        if inner.addresses.is_empty() {
+            return;
+        }
+
+        if inner.addresses.get(inner.next) == Some(address) {
+            inner.next = (inner.next + 1) % inner.addresses.len();
+        }
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+    }
+
+    /// How long to wait before dialing the next address.
+    ///
+    /// All addresses are tried once without delay, afterwards the delay doubles with every round.
+    pub fn backoff(&self) -> Duration {
+        let inner = self.inner.lock().expect("lock not to be poisoned");
+
+        let len = inner.addresses.len().max(1) as u32;
+        let failed_rounds = inner.consecutive_failures / len;
+        if failed_rounds == 0 {
+            return Duration::ZERO;
+        }
+
+        INITIAL_BACKOFF
+            .checked_mul(2u32.saturating_pow(failed_rounds - 1))
+            .unwrap_or(MAX_BACKOFF)
+            .min(MAX_BACKOFF)
+    }
+}
+
 /// xtra actor that takes care of dialing (connecting) to an Endpoint.
 ///
 /// Polls Endpoint at startup to check whether connection got established correctly, and
 /// then listens for ConnectionDropped message to stop itself.
 /// Should be used in conjunction with supervisor maintaining resilient connection.
+///
+/// Every instance dials a single address taken from the [`AddressBook`]. If dialing fails, the
+/// next instance created by the supervisor moves on to the next address.
 pub struct Actor {
     endpoint: Address<Endpoint>,
-    connect_address: Multiaddr,
+    address_book: AddressBook,
+    connect_address: Option<Multiaddr>,
     listener_peer_id: Option<PeerId>,
     stop_reason: Option<Error>,
 }
 
 impl Actor {
     pub fn new(endpoint: Address<Endpoint>, connect_address: Multiaddr) -> Self {
+        Self::with_address_book(endpoint, AddressBook::new(vec![connect_address]))
+    }
+
+    pub fn with_address_book(endpoint: Address<Endpoint>, address_book: AddressBook) -> Self {
         Self {
             endpoint,
-            connect_address,
+            address_book,
+            connect_address: None,
             listener_peer_id: None,
             stop_reason: None,
         }
@@ -43,7 +183,7 @@ impl Actor {
     #[instrument(skip(self))]
     async fn connect(&self) -> Result<(), Error> {
         self.endpoint
-            .send(Connect(self.connect_address.clone()))
+            .send(Connect(self.connect_address()))
             .await
             .map_err(|_| Error::NoEndpoint)?
             .map_err(|e| Error::Failed { source: anyhow!(e) })
@@ -63,15 +203,28 @@ impl xtra::Actor for Actor {
     #[tracing::instrument("Start dialer actor", skip_all)]
     async fn started(&mut self, ctx: &mut xtra::Context<Self>) {
         tracing::debug!("Starting dialer actor");
-        match self
-            .connect_address
+        let connect_address = match self.address_book.next() {
+            Some(connect_address) => connect_address,
+            None => {
+                self.stop_with_error(Error::NoAddress, ctx);
+                return;
+            }
+        };
+
+        match connect_address
             .clone()
             .extract_peer_id()
             .ok_or(Error::InvalidPeerId)
         {
-            Ok(peer_id) => self.listener_peer_id = Some(peer_id),
+            Ok(peer_id) => {
+                self.listener_peer_id = Some(peer_id);
+                self.connect_address = Some(connect_address);
+            }
             Err(e) => {
+                // Skip the invalid address the next time around
+                self.address_book.record_failure(&connect_address);
                 self.stop_with_error(e, ctx);
+                return;
             }
         }
 
@@ -90,6 +243,12 @@ impl Actor {
             .expect("to always have peer id if successfully started")
     }
 
+    fn connect_address(&self) -> Multiaddr {
+        self.connect_address
+            .clone()
+            .expect("to always have connect address if successfully started")
+    }
+
     #[instrument(skip(self), err)]
     async fn is_connection_established(&self) -> Result<bool> {
         Ok(self
@@ -107,6 +266,13 @@ impl Actor {
             return Ok(());
         }
 
+        let backoff = self.address_book.backoff();
+        if !backoff.is_zero() {
+            tracing::debug!(?backoff, "Dialing all addresses failed, backing off");
+            tokio_extras::time::sleep(backoff).await;
+        }
+
+        tracing::debug!(address = %self.connect_address(), "Dialing");
         if let Err(e) = self.connect().await {
             tracing::warn!("Failed to request connection from endpoint: {e:#}");
         }
@@ -125,8 +291,14 @@ impl Actor {
 #[xtra_productivity]
 impl Actor {
     async fn handle(&mut self, _msg: Dial, ctx: &mut xtra::Context<Self>) {
-        if let Err(e) = self.dial().await {
-            self.stop_with_error(Error::Failed { source: e }, ctx);
+        let connect_address = self.connect_address();
+
+        match self.dial().await {
+            Ok(()) => self.address_book.record_success(&connect_address),
+            Err(e) => {
+                self.address_book.record_failure(&connect_address);
+                self.stop_with_error(Error::Failed { source: e }, ctx);
+            }
         }
     }
 }
@@ -154,8 +326,88 @@ pub enum Error {
     ConnectionDropped,
     #[error("Invalid Peer Id")]
     InvalidPeerId,
+    #[error("No address to dial")]
+    NoAddress,
     #[error("Stop reason was not specified")]
     Unspecified,
 }
 
 struct Dial;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER_ID: &str = "12D3KooWP3BN6bq9jPy8cP7Grj1QyUBfr7U6BeQFgMwfTTu12wuY";
+
+    fn address(address: &str) -> Multiaddr {
+        format!("{address}/p2p/{PEER_ID}").parse().unwrap()
+    }
+
+    #[test]
+    fn rotates_through_addresses_on_failure() {
+        let first = address("/ip4/127.0.0.1/tcp/10000");
+        let second = address("/ip6/::1/tcp/10000");
+        let book = AddressBook::new(vec![first.clone(), second.clone()]);
+
+        assert_eq!(book.next(), Some(first.clone()));
+        book.record_failure(&first);
+        assert_eq!(book.next(), Some(second.clone()));
+        book.record_failure(&second);
+        assert_eq!(book.next(), Some(first));
+    }
+
+    #[test]
+    fn remembers_address_that_worked_last() {
+        let first = address("/ip4/127.0.0.1/tcp/10000");
+        let second = address("/ip6/::1/tcp/10000");
+        let book = AddressBook::new(vec![first.clone(), second.clone()]);
+
+        book.record_failure(&first);
+        book.record_success(&second);
+
+        assert_eq!(book.next(), Some(second.clone()));
+        assert_eq!(book.backoff(), Duration::ZERO);
+    }
+
+    #[test]
+    fn backs_off_exponentially_after_every_address_failed() {
+        let first = address("/ip4/127.0.0.1/tcp/10000");
+        let second = address("/ip6/::1/tcp/10000");
+        let book = AddressBook::new(vec![first.clone(), second.clone()]);
+
+        book.record_failure(&first);
+        assert_eq!(book.backoff(), Duration::ZERO);
+        book.record_failure(&second);
+        assert_eq!(book.backoff(), INITIAL_BACKOFF);
+        book.record_failure(&first);
+        book.record_failure(&second);
+        assert_eq!(book.backoff(), INITIAL_BACKOFF * 2);
+
+        for _ in 0..100 {
+            book.record_failure(&book.next().unwrap());
+        }
+        assert_eq!(book.backoff(), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn added_addresses_get_peer_id_of_known_addresses() {
+        let book = AddressBook::new(vec![address("/ip4/127.0.0.1/tcp/10000")]);
+
+        assert!(book.add("/dns4/maker.example.com/tcp/10000".parse().unwrap()));
+        assert!(!book.add(address("/dns4/maker.example.com/tcp/10000")));
+        assert!(!book.add(
+            format!("/ip4/10.0.0.1/tcp/10000/p2p/{}", PeerId::random())
+                .parse()
+                .unwrap()
+        ));
+
+        assert_eq!(
+            book.addresses(),
+            vec![
+                address("/ip4/127.0.0.1/tcp/10000"),
+                address("/dns4/maker.example.com/tcp/10000")
+            ]
+        );
+    }
+}
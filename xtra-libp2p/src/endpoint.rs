@@ -1,4 +1,10 @@
+use crate::limits::BannedPeer;
+use crate::limits::Bans;
+use crate::limits::ConnectionLimits;
+use crate::limits::InboundSubstreamLimiter;
+use crate::limits::LimitExceeded;
 use crate::multiaddress_ext::MultiaddrExt as _;
+use crate::relay;
 use crate::upgrade;
 use crate::Connection;
 use crate::Substream;
@@ -25,8 +31,8 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::pin::Pin;
-use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 use thiserror::Error;
 use tokio_extras::Tasks;
 use tracing::instrument;
@@ -58,10 +64,14 @@ use xtras::SendAsyncSafe;
 pub struct Endpoint {
     transport_fn: Box<dyn Fn() -> Boxed<Connection> + Send + 'static>,
     controls: HashMap<PeerId, (yamux::Control, Tasks)>,
+    connection_addresses: HashMap<PeerId, Multiaddr>,
     inbound_substream_channels: HashMap<&'static str, MessageChannel<NewInboundSubstream, ()>>,
     listen_addresses: HashSet<Multiaddr>,
     inflight_connections: HashSet<PeerId>,
-    blocked_peers: Arc<HashSet<PeerId>>,
+    blocked_peers: HashSet<PeerId>,
+    allowed_peers: Option<HashSet<PeerId>>,
+    limits: ConnectionLimits,
+    bans: Bans,
     connection_timeout: Duration,
     subscribers: Subscribers,
     peer_listen_protocols: HashMap<PeerId, HashSet<String>>,
@@ -139,6 +149,40 @@ pub struct Disconnect(pub PeerId);
 /// transport.
 pub struct ListenOn(pub Multiaddr);
 
+/// Ban a peer for the given duration.
+///
+/// The peer is disconnected if currently connected and cannot connect again until the ban expires
+/// or is lifted through [`UnbanPeer`].
+#[derive(Clone, Debug)]
+pub struct BanPeer {
+    pub peer_id: PeerId,
+    pub duration: Duration,
+    pub reason: String,
+}
+
+/// Lift the ban of a peer.
+///
+/// Returns whether the peer was banned.
+#[derive(Clone, Copy, Debug)]
+pub struct UnbanPeer(pub PeerId);
+
+/// Replace the set of peers which are not allowed to connect.
+///
+/// Does not affect existing connections, send [`Disconnect`] for those.
+#[derive(Clone, Debug)]
+pub struct SetBlockedPeers(pub HashSet<PeerId>);
+
+/// Restrict connections to the given set of peers.
+///
+/// `None` allows all peers which are not blocked to connect. Does not affect existing connections,
+/// send [`Disconnect`] for those.
+#[derive(Clone, Debug)]
+pub struct SetAllowedPeers(pub Option<HashSet<PeerId>>);
+
+/// Retrieve the currently banned peers from the [`Endpoint`].
+#[derive(Clone, Copy, Debug)]
+pub struct GetBannedPeers;
+
 /// Retrieve [`ConnectionStats`] from the [`Endpoint`].
 #[derive(Clone, Copy, Debug)]
 pub struct GetConnectionStats;
@@ -146,6 +190,8 @@ pub struct GetConnectionStats;
 #[derive(Debug, Default)]
 pub struct ConnectionStats {
     pub connected_peers: HashSet<PeerId>,
+    /// The address each connected peer was dialed on or connected from.
+    pub connection_addresses: HashMap<PeerId, Multiaddr>,
     pub listen_addresses: HashSet<Multiaddr>,
 }
 
@@ -220,13 +266,17 @@ impl Endpoint {
     ///
     /// The provided substream handlers are actors that will be given the fully-negotiated
     /// substreams whenever a peer opens a new substream for the provided protocol.
+    ///
+    /// Connections and inbound substreams are subject to the given [`ConnectionLimits`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new<T, const N: usize>(
         transport: Box<dyn Fn() -> T + Send + 'static>,
         identity: Keypair,
         connection_timeout: Duration,
         inbound_substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
         subscribers: Subscribers,
-        blocked_peers: Arc<HashSet<PeerId>>,
+        blocked_peers: HashSet<PeerId>,
+        limits: ConnectionLimits,
     ) -> Self
     where
         T: Transport + Send + Sync + 'static,
@@ -258,9 +308,13 @@ impl Endpoint {
             transport_fn,
             inbound_substream_channels: verify_unique_handlers(inbound_substream_handlers),
             controls: HashMap::default(),
+            connection_addresses: HashMap::default(),
             listen_addresses: HashSet::default(),
             inflight_connections: HashSet::default(),
             blocked_peers,
+            allowed_peers: None,
+            limits,
+            bans: Bans::default(),
             connection_timeout,
             subscribers,
             peer_listen_protocols: HashMap::default(),
@@ -290,8 +344,18 @@ impl Endpoint {
         Ok(())
     }
 
+    fn is_permitted(&self, peer_id: &PeerId) -> bool {
+        let is_allowed = match &self.allowed_peers {
+            Some(allowed_peers) => allowed_peers.contains(peer_id),
+            None => true,
+        };
+
+        is_allowed && !self.blocked_peers.contains(peer_id)
+    }
+
     async fn drop_connection(&mut self, this: &Address<Self>, peer_id: &PeerId) {
         self.peer_listen_protocols.remove(peer_id);
+        self.connection_addresses.remove(peer_id);
 
         let (mut control, tasks) = match self.controls.remove(peer_id) {
             None => return,
@@ -342,11 +406,34 @@ impl Endpoint {
 
         let NewConnection {
             peer_id,
+            address,
             control,
             mut incoming_substreams,
             worker,
         } = msg;
 
+        if !self.is_permitted(&peer_id) {
+            tracing::info!(
+                target: "blocked_peers",
+                peer_id = %peer_id, // Weird but required
+                "Blocked peer from connecting"
+            );
+            return; // Dropping the connection closes it
+        }
+
+        if self.bans.is_banned(&peer_id, Instant::now()) {
+            tracing::info!(%peer_id, %address, "Rejecting connection from banned peer");
+            return; // Dropping the connection closes it
+        }
+
+        if let Err(e) = self
+            .limits
+            .check_connection(&address, self.connection_addresses.values())
+        {
+            tracing::info!(%peer_id, %address, "Rejecting connection: {e}");
+            return; // Dropping the connection closes it
+        }
+
         let mut tasks = Tasks::default();
         tasks.add(worker);
         tasks.add_fallible(
@@ -356,6 +443,8 @@ impl Endpoint {
                     .iter()
                     .map(|(proto, channel)| (proto.to_owned(), channel.clone()))
                     .collect::<HashMap<_, _>>();
+                let mut limiter = InboundSubstreamLimiter::new(&self.limits);
+                let this = this.clone();
 
                 async move {
                     loop {
@@ -380,6 +469,21 @@ impl Endpoint {
                         let stream =
                             Substream::new(stream, protocol, libp2p_core::Endpoint::Listener);
 
+                        // Each circuit handed to us by a relay becomes a connection of its own,
+                        // which is subject to the connection limits instead
+                        let stream = if protocol == relay::STOP_PROTOCOL {
+                            stream
+                        } else {
+                            match limiter.check(protocol, Instant::now()) {
+                                Ok(active) => stream.with_limit_tracking(active),
+                                Err(error) => {
+                                    this.send_async_next(SubstreamLimitExceeded { peer_id, error })
+                                        .await;
+                                    bail!("Peer exceeded substream limits");
+                                }
+                            }
+                        };
+
                         let substream = NewInboundSubstream { peer_id, stream };
                         let span =
                             tracing::debug_span!("Register new inbound substream", ?substream);
@@ -397,7 +501,8 @@ impl Endpoint {
             tracing::warn!(%peer_id, "Missed drop event, replacing old connection")
         }
 
-        self.notify_connection_established(peer_id).await;
+        self.connection_addresses.insert(peer_id, address.clone());
+        self.notify_connection_established(peer_id, address).await;
     }
 
     async fn handle(&mut self, msg: ListenerFailed) {
@@ -424,9 +529,47 @@ impl Endpoint {
             .await;
     }
 
+    async fn handle(&mut self, msg: SubstreamLimitExceeded, ctx: &mut Context<Self>) {
+        let reason = msg.error.to_string();
+        tracing::info!(peer_id = %msg.peer_id, "Peer exceeded limits: {reason}");
+
+        self.bans.ban(
+            msg.peer_id,
+            self.limits.ban_duration,
+            reason,
+            Instant::now(),
+        );
+        self.drop_connection(&ctx.address().expect("self to be alive"), &msg.peer_id)
+            .await;
+    }
+
+    async fn handle(&mut self, msg: BanPeer, ctx: &mut Context<Self>) {
+        self.bans
+            .ban(msg.peer_id, msg.duration, msg.reason, Instant::now());
+        self.drop_connection(&ctx.address().expect("self to be alive"), &msg.peer_id)
+            .await;
+    }
+
+    async fn handle(&mut self, msg: UnbanPeer) -> bool {
+        self.bans.unban(&msg.0, Instant::now())
+    }
+
+    async fn handle(&mut self, msg: SetBlockedPeers) {
+        self.blocked_peers = msg.0;
+    }
+
+    async fn handle(&mut self, msg: SetAllowedPeers) {
+        self.allowed_peers = msg.0;
+    }
+
+    async fn handle(&mut self, _: GetBannedPeers) -> Vec<BannedPeer> {
+        self.bans.list(Instant::now())
+    }
+
     async fn handle(&mut self, _: GetConnectionStats) -> ConnectionStats {
         ConnectionStats {
             connected_peers: self.controls.keys().copied().collect(),
+            connection_addresses: self.connection_addresses.clone(),
             listen_addresses: self.listen_addresses.clone(),
         }
     }
@@ -454,10 +597,11 @@ impl Endpoint {
                 let connection_timeout = self.connection_timeout;
 
                 let fut = async move {
+                    let address = msg.0;
                     let (peer_id, control, incoming_substreams, worker) =
                         tokio_extras::time::timeout(
                             connection_timeout,
-                            transport.dial(msg.0)?,
+                            transport.dial(address.clone())?,
                             || tracing::debug_span!("transport dial"),
                         )
                         .await
@@ -465,6 +609,7 @@ impl Endpoint {
 
                     this.send_async_next(NewConnection {
                         peer_id,
+                        address,
                         control,
                         incoming_substreams,
                         worker,
@@ -499,7 +644,6 @@ impl Endpoint {
         tokio_extras::spawn_fallible::<_, _, _, (), _, _, _>(
             &this.clone(),
             {
-                let blocked_peers = self.blocked_peers.clone();
                 let this = this.clone();
                 let listen_address = listen_address.clone();
 
@@ -523,19 +667,6 @@ impl Endpoint {
                                 remote_addr,
                                 ..
                             }) => {
-                                match PeerId::try_from_multiaddr(&remote_addr) {
-                                    Some(peer_id) if blocked_peers.contains(&peer_id) => {
-                                        tracing::info!(
-                                            target: "blocked_peers",
-                                            peer_id = %peer_id, // Weird but required
-                                            "Blocked peer from connecting"
-                                        );
-                                        continue; // Skip this peer
-                                    }
-                                    _ => (),
-                                };
-
-                                let blocked_peers = blocked_peers.clone();
                                 let this = this.clone();
                                 tasks.add_fallible(
                                     async move {
@@ -549,17 +680,9 @@ impl Endpoint {
                                                 }
                                             })?;
 
-                                        if blocked_peers.contains(&peer_id) {
-                                            tracing::info!(
-                                                target: "blocked_peers",
-                                                peer_id = %peer_id, // Weird but required
-                                                "Blocked peer from connecting"
-                                            );
-                                            return Ok(()); // Skip this peer
-                                        }
-
                                         this.send_async_next(NewConnection {
                                             peer_id,
+                                            address: remote_addr,
                                             control,
                                             incoming_substreams,
                                             worker,
@@ -686,12 +809,15 @@ impl Endpoint {
 }
 
 impl Endpoint {
-    async fn notify_connection_established(&mut self, peer_id: PeerId) {
-        tracing::info!(%peer_id, "Connection established");
+    async fn notify_connection_established(&mut self, peer_id: PeerId, address: Multiaddr) {
+        tracing::info!(%peer_id, %address, "Connection established");
 
         for subscriber in &self.subscribers.connection_established {
             subscriber
-                .send_async_next(ConnectionEstablished { peer_id })
+                .send_async_next(ConnectionEstablished {
+                    peer_id,
+                    address: address.clone(),
+                })
                 .await;
         }
     }
@@ -777,8 +903,14 @@ struct NewListenAddress {
     listen_address: Multiaddr,
 }
 
+struct SubstreamLimitExceeded {
+    peer_id: PeerId,
+    error: LimitExceeded,
+}
+
 struct NewConnection {
     peer_id: PeerId,
+    address: Multiaddr,
     control: yamux::Control,
     #[allow(clippy::type_complexity)]
     incoming_substreams: BoxStream<
@@ -791,9 +923,11 @@ struct NewConnection {
     worker: BoxFuture<'static, ()>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct ConnectionEstablished {
     pub peer_id: PeerId,
+    /// The address we dialed or, for inbound connections, the address of the remote.
+    pub address: Multiaddr,
 }
 
 #[derive(Clone, Copy)]
@@ -1,14 +1,19 @@
+pub use crate::endpoint::BanPeer;
 pub use crate::endpoint::Connect;
 pub use crate::endpoint::ConnectionStats;
 pub use crate::endpoint::Disconnect;
 pub use crate::endpoint::Endpoint;
 pub use crate::endpoint::Error;
+pub use crate::endpoint::GetBannedPeers;
 pub use crate::endpoint::GetConnectionStats;
 pub use crate::endpoint::ListenOn;
 pub use crate::endpoint::Multiple;
 pub use crate::endpoint::NewInboundSubstream;
 pub use crate::endpoint::OpenSubstream;
+pub use crate::endpoint::SetAllowedPeers;
+pub use crate::endpoint::SetBlockedPeers;
 pub use crate::endpoint::Single;
+pub use crate::endpoint::UnbanPeer;
 pub use crate::substream::Substream;
 pub use libp2p_core as libp2p;
 pub use multistream_select::NegotiationError;
@@ -20,11 +25,15 @@ use libp2p_core::PeerId;
 
 pub mod dialer;
 pub mod endpoint;
+pub mod limits;
 pub mod listener;
 pub mod multiaddress_ext;
+pub mod relay;
 mod substream;
+pub mod tor;
 mod upgrade;
 mod verify_peer_id;
+pub mod versions;
 
 type Connection = (
     PeerId,
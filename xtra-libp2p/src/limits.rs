@@ -0,0 +1,391 @@
+use crate::relay;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::Multiaddr;
+use libp2p_core::PeerId;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How long peers are banned for after exceeding one of the [`ConnectionLimits`], unless
+/// configured otherwise.
+pub const DEFAULT_BAN_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Limits protecting an [`Endpoint`](crate::Endpoint) from peers exhausting its resources.
+///
+/// Connections exceeding the connection limits are rejected. Peers exceeding the substream limits
+/// are disconnected and banned for [`ConnectionLimits::ban_duration`].
+///
+/// The default does not impose any limits.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionLimits {
+    /// Maximum number of connections across all peers.
+    pub max_connections: Option<usize>,
+    /// Maximum number of connections from the same IP address.
+    pub max_connections_per_ip: Option<usize>,
+    /// Maximum number of concurrent inbound substreams a peer may have open for each protocol.
+    pub max_substreams_per_protocol: Option<usize>,
+    /// Maximum rate at which a peer may open inbound substreams, across all protocols.
+    pub max_inbound_substream_rate: Option<Rate>,
+    /// How long peers exceeding the substream limits are banned for.
+    pub ban_duration: Duration,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: None,
+            max_connections_per_ip: None,
+            max_substreams_per_protocol: None,
+            max_inbound_substream_rate: None,
+            ban_duration: DEFAULT_BAN_DURATION,
+        }
+    }
+}
+
+/// At most `count` events within `interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub count: usize,
+    pub interval: Duration,
+}
+
+impl Rate {
+    pub fn per_minute(count: usize) -> Self {
+        Self {
+            count,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    #[error("Already connected to {limit} peers")]
+    TooManyConnections { limit: usize },
+    #[error("Already connected to {limit} peers from {ip}")]
+    TooManyConnectionsFromIp { ip: IpAddr, limit: usize },
+    #[error("More than {limit} concurrent substreams for protocol {protocol}")]
+    TooManySubstreams {
+        protocol: &'static str,
+        limit: usize,
+    },
+    #[error("More than {} substreams within {}s", .rate.count, .rate.interval.as_secs())]
+    SubstreamRateExceeded { rate: Rate },
+}
+
+impl ConnectionLimits {
+    /// Check whether we can accept another connection from `address`, given the addresses of the
+    /// existing connections.
+    pub(crate) fn check_connection<'a>(
+        &self,
+        address: &Multiaddr,
+        existing: impl ExactSizeIterator<Item = &'a Multiaddr>,
+    ) -> Result<(), LimitExceeded> {
+        if let Some(limit) = self.max_connections {
+            if existing.len() >= limit {
+                return Err(LimitExceeded::TooManyConnections { limit });
+            }
+        }
+
+        if let (Some(limit), Some(ip)) = (self.max_connections_per_ip, ip(address)) {
+            let connections_from_ip = existing
+                .filter(|existing| self::ip(existing) == Some(ip))
+                .count();
+
+            if connections_from_ip >= limit {
+                return Err(LimitExceeded::TooManyConnectionsFromIp { ip, limit });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The IP address a connection was made to or from, if any.
+///
+/// Connections through a relay are not attributed to the IP address of the relay.
+pub fn ip(address: &Multiaddr) -> Option<IpAddr> {
+    if relay::is_circuit_address(address) {
+        return None;
+    }
+
+    match address.iter().next()? {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+/// Enforces the substream limits for the inbound substreams of a single connection.
+pub(crate) struct InboundSubstreamLimiter {
+    max_per_protocol: Option<usize>,
+    rate: Option<Rate>,
+    active: HashMap<&'static str, Arc<()>>,
+    recent: VecDeque<Instant>,
+}
+
+/// Held by an inbound substream for as long as it is alive, to track the number of concurrent
+/// substreams per protocol.
+#[derive(Debug)]
+pub(crate) struct ActiveSubstream {
+    _active: Arc<()>,
+}
+
+impl InboundSubstreamLimiter {
+    pub(crate) fn new(limits: &ConnectionLimits) -> Self {
+        Self {
+            max_per_protocol: limits.max_substreams_per_protocol,
+            rate: limits.max_inbound_substream_rate,
+            active: HashMap::default(),
+            recent: VecDeque::default(),
+        }
+    }
+
+    /// Account for a new inbound substream for `protocol`.
+    pub(crate) fn check(
+        &mut self,
+        protocol: &'static str,
+        now: Instant,
+    ) -> Result<ActiveSubstream, LimitExceeded> {
+        if let Some(rate) = self.rate {
+            while let Some(oldest) = self.recent.front() {
+                if now.duration_since(*oldest) < rate.interval {
+                    break;
+                }
+                self.recent.pop_front();
+            }
+
+            if self.recent.len() >= rate.count {
+                return Err(LimitExceeded::SubstreamRateExceeded { rate });
+            }
+            self.recent.push_back(now);
+        }
+
+        let active = self.active.entry(protocol).or_default();
+        if let Some(limit) = self.max_per_protocol {
+            // One reference is held by the limiter itself
+            if Arc::strong_count(active) > limit {
+                return Err(LimitExceeded::TooManySubstreams { protocol, limit });
+            }
+        }
+
+        Ok(ActiveSubstream {
+            _active: active.clone(),
+        })
+    }
+}
+
+/// A peer that is currently not allowed to connect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedPeer {
+    pub peer_id: PeerId,
+    /// How long the ban is still in place.
+    pub remaining: Duration,
+    pub reason: String,
+}
+
+/// Temporary bans, which expire on their own.
+#[derive(Debug, Default)]
+pub(crate) struct Bans {
+    bans: HashMap<PeerId, (Instant, String)>,
+}
+
+impl Bans {
+    pub(crate) fn ban(
+        &mut self,
+        peer_id: PeerId,
+        duration: Duration,
+        reason: String,
+        now: Instant,
+    ) {
+        tracing::info!(%peer_id, ban_duration_secs = %duration.as_secs(), %reason, "Banning peer");
+
+        self.bans.insert(peer_id, (now + duration, reason));
+    }
+
+    /// Lift the ban of the peer, returns whether the peer was banned.
+    pub(crate) fn unban(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        self.remove_expired(now);
+
+        self.bans.remove(peer_id).is_some()
+    }
+
+    pub(crate) fn is_banned(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        self.remove_expired(now);
+
+        self.bans.contains_key(peer_id)
+    }
+
+    pub(crate) fn list(&mut self, now: Instant) -> Vec<BannedPeer> {
+        self.remove_expired(now);
+
+        self.bans
+            .iter()
+            .map(|(peer_id, (until, reason))| BannedPeer {
+                peer_id: *peer_id,
+                remaining: until.duration_since(now),
+                reason: reason.clone(),
+            })
+            .collect()
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        self.bans.retain(|_, (until, _)| *until > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROTOCOL: &str = "/some-protocol/1.0.0";
+
+    #[test]
+    fn rejects_connections_above_limits() {
+        let limits = ConnectionLimits {
+            max_connections: Some(3),
+            max_connections_per_ip: Some(1),
+            ..ConnectionLimits::default()
+        };
+        let existing = [
+            "/ip4/10.0.0.1/tcp/50000".parse().unwrap(),
+            "/ip6/::1/tcp/50000".parse().unwrap(),
+        ];
+        let at_total_limit = [
+            "/ip4/10.0.0.1/tcp/50000".parse().unwrap(),
+            "/ip6/::1/tcp/50000".parse().unwrap(),
+            "/dns4/example.com/tcp/10000".parse().unwrap(),
+        ];
+
+        let from_same_ip =
+            limits.check_connection(&"/ip4/10.0.0.1/tcp/50001".parse().unwrap(), existing.iter());
+        let from_other_ip =
+            limits.check_connection(&"/ip4/10.0.0.2/tcp/50000".parse().unwrap(), existing.iter());
+        let above_total = limits.check_connection(
+            &"/ip4/10.0.0.2/tcp/50000".parse().unwrap(),
+            at_total_limit.iter(),
+        );
+
+        assert!(matches!(
+            from_same_ip,
+            Err(LimitExceeded::TooManyConnectionsFromIp { limit: 1, .. })
+        ));
+        assert_eq!(from_other_ip, Ok(()));
+        assert_eq!(
+            above_total,
+            Err(LimitExceeded::TooManyConnections { limit: 3 })
+        );
+    }
+
+    #[test]
+    fn relayed_connections_are_not_limited_per_ip_of_relay() {
+        let limits = ConnectionLimits {
+            max_connections_per_ip: Some(1),
+            ..ConnectionLimits::default()
+        };
+        let relay =
+            "/ip4/10.0.0.1/tcp/10000/p2p/12D3KooWP3BN6bq9jPy8cP7Grj1QyUBfr7U6BeQFgMwfTTu12wuY";
+        let existing = [
+            relay.parse().unwrap(),
+            format!("{relay}/p2p-circuit/p2p/{}", PeerId::random())
+                .parse()
+                .unwrap(),
+        ];
+
+        let relayed = limits.check_connection(
+            &format!("{relay}/p2p-circuit/p2p/{}", PeerId::random())
+                .parse()
+                .unwrap(),
+            existing.iter(),
+        );
+
+        assert_eq!(relayed, Ok(()));
+    }
+
+    #[test]
+    fn limits_concurrent_substreams_per_protocol() {
+        let mut limiter = InboundSubstreamLimiter::new(&ConnectionLimits {
+            max_substreams_per_protocol: Some(2),
+            ..ConnectionLimits::default()
+        });
+        let now = Instant::now();
+
+        let first = limiter.check(PROTOCOL, now).unwrap();
+        let _second = limiter.check(PROTOCOL, now).unwrap();
+        let _other_protocol = limiter.check("/other-protocol/1.0.0", now).unwrap();
+
+        assert!(matches!(
+            limiter.check(PROTOCOL, now),
+            Err(LimitExceeded::TooManySubstreams { limit: 2, .. })
+        ));
+
+        drop(first);
+
+        assert!(limiter.check(PROTOCOL, now).is_ok());
+    }
+
+    #[test]
+    fn limits_substream_rate() {
+        let rate = Rate {
+            count: 2,
+            interval: Duration::from_secs(10),
+        };
+        let mut limiter = InboundSubstreamLimiter::new(&ConnectionLimits {
+            max_inbound_substream_rate: Some(rate),
+            ..ConnectionLimits::default()
+        });
+        let now = Instant::now();
+
+        limiter.check(PROTOCOL, now).unwrap();
+        limiter
+            .check(PROTOCOL, now + Duration::from_secs(5))
+            .unwrap();
+
+        assert_eq!(
+            limiter
+                .check(PROTOCOL, now + Duration::from_secs(9))
+                .unwrap_err(),
+            LimitExceeded::SubstreamRateExceeded { rate }
+        );
+        assert!(limiter
+            .check(PROTOCOL, now + Duration::from_secs(11))
+            .is_ok());
+    }
+
+    #[test]
+    fn bans_expire() {
+        let mut bans = Bans::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        bans.ban(peer_id, Duration::from_secs(60), "test".to_owned(), now);
+
+        assert!(bans.is_banned(&peer_id, now + Duration::from_secs(59)));
+        assert_eq!(
+            bans.list(now + Duration::from_secs(50)),
+            vec![BannedPeer {
+                peer_id,
+                remaining: Duration::from_secs(10),
+                reason: "test".to_owned()
+            }]
+        );
+        assert!(!bans.is_banned(&peer_id, now + Duration::from_secs(60)));
+        assert!(bans.list(now + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn unban_lifts_ban() {
+        let mut bans = Bans::default();
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+
+        bans.ban(peer_id, Duration::from_secs(60), "test".to_owned(), now);
+
+        assert!(bans.unban(&peer_id, now));
+        assert!(!bans.is_banned(&peer_id, now));
+        assert!(!bans.unban(&peer_id, now));
+    }
+}
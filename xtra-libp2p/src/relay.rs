@@ -0,0 +1,234 @@
+//! Circuit relay, allowing peers which cannot be dialed directly, e.g. because they are behind a
+//! NAT, to accept connections.
+//!
+//! A peer behind a NAT connects to a publicly reachable relay and listens on
+//! `<relay address>/p2p/<relay>/p2p-circuit` through [`RelayTransport`]. Other peers dial it on
+//! `<relay address>/p2p/<relay>/p2p-circuit/p2p/<peer>`.
+//!
+//! Dialing opens a substream to the relay using [`HOP_PROTOCOL`], naming the peer we want to
+//! reach. The relay in turn opens a substream to that peer using [`STOP_PROTOCOL`], naming the
+//! peer the circuit originates from. Once both have accepted the circuit, the relay forwards all
+//! bytes between the two substreams. The circuit is then upgraded like any other connection, thus
+//! it is encrypted end-to-end and the relay cannot read nor tamper with what is sent through it.
+//!
+//! The relay is implemented by [`server::Actor`], incoming circuits are accepted by
+//! [`client::Actor`].
+
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::Multiaddr;
+use libp2p_core::PeerId;
+use std::io;
+use std::time::Duration;
+
+pub use transport::Error;
+pub use transport::RelayTransport;
+
+pub mod client;
+pub mod server;
+mod transport;
+
+/// Protocol used to ask a relay to establish a circuit to another peer.
+pub const HOP_PROTOCOL: &str = "/itchysats/relay/hop/1.0.0";
+
+/// Protocol used by a relay to hand a circuit to the peer it was established to.
+pub const STOP_PROTOCOL: &str = "/itchysats/relay/stop/1.0.0";
+
+/// How long we wait for the other party to request or accept a circuit.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The outcome of requesting a circuit, sent as a single byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// The relay is not connected to the requested peer.
+    NoConnection,
+    /// The requested peer did not accept the circuit.
+    ConnectionFailed,
+    /// The relay does not accept any more circuits.
+    ResourceLimitExceeded,
+}
+
+impl Status {
+    fn to_byte(self) -> u8 {
+        match self {
+            Status::Ok => 0,
+            Status::NoConnection => 1,
+            Status::ConnectionFailed => 2,
+            Status::ResourceLimitExceeded => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        let status = match byte {
+            0 => Status::Ok,
+            1 => Status::NoConnection,
+            2 => Status::ConnectionFailed,
+            3 => Status::ResourceLimitExceeded,
+            _ => return None,
+        };
+
+        Some(status)
+    }
+}
+
+/// The parts of a circuit address, `<relay address>/p2p/<relay>/p2p-circuit[/p2p/<peer>]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CircuitAddress {
+    /// The address of the relay, including its peer ID.
+    relay_address: Multiaddr,
+    relay: PeerId,
+    /// The peer to establish a circuit to, if any.
+    destination: Option<PeerId>,
+}
+
+impl CircuitAddress {
+    fn parse(address: &Multiaddr) -> Option<Self> {
+        let mut protocols = address.iter();
+
+        let mut relay_address = Multiaddr::empty();
+        loop {
+            match protocols.next()? {
+                Protocol::P2pCircuit => break,
+                protocol => relay_address.push(protocol),
+            }
+        }
+
+        let relay = match relay_address.iter().last()? {
+            Protocol::P2p(hash) => PeerId::from_multihash(hash).ok()?,
+            _ => return None,
+        };
+
+        let destination = match protocols.next() {
+            None => None,
+            Some(Protocol::P2p(hash)) => Some(PeerId::from_multihash(hash).ok()?),
+            Some(_) => return None,
+        };
+
+        if protocols.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            relay_address,
+            relay,
+            destination,
+        })
+    }
+}
+
+/// Whether the address is reached through a relay.
+pub fn is_circuit_address(address: &Multiaddr) -> bool {
+    address
+        .iter()
+        .any(|protocol| matches!(protocol, Protocol::P2pCircuit))
+}
+
+async fn write_peer_id(stream: &mut (impl AsyncWrite + Unpin), peer_id: PeerId) -> io::Result<()> {
+    let bytes = peer_id.to_bytes();
+
+    stream.write_all(&[bytes.len() as u8]).await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await
+}
+
+async fn read_peer_id(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<PeerId> {
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).await?;
+
+    let mut bytes = vec![0u8; len[0] as usize];
+    stream.read_exact(&mut bytes).await?;
+
+    PeerId::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+async fn write_status(stream: &mut (impl AsyncWrite + Unpin), status: Status) -> io::Result<()> {
+    stream.write_all(&[status.to_byte()]).await?;
+    stream.flush().await
+}
+
+async fn read_status(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<Status> {
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status).await?;
+
+    Status::from_byte(status[0]).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unknown status {}", status[0]),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAY: &str = "12D3KooWP3BN6bq9jPy8cP7Grj1QyUBfr7U6BeQFgMwfTTu12wuY";
+    const PEER: &str = "12D3KooWDjzHna3pNi1Bt1DoRfrpsBREykJKXDRDxXvhJNAdDZEk";
+
+    #[test]
+    fn parses_circuit_addresses() {
+        let listen = format!("/ip4/127.0.0.1/tcp/10000/p2p/{RELAY}/p2p-circuit")
+            .parse()
+            .unwrap();
+        let dial = format!("/dns4/relay.example.com/tcp/10000/p2p/{RELAY}/p2p-circuit/p2p/{PEER}")
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            CircuitAddress::parse(&listen),
+            Some(CircuitAddress {
+                relay_address: format!("/ip4/127.0.0.1/tcp/10000/p2p/{RELAY}")
+                    .parse()
+                    .unwrap(),
+                relay: RELAY.parse().unwrap(),
+                destination: None,
+            })
+        );
+        assert_eq!(
+            CircuitAddress::parse(&dial),
+            Some(CircuitAddress {
+                relay_address: format!("/dns4/relay.example.com/tcp/10000/p2p/{RELAY}")
+                    .parse()
+                    .unwrap(),
+                relay: RELAY.parse().unwrap(),
+                destination: Some(PEER.parse().unwrap()),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_circuit_addresses() {
+        for address in [
+            format!("/ip4/127.0.0.1/tcp/10000/p2p/{RELAY}"),
+            "/ip4/127.0.0.1/tcp/10000/p2p-circuit".to_owned(),
+            format!("/ip4/127.0.0.1/tcp/10000/p2p/{RELAY}/p2p-circuit/tcp/10000"),
+            format!("/ip4/127.0.0.1/tcp/10000/p2p/{RELAY}/p2p-circuit/p2p/{PEER}/p2p/{PEER}"),
+        ] {
+            let parsed = CircuitAddress::parse(&address.parse().unwrap());
+
+            assert_eq!(parsed, None, "{address}");
+        }
+    }
+
+    #[tokio::test]
+    async fn peer_id_roundtrip() {
+        let peer_id = PEER.parse().unwrap();
+        let mut buffer = Vec::new();
+
+        write_peer_id(&mut buffer, peer_id).await.unwrap();
+        let read = read_peer_id(&mut buffer.as_slice()).await.unwrap();
+
+        assert_eq!(read, peer_id);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_status() {
+        let result = read_status(&mut [42u8].as_slice()).await;
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
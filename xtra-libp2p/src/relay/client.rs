@@ -0,0 +1,113 @@
+use crate::endpoint;
+use crate::relay::read_peer_id;
+use crate::relay::write_status;
+use crate::relay::Status;
+use crate::relay::HANDSHAKE_TIMEOUT;
+use crate::NewInboundSubstream;
+use crate::Substream;
+use anyhow::Context as _;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::SinkExt;
+use libp2p_core::PeerId;
+use std::collections::HashMap;
+use tokio_extras::spawn_fallible;
+use xtra::Context;
+use xtra_productivity::xtra_productivity;
+
+/// Number of accepted circuits which can be queued before the relay has to wait for us to process
+/// them.
+const CIRCUIT_BUFFER_SIZE: usize = 5;
+
+/// An actor accepting circuits relayed to us, for the relays we listen on through
+/// [`RelayTransport`](crate::relay::RelayTransport).
+///
+/// Needs to be registered as handler of [`STOP_PROTOCOL`](crate::relay::STOP_PROTOCOL) and as
+/// subscriber of [`endpoint::ConnectionDropped`], to stop listening on a relay once we are no
+/// longer connected to it.
+#[derive(Default)]
+pub struct Actor {
+    listeners: HashMap<PeerId, mpsc::Sender<(PeerId, Substream)>>,
+}
+
+/// Accept circuits from the given relay.
+///
+/// Returns the accepted circuits together with the peer they originate from. Replaces a previous
+/// listener for the same relay.
+pub(crate) struct Listen {
+    pub(crate) relay: PeerId,
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut Context<Self>) {
+        let NewInboundSubstream {
+            peer_id: relay,
+            stream,
+        } = msg;
+
+        let listener = match self.listeners.get(&relay) {
+            Some(listener) => listener.clone(),
+            None => {
+                // Dropping the substream lets the relay know that we don't accept the circuit
+                tracing::debug!(%relay, "Rejecting circuit from relay we are not listening on");
+                return;
+            }
+        };
+
+        spawn_fallible(
+            &ctx.address().expect("we are alive"),
+            accept_circuit(stream, listener),
+            move |e| async move {
+                tracing::debug!(%relay, "Failed to accept circuit: {e:#}");
+            },
+        );
+    }
+
+    async fn handle(&mut self, msg: Listen) -> mpsc::Receiver<(PeerId, Substream)> {
+        let (sender, receiver) = mpsc::channel(CIRCUIT_BUFFER_SIZE);
+        self.listeners.insert(msg.relay, sender);
+
+        receiver
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle_connection_dropped(&mut self, msg: endpoint::ConnectionDropped) {
+        // Closes the listener, which allows the endpoint to listen on the relay again
+        if self.listeners.remove(&msg.peer_id).is_some() {
+            tracing::debug!(relay = %msg.peer_id, "Stopped listening on relay");
+        }
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+async fn accept_circuit(
+    mut stream: Substream,
+    mut listener: mpsc::Sender<(PeerId, Substream)>,
+) -> Result<()> {
+    let source = tokio_extras::time::timeout(HANDSHAKE_TIMEOUT, read_peer_id(&mut stream), || {
+        tracing::debug_span!("Read circuit source")
+    })
+    .await
+    .context("Relay did not hand over circuit in time")??;
+
+    write_status(&mut stream, Status::Ok).await?;
+
+    listener
+        .send((source, stream))
+        .await
+        .context("No longer listening on relay")?;
+
+    tracing::debug!(%source, "Accepted relayed circuit");
+
+    Ok(())
+}
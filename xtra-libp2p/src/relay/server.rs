@@ -0,0 +1,211 @@
+use crate::relay::read_peer_id;
+use crate::relay::read_status;
+use crate::relay::write_peer_id;
+use crate::relay::write_status;
+use crate::relay::Status;
+use crate::relay::HANDSHAKE_TIMEOUT;
+use crate::relay::STOP_PROTOCOL;
+use crate::Endpoint;
+use crate::NewInboundSubstream;
+use crate::OpenSubstream;
+use crate::Substream;
+use anyhow::bail;
+use anyhow::Context as _;
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::AsyncRead;
+use futures::AsyncReadExt;
+use futures::AsyncWrite;
+use futures::AsyncWriteExt;
+use libp2p_core::PeerId;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_extras::spawn_fallible;
+use xtra::Address;
+use xtra::Context;
+use xtra_productivity::xtra_productivity;
+
+/// Size of the buffer used to forward bytes from one peer to the other.
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Limits of a relay.
+///
+/// The default does not impose any limits. Note that the number of circuits a single peer can
+/// request at the same time is limited by
+/// [`ConnectionLimits::max_substreams_per_protocol`](crate::limits::ConnectionLimits).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Config {
+    /// Maximum number of circuits relayed at the same time.
+    pub max_circuits: Option<usize>,
+    /// Circuits are closed after this duration.
+    pub max_circuit_duration: Option<Duration>,
+}
+
+/// An actor relaying circuits between the peers connected to the [`Endpoint`].
+///
+/// Needs to be registered as handler of [`HOP_PROTOCOL`](crate::relay::HOP_PROTOCOL).
+pub struct Actor {
+    endpoint: Address<Endpoint>,
+    config: Config,
+    /// Held by every circuit for as long as it is being relayed.
+    active_circuits: Arc<()>,
+}
+
+impl Actor {
+    pub fn new(endpoint: Address<Endpoint>, config: Config) -> Self {
+        Self {
+            endpoint,
+            config,
+            active_circuits: Arc::new(()),
+        }
+    }
+}
+
+#[xtra_productivity]
+impl Actor {
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut Context<Self>) {
+        let NewInboundSubstream {
+            peer_id: source,
+            stream,
+        } = msg;
+
+        let active = self.active_circuits.clone();
+        // One reference is held by the actor itself
+        let limit_exceeded = matches!(
+            self.config.max_circuits,
+            Some(limit) if Arc::strong_count(&active) - 1 > limit
+        );
+
+        let circuit = relay_circuit(
+            self.endpoint.clone(),
+            source,
+            stream,
+            limit_exceeded,
+            self.config.max_circuit_duration,
+            active,
+        );
+
+        spawn_fallible(
+            &ctx.address().expect("we are alive"),
+            circuit,
+            move |e| async move {
+                tracing::debug!(%source, "Failed to relay circuit: {e:#}");
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl xtra::Actor for Actor {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
+
+async fn relay_circuit(
+    endpoint: Address<Endpoint>,
+    source: PeerId,
+    mut source_stream: Substream,
+    limit_exceeded: bool,
+    max_duration: Option<Duration>,
+    _active: Arc<()>,
+) -> Result<()> {
+    let destination =
+        tokio_extras::time::timeout(HANDSHAKE_TIMEOUT, read_peer_id(&mut source_stream), || {
+            tracing::debug_span!("Read circuit request")
+        })
+        .await
+        .context("Peer did not request circuit in time")??;
+
+    if limit_exceeded {
+        write_status(&mut source_stream, Status::ResourceLimitExceeded).await?;
+        bail!("Too many circuits");
+    }
+
+    let mut destination_stream = match open_stop_substream(&endpoint, destination).await? {
+        Ok(stream) => stream,
+        Err(crate::Error::NoConnection(_)) => {
+            write_status(&mut source_stream, Status::NoConnection).await?;
+            bail!("Not connected to {destination}");
+        }
+        Err(e) => {
+            write_status(&mut source_stream, Status::ConnectionFailed).await?;
+            return Err(e).with_context(|| format!("Failed to open substream to {destination}"));
+        }
+    };
+
+    write_peer_id(&mut destination_stream, source).await?;
+    let status = tokio_extras::time::timeout(
+        HANDSHAKE_TIMEOUT,
+        read_status(&mut destination_stream),
+        || tracing::debug_span!("Read circuit response"),
+    )
+    .await;
+
+    if !matches!(status, Ok(Ok(Status::Ok))) {
+        write_status(&mut source_stream, Status::ConnectionFailed).await?;
+        bail!("{destination} did not accept circuit: {status:?}");
+    }
+
+    write_status(&mut source_stream, Status::Ok).await?;
+
+    tracing::debug!(%source, %destination, "Relaying circuit");
+
+    let (source_read, source_write) = source_stream.split();
+    let (destination_read, destination_write) = destination_stream.split();
+    let circuit = futures::future::try_join(
+        forward(source_read, destination_write),
+        forward(destination_read, source_write),
+    );
+
+    match max_duration {
+        Some(max_duration) => {
+            let _ = tokio_extras::time::timeout(max_duration, circuit, || {
+                tracing::debug_span!("Relay circuit")
+            })
+            .await;
+        }
+        None => {
+            circuit.await?;
+        }
+    }
+
+    tracing::debug!(%source, %destination, "Circuit closed");
+
+    Ok(())
+}
+
+async fn open_stop_substream(
+    endpoint: &Address<Endpoint>,
+    destination: PeerId,
+) -> Result<Result<Substream, crate::Error>> {
+    let stream = match endpoint
+        .send(OpenSubstream::single_protocol(destination, STOP_PROTOCOL))
+        .await
+        .context("Endpoint is disconnected")?
+    {
+        Ok(stream) => stream.await,
+        Err(e) => Err(e),
+    };
+
+    Ok(stream)
+}
+
+/// Forward all bytes from `reader` to `writer`, until `reader` is closed.
+async fn forward(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            return writer.close().await;
+        }
+
+        writer.write_all(&buffer[..n]).await?;
+        writer.flush().await?;
+    }
+}
@@ -0,0 +1,233 @@
+use crate::relay::client;
+use crate::relay::read_status;
+use crate::relay::write_peer_id;
+use crate::relay::CircuitAddress;
+use crate::relay::Status;
+use crate::relay::HOP_PROTOCOL;
+use crate::Connect;
+use crate::Endpoint;
+use crate::GetConnectionStats;
+use crate::OpenSubstream;
+use crate::Substream;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::stream;
+use futures::stream::BoxStream;
+use futures::FutureExt;
+use futures::StreamExt;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::transport::TransportError;
+use libp2p_core::Multiaddr;
+use libp2p_core::PeerId;
+use libp2p_core::Transport;
+use std::time::Duration;
+use xtra::Address;
+use xtra::WeakAddress;
+
+/// How long we wait for the connection to the relay when listening.
+///
+/// When dialing, the connection timeout of the [`Endpoint`] applies instead.
+const RELAY_CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How often we check whether the connection to the relay has been established.
+const RELAY_CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A [`Transport`] establishing connections through a relay, see the [module
+/// documentation](crate::relay).
+///
+/// Supports dialing `<relay address>/p2p/<relay>/p2p-circuit/p2p/<peer>` and, if constructed
+/// [`with_listener`](RelayTransport::with_listener), listening on
+/// `<relay address>/p2p/<relay>/p2p-circuit`. The connection to the relay is established through
+/// the given [`Endpoint`] if we are not connected to it yet. Combine it with a transport capable
+/// of reaching the relay, e.g. through [`Transport::or_transport`].
+#[derive(Clone)]
+pub struct RelayTransport {
+    /// The endpoint this transport is used by, thus we must not keep it alive.
+    endpoint: WeakAddress<Endpoint>,
+    client: Option<Address<client::Actor>>,
+}
+
+impl RelayTransport {
+    /// A transport which dials through relays but does not listen on them.
+    pub fn new(endpoint: &Address<Endpoint>) -> Self {
+        Self {
+            endpoint: endpoint.downgrade(),
+            client: None,
+        }
+    }
+
+    /// Also listen on relays, accepting circuits through the given actor.
+    pub fn with_listener(self, client: Address<client::Actor>) -> Self {
+        Self {
+            client: Some(client),
+            ..self
+        }
+    }
+}
+
+impl Transport for RelayTransport {
+    type Output = Substream;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<Self::ListenerUpgrade, Self::Error>, Self::Error>>;
+    type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let (client, relay_address, relay) = match (&self.client, CircuitAddress::parse(&addr)) {
+            (
+                Some(client),
+                Some(CircuitAddress {
+                    relay_address,
+                    relay,
+                    destination: None,
+                }),
+            ) => (client.clone(), relay_address, relay),
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        tracing::debug!(%addr, "Listening through relay");
+
+        let endpoint = self.endpoint.clone();
+        let circuits = async move {
+            tokio_extras::time::timeout(
+                RELAY_CONNECTION_TIMEOUT,
+                connect_to_relay(&endpoint, relay_address, relay),
+                || tracing::debug_span!("Connect to relay"),
+            )
+            .await
+            .map_err(|_| Error::RelayUnreachable)??;
+
+            client
+                .send(client::Listen { relay })
+                .await
+                .map_err(|_| Error::Disconnected)
+        };
+
+        let listener = stream::once(circuits)
+            .flat_map(move |circuits| -> Self::Listener {
+                match circuits {
+                    Ok(circuits) => {
+                        let listen_address = addr.clone();
+                        let new_address = ListenerEvent::NewAddress(listen_address.clone());
+
+                        let upgrades = circuits.map(move |(source, stream)| {
+                            let mut remote_addr = listen_address.clone();
+                            remote_addr.push(Protocol::P2p(source.into()));
+
+                            Ok(ListenerEvent::Upgrade {
+                                upgrade: future::ok(stream).boxed(),
+                                local_addr: listen_address.clone(),
+                                remote_addr,
+                            })
+                        });
+
+                        stream::once(future::ok(new_address))
+                            .chain(upgrades)
+                            .boxed()
+                    }
+                    Err(e) => stream::once(future::err(e)).boxed(),
+                }
+            })
+            .boxed();
+
+        Ok(listener)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let (relay_address, relay, destination) = match CircuitAddress::parse(&addr) {
+            Some(CircuitAddress {
+                relay_address,
+                relay,
+                destination: Some(destination),
+            }) => (relay_address, relay, destination),
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        tracing::debug!(%addr, "Dialing through relay");
+
+        let endpoint = self.endpoint.clone();
+        let dial = async move {
+            connect_to_relay(&endpoint, relay_address, relay).await?;
+
+            let mut stream = endpoint
+                .send(OpenSubstream::single_protocol(relay, HOP_PROTOCOL))
+                .await
+                .map_err(|_| Error::Disconnected)??
+                .await?;
+
+            write_peer_id(&mut stream, destination).await?;
+
+            match read_status(&mut stream).await? {
+                Status::Ok => Ok(stream),
+                status => Err(Error::CircuitRefused(status)),
+            }
+        };
+
+        Ok(dial.boxed())
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        self.dial(addr)
+    }
+
+    fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
+/// Connect to the relay unless we are connected already.
+async fn connect_to_relay(
+    endpoint: &WeakAddress<Endpoint>,
+    relay_address: Multiaddr,
+    relay: PeerId,
+) -> Result<(), Error> {
+    loop {
+        let stats = endpoint
+            .send(GetConnectionStats)
+            .await
+            .map_err(|_| Error::Disconnected)?;
+        if stats.connected_peers.contains(&relay) {
+            return Ok(());
+        }
+
+        match endpoint
+            .send(Connect(relay_address.clone()))
+            .await
+            .map_err(|_| Error::Disconnected)?
+        {
+            Ok(()) | Err(crate::Error::AlreadyTryingToConnected(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        tokio_extras::time::sleep_silent(RELAY_CONNECTION_POLL_INTERVAL).await;
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to communicate with relay")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to open substream to relay")]
+    Endpoint(#[from] crate::Error),
+    #[error("Relay refused circuit: {0:?}")]
+    CircuitRefused(Status),
+    #[error("Failed to connect to relay in time")]
+    RelayUnreachable,
+    #[error("Endpoint or relay client is no longer running")]
+    Disconnected,
+}
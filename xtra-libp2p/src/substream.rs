@@ -1,3 +1,4 @@
+use crate::limits::ActiveSubstream;
 use conquer_once::Lazy;
 use futures::ready;
 use futures::AsyncRead;
@@ -34,6 +35,9 @@ pub struct Substream {
     #[pin]
     inner: Negotiated<yamux::Stream>,
 
+    /// The protocol negotiated for this substream.
+    protocol: &'static str,
+
     /// The prometheus timer tracking the duration of the substream.
     ///
     /// This timer is started upon construction and automatically stops once it is dropped. Thus,
@@ -45,12 +49,17 @@ pub struct Substream {
 
     /// The prometheus counter for the number of bytes written.
     written_counter: IntCounter,
+
+    /// Marks the substream as active for as long as it is alive, to enforce the
+    /// [`ConnectionLimits`](crate::limits::ConnectionLimits) of inbound substreams.
+    _active: Option<ActiveSubstream>,
 }
 
 impl Debug for Substream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Substream")
             .field("inner", &self.inner)
+            .field("protocol", &self.protocol)
             .finish()
     }
 }
@@ -69,11 +78,23 @@ impl Substream {
 
         Self {
             inner,
+            protocol,
             _timer: SUBSTREAM_DURATION_HISTOGRAM.with(&labels).start_timer(),
             read_counter: SUBSTREAM_BYTES_READ_COUNTER.with(&labels),
             written_counter: SUBSTREAM_BYTES_WRITTEN_COUNTER.with(&labels),
+            _active: None,
         }
     }
+
+    /// The protocol negotiated for this substream.
+    pub fn protocol(&self) -> &'static str {
+        self.protocol
+    }
+
+    pub(crate) fn with_limit_tracking(mut self, active: ActiveSubstream) -> Self {
+        self._active = Some(active);
+        self
+    }
 }
 
 impl AsyncRead for Substream {
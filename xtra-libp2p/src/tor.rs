@@ -0,0 +1,362 @@
+use data_encoding::BASE32_NOPAD;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::FutureExt;
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::transport::ListenerEvent;
+use libp2p_core::transport::TransportError;
+use libp2p_core::Multiaddr;
+use libp2p_core::Transport;
+use std::net::SocketAddr;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio_util::compat::Compat;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const CONNECT: u8 = 0x01;
+const RESERVED: u8 = 0x00;
+const SUCCEEDED: u8 = 0x00;
+const ADDRESS_TYPE_IPV4: u8 = 0x01;
+const ADDRESS_TYPE_DOMAIN: u8 = 0x03;
+const ADDRESS_TYPE_IPV6: u8 = 0x04;
+
+/// A [`Transport`] that dials all connections through a SOCKS5 proxy, usually the one of a Tor
+/// daemon.
+///
+/// Supports `/onion3`, `/dns`, `/dns4`, `/dns6`, `/ip4` and `/ip6` addresses. Hostnames are
+/// resolved by the proxy, i.e. they do not leak to the local DNS resolver.
+///
+/// Listening is not supported. To be reachable through Tor, configure a hidden service that
+/// forwards to a regular TCP listener and advertise its onion address.
+#[derive(Debug, Clone, Copy)]
+pub struct TorTransport {
+    socks_proxy: SocketAddr,
+}
+
+impl TorTransport {
+    pub fn new(socks_proxy: SocketAddr) -> Self {
+        Self { socks_proxy }
+    }
+}
+
+impl Transport for TorTransport {
+    type Output = Compat<TcpStream>;
+    type Error = Error;
+    #[allow(clippy::type_complexity)]
+    type Listener =
+        BoxStream<'static, Result<ListenerEvent<Self::ListenerUpgrade, Self::Error>, Self::Error>>;
+    type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let target = match Target::from_multiaddr(&addr) {
+            Some(target) => target,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        tracing::debug!(%addr, proxy = %self.socks_proxy, "Dialing through SOCKS5 proxy");
+
+        Ok(connect(self.socks_proxy, target).boxed())
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        self.dial(addr)
+    }
+
+    fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
+/// The address the SOCKS5 proxy is asked to connect to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Ip(SocketAddr),
+    Domain(String, u16),
+}
+
+impl Target {
+    fn from_multiaddr(addr: &Multiaddr) -> Option<Self> {
+        let mut protocols = addr.iter();
+
+        let target = match protocols.next()? {
+            Protocol::Onion3(onion) => {
+                let host = BASE32_NOPAD.encode(onion.hash()).to_lowercase();
+                Target::Domain(format!("{host}.onion"), onion.port())
+            }
+            Protocol::Ip4(ip) => Target::Ip(SocketAddr::from((ip, tcp_port(protocols.next())?))),
+            Protocol::Ip6(ip) => Target::Ip(SocketAddr::from((ip, tcp_port(protocols.next())?))),
+            Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => {
+                if host.len() > u8::MAX as usize {
+                    return None;
+                }
+                Target::Domain(host.to_string(), tcp_port(protocols.next())?)
+            }
+            _ => return None,
+        };
+
+        // Only the peer id may follow the address
+        match protocols.next() {
+            None | Some(Protocol::P2p(_)) => Some(target),
+            Some(_) => None,
+        }
+    }
+
+    fn connect_request(&self) -> Vec<u8> {
+        let mut request = vec![SOCKS_VERSION, CONNECT, RESERVED];
+
+        let port = match self {
+            Target::Ip(SocketAddr::V4(addr)) => {
+                request.push(ADDRESS_TYPE_IPV4);
+                request.extend(addr.ip().octets());
+                addr.port()
+            }
+            Target::Ip(SocketAddr::V6(addr)) => {
+                request.push(ADDRESS_TYPE_IPV6);
+                request.extend(addr.ip().octets());
+                addr.port()
+            }
+            Target::Domain(host, port) => {
+                request.push(ADDRESS_TYPE_DOMAIN);
+                request.push(host.len() as u8);
+                request.extend(host.as_bytes());
+                *port
+            }
+        };
+        request.extend(port.to_be_bytes());
+
+        request
+    }
+}
+
+fn tcp_port(protocol: Option<Protocol>) -> Option<u16> {
+    match protocol? {
+        Protocol::Tcp(port) => Some(port),
+        _ => None,
+    }
+}
+
+/// Establish a connection to the target through the SOCKS5 proxy, see
+/// [RFC 1928](https://www.rfc-editor.org/rfc/rfc1928).
+async fn connect(socks_proxy: SocketAddr, target: Target) -> Result<Compat<TcpStream>, Error> {
+    let mut stream = TcpStream::connect(socks_proxy).await?;
+
+    stream
+        .write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION])
+        .await?;
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method).await?;
+    if method[0] != SOCKS_VERSION {
+        return Err(Error::InvalidReply);
+    }
+    if method[1] != NO_AUTHENTICATION {
+        return Err(Error::AuthenticationRequired);
+    }
+
+    stream.write_all(&target.connect_request()).await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(Error::InvalidReply);
+    }
+    if reply[1] != SUCCEEDED {
+        return Err(Error::ConnectFailed(reply[1]));
+    }
+
+    // The reply ends with the address the proxy bound to, which is of no interest to us
+    let bound_address_len = match reply[3] {
+        ADDRESS_TYPE_IPV4 => 4,
+        ADDRESS_TYPE_IPV6 => 16,
+        ADDRESS_TYPE_DOMAIN => stream.read_u8().await? as usize,
+        _ => return Err(Error::InvalidReply),
+    };
+    let mut bound_address = vec![0u8; bound_address_len + 2];
+    stream.read_exact(&mut bound_address).await?;
+
+    Ok(stream.compat())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to communicate with SOCKS5 proxy")]
+    Io(#[from] std::io::Error),
+    #[error("SOCKS5 proxy sent an invalid reply")]
+    InvalidReply,
+    #[error("SOCKS5 proxy requires authentication")]
+    AuthenticationRequired,
+    #[error("SOCKS5 proxy failed to connect to target with reply code {0}")]
+    ConnectFailed(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::AsyncReadExt as _;
+    use futures::AsyncWriteExt as _;
+    use tokio::net::TcpListener;
+
+    const ONION: &str = "vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd";
+
+    #[test]
+    fn parses_supported_addresses() {
+        let cases = [
+            (
+                format!("/onion3/{ONION}:10000"),
+                Target::Domain(format!("{ONION}.onion"), 10000),
+            ),
+            (
+                "/dns4/maker.example.com/tcp/10000/p2p/12D3KooWP3BN6bq9jPy8cP7Grj1QyUBfr7U6BeQFgMwfTTu12wuY".to_owned(),
+                Target::Domain("maker.example.com".to_owned(), 10000),
+            ),
+            (
+                "/ip6/::1/tcp/10000".to_owned(),
+                Target::Ip("[::1]:10000".parse().unwrap()),
+            ),
+        ];
+
+        for (addr, expected) in cases {
+            let target = Target::from_multiaddr(&addr.parse().unwrap());
+
+            assert_eq!(target, Some(expected), "{addr}");
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_addresses() {
+        for addr in [
+            "/memory/10000",
+            "/ip4/127.0.0.1/udp/10000",
+            "/dns/example.com",
+        ] {
+            let target = Target::from_multiaddr(&addr.parse().unwrap());
+
+            assert_eq!(target, None, "{addr}");
+        }
+    }
+
+    #[test]
+    fn listening_is_not_supported() {
+        let mut transport = TorTransport::new("127.0.0.1:9050".parse().unwrap());
+
+        let result = transport.listen_on("/ip4/127.0.0.1/tcp/10000".parse().unwrap());
+
+        assert!(matches!(
+            result,
+            Err(TransportError::MultiaddrNotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn dials_onion_address_through_socks_proxy() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut transport = TorTransport::new(proxy.local_addr().unwrap());
+
+        let proxy = async move {
+            let (mut stream, _) = proxy.accept().await.unwrap();
+
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [SOCKS_VERSION, 1, NO_AUTHENTICATION]);
+            stream
+                .write_all(&[SOCKS_VERSION, NO_AUTHENTICATION])
+                .await
+                .unwrap();
+
+            let expected_request =
+                Target::Domain(format!("{ONION}.onion"), 10000).connect_request();
+            let mut request = vec![0u8; expected_request.len()];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request, expected_request);
+            stream
+                .write_all(&[
+                    SOCKS_VERSION,
+                    SUCCEEDED,
+                    RESERVED,
+                    ADDRESS_TYPE_IPV4,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                ])
+                .await
+                .unwrap();
+
+            // Behave like the hidden service and echo back what we receive
+            let mut message = [0u8; 5];
+            stream.read_exact(&mut message).await.unwrap();
+            stream.write_all(&message).await.unwrap();
+        };
+
+        let dialer = async move {
+            let mut stream = transport
+                .dial(format!("/onion3/{ONION}:10000").parse().unwrap())
+                .unwrap()
+                .await
+                .unwrap();
+
+            stream.write_all(b"hello").await.unwrap();
+            let mut echo = [0u8; 5];
+            stream.read_exact(&mut echo).await.unwrap();
+
+            echo
+        };
+
+        let ((), echo) = futures::join!(proxy, dialer);
+
+        assert_eq!(&echo, b"hello");
+    }
+
+    #[tokio::test]
+    async fn fails_if_proxy_cannot_connect() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut transport = TorTransport::new(proxy.local_addr().unwrap());
+
+        let proxy = async move {
+            let (mut stream, _) = proxy.accept().await.unwrap();
+
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream
+                .write_all(&[SOCKS_VERSION, NO_AUTHENTICATION])
+                .await
+                .unwrap();
+
+            let mut request = [0u8; 10];
+            stream.read_exact(&mut request).await.unwrap();
+            // Host unreachable
+            stream
+                .write_all(&[SOCKS_VERSION, 0x04, RESERVED, ADDRESS_TYPE_IPV4])
+                .await
+                .unwrap();
+        };
+
+        let dialer = transport
+            .dial("/ip4/10.0.0.1/tcp/10000".parse().unwrap())
+            .unwrap();
+
+        let ((), result) = futures::join!(proxy, dialer);
+
+        assert!(matches!(result, Err(Error::ConnectFailed(0x04))));
+    }
+}
@@ -0,0 +1,124 @@
+//! Support for protocols which are spoken in several versions.
+//!
+//! A protocol declares the versions it supports as [`Versions`], associating each protocol name
+//! with a value describing how to speak that version. Usually that value is an enum defined next
+//! to the protocol, whose methods adapt the messages to the version in question. Thus a single
+//! actor can handle all versions of a protocol.
+//!
+//! Dialers pass [`Versions::protocols`] to [`OpenSubstream::multiple_protocols`], listeners are
+//! registered for all of them. Either way, the highest version supported by both peers is
+//! negotiated and can be looked up through [`Versions::get`]. If the protocols of the peer are
+//! already known, e.g. through the identify protocol, [`Versions::negotiate`] yields the same
+//! result without opening a substream.
+//!
+//! [`OpenSubstream::multiple_protocols`]: crate::OpenSubstream::multiple_protocols
+
+use std::collections::HashSet;
+
+/// The versions of a protocol supported by this node, ordered from newest to oldest.
+#[derive(Debug)]
+pub struct Versions<V: 'static> {
+    versions: &'static [(&'static str, V)],
+}
+
+impl<V> Versions<V> {
+    /// Declare the supported versions, **newest first**.
+    ///
+    /// Panics if no version is given.
+    pub const fn new(versions: &'static [(&'static str, V)]) -> Self {
+        assert!(
+            !versions.is_empty(),
+            "A protocol needs at least one version"
+        );
+
+        Self { versions }
+    }
+
+    /// The number of supported versions.
+    pub const fn count(&self) -> usize {
+        self.versions.len()
+    }
+
+    /// All supported protocol names, in order of preference.
+    pub fn protocols(&self) -> Vec<&'static str> {
+        self.versions
+            .iter()
+            .map(|(protocol, _)| *protocol)
+            .collect()
+    }
+}
+
+impl<V> Versions<V>
+where
+    V: Copy,
+{
+    /// The newest supported version.
+    pub const fn latest(&self) -> (&'static str, V) {
+        self.versions[0]
+    }
+
+    /// The version identified by the given protocol name, usually the one negotiated for a
+    /// substream.
+    pub fn get(&self, protocol: &str) -> Option<V> {
+        self.versions
+            .iter()
+            .find(|(candidate, _)| *candidate == protocol)
+            .map(|(_, version)| *version)
+    }
+
+    /// The newest version which is also supported by a peer listening on `peer_protocols`.
+    ///
+    /// Returns `None` if we don't have a version in common.
+    pub fn negotiate(&self, peer_protocols: &HashSet<String>) -> Option<(&'static str, V)> {
+        self.versions
+            .iter()
+            .find(|(protocol, _)| peer_protocols.contains(*protocol))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Version {
+        V1,
+        V2,
+        V3,
+    }
+
+    const VERSIONS: Versions<Version> = Versions::new(&[
+        ("/foo/3.0.0", Version::V3),
+        ("/foo/2.0.0", Version::V2),
+        ("/foo/1.0.0", Version::V1),
+    ]);
+
+    #[test]
+    fn negotiates_newest_common_version() {
+        let peer_protocols = HashSet::from(["/foo/1.0.0".to_owned(), "/foo/2.0.0".to_owned()]);
+
+        assert_eq!(
+            VERSIONS.negotiate(&peer_protocols),
+            Some(("/foo/2.0.0", Version::V2))
+        );
+    }
+
+    #[test]
+    fn no_common_version() {
+        let peer_protocols = HashSet::from(["/foo/4.0.0".to_owned(), "/bar/1.0.0".to_owned()]);
+
+        assert_eq!(VERSIONS.negotiate(&peer_protocols), None);
+    }
+
+    #[test]
+    fn protocols_are_in_order_of_preference() {
+        assert_eq!(
+            VERSIONS.protocols(),
+            vec!["/foo/3.0.0", "/foo/2.0.0", "/foo/1.0.0"]
+        );
+        assert_eq!(VERSIONS.latest(), ("/foo/3.0.0", Version::V3));
+        assert_eq!(VERSIONS.get("/foo/1.0.0"), Some(Version::V1));
+        assert_eq!(VERSIONS.get("/foo/0.1.0"), None);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::util::make_node;
 use crate::util::make_node_with_blocklist;
+use crate::util::make_node_with_limits;
 use crate::util::GetConnectedPeers;
 use crate::util::GetListenAddresses;
 use crate::util::Node;
@@ -12,7 +13,7 @@ use futures::StreamExt;
 use libp2p_core::multiaddr::Protocol;
 use libp2p_core::Multiaddr;
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::time::Duration;
 use xtra::message_channel::MessageChannel;
 use xtra::spawn::TokioGlobalSpawnExt;
 use xtra::Actor;
@@ -20,12 +21,18 @@ use xtra::Context;
 use xtra_libp2p::endpoint;
 use xtra_libp2p::endpoint::RegisterListenProtocols;
 use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::ConnectionLimits;
+use xtra_libp2p::limits::Rate;
+use xtra_libp2p::BanPeer;
 use xtra_libp2p::Connect;
 use xtra_libp2p::Disconnect;
+use xtra_libp2p::GetBannedPeers;
 use xtra_libp2p::GetConnectionStats;
 use xtra_libp2p::ListenOn;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_libp2p::OpenSubstream;
+use xtra_libp2p::SetAllowedPeers;
+use xtra_libp2p::UnbanPeer;
 use xtra_productivity::xtra_productivity;
 
 mod util;
@@ -68,7 +75,7 @@ async fn blocked_peers_cannot_connect() {
             "/hello-world/1.0.0",
             HelloWorld::default().create(None).spawn_global().into(),
         )],
-        Arc::new(HashSet::from([bob.peer_id])),
+        HashSet::from([bob.peer_id]),
     );
 
     let port = rand::random::<u16>();
@@ -424,6 +431,160 @@ async fn given_alice_knows_bob_does_support_hello_world_when_dial_then_open_subs
     assert!(matches!(res, Ok(_)));
 }
 
+#[tokio::test]
+async fn banned_peer_is_disconnected_until_unbanned() {
+    let (alice, bob, alice_listen) = alice_and_bob([], []).await;
+    let connect_to_alice = alice_listen.with(Protocol::P2p(alice.peer_id.into()));
+    let (alice, bob, connect_to_alice) = (&alice, &bob, &connect_to_alice);
+    retry_until(|| is_connected(alice, bob.peer_id)).await;
+
+    alice
+        .endpoint
+        .send(BanPeer {
+            peer_id: bob.peer_id,
+            duration: Duration::from_secs(60),
+            reason: "test".to_owned(),
+        })
+        .await
+        .unwrap();
+    retry_until(|| async move { !is_connected(bob, alice.peer_id).await }).await;
+
+    let banned_peers = alice.endpoint.send(GetBannedPeers).await.unwrap();
+    assert_eq!(banned_peers.len(), 1);
+    assert_eq!(banned_peers[0].peer_id, bob.peer_id);
+
+    bob.endpoint
+        .send(Connect(connect_to_alice.clone()))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio_extras::time::sleep(Duration::from_millis(500)).await;
+    assert!(!is_connected(alice, bob.peer_id).await);
+
+    let was_banned = alice.endpoint.send(UnbanPeer(bob.peer_id)).await.unwrap();
+    assert!(was_banned);
+
+    retry_until(|| async move {
+        let _ = bob.endpoint.send(Connect(connect_to_alice.clone())).await;
+        is_connected(alice, bob.peer_id).await
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn peer_exceeding_substream_rate_is_banned() {
+    let alice = make_node_with_limits(
+        [(
+            "/hello-world/1.0.0",
+            HelloWorld::default().create(None).spawn_global().into(),
+        )],
+        ConnectionLimits {
+            max_inbound_substream_rate: Some(Rate::per_minute(1)),
+            ..ConnectionLimits::default()
+        },
+    );
+    let bob = make_node([]);
+
+    let port = rand::random::<u16>();
+    alice
+        .endpoint
+        .send(ListenOn(format!("/memory/{port}").parse().unwrap()))
+        .await
+        .unwrap();
+    let alice_peer_id = &alice.peer_id;
+    bob.endpoint
+        .send(Connect(
+            format!("/memory/{port}/p2p/{alice_peer_id}")
+                .parse()
+                .unwrap(),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let (alice, bob) = (&alice, &bob);
+    retry_until(|| is_connected(bob, alice.peer_id)).await;
+
+    for _ in 0..2 {
+        if let Ok(Ok(substream)) = bob
+            .endpoint
+            .send(OpenSubstream::single_protocol(
+                alice.peer_id,
+                "/hello-world/1.0.0",
+            ))
+            .await
+            .unwrap()
+        {
+            let _ = hello_world_dialer(substream.await.unwrap(), "Bob").await;
+        }
+    }
+
+    retry_until(|| async move {
+        let banned_peers = alice.endpoint.send(GetBannedPeers).await.unwrap();
+        banned_peers
+            .iter()
+            .any(|banned| banned.peer_id == bob.peer_id)
+    })
+    .await;
+    assert!(!is_connected(alice, bob.peer_id).await);
+}
+
+#[tokio::test]
+async fn only_allowed_peers_can_connect() {
+    let (alice, bob, alice_listen) = alice_and_bob([], []).await;
+    let connect_to_alice = alice_listen.with(Protocol::P2p(alice.peer_id.into()));
+    let (alice, bob, connect_to_alice) = (&alice, &bob, &connect_to_alice);
+    retry_until(|| is_connected(alice, bob.peer_id)).await;
+
+    alice
+        .endpoint
+        .send(SetAllowedPeers(Some(HashSet::new())))
+        .await
+        .unwrap();
+    alice.endpoint.send(Disconnect(bob.peer_id)).await.unwrap();
+    retry_until(|| async move { !is_connected(bob, alice.peer_id).await }).await;
+
+    bob.endpoint
+        .send(Connect(connect_to_alice.clone()))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio_extras::time::sleep(Duration::from_millis(500)).await;
+    assert!(!is_connected(alice, bob.peer_id).await);
+
+    alice
+        .endpoint
+        .send(SetAllowedPeers(Some(HashSet::from([bob.peer_id]))))
+        .await
+        .unwrap();
+
+    retry_until(|| async move {
+        let _ = bob.endpoint.send(Connect(connect_to_alice.clone())).await;
+        is_connected(alice, bob.peer_id).await
+    })
+    .await;
+}
+
+async fn is_connected(node: &Node, peer_id: PeerId) -> bool {
+    let stats = node.endpoint.send(GetConnectionStats).await.unwrap();
+
+    stats.connected_peers.contains(&peer_id)
+}
+
+async fn retry_until<F, Fut>(mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..50 {
+        if condition().await {
+            return;
+        }
+        tokio_extras::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("Condition not met in time");
+}
+
 async fn alice_and_bob<const AN: usize, const BN: usize>(
     alice_inbound_substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); AN],
     bob_inbound_substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); BN],
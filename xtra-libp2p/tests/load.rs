@@ -10,7 +10,6 @@ use libp2p_core::identity::Keypair;
 use libp2p_core::transport::MemoryTransport;
 use libp2p_core::Multiaddr;
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 use tracing::subscriber::DefaultGuard;
 use tracing_subscriber::util::SubscriberInitExt;
@@ -21,6 +20,7 @@ use xtra::Address;
 use xtra::Context;
 use xtra_libp2p::endpoint::ConnectionEstablished;
 use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::Connect;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::ListenOn;
@@ -159,7 +159,8 @@ async fn test_runner<
                     vec![subscriber_stats.clone().into()],
                     vec![subscriber_stats.clone().into()],
                 ),
-                Arc::new(HashSet::default()),
+                HashSet::default(),
+                ConnectionLimits::default(),
             );
 
             #[allow(clippy::disallowed_methods)]
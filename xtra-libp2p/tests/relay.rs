@@ -0,0 +1,244 @@
+use anyhow::Context as _;
+use anyhow::Result;
+use async_trait::async_trait;
+use asynchronous_codec::Bytes;
+use futures::SinkExt;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::time::Duration;
+use xtra::spawn::TokioGlobalSpawnExt;
+use xtra::Actor;
+use xtra::Address;
+use xtra::Context;
+use xtra_libp2p::endpoint::Subscribers;
+use xtra_libp2p::libp2p::identity::Keypair;
+use xtra_libp2p::libp2p::transport::MemoryTransport;
+use xtra_libp2p::libp2p::Multiaddr;
+use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::libp2p::Transport;
+use xtra_libp2p::limits::ConnectionLimits;
+use xtra_libp2p::relay;
+use xtra_libp2p::relay::RelayTransport;
+use xtra_libp2p::Connect;
+use xtra_libp2p::Endpoint;
+use xtra_libp2p::GetConnectionStats;
+use xtra_libp2p::ListenOn;
+use xtra_libp2p::NewInboundSubstream;
+use xtra_libp2p::OpenSubstream;
+use xtra_productivity::xtra_productivity;
+
+const GREET_PROTOCOL: &str = "/greet/1.0.0";
+
+#[tokio::test]
+async fn peers_connected_through_relay_can_open_substreams_in_both_directions() {
+    let (relay, relay_address) = start_relay().await;
+
+    let alice = make_node();
+    alice
+        .endpoint
+        .send(ListenOn(
+            format!("{relay_address}/p2p-circuit").parse().unwrap(),
+        ))
+        .await
+        .unwrap();
+    wait_until_connected(&alice.endpoint, relay).await;
+
+    let bob = make_node();
+    connect_eventually(
+        &bob.endpoint,
+        format!("{relay_address}/p2p-circuit/p2p/{}", alice.peer_id)
+            .parse()
+            .unwrap(),
+        alice.peer_id,
+    )
+    .await;
+
+    let bob_to_alice = open_greet_substream(&bob.endpoint, alice.peer_id).await;
+    let alice_to_bob = open_greet_substream(&alice.endpoint, bob.peer_id).await;
+
+    assert_eq!(greet(bob_to_alice, "Bob").await.unwrap(), "Hello Bob!");
+    assert_eq!(greet(alice_to_bob, "Alice").await.unwrap(), "Hello Alice!");
+}
+
+#[tokio::test]
+async fn cannot_connect_to_peer_which_does_not_listen_on_relay() {
+    let (relay, relay_address) = start_relay().await;
+
+    let alice = make_node();
+    alice
+        .endpoint
+        .send(Connect(relay_address.clone()))
+        .await
+        .unwrap()
+        .unwrap();
+    wait_until_connected(&alice.endpoint, relay).await;
+
+    let bob = make_node();
+    bob.endpoint
+        .send(Connect(
+            format!("{relay_address}/p2p-circuit/p2p/{}", alice.peer_id)
+                .parse()
+                .unwrap(),
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio_extras::time::sleep(Duration::from_secs(2)).await;
+
+    let bob_stats = bob.endpoint.send(GetConnectionStats).await.unwrap();
+    assert!(bob_stats.connected_peers.contains(&relay));
+    assert!(!bob_stats.connected_peers.contains(&alice.peer_id));
+}
+
+struct Node {
+    peer_id: PeerId,
+    endpoint: Address<Endpoint>,
+}
+
+/// Constructs a node which can reach others through relays, in addition to connecting through
+/// `/memory` addresses.
+fn make_node() -> Node {
+    let id = Keypair::generate_ed25519();
+    let peer_id = id.public().to_peer_id();
+
+    let (endpoint, endpoint_context) = Context::new(None);
+    let relay_client = relay::client::Actor::default().create(None).spawn_global();
+    let greeter = Greeter.create(None).spawn_global();
+
+    let transport = RelayTransport::new(&endpoint).with_listener(relay_client.clone());
+    let endpoint_actor = Endpoint::new(
+        Box::new(move || transport.clone().or_transport(MemoryTransport::default())),
+        id,
+        Duration::from_secs(20),
+        [
+            (GREET_PROTOCOL, greeter.into()),
+            (relay::STOP_PROTOCOL, relay_client.clone().into()),
+        ],
+        Subscribers::new(vec![], vec![relay_client.into()], vec![], vec![]),
+        HashSet::new(),
+        ConnectionLimits::default(),
+    );
+
+    #[allow(clippy::disallowed_methods)]
+    tokio::spawn(endpoint_context.run(endpoint_actor));
+
+    Node { peer_id, endpoint }
+}
+
+/// Starts a relay listening on a `/memory` address, returning its peer ID and address.
+async fn start_relay() -> (PeerId, Multiaddr) {
+    let id = Keypair::generate_ed25519();
+    let peer_id = id.public().to_peer_id();
+
+    let (endpoint, endpoint_context) = Context::new(None);
+    let relay_server =
+        relay::server::Actor::new(endpoint.clone(), relay::server::Config::default())
+            .create(None)
+            .spawn_global();
+
+    let endpoint_actor = Endpoint::new(
+        Box::new(MemoryTransport::default),
+        id,
+        Duration::from_secs(20),
+        [(relay::HOP_PROTOCOL, relay_server.into())],
+        Subscribers::default(),
+        HashSet::new(),
+        ConnectionLimits::default(),
+    );
+
+    #[allow(clippy::disallowed_methods)]
+    tokio::spawn(endpoint_context.run(endpoint_actor));
+
+    let port = rand::random::<u16>();
+    endpoint
+        .send(ListenOn(format!("/memory/{port}").parse().unwrap()))
+        .await
+        .unwrap();
+
+    let address = format!("/memory/{port}/p2p/{peer_id}").parse().unwrap();
+
+    (peer_id, address)
+}
+
+async fn wait_until_connected(endpoint: &Address<Endpoint>, peer_id: PeerId) {
+    for _ in 0..50 {
+        let stats = endpoint.send(GetConnectionStats).await.unwrap();
+        if stats.connected_peers.contains(&peer_id) {
+            return;
+        }
+
+        tokio_extras::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    panic!("Failed to connect to {peer_id}");
+}
+
+/// Dial the peer until we are connected, as the peer might not be listening on the relay yet.
+async fn connect_eventually(endpoint: &Address<Endpoint>, address: Multiaddr, peer_id: PeerId) {
+    for _ in 0..50 {
+        let _ = endpoint.send(Connect(address.clone())).await.unwrap();
+        tokio_extras::time::sleep(Duration::from_millis(100)).await;
+
+        let stats = endpoint.send(GetConnectionStats).await.unwrap();
+        if stats.connected_peers.contains(&peer_id) {
+            return;
+        }
+    }
+
+    panic!("Failed to connect to {peer_id} through relay");
+}
+
+async fn open_greet_substream(
+    endpoint: &Address<Endpoint>,
+    peer_id: PeerId,
+) -> xtra_libp2p::Substream {
+    endpoint
+        .send(OpenSubstream::single_protocol(peer_id, GREET_PROTOCOL))
+        .await
+        .unwrap()
+        .unwrap()
+        .await
+        .unwrap()
+}
+
+async fn greet(stream: xtra_libp2p::Substream, name: &'static str) -> Result<String> {
+    let mut stream = asynchronous_codec::Framed::new(stream, asynchronous_codec::LengthCodec);
+
+    stream.send(Bytes::from(name)).await?;
+    let bytes = stream.next().await.context("Expected message")??;
+    let message = String::from_utf8(bytes.to_vec())?;
+
+    Ok(message)
+}
+
+#[derive(Clone, Copy)]
+struct Greeter;
+
+#[xtra_productivity]
+impl Greeter {
+    async fn handle(&mut self, msg: NewInboundSubstream, ctx: &mut Context<Self>) {
+        tokio_extras::spawn_fallible(
+            &ctx.address().unwrap(),
+            async move {
+                let mut stream =
+                    asynchronous_codec::Framed::new(msg.stream, asynchronous_codec::LengthCodec)
+                        .fuse();
+
+                let bytes = stream.select_next_some().await?;
+                let name = String::from_utf8(bytes.to_vec())?;
+
+                stream.send(Bytes::from(format!("Hello {name}!"))).await?;
+
+                anyhow::Ok(())
+            },
+            |e| async move { tracing::warn!("Greet protocol failed: {e:#}") },
+        );
+    }
+}
+
+#[async_trait]
+impl Actor for Greeter {
+    type Stop = ();
+
+    async fn stopped(self) -> Self::Stop {}
+}
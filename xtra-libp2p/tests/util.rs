@@ -1,7 +1,6 @@
 use async_trait::async_trait;
 use libp2p_core::Multiaddr;
 use std::collections::HashSet;
-use std::sync::Arc;
 use std::time::Duration;
 use xtra::message_channel::MessageChannel;
 use xtra::spawn::TokioGlobalSpawnExt;
@@ -12,6 +11,7 @@ use xtra_libp2p::endpoint::Subscribers;
 use xtra_libp2p::libp2p::identity::Keypair;
 use xtra_libp2p::libp2p::transport::MemoryTransport;
 use xtra_libp2p::libp2p::PeerId;
+use xtra_libp2p::limits::ConnectionLimits;
 use xtra_libp2p::Endpoint;
 use xtra_libp2p::NewInboundSubstream;
 use xtra_productivity::xtra_productivity;
@@ -27,12 +27,31 @@ pub struct Node {
 pub fn make_node<const N: usize>(
     substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
 ) -> Node {
-    make_node_with_blocklist(substream_handlers, Arc::new(HashSet::new()))
+    make_node_with_blocklist(substream_handlers, HashSet::new())
 }
 
 pub fn make_node_with_blocklist<const N: usize>(
     substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
-    blocked_peers: Arc<HashSet<PeerId>>,
+    blocked_peers: HashSet<PeerId>,
+) -> Node {
+    make_node_with(
+        substream_handlers,
+        blocked_peers,
+        ConnectionLimits::default(),
+    )
+}
+
+pub fn make_node_with_limits<const N: usize>(
+    substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+    limits: ConnectionLimits,
+) -> Node {
+    make_node_with(substream_handlers, HashSet::new(), limits)
+}
+
+fn make_node_with<const N: usize>(
+    substream_handlers: [(&'static str, MessageChannel<NewInboundSubstream, ()>); N],
+    blocked_peers: HashSet<PeerId>,
+    limits: ConnectionLimits,
 ) -> Node {
     let id = Keypair::generate_ed25519();
     let peer_id = id.public().to_peer_id();
@@ -53,6 +72,7 @@ pub fn make_node_with_blocklist<const N: usize>(
             vec![subscriber_stats.clone().into()],
         ),
         blocked_peers,
+        limits,
     )
     .create(None)
     .spawn_global();